@@ -1,8 +1,40 @@
+mod assignments;
+mod backup;
 mod calculate;
+#[cfg(not(target_arch = "wasm32"))]
+mod clipboard;
+pub(crate) mod config;
+mod export_job;
 mod gif_recorder;
+mod gif_sink;
+#[cfg(not(target_arch = "wasm32"))]
+mod gpu_quantize;
 mod gui;
+mod icc;
+#[cfg(not(target_arch = "wasm32"))]
+mod instanced;
+mod json;
+mod live_source;
+mod lut;
 mod morph_sim;
+mod mosaic;
+mod overlay;
+mod power;
 mod preset;
+#[cfg(target_os = "linux")]
+mod screen_capture;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod software_preview;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod software_render;
+mod sprite_sheet;
+mod telemetry;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod trace;
+mod uv_map;
+#[cfg(not(target_arch = "wasm32"))]
+mod video_recorder;
+mod zip;
 
 #[cfg(target_arch = "wasm32")]
 pub use crate::app::calculate::worker::worker_entry;
@@ -44,6 +76,10 @@ struct ParamsCommon {
     width: u32,
     height: u32,
     n_seeds: u32,
+    render_mode: u32,
+    cvd_filter: u32,
+    tiled: u32,      // 1: show the grid repeated 3x3
+    provenance: u32, // 1: tint cells by where in the source they came from
     _pad: u32,
 }
 
@@ -61,6 +97,56 @@ const DEFAULT_RESOLUTION: u32 = 2048;
 #[cfg(target_arch = "wasm32")]
 const DEFAULT_RESOLUTION: u32 = 1024;
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum RenderMode {
+    Pixels = 0,
+    Emoji = 1,
+    Photomosaic = 2,
+}
+
+impl RenderMode {
+    pub const ALL: [RenderMode; 3] = [
+        RenderMode::Pixels,
+        RenderMode::Emoji,
+        RenderMode::Photomosaic,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RenderMode::Pixels => "pixels",
+            RenderMode::Emoji => "emoji",
+            RenderMode::Photomosaic => "photomosaic",
+        }
+    }
+}
+
+/// Color blindness simulated over the preview, never recorded.
+#[derive(Clone, Copy, PartialEq)]
+pub enum CvdFilter {
+    None = 0,
+    Protanopia = 1,
+    Deuteranopia = 2,
+    Tritanopia = 3,
+}
+
+impl CvdFilter {
+    pub const ALL: [CvdFilter; 4] = [
+        CvdFilter::None,
+        CvdFilter::Protanopia,
+        CvdFilter::Deuteranopia,
+        CvdFilter::Tritanopia,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CvdFilter::None => "normal vision",
+            CvdFilter::Protanopia => "protanopia",
+            CvdFilter::Deuteranopia => "deuteranopia",
+            CvdFilter::Tritanopia => "tritanopia",
+        }
+    }
+}
+
 pub enum GuiMode {
     Transform,
     #[cfg(not(target_arch = "wasm32"))]
@@ -68,7 +154,12 @@ pub enum GuiMode {
 }
 
 use crate::app::{calculate::ProgressMsg, morph_sim::Sim, preset::UnprocessedPreset};
-use crate::app::{calculate::util::GenerationSettings, preset::Preset};
+use crate::app::{
+    calculate::util::{Aspect, GenerationSettings},
+    gif_recorder::ExportProfile,
+    preset::{Preset, PresetMeta},
+    zip::ZipWriter,
+};
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::closure::Closure;
@@ -81,6 +172,8 @@ pub struct ObamifyApp {
     //prev_frame_time: std::time::Instant,
     // UI state
     size: (u32, u32),
+    lod: u32,      // pixels each way merged into one while the preview is shown small
+    shown_px: u32, // side of the preview on screen last frame, in physical pixels
     seed_count: u32,
 
     #[cfg(not(target_arch = "wasm32"))]
@@ -95,6 +188,9 @@ pub struct ObamifyApp {
     inbox: Vec<ProgressMsg>,
 
     gif_recorder: gif_recorder::GifRecorder,
+    telemetry: telemetry::Telemetry,
+    power: power::PowerState,
+    config: config::Config,
     sim: Sim,
 
     // Seeds CPU copy
@@ -119,6 +215,14 @@ pub struct ObamifyApp {
     seed_tex_view: wgpu::TextureView,
     color_lookup_tex: wgpu::Texture, // Color lookup table as texture (WebGL compatible)
     color_lookup_tex_view: wgpu::TextureView,
+    emoji_atlas: mosaic::TileAtlas,
+    photo_atlas: Option<mosaic::TileAtlas>,
+    pub unique_tiles: bool,
+    pending_tile_atlas: Option<mosaic::TileAtlas>, // loaded asynchronously, uploaded next frame
+    _tile_atlas_tex: wgpu::Texture,
+    tile_atlas_view: wgpu::TextureView,
+    tile_index_tex: wgpu::Texture, // Per-seed tile index, same layout as color_lookup_tex
+    tile_index_tex_view: wgpu::TextureView,
 
     ids_a: wgpu::Texture,
     ids_b: wgpu::Texture,
@@ -134,6 +238,8 @@ pub struct ObamifyApp {
     seed_splat_pipeline: wgpu::RenderPipeline,
     jfa_pipeline: wgpu::RenderPipeline,
     shade_pipeline: wgpu::RenderPipeline,
+    #[cfg(not(target_arch = "wasm32"))]
+    instanced: Option<instanced::InstancedMotion>, // draws big grids instead, where it can
 
     // Bind group layouts
     clear_bgl: wgpu::BindGroupLayout,
@@ -161,6 +267,13 @@ pub struct ObamifyApp {
     #[cfg(not(target_arch = "wasm32"))]
     current_drawing_id: Arc<AtomicU32>,
     current_filter_mode: wgpu::FilterMode,
+    render_mode: RenderMode,
+    cvd_filter: CvdFilter,
+    tiled_preview: bool,      // shows the result repeated, to check it tiles
+    provenance_preview: bool, // tints cells by where they came from
+    color_lookup_stale: bool, // the LUT or its preview changed, so seed colors need uploading
+    live_source: Option<live_source::LiveSource>, // shown through the assignment instead
+    source_colors: Option<Vec<SeedColor>>, // the preset's own, while a live source is shown
 
     reverse: bool,
 }
@@ -178,6 +291,10 @@ impl ObamifyApp {
         self.seed_count = seed_count;
         self.seeds = seeds;
         self.sim = sim;
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(instanced) = &mut self.instanced {
+            instanced.upload(device, &self.sim);
+        }
 
         // Update GPU buffers
         self.seed_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -188,16 +305,11 @@ impl ObamifyApp {
 
         // Update seed texture (WebGL compatible)
         let (seed_tex, seed_tex_view) =
-            Self::make_seed_texture(device, queue, &self.seeds, self.seed_count);
+            Self::make_seed_texture(device, queue, &self.seeds, self.seed_count, self.lod);
         self.seed_tex = seed_tex;
         self.seed_tex_view = seed_tex_view;
 
-        let params_common = ParamsCommon {
-            width: self.size.0,
-            height: self.size.1,
-            n_seeds: self.seed_count,
-            _pad: 0,
-        };
+        let params_common = self.params_common();
         self.params_common_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("params_common"),
             contents: bytemuck::bytes_of(&params_common),
@@ -216,7 +328,23 @@ impl ObamifyApp {
         self.color_lookup_tex = color_lookup_tex;
         self.color_lookup_tex_view = color_lookup_tex_view;
 
+        let (tile_index_tex, tile_index_tex_view) = Self::make_tile_index_texture(
+            device,
+            queue,
+            &self.tile_indices(&colors),
+            self.seed_count,
+        );
+        self.tile_index_tex = tile_index_tex;
+        self.tile_index_tex_view = tile_index_tex_view;
+
         *self.colors.write().unwrap() = colors;
+        self.source_colors = None;
+        if let Some(live) = &mut self.live_source {
+            live.refresh(); // shown through the new assignment next frame
+        }
+        if self.preview_graded() {
+            self.write_color_lookup(queue);
+        }
         #[cfg(not(target_arch = "wasm32"))]
         {
             *self.pixeldata.write().unwrap() =
@@ -233,7 +361,23 @@ impl ObamifyApp {
         source: Preset,
         change_index: usize,
     ) {
+        if change_index != self.gui.current_preset {
+            self.gif_recorder.invalidate();
+        }
+        // record the preset's frame, unless a region was picked by hand
+        let region = &mut self.gif_recorder.capture_region;
+        if Aspect::ALL.iter().any(|&a| *region == aspect_region(a)) {
+            *region = aspect_region(source.meta.aspect);
+        }
+        self.tiled_preview = source.meta.tileable;
+        let weights = source.meta.weight_map();
         let (seed_count, mut seeds, colors, mut sim) = morph_sim::init_image(self.size.0, source);
+        sim.focus_on_weights(
+            &weights,
+            preset::WEIGHT_MAP_SIDE,
+            self.size.0,
+            self.gui.motion_focus,
+        );
         sim.prepare_play(&mut seeds, self.reverse);
         self.apply_sim_init(device, queue, seed_count, seeds, colors, sim);
         self.gui.current_preset = change_index;
@@ -247,10 +391,14 @@ impl ObamifyApp {
         source: &UnprocessedPreset,
     ) {
         let (seed_count, seeds, colors, sim) = morph_sim::init_canvas(self.size.0, source.clone());
+        self.live_source = None; // it would paint over the drawing
         self.apply_sim_init(device, queue, seed_count, seeds, colors, sim);
     }
 
-    pub fn new(cc: &CreationContext<'_>) -> Self {
+    pub fn new(
+        cc: &CreationContext<'_>,
+        #[cfg(not(target_arch = "wasm32"))] overrides: &config::Overrides,
+    ) -> Self {
         let rs = cc
             .wgpu_render_state
             .as_ref()
@@ -267,12 +415,41 @@ impl ObamifyApp {
             get_presets()
         };
 
+        let (mut presets, newly_broken) = preset::validate(presets);
+        if presets.is_empty() {
+            presets = get_presets();
+        }
+        let mut broken_presets: Vec<preset::BrokenPreset> = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, "broken_presets"))
+            .unwrap_or_default();
+        broken_presets.extend(newly_broken);
+
         let has_obamified_once = if let Some(storage) = cc.storage {
             eframe::get_value::<bool>(storage, "has_obamified_once").unwrap_or(false)
         } else {
             false
         };
 
+        #[cfg(not(target_arch = "wasm32"))]
+        let config = config::Config::load(overrides);
+        #[cfg(target_arch = "wasm32")]
+        let config = cc
+            .storage
+            .and_then(|storage| eframe::get_value::<String>(storage, "config"))
+            .and_then(|text| config::Config::parse(&text).ok())
+            .unwrap_or_default();
+
+        let mut telemetry: telemetry::Telemetry = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, "telemetry"))
+            .unwrap_or_default();
+        let adapter = rs.adapter.get_info();
+        telemetry.adapter = format!(
+            "{} ({:?}, {:?})",
+            adapter.name, adapter.backend, adapter.device_type
+        );
+
         #[cfg(target_arch = "wasm32")]
         let random_preset = (js_sys::Math::random() * (presets.len() as f64)) as usize;
 
@@ -300,14 +477,28 @@ impl ObamifyApp {
 
         // Create textures for WebGL compatibility (no storage buffers in shaders)
         let (seed_tex, seed_tex_view) =
-            Self::make_seed_texture(device, &rs.queue, &seeds, seed_count);
+            Self::make_seed_texture(device, &rs.queue, &seeds, seed_count, 1);
         let (color_lookup_tex, color_lookup_tex_view) =
             Self::make_color_lookup_texture(device, &rs.queue, &colors, seed_count);
 
+        let emoji_atlas = mosaic::TileAtlas::emoji();
+        let (tile_atlas_tex, tile_atlas_view) =
+            Self::make_tile_atlas_texture(device, &rs.queue, &emoji_atlas);
+        let (tile_index_tex, tile_index_tex_view) = Self::make_tile_index_texture(
+            device,
+            &rs.queue,
+            &emoji_atlas.indices_for(&colors, false),
+            seed_count,
+        );
+
         let params_common = ParamsCommon {
             width: size.0,
             height: size.1,
             n_seeds: seed_count,
+            render_mode: RenderMode::Pixels as u32,
+            cvd_filter: CvdFilter::None as u32,
+            tiled: 0,
+            provenance: 0,
             _pad: 0,
         };
         let params_common_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -471,6 +662,28 @@ impl ObamifyApp {
                     },
                     count: None,
                 },
+                // tile atlas texture
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // tile index texture
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
             ],
         });
 
@@ -742,14 +955,31 @@ impl ObamifyApp {
                     binding: 4,
                     resource: params_common_buf.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(&tile_atlas_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(&tile_index_tex_view),
+                },
             ],
         });
 
         #[cfg(not(target_arch = "wasm32"))]
         let (progress_tx, progress_rx) = mpsc::sync_channel::<ProgressMsg>(1);
 
-        Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        let mut instanced = instanced::InstancedMotion::new(&rs.adapter, device);
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(instanced) = &mut instanced {
+            instanced.upload(device, &sim);
+        }
+
+        let mut app = Self {
             size,
+            lod: 1,
+            shown_px: 0,
             seed_count,
 
             seeds,
@@ -768,6 +998,14 @@ impl ObamifyApp {
             seed_tex_view,
             color_lookup_tex,
             color_lookup_tex_view,
+            emoji_atlas,
+            photo_atlas: None,
+            unique_tiles: false,
+            pending_tile_atlas: None,
+            _tile_atlas_tex: tile_atlas_tex,
+            tile_atlas_view,
+            tile_index_tex,
+            tile_index_tex_view,
             ids_a,
             ids_b,
             ids_a_view,
@@ -778,6 +1016,8 @@ impl ObamifyApp {
             seed_splat_pipeline,
             jfa_pipeline,
             shade_pipeline,
+            #[cfg(not(target_arch = "wasm32"))]
+            instanced,
             clear_bgl,
             seed_bgl,
             jfa_bgl,
@@ -795,10 +1035,13 @@ impl ObamifyApp {
             #[cfg(not(target_arch = "wasm32"))]
             progress_rx,
             gif_recorder: gif_recorder::GifRecorder::new(),
+            telemetry,
+            power: power::PowerState::default(),
+            config,
             preview_image: None,
             #[cfg(not(target_arch = "wasm32"))]
             stroke_count: 0,
-            gui: gui::GuiState::default(presets, random_preset, has_obamified_once),
+            gui: gui::GuiState::default(presets, broken_presets, random_preset, has_obamified_once),
             frame_count: 0,
             #[cfg(not(target_arch = "wasm32"))]
             current_drawing_id: Arc::new(AtomicU32::new(0)),
@@ -807,9 +1050,69 @@ impl ObamifyApp {
             #[cfg(target_arch = "wasm32")]
             inbox: Vec::new(),
             current_filter_mode: wgpu::FilterMode::Linear,
+            render_mode: RenderMode::Pixels,
+            cvd_filter: CvdFilter::None,
+            tiled_preview: false,
+            color_lookup_stale: false,
+            provenance_preview: false,
+            live_source: None,
+            source_colors: None,
 
             reverse: false,
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            app.gif_recorder.quantizer = gpu_quantize::GpuQuantizer::new(&rs.adapter, device);
+        }
+        app.apply_config(&cc.egui_ctx);
+        app
+    }
+
+    /// Whether the transformation is drawn by `instanced` instead of stepping the physics,
+    /// which gets too slow for big grids. Drawing, tiles and motion mattes need the
+    /// physics' positions and speeds, and devices without storage buffers in vertex
+    /// shaders can only step it.
+    fn instanced_motion(&self) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.instanced.is_some()
+                && self.seed_count >= instanced::MIN_CELLS
+                && self.render_mode == RenderMode::Pixels
+                && matches!(self.gui.mode, GuiMode::Transform)
+                && self.gif_recorder.png_dir.is_none()
+        }
+        #[cfg(target_arch = "wasm32")]
+        false
+    }
+
+    /// Moves the transformation on by a frame.
+    pub(crate) fn step_sim(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let (true, Some(instanced)) = (self.instanced_motion(), &mut self.instanced) {
+            instanced.frame += 1;
+            return;
         }
+        self.sim.update(&mut self.seeds, self.size.0);
+    }
+
+    /// Whether the transformation has played out, with every cell at its destination.
+    pub(crate) fn animation_arrived(&self) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let (true, Some(instanced)) = (self.instanced_motion(), &self.instanced) {
+            return instanced.arrived();
+        }
+        self.sim.arrived(&self.seeds, self.size.0)
+    }
+
+    /// Puts every cell back at its start, ready to play the transformation.
+    pub(crate) fn prepare_play(&mut self, device: &wgpu::Device) {
+        self.sim.prepare_play(&mut self.seeds, self.reverse);
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(instanced) = &mut self.instanced {
+            instanced.upload(device, &self.sim);
+        }
+        #[cfg(target_arch = "wasm32")]
+        let _ = device;
     }
 
     pub fn get_latest_msg(&mut self) -> Option<ProgressMsg> {
@@ -1007,6 +1310,7 @@ impl ObamifyApp {
         queue: &wgpu::Queue,
         seeds: &[SeedPos],
         max_seeds: u32,
+        lod: u32,
     ) -> (wgpu::Texture, wgpu::TextureView) {
         // Pack seeds into a 2D texture to respect WebGL texture size limits (typically 2048-4096)
         // Use a square-ish layout: width = 1024, height = ceil(max_seeds / 1024)
@@ -1031,8 +1335,8 @@ impl ObamifyApp {
         // Upload seed data to texture (packed in 2D)
         let mut data = vec![0.0f32; (TEX_WIDTH * tex_height * 2) as usize];
         for (i, seed) in seeds.iter().enumerate() {
-            data[i * 2] = seed.xy[0];
-            data[i * 2 + 1] = seed.xy[1];
+            data[i * 2] = seed.xy[0] / lod as f32;
+            data[i * 2 + 1] = seed.xy[1] / lod as f32;
         }
 
         queue.write_texture(
@@ -1066,8 +1370,8 @@ impl ObamifyApp {
 
         let mut data = vec![0.0f32; (TEX_WIDTH * tex_height * 2) as usize];
         for (i, seed) in seeds.iter().enumerate() {
-            data[i * 2] = seed.xy[0];
-            data[i * 2 + 1] = seed.xy[1];
+            data[i * 2] = seed.xy[0] / self.lod as f32;
+            data[i * 2 + 1] = seed.xy[1] / self.lod as f32;
         }
 
         queue.write_texture(
@@ -1149,6 +1453,206 @@ impl ObamifyApp {
         (tex, view)
     }
 
+    fn make_tile_atlas_texture(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        atlas: &mosaic::TileAtlas,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let tex = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("tile_atlas"),
+                size: wgpu::Extent3d {
+                    width: atlas.image.width(),
+                    height: atlas.image.height(),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            atlas.image.as_raw(),
+        );
+        let view = tex.create_view(&wgpu::TextureViewDescriptor::default());
+        (tex, view)
+    }
+
+    fn make_tile_index_texture(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        indices: &[u16],
+        max_seeds: u32,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        // Same packing as the color lookup texture, index stored little-endian in red/green
+        const TEX_WIDTH: u32 = 1024;
+        let tex_height = max_seeds.div_ceil(TEX_WIDTH);
+
+        let tex = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("tile_index"),
+            size: wgpu::Extent3d {
+                width: TEX_WIDTH,
+                height: tex_height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        Self::write_tile_indices(queue, &tex, indices, max_seeds);
+
+        let view = tex.create_view(&wgpu::TextureViewDescriptor::default());
+        (tex, view)
+    }
+
+    fn write_tile_indices(
+        queue: &wgpu::Queue,
+        tex: &wgpu::Texture,
+        indices: &[u16],
+        max_seeds: u32,
+    ) {
+        const TEX_WIDTH: u32 = 1024;
+        let tex_height = max_seeds.div_ceil(TEX_WIDTH);
+
+        let mut data = vec![0u8; (TEX_WIDTH * tex_height * 4) as usize];
+        for (i, index) in indices.iter().enumerate() {
+            data[i * 4..i * 4 + 2].copy_from_slice(&index.to_le_bytes());
+        }
+
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: tex,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &data,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(TEX_WIDTH * 4),
+                rows_per_image: Some(tex_height),
+            },
+            wgpu::Extent3d {
+                width: TEX_WIDTH,
+                height: tex_height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    fn active_atlas(&self) -> &mosaic::TileAtlas {
+        match (self.render_mode, &self.photo_atlas) {
+            (RenderMode::Photomosaic, Some(atlas)) => atlas,
+            _ => &self.emoji_atlas,
+        }
+    }
+
+    fn tile_indices(&self, colors: &[SeedColor]) -> Vec<u16> {
+        let unique = self.unique_tiles && self.render_mode == RenderMode::Photomosaic;
+        self.active_atlas().indices_for(colors, unique)
+    }
+
+    /// Re-uploads the atlas and per-seed tile indices after the render mode or tile
+    /// library changes.
+    pub(crate) fn refresh_tiles(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let (tile_atlas_tex, tile_atlas_view) =
+            Self::make_tile_atlas_texture(device, queue, self.active_atlas());
+        self._tile_atlas_tex = tile_atlas_tex;
+        self.tile_atlas_view = tile_atlas_view;
+        let indices = self.tile_indices(&self.colors.read().unwrap());
+        Self::write_tile_indices(queue, &self.tile_index_tex, &indices, self.seed_count);
+        self.rebuild_bind_groups(device);
+    }
+
+    pub(crate) fn set_photo_atlas(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        atlas: mosaic::TileAtlas,
+    ) {
+        self.photo_atlas = Some(atlas);
+        self.refresh_tiles(device, queue);
+    }
+
+    fn params_common(&self) -> ParamsCommon {
+        // recordings always get the real colors, and a single tile
+        let recording = !self.gif_recorder.not_recording();
+        let cvd_filter = if recording {
+            CvdFilter::None
+        } else {
+            self.cvd_filter
+        };
+        let (width, height) = self.render_size();
+        ParamsCommon {
+            width,
+            height,
+            n_seeds: self.seed_count,
+            render_mode: self.render_mode as u32,
+            cvd_filter: cvd_filter as u32,
+            tiled: (self.tiled_preview && !recording) as u32,
+            provenance: (self.provenance_preview && !recording) as u32,
+            _pad: 0,
+        }
+    }
+
+    pub(crate) fn set_cvd_filter(&mut self, queue: &wgpu::Queue, filter: CvdFilter) {
+        self.cvd_filter = filter;
+        queue.write_buffer(
+            &self.params_common_buf,
+            0,
+            bytemuck::bytes_of(&self.params_common()),
+        );
+    }
+
+    pub(crate) fn set_provenance_preview(&mut self, queue: &wgpu::Queue, provenance: bool) {
+        self.provenance_preview = provenance;
+        queue.write_buffer(
+            &self.params_common_buf,
+            0,
+            bytemuck::bytes_of(&self.params_common()),
+        );
+    }
+
+    pub(crate) fn set_tiled_preview(&mut self, queue: &wgpu::Queue, tiled: bool) {
+        self.tiled_preview = tiled;
+        queue.write_buffer(
+            &self.params_common_buf,
+            0,
+            bytemuck::bytes_of(&self.params_common()),
+        );
+    }
+
+    pub(crate) fn set_render_mode(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mode: RenderMode,
+    ) {
+        self.render_mode = mode;
+        self.refresh_tiles(device, queue);
+        self.write_color_lookup(queue);
+        let params_common = self.params_common();
+        queue.write_buffer(
+            &self.params_common_buf,
+            0,
+            bytemuck::bytes_of(&params_common),
+        );
+    }
+
+    /// Colors that can appear in the rendered output, used to build export palettes.
+    pub(crate) fn render_palette(&self) -> Vec<SeedColor> {
+        match self.render_mode {
+            RenderMode::Pixels => self.preview_colors(),
+            RenderMode::Emoji | RenderMode::Photomosaic => self.active_atlas().palette_colors(),
+        }
+    }
+
     fn ensure_registered_texture(
         &mut self,
         rs: &egui_wgpu::RenderState,
@@ -1259,16 +1763,55 @@ impl ObamifyApp {
                     binding: 4,
                     resource: self.params_common_buf.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(&self.tile_atlas_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(&self.tile_index_tex_view),
+                },
             ],
         });
     }
 
+    /// How many pixels each way the preview merges into one. Small previews don't need
+    /// every pixel, but recordings always get them.
+    fn wanted_lod(&self) -> u32 {
+        if !self.gif_recorder.not_recording()
+            || self.gif_recorder.snapshot_pending()
+            || self.shown_px == 0
+        {
+            return 1;
+        }
+        [4, 2]
+            .into_iter()
+            .find(|&lod| self.size.0 / lod >= self.shown_px)
+            .unwrap_or(1)
+    }
+
+    /// The size the preview is rendered at, which is smaller than the sim's at a coarse
+    /// level of detail.
+    pub(crate) fn render_size(&self) -> (u32, u32) {
+        (self.size.0 / self.lod, self.size.1 / self.lod)
+    }
+
+    /// Switches the level of detail if the preview's size on screen calls for another.
+    pub(crate) fn update_lod(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if self.wanted_lod() != self.lod {
+            self.resize_textures(device, self.size, true);
+            self.update_seed_texture_data(queue, &self.seeds);
+        }
+    }
+
     fn resize_textures(&mut self, device: &wgpu::Device, new_size: (u32, u32), rebuild_bg: bool) {
         self.size = new_size;
+        self.lod = self.wanted_lod();
+        let render_size = self.render_size();
         // Recreate textures
-        let (ids_a, ids_a_view) = Self::make_ids_texture(device, self.size, Some("ids_a"));
-        let (ids_b, ids_b_view) = Self::make_ids_texture(device, self.size, Some("ids_b"));
-        let (color_tex, color_view) = Self::make_color_texture(device, self.size, Some("color"));
+        let (ids_a, ids_a_view) = Self::make_ids_texture(device, render_size, Some("ids_a"));
+        let (ids_b, ids_b_view) = Self::make_ids_texture(device, render_size, Some("ids_b"));
+        let (color_tex, color_view) = Self::make_color_texture(device, render_size, Some("color"));
         self.ids_a = ids_a;
         self.ids_a_view = ids_a_view;
         self.ids_b = ids_b;
@@ -1277,12 +1820,7 @@ impl ObamifyApp {
         self.color_view = color_view;
 
         // Update params_common
-        let params_common = ParamsCommon {
-            width: self.size.0,
-            height: self.size.1,
-            n_seeds: self.seed_count,
-            _pad: 0,
-        };
+        let params_common = self.params_common();
         self.params_common_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("params_common"),
             contents: bytemuck::bytes_of(&params_common),
@@ -1290,8 +1828,8 @@ impl ObamifyApp {
         });
 
         let params_jfa = ParamsJfa {
-            width: self.size.0,
-            height: self.size.1,
+            width: render_size.0,
+            height: render_size.1,
             step: 1,
             _pad: 0,
         };
@@ -1317,97 +1855,44 @@ impl ObamifyApp {
             label: Some("voronoi_jfa_encoder"),
         });
 
-        // 1) Clear ID texture A (where we'll splat seeds)
-        {
-            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("clear_ids_a"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &self.ids_a_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-            rpass.set_pipeline(&self.clear_pipeline);
-            rpass.set_bind_group(0, &self.clear_bg_a, &[]);
-            rpass.draw(0..4, 0..1);
-        }
-
-        // 2) Seed splat into A
-        {
-            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("seed_splat"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &self.ids_a_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-            rpass.set_pipeline(&self.seed_splat_pipeline);
-            rpass.set_bind_group(0, &self.seed_bg, &[]);
-            rpass.draw(0..self.seed_count, 0..1);
-        }
-
-        // 3) JFA passes, ping-pong A<->B
-
-        let max_dim = self.size.0.max(self.size.1);
-        let mut step = 1u32;
-        while step < max_dim {
-            step <<= 1;
-        }
-        step >>= 1;
-
         let mut flip = false;
-        let mut is_first_jfa_pass = true;
-        while step >= 1 {
-            let pj = ParamsJfa {
-                width: self.size.0,
-                height: self.size.1,
-                step,
-                _pad: 0,
-            };
-            let staging = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("params_jfa_staging"),
-                contents: bytemuck::bytes_of(&pj),
-                usage: wgpu::BufferUsages::COPY_SRC,
-            });
-            encoder.copy_buffer_to_buffer(
-                &staging,
-                0,
-                &self.params_jfa_buf,
-                0,
-                std::mem::size_of::<ParamsJfa>() as u64,
-            );
+        if self.instanced_motion() {
+            // big grids: each cell's quad is drawn where it is, no splat or flood needed
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(instanced) = &self.instanced {
+                instanced.draw(&rs.queue, &mut encoder, &self.ids_a_view, self.size);
+            }
+        } else {
+            // 1) Clear ID texture A (where we'll splat seeds)
             {
-                // On first pass writing to B, clear it. After that, always load previous content.
-                let load_op = if is_first_jfa_pass && !flip {
-                    wgpu::LoadOp::Clear(wgpu::Color::WHITE)
-                } else {
-                    wgpu::LoadOp::Load
-                };
-
                 let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: Some("jfa_step"),
+                    label: Some("clear_ids_a"),
                     color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                        view: if !flip {
-                            &self.ids_b_view
-                        } else {
-                            &self.ids_a_view
+                        view: &self.ids_a_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                            store: wgpu::StoreOp::Store,
                         },
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+                rpass.set_pipeline(&self.clear_pipeline);
+                rpass.set_bind_group(0, &self.clear_bg_a, &[]);
+                rpass.draw(0..4, 0..1);
+            }
+
+            // 2) Seed splat into A
+            {
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("seed_splat"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &self.ids_a_view,
                         resolve_target: None,
                         ops: wgpu::Operations {
-                            load: load_op,
+                            load: wgpu::LoadOp::Load,
                             store: wgpu::StoreOp::Store,
                         },
                     })],
@@ -1415,63 +1900,125 @@ impl ObamifyApp {
                     timestamp_writes: None,
                     occlusion_query_set: None,
                 });
-                rpass.set_pipeline(&self.jfa_pipeline);
-                rpass.set_bind_group(
+                rpass.set_pipeline(&self.seed_splat_pipeline);
+                rpass.set_bind_group(0, &self.seed_bg, &[]);
+                rpass.draw(0..self.seed_count, 0..1);
+            }
+
+            // 3) JFA passes, ping-pong A<->B
+
+            let render_size = self.render_size();
+            let max_dim = render_size.0.max(render_size.1);
+            let mut step = 1u32;
+            while step < max_dim {
+                step <<= 1;
+            }
+            step >>= 1;
+
+            let mut is_first_jfa_pass = true;
+            while step >= 1 {
+                let pj = ParamsJfa {
+                    width: render_size.0,
+                    height: render_size.1,
+                    step,
+                    _pad: 0,
+                };
+                let staging = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("params_jfa_staging"),
+                    contents: bytemuck::bytes_of(&pj),
+                    usage: wgpu::BufferUsages::COPY_SRC,
+                });
+                encoder.copy_buffer_to_buffer(
+                    &staging,
                     0,
-                    if !flip {
-                        &self.jfa_bg_a_to_b
-                    } else {
-                        &self.jfa_bg_b_to_a
-                    },
-                    &[],
+                    &self.params_jfa_buf,
+                    0,
+                    std::mem::size_of::<ParamsJfa>() as u64,
                 );
-                rpass.draw(0..4, 0..1);
+                {
+                    // On first pass writing to B, clear it. After that, always load previous content.
+                    let load_op = if is_first_jfa_pass && !flip {
+                        wgpu::LoadOp::Clear(wgpu::Color::WHITE)
+                    } else {
+                        wgpu::LoadOp::Load
+                    };
+
+                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("jfa_step"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: if !flip {
+                                &self.ids_b_view
+                            } else {
+                                &self.ids_a_view
+                            },
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: load_op,
+                                store: wgpu::StoreOp::Store,
+                            },
+                        })],
+                        depth_stencil_attachment: None,
+                        timestamp_writes: None,
+                        occlusion_query_set: None,
+                    });
+                    rpass.set_pipeline(&self.jfa_pipeline);
+                    rpass.set_bind_group(
+                        0,
+                        if !flip {
+                            &self.jfa_bg_a_to_b
+                        } else {
+                            &self.jfa_bg_b_to_a
+                        },
+                        &[],
+                    );
+                    rpass.draw(0..4, 0..1);
+                }
+                is_first_jfa_pass = false;
+                flip = !flip;
+                step >>= 1;
             }
-            is_first_jfa_pass = false;
-            flip = !flip;
-            step >>= 1;
-        }
 
-        // if self.refined {
-        //     for _ in 0..2 {
-        //         let pj = ParamsJfa {
-        //             width: self.size.0,
-        //             height: self.size.1,
-        //             step: 1,
-        //             _pad: 0,
-        //         };
-        //         let staging = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        //             label: Some("params_jfa_staging"),
-        //             contents: bytemuck::bytes_of(&pj),
-        //             usage: wgpu::BufferUsages::COPY_SRC,
-        //         });
-        //         encoder.copy_buffer_to_buffer(
-        //             &staging,
-        //             0,
-        //             &self.params_jfa_buf,
-        //             0,
-        //             std::mem::size_of::<ParamsJfa>() as u64,
-        //         );
-        //         {
-        //             let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-        //                 label: Some("jfa_step"),
-        //                 timestamp_writes: None,
-        //             });
-        //             cpass.set_pipeline(&self.jfa_pipeline);
-        //             cpass.set_bind_group(
-        //                 0,
-        //                 if !flip {
-        //                     &self.jfa_bg_a_to_b
-        //                 } else {
-        //                     &self.jfa_bg_b_to_a
-        //                 },
-        //                 &[],
-        //             );
-        //             cpass.dispatch_workgroups(groups_x, groups_y, 1);
-        //         }
-        //         flip = !flip;
-        //     }
-        // }
+            // if self.refined {
+            //     for _ in 0..2 {
+            //         let pj = ParamsJfa {
+            //             width: self.size.0,
+            //             height: self.size.1,
+            //             step: 1,
+            //             _pad: 0,
+            //         };
+            //         let staging = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            //             label: Some("params_jfa_staging"),
+            //             contents: bytemuck::bytes_of(&pj),
+            //             usage: wgpu::BufferUsages::COPY_SRC,
+            //         });
+            //         encoder.copy_buffer_to_buffer(
+            //             &staging,
+            //             0,
+            //             &self.params_jfa_buf,
+            //             0,
+            //             std::mem::size_of::<ParamsJfa>() as u64,
+            //         );
+            //         {
+            //             let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            //                 label: Some("jfa_step"),
+            //                 timestamp_writes: None,
+            //             });
+            //             cpass.set_pipeline(&self.jfa_pipeline);
+            //             cpass.set_bind_group(
+            //                 0,
+            //                 if !flip {
+            //                     &self.jfa_bg_a_to_b
+            //                 } else {
+            //                     &self.jfa_bg_b_to_a
+            //                 },
+            //                 &[],
+            //             );
+            //             cpass.dispatch_workgroups(groups_x, groups_y, 1);
+            //         }
+            //         flip = !flip;
+            //     }
+            // }
+        }
 
         // 4) Shade to color (the final IDs are in A if flip is true, else in B).
         // Our shade BG was built with ids_a_view at binding 0. If the last write ended in B,
@@ -1502,6 +2049,14 @@ impl ObamifyApp {
                         binding: 4,
                         resource: self.params_common_buf.as_entire_binding(),
                     },
+                    wgpu::BindGroupEntry {
+                        binding: 5,
+                        resource: wgpu::BindingResource::TextureView(&self.tile_atlas_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 6,
+                        resource: wgpu::BindingResource::TextureView(&self.tile_index_tex_view),
+                    },
                 ],
             });
             let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -1547,11 +2102,228 @@ impl ObamifyApp {
 
     fn stop_recording_gif(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
         self.gif_recorder.stop();
+        self.gif_recorder.limits = self.config.gif; // undoes any export profile
+        self.gui.quantized_texture = None;
         self.gui.animate = false;
         self.resize_textures(device, (DEFAULT_RESOLUTION, DEFAULT_RESOLUTION), false);
         self.reset_sim(device, queue);
     }
 
+    /// Pushes the config's defaults into the parts of the app that use them.
+    fn apply_config(&mut self, ctx: &egui::Context) {
+        self.gif_recorder.limits = self.config.gif;
+        self.gif_recorder.output = self.config.output();
+        ctx.set_theme(self.config.theme.preference());
+    }
+
+    /// Records and saves a single gif. `now` is the egui time, for telemetry.
+    fn start_gif(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, now: f64) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.gif_recorder.stream {
+            let name = self.gif_recorder.get_name(self.sim.name(), self.reverse);
+            let Some(path) =
+                self.config
+                    .output()
+                    .save_path("save gif", &format!("{}.gif", name), "gif")
+            else {
+                return;
+            };
+            self.gif_recorder.stream_to = Some(path);
+        }
+        self.gif_recorder.start_takes(1);
+        self.telemetry
+            .start_export("gif", self.gif_recorder.render_size(), now);
+        self.begin_recording(device, queue, now);
+    }
+
+    /// Asks for a folder and records an rgba png sequence into it.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn start_png_frames(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, now: f64) {
+        let name = self.gif_recorder.get_name(self.sim.name(), self.reverse);
+        if let Some(dir) = self
+            .config
+            .output()
+            .folder("choose folder for frames", &format!("{}_frames", name))
+        {
+            self.gif_recorder.start_takes(1);
+            self.gif_recorder.png_dir = Some(dir);
+            self.telemetry
+                .start_export("png frames", self.gif_recorder.render_size(), now);
+            self.begin_recording(device, queue, now);
+        }
+    }
+
+    /// Records and saves a webm of the transformation.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn start_video(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, now: f64) {
+        self.gif_recorder.start_takes(1);
+        self.gif_recorder.record_video = true;
+        self.telemetry
+            .start_export("video", self.gif_recorder.render_size(), now);
+        self.begin_recording(device, queue, now);
+    }
+
+    /// Records every frame as a png and saves them together in a zip.
+    fn start_png_zip(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, now: f64) {
+        self.gif_recorder.start_takes(1);
+        self.gif_recorder.png_zip = Some(ZipWriter::default());
+        self.telemetry
+            .start_export("png zip", self.gif_recorder.render_size(), now);
+        self.begin_recording(device, queue, now);
+    }
+
+    /// Records the transformation and saves a few of its frames tiled into a sprite sheet.
+    fn start_sprite_sheet(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, now: f64) {
+        self.gif_recorder.start_takes(1);
+        self.gif_recorder.sprite_frames = Some(Vec::new());
+        self.telemetry
+            .start_export("sprite sheet", self.gif_recorder.render_size(), now);
+        self.begin_recording(device, queue, now);
+    }
+
+    /// Records and saves a full color apng.
+    fn start_apng(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, now: f64) {
+        self.gif_recorder.start_takes(1);
+        self.gif_recorder.apng = Some(Vec::new());
+        self.telemetry
+            .start_export("apng", self.gif_recorder.render_size(), now);
+        self.begin_recording(device, queue, now);
+    }
+
+    /// Exports the current preset the way `profile` says.
+    fn start_profile_export(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        profile: &ExportProfile,
+        now: f64,
+    ) {
+        self.gif_recorder.limits = profile.limits(self.config.gif);
+        match profile.format {
+            #[cfg(not(target_arch = "wasm32"))]
+            gif_recorder::ExportFormat::PngFrames => self.start_png_frames(device, queue, now),
+            #[cfg(not(target_arch = "wasm32"))]
+            gif_recorder::ExportFormat::Video => self.start_video(device, queue, now),
+            gif_recorder::ExportFormat::Apng => self.start_apng(device, queue, now),
+            gif_recorder::ExportFormat::PngZip => self.start_png_zip(device, queue, now),
+            gif_recorder::ExportFormat::SpriteSheet => self.start_sprite_sheet(device, queue, now),
+            _ => self.start_gif(device, queue, now),
+        }
+        if self.gif_recorder.not_recording() {
+            self.gif_recorder.limits = self.config.gif; // the folder dialog was cancelled
+        }
+    }
+
+    /// Starts recording the first take, or waits out the start delay asked for at `now`.
+    fn begin_recording(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, now: f64) {
+        if !self.gif_recorder.delay_start(now) {
+            self.start_recording_take(device, queue);
+        }
+    }
+
+    /// Starts recording the next take, each one with a different stagger seed.
+    fn start_recording_take(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.gif_recorder.encoder = None;
+        let palette = self.render_palette();
+        let graded = self.preview_graded();
+        if let Err(err) = self.gif_recorder.init_encoder(&palette, graded) {
+            self.gif_recorder.status = gif_recorder::GifStatus::Error(err.to_string());
+        } else {
+            let size = self.gif_recorder.render_size();
+            self.resize_textures(device, (size, size), false);
+            self.reset_sim(device, queue);
+            self.sim.stagger(self.gif_recorder.takes.len() as u64);
+            self.gui.animate = true;
+            for _ in 0..20 {
+                self.step_sim();
+            }
+        }
+    }
+
+    /// Whether the preview shows the LUT's grading, in which case captured frames
+    /// already have it. Only plain pixels are graded; tiles keep their own colors.
+    fn preview_graded(&self) -> bool {
+        self.render_mode == RenderMode::Pixels
+            && self.gif_recorder.lut_preview
+            && self.gif_recorder.lut.is_some()
+    }
+
+    /// The seed colors as the preview shows them.
+    fn preview_colors(&self) -> Vec<SeedColor> {
+        let colors = self.colors.read().unwrap();
+        match &self.gif_recorder.lut {
+            Some(lut) if self.preview_graded() => lut.apply_colors(&colors),
+            _ => colors.clone(),
+        }
+    }
+
+    /// Uploads the colors the shaders look seeds up in.
+    fn write_color_lookup(&self, queue: &wgpu::Queue) {
+        const TEX_WIDTH: u32 = 1024;
+        let tex_height = self.seed_count.div_ceil(TEX_WIDTH);
+
+        let colors = self.preview_colors();
+        let mut data = vec![0.0f32; (TEX_WIDTH * tex_height * 4) as usize];
+        for (i, color) in colors.iter().enumerate() {
+            data[i * 4..i * 4 + 4].copy_from_slice(&color.rgba);
+        }
+
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.color_lookup_tex,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(&data),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(TEX_WIDTH * 16), // 4 floats * 4 bytes per pixel
+                rows_per_image: Some(tex_height),
+            },
+            wgpu::Extent3d {
+                width: TEX_WIDTH,
+                height: tex_height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Makes cells transparent unless they're moving, for compositing exports.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn write_motion_alpha(&self, queue: &wgpu::Queue) {
+        const TEX_WIDTH: u32 = 1024;
+        const OPAQUE_SPEED: f32 = 0.5; // pixels per frame
+        let tex_height = self.seed_count.div_ceil(TEX_WIDTH);
+
+        let colors = self.preview_colors();
+        let mut data = vec![0.0f32; (TEX_WIDTH * tex_height * 4) as usize];
+        for (i, (color, cell)) in colors.iter().zip(&self.sim.cells).enumerate() {
+            data[i * 4..i * 4 + 3].copy_from_slice(&color.rgba[..3]);
+            data[i * 4 + 3] = (cell.speed() / OPAQUE_SPEED).min(1.0);
+        }
+
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.color_lookup_tex,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(&data),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(TEX_WIDTH * 16), // 4 floats * 4 bytes per pixel
+                rows_per_image: Some(tex_height),
+            },
+            wgpu::Extent3d {
+                width: TEX_WIDTH,
+                height: tex_height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
     fn reset_sim(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
         self.change_sim(
             device,
@@ -1621,47 +2393,119 @@ impl ObamifyApp {
             }
         }
 
+        self.upload_colors(device, queue);
+    }
+
+    /// Sends the seed colors, after they were changed in place, to everything drawing them.
+    fn upload_colors(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
         // Update the color lookup texture with modified colors
-        const TEX_WIDTH: u32 = 1024;
-        let tex_height = self.seed_count.div_ceil(TEX_WIDTH);
+        self.write_color_lookup(queue);
 
         let colors = self.colors.read().unwrap();
-        let mut data = vec![0.0f32; (TEX_WIDTH * tex_height * 4) as usize];
-        for (i, color) in colors.iter().enumerate() {
-            data[i * 4] = color.rgba[0];
-            data[i * 4 + 1] = color.rgba[1];
-            data[i * 4 + 2] = color.rgba[2];
-            data[i * 4 + 3] = color.rgba[3];
-        }
 
-        queue.write_texture(
-            wgpu::TexelCopyTextureInfo {
-                texture: &self.color_lookup_tex,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            bytemuck::cast_slice(&data),
-            wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(TEX_WIDTH * 16), // 4 floats * 4 bytes per pixel
-                rows_per_image: Some(tex_height),
-            },
-            wgpu::Extent3d {
-                width: TEX_WIDTH,
-                height: tex_height,
-                depth_or_array_layers: 1,
-            },
-        );
+        if self.render_mode != RenderMode::Pixels {
+            Self::write_tile_indices(
+                queue,
+                &self.tile_index_tex,
+                &self.tile_indices(&colors),
+                self.seed_count,
+            );
+        }
 
         // Keep the buffer for backward compatibility if needed elsewhere
         self.color_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("colors"),
-            contents: bytemuck::cast_slice(&*colors),
+            contents: bytemuck::cast_slice(&colors),
             usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
         });
     }
 
+    /// Starts showing `source` through the current assignment in place of the preset's
+    /// own colors, from the next frame.
+    pub fn set_live_source(&mut self, source: live_source::LiveSource) {
+        self.live_source = Some(source);
+    }
+
+    /// Goes back to the preset's own colors.
+    pub fn clear_live_source(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.live_source = None;
+        if let Some(colors) = self.source_colors.take() {
+            *self.colors.write().unwrap() = colors;
+            self.upload_colors(device, queue);
+        }
+    }
+
+    /// Recolors the seeds with the live source's current picture, if it has a new one.
+    /// Each seed takes the color at the spot it starts from, so the picture morphs the
+    /// way the preset's source does, without solving again. A source that fails is stopped.
+    fn poll_live_source(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        now: f64,
+    ) -> Result<(), String> {
+        let Some(live) = &mut self.live_source else {
+            return Ok(());
+        };
+        let side = (self.seed_count as f64).sqrt() as u32;
+        let colors = match live.poll(now) {
+            Ok(Some(img)) => live_source::seed_colors(img, side),
+            Ok(None) => return Ok(()),
+            Err(err) => {
+                self.clear_live_source(device, queue);
+                return Err(err);
+            }
+        };
+        if colors.len() != self.seed_count as usize {
+            return Ok(()); // not a square grid
+        }
+        let previous = std::mem::replace(&mut *self.colors.write().unwrap(), colors);
+        self.source_colors.get_or_insert(previous);
+        self.upload_colors(device, queue);
+        Ok(())
+    }
+
+    /// Lets the user drag out the part of the animation to record, and outlines it.
+    fn handle_capture_region(&mut self, ui: &mut egui::Ui, image: &egui::Response) {
+        let rect = image.rect;
+        let to_uv = |pos: egui::Pos2| {
+            let uv = (pos - rect.min) / rect.size();
+            egui::pos2(uv.x.clamp(0.0, 1.0), uv.y.clamp(0.0, 1.0))
+        };
+
+        if self.gui.selecting_capture_region {
+            let response = ui.interact(rect, ui.id().with("capture_region"), egui::Sense::drag());
+            if let Some(pos) = response.interact_pointer_pos() {
+                if response.drag_started() {
+                    self.gui.capture_drag_start = Some(to_uv(pos));
+                }
+                if let Some(start) = self.gui.capture_drag_start {
+                    let region = egui::Rect::from_two_pos(start, to_uv(pos));
+                    if region.width() > 0.05 && region.height() > 0.05 {
+                        self.gif_recorder.capture_region = Some(region);
+                    }
+                }
+            }
+            if response.drag_stopped() {
+                self.gui.capture_drag_start = None;
+                self.gui.selecting_capture_region = false;
+            }
+        }
+
+        if let Some(region) = self.gif_recorder.capture_region {
+            let screen = egui::Rect::from_min_max(
+                rect.min + region.min.to_vec2() * rect.size(),
+                rect.min + region.max.to_vec2() * rect.size(),
+            );
+            ui.painter().rect_stroke(
+                screen,
+                0.0,
+                egui::Stroke::new(2.0, egui::Color32::LIGHT_RED),
+                egui::StrokeKind::Outside,
+            );
+        }
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     fn handle_drawing(
         &mut self,
@@ -1778,6 +2622,13 @@ fn point_to_line_dist(px: f32, py: f32, x0: f32, y0: f32, x1: f32, y1: f32) -> f
     }
 }
 
+/// Part of the animation a preset with this aspect is recorded from.
+fn aspect_region(aspect: Aspect) -> Option<egui::Rect> {
+    let (w, h) = aspect.frame();
+    (aspect != Aspect::Square)
+        .then(|| egui::Rect::from_center_size(egui::pos2(0.5, 0.5), egui::vec2(w, h)))
+}
+
 macro_rules! include_presets {
     ($($name:literal),*) => {
         fn get_presets() -> Vec<Preset> {
@@ -1797,15 +2648,15 @@ macro_rules! include_presets {
                             height: img.height(),
                             source_img: img.into_raw(),
                         },
-                        assignments: include_str!(concat!("../presets/", $name, "/assignments.json"))
-                            .to_string()
-                            .strip_prefix('[')
-                            .unwrap()
-                            .strip_suffix(']')
-                            .unwrap()
-                            .split(',')
-                            .map(|s| s.parse().unwrap())
-                            .collect::<Vec<usize>>(),
+                        assignments: assignments::decode(include_bytes!(concat!(
+                            "../presets/",
+                            $name,
+                            "/assignments.bin"
+                        )))
+                        .unwrap(),
+                        stats: Vec::new(),
+                        quality: None,
+                        meta: PresetMeta::default(),
                     }
                 }),*
             ]
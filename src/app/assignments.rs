@@ -0,0 +1,375 @@
+//! Compact binary storage for assignments.
+//!
+//! Layout, all integers little endian:
+//! `b"OBAS"`, version (u8), entry count (u32), entries per chunk (u32), chunk count (u32),
+//! then `chunk count + 1` u32 offsets into the chunk data, then the chunks. Each chunk is
+//! the deflated zigzag varints of `assignment - index`, which are small since most pixels
+//! don't travel far. Chunks decode independently, so big files can be read piecemeal.
+
+use std::error::Error;
+use std::io::{Read, Write};
+
+const MAGIC: &[u8; 4] = b"OBAS";
+const VERSION: u8 = 1;
+pub const CHUNK_LEN: usize = 1 << 16;
+const HEADER_LEN: usize = 17;
+// deflate inflates at most this much, and every entry takes at least one varint byte
+const MAX_DEFLATE_RATIO: usize = 1032;
+const MAX_VARINT_LEN: usize = 10;
+
+pub fn encode(assignments: &[usize]) -> Vec<u8> {
+    let mut chunks = Vec::new();
+    let mut offsets = vec![0u32];
+    for (chunk_index, chunk) in assignments.chunks(CHUNK_LEN).enumerate() {
+        let mut varints = Vec::with_capacity(chunk.len() * 2);
+        for (i, &a) in chunk.iter().enumerate() {
+            let index = (chunk_index * CHUNK_LEN + i) as i64;
+            write_varint(&mut varints, zigzag(a as i64 - index));
+        }
+        let mut encoder =
+            flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(&varints).unwrap(); // writing to a vec can't fail
+        chunks.extend(encoder.finish().unwrap());
+        offsets.push(chunks.len() as u32);
+    }
+
+    let mut out = Vec::with_capacity(HEADER_LEN + offsets.len() * 4 + chunks.len());
+    out.extend(MAGIC);
+    out.push(VERSION);
+    out.extend((assignments.len() as u32).to_le_bytes());
+    out.extend((CHUNK_LEN as u32).to_le_bytes());
+    out.extend(((offsets.len() - 1) as u32).to_le_bytes());
+    for offset in offsets {
+        out.extend(offset.to_le_bytes());
+    }
+    out.extend(chunks);
+    out
+}
+
+/// The header of an encoded file, with where each chunk's data is.
+pub struct Header {
+    pub count: usize,
+    pub chunk_len: usize,
+    offsets: Vec<usize>, // absolute, one more than there are chunks
+}
+
+impl Header {
+    pub fn parse(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if data.len() < HEADER_LEN || &data[..4] != MAGIC {
+            return Err("not an assignments file".into());
+        }
+        if data[4] > VERSION {
+            return Err("assignments file is from a newer version of obamify".into());
+        }
+        let u32_at = |pos: usize| -> Result<usize, Box<dyn Error>> {
+            let bytes = data
+                .get(pos..pos + 4)
+                .ok_or("assignments file is truncated")?;
+            Ok(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+        };
+        let count = u32_at(5)?;
+        let chunk_len = u32_at(9)?;
+        let chunk_count = u32_at(13)?;
+        if chunk_len == 0 || count.div_ceil(chunk_len) != chunk_count {
+            return Err("assignments file has an invalid header".into());
+        }
+        let data_start = HEADER_LEN + (chunk_count + 1) * 4;
+        let offsets = (0..=chunk_count)
+            .map(|i| Ok(data_start + u32_at(HEADER_LEN + i * 4)?))
+            .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+        if offsets.windows(2).any(|w| w[0] > w[1]) || offsets[chunk_count] > data.len() {
+            return Err("assignments file is truncated".into());
+        }
+        // so a damaged count can't have decoding reserve more than the file could hold
+        if count > (offsets[chunk_count] - data_start).saturating_mul(MAX_DEFLATE_RATIO) {
+            return Err("assignments file has an invalid header".into());
+        }
+        Ok(Self {
+            count,
+            chunk_len,
+            offsets,
+        })
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// Decodes one chunk of `data`, the whole file the header was parsed from.
+    pub fn decode_chunk(&self, data: &[u8], chunk: usize) -> Result<Vec<usize>, Box<dyn Error>> {
+        let start = chunk * self.chunk_len;
+        let len = self.chunk_len.min(self.count - start);
+        let mut varints = Vec::new();
+        flate2::read::DeflateDecoder::new(&data[self.offsets[chunk]..self.offsets[chunk + 1]])
+            .take(len.saturating_mul(MAX_VARINT_LEN) as u64)
+            .read_to_end(&mut varints)?;
+        if varints.len() < len {
+            return Err("assignments file is corrupt".into());
+        }
+
+        let mut out = Vec::with_capacity(len);
+        let mut bytes = varints.iter();
+        for i in 0..len {
+            let delta = unzigzag(read_varint(&mut bytes).ok_or("assignments file is corrupt")?);
+            let value = (start + i) as i64 + delta;
+            if value < 0 || value >= self.count as i64 {
+                return Err("assignments file is corrupt".into());
+            }
+            out.push(value as usize);
+        }
+        Ok(out)
+    }
+}
+
+pub fn decode(data: &[u8]) -> Result<Vec<usize>, Box<dyn Error>> {
+    let header = Header::parse(data)?;
+    let mut out = Vec::with_capacity(header.count);
+    for chunk in 0..header.chunk_count() {
+        out.extend(header.decode_chunk(data, chunk)?);
+    }
+    Ok(out)
+}
+
+/// Reads the binary format, or the json array older presets were saved as.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read(data: &[u8]) -> Result<Vec<usize>, Box<dyn Error>> {
+    if data.starts_with(MAGIC) {
+        return decode(data);
+    }
+    let text = std::str::from_utf8(data)?.trim();
+    let inner = text
+        .strip_prefix('[')
+        .and_then(|t| t.strip_suffix(']'))
+        .ok_or("assignments are neither binary nor a json array")?;
+    if inner.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(inner
+        .split(',')
+        .map(|s| s.trim().parse::<usize>())
+        .collect::<Result<_, _>>()?)
+}
+
+/// An assignments file mapped into memory instead of read, decoded a chunk at a time as
+/// it's asked for. Big grids then never need the whole file, or a second copy of it, in
+/// memory at once.
+///
+/// Only preset folders opened with `--render` are read this way. Presets in the app are
+/// kept in eframe's storage and load through serde with everything else.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct MappedAssignments {
+    map: memmap2::Mmap,
+    header: Option<Header>, // none for legacy json, which has no chunks
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl MappedAssignments {
+    pub fn open(path: &std::path::Path) -> Result<Self, Box<dyn Error>> {
+        let file = std::fs::File::open(path)?;
+        // safety: if the file is truncated while it's mapped, touching the pages that are
+        // gone raises SIGBUS and takes the whole process down. Preset folders are only
+        // written when they're saved, not while they're being opened, so we accept that.
+        // Other changes in place only give garbage, which decoding rejects or validates
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        let header = if map.starts_with(MAGIC) {
+            Some(Header::parse(&map)?)
+        } else {
+            None
+        };
+        Ok(Self { map, header })
+    }
+
+    /// How many assignments there are, from the header alone. Legacy json has to be
+    /// parsed to tell, so that's `None`.
+    pub fn count(&self) -> Option<usize> {
+        self.header.as_ref().map(|header| header.count)
+    }
+
+    /// How many chunks `chunk` can decode; legacy json is all one chunk.
+    pub fn chunk_count(&self) -> usize {
+        self.header.as_ref().map_or(1, Header::chunk_count)
+    }
+
+    /// Decodes chunk `i`, the assignments of pixels `i * CHUNK_LEN` on, checking each
+    /// index is in range. Only that chunk's part of the file is touched.
+    pub fn chunk(&self, i: usize) -> Result<Vec<usize>, Box<dyn Error>> {
+        match &self.header {
+            Some(header) if i < header.chunk_count() => header.decode_chunk(&self.map, i),
+            Some(_) => Err("no such chunk in the assignments file".into()),
+            None if i == 0 => read(&self.map),
+            None => Err("no such chunk in the assignments file".into()),
+        }
+    }
+
+    /// Every chunk in order, decoded as it's reached.
+    pub fn chunks(&self) -> impl Iterator<Item = Result<Vec<usize>, Box<dyn Error>>> + '_ {
+        (0..self.chunk_count()).map(|i| self.chunk(i))
+    }
+}
+
+fn zigzag(v: i64) -> u64 {
+    ((v << 1) ^ (v >> 63)) as u64
+}
+
+fn unzigzag(v: u64) -> i64 {
+    (v >> 1) as i64 ^ -((v & 1) as i64)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn read_varint<'a>(bytes: &mut impl Iterator<Item = &'a u8>) -> Option<u64> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let b = *bytes.next()?;
+        v |= ((b & 0x7f) as u64) << shift;
+        if b & 0x80 == 0 {
+            return Some(v);
+        }
+    }
+    None
+}
+
+/// Serde adapter storing assignments in the binary format, while still reading the plain
+/// list of numbers presets used to be saved as.
+pub mod compact {
+    use serde::de::{self, SeqAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(assignments: &[usize], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_bytes(&super::encode(assignments))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<usize>, D::Error> {
+        struct CompactVisitor;
+
+        impl<'de> Visitor<'de> for CompactVisitor {
+            type Value = Vec<usize>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("encoded assignments or a list of indices")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                super::decode(v).map_err(|err| E::custom(err.to_string()))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                // a list of numbers, or a byte array from formats without a bytes type. A
+                // list can start like the magic too, so it's only bytes if they decode
+                let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(v) = seq.next_element::<u64>()? {
+                    values.push(v);
+                }
+                if values.starts_with(&super::MAGIC.map(u64::from))
+                    && values.iter().all(|&v| v <= u8::MAX as u64)
+                {
+                    let bytes = values.iter().map(|&v| v as u8).collect::<Vec<_>>();
+                    if let Ok(assignments) = super::decode(&bytes) {
+                        return Ok(assignments);
+                    }
+                }
+                Ok(values.into_iter().map(|v| v as usize).collect())
+            }
+        }
+
+        d.deserialize_any(CompactVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::value::{Error as ValueError, SeqDeserializer};
+
+    fn from_seq(values: Vec<u64>) -> Result<Vec<usize>, ValueError> {
+        compact::deserialize(SeqDeserializer::<_, ValueError>::new(values.into_iter()))
+    }
+
+    #[test]
+    fn round_trips_across_chunks() {
+        let len = CHUNK_LEN * 2 + 5;
+        // mostly short moves, with some from one end of the grid to the other
+        let assignments = (0..len)
+            .map(|i| {
+                if i % 997 == 0 {
+                    len - 1 - i
+                } else {
+                    (i + 3) % len
+                }
+            })
+            .collect::<Vec<_>>();
+        let data = encode(&assignments);
+        assert_eq!(Header::parse(&data).unwrap().chunk_count(), 3);
+        assert_eq!(decode(&data).unwrap(), assignments);
+        assert_eq!(read(&data).unwrap(), assignments);
+
+        let empty = encode(&[]);
+        assert_eq!(Header::parse(&empty).unwrap().chunk_count(), 0);
+        assert!(decode(&empty).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reads_legacy_json() {
+        assert_eq!(read(b" [2, 0,1]\n").unwrap(), [2, 0, 1]);
+        assert!(read(b"[]").unwrap().is_empty());
+        assert!(read(b"{\"a\": 1}").is_err());
+        assert!(read(b"[1, -2]").is_err());
+    }
+
+    #[test]
+    fn lists_that_start_like_the_magic_stay_lists() {
+        let legacy = MAGIC.iter().map(|&b| b as u64).chain([1, 0, 0]).collect();
+        assert_eq!(from_seq(legacy).unwrap(), [79, 66, 65, 83, 1, 0, 0]);
+
+        // while a byte array from a format without a bytes type is decoded
+        let assignments = vec![1, 2, 0];
+        let bytes = encode(&assignments).into_iter().map(u64::from).collect();
+        assert_eq!(from_seq(bytes).unwrap(), assignments);
+    }
+
+    #[test]
+    fn rejects_damaged_files() {
+        let data = encode(&(0..1000).rev().collect::<Vec<_>>());
+        for len in [0, 3, HEADER_LEN - 1, HEADER_LEN + 2, data.len() - 1] {
+            assert!(decode(&data[..len]).is_err(), "truncated to {}", len);
+        }
+
+        let with = |pos: usize, byte: u8| {
+            let mut data = data.clone();
+            data[pos] = byte;
+            decode(&data)
+        };
+        assert!(with(0, b'X').is_err()); // magic
+        assert!(with(4, VERSION + 1).is_err());
+        assert!(with(13, 7).is_err()); // chunk count disagrees with the entry count
+        assert!(with(11, 0).is_err()); // chunk length 0
+        assert!(with(12, 1).is_ok_and(|a| a.len() == 1000)); // longer, but still one chunk
+        assert!(with(HEADER_LEN + 4, 0xff).is_err()); // chunk ends past the file
+
+        // fewer entries than encoded leaves ones pointing outside the grid
+        let mut small = encode(&[2, 0, 1]);
+        small[5] = 2;
+        assert!(decode(&small).is_err());
+
+        // a header claiming far more than its chunks could hold is refused up front,
+        // rather than reserving room for it
+        let mut huge = MAGIC.to_vec();
+        huge.push(VERSION);
+        for value in [u32::MAX, u32::MAX, 1, 0, 0] {
+            huge.extend(value.to_le_bytes());
+        }
+        assert_eq!(huge.len(), 25);
+        assert!(Header::parse(&huge).is_err());
+        // and one the chunks might hold fails on the chunk that comes up short
+        let mut grown = data.clone();
+        grown[5..9].copy_from_slice(&(1000 * MAX_DEFLATE_RATIO as u32).to_le_bytes());
+        grown[9..13].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(decode(&grown).is_err());
+    }
+}
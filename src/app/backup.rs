@@ -0,0 +1,251 @@
+use std::error::Error;
+use std::io::{Read, Write};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::app::preset::Preset;
+
+const BACKUP_VERSION: u32 = 1;
+pub const BACKUP_EXTENSION: &str = "obamify";
+
+/// Everything the app keeps: presets with their folders and tags, and the config file.
+/// Saved as gzipped ron.
+#[derive(Serialize, Deserialize)]
+pub struct Backup {
+    pub version: u32,
+    pub presets: Vec<Preset>,
+    pub config: String, // toml, as written to config.toml
+}
+
+impl Backup {
+    pub fn new(presets: Vec<Preset>, config: String) -> Self {
+        Self {
+            version: BACKUP_VERSION,
+            presets,
+            config,
+        }
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let text = ron::to_string(self)?;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(text.as_bytes())?;
+        Ok(encoder.finish()?)
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(data).read_to_string(&mut text)?;
+        let backup: Self = ron::from_str(&text)?;
+        if backup.version > BACKUP_VERSION {
+            return Err("this backup was made by a newer version of obamify".into());
+        }
+        Ok(backup)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Resolution {
+    KeepBoth,
+    Replace,
+    Skip,
+}
+
+impl Resolution {
+    pub const ALL: [Resolution; 3] = [Resolution::KeepBoth, Resolution::Replace, Resolution::Skip];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Resolution::KeepBoth => "keep both",
+            Resolution::Replace => "replace",
+            Resolution::Skip => "skip",
+        }
+    }
+}
+
+/// An incoming preset with the same id, or the same name, as one already here.
+pub struct Conflict {
+    pub incoming: usize,
+    pub existing: Existing,
+    pub resolution: Resolution,
+}
+
+/// The preset here a conflict is with. Kept as what matched rather than an index, since
+/// presets can be added or deleted while the import waits.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Existing {
+    Id(Uuid),
+    Name(String),
+}
+
+impl Existing {
+    pub fn find(&self, presets: &[Preset]) -> Option<usize> {
+        presets.iter().position(|p| match self {
+            Existing::Id(id) => p.meta.id == *id,
+            Existing::Name(name) => p.inner.name == *name,
+        })
+    }
+}
+
+/// A backup being merged into the current presets, waiting for the user to resolve
+/// conflicts.
+pub struct Import {
+    pub backup: Backup,
+    pub conflicts: Vec<Conflict>,
+    pub duplicates: usize, // identical to a preset already here, so left out
+    pub import_config: bool,
+}
+
+impl Import {
+    pub fn new(mut backup: Backup, existing: &[Preset]) -> Self {
+        let same = |a: &Preset, b: &Preset| {
+            a.assignments == b.assignments && a.inner.source_img == b.inner.source_img
+        };
+        let before = backup.presets.len();
+        backup.presets.retain(|p| {
+            !existing
+                .iter()
+                .any(|e| e.inner.name == p.inner.name && same(e, p))
+        });
+        let duplicates = before - backup.presets.len();
+
+        let conflicts = backup
+            .presets
+            .iter()
+            .enumerate()
+            .filter_map(|(incoming, preset)| {
+                let id = preset.meta.id;
+                let by_id = Existing::Id(id);
+                let by_name = Existing::Name(preset.inner.name.clone());
+                let existing = if !id.is_nil() && by_id.find(existing).is_some() {
+                    by_id
+                } else {
+                    by_name.find(existing).map(|_| by_name)?
+                };
+                Some(Conflict {
+                    incoming,
+                    existing,
+                    resolution: Resolution::KeepBoth,
+                })
+            })
+            .collect();
+        Self {
+            backup,
+            conflicts,
+            duplicates,
+            import_config: false,
+        }
+    }
+
+    /// Merges the presets, returning the backup's config if it should replace this one.
+    pub fn apply(self, presets: &mut Vec<Preset>) -> Option<String> {
+        for (i, mut preset) in self.backup.presets.into_iter().enumerate() {
+            match self.conflicts.iter().find(|c| c.incoming == i) {
+                None => presets.push(preset),
+                Some(conflict) => match conflict.resolution {
+                    Resolution::Skip => {}
+                    Resolution::Replace => match conflict.existing.find(presets) {
+                        Some(existing) => presets[existing] = preset,
+                        None => presets.push(preset), // deleted since
+                    },
+                    Resolution::KeepBoth => {
+                        preset.meta.id = Uuid::new_v4();
+                        preset.inner.name = unique_name(&preset.inner.name, presets);
+                        presets.push(preset);
+                    }
+                },
+            }
+        }
+        self.import_config.then_some(self.backup.config)
+    }
+}
+
+fn unique_name(name: &str, presets: &[Preset]) -> String {
+    (2..)
+        .map(|n| format!("{} ({})", name, n))
+        .find(|candidate| presets.iter().all(|p| p.inner.name != *candidate))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::preset::{PresetMeta, UnprocessedPreset};
+
+    fn preset(name: &str, id: Uuid, pixel: u8) -> Preset {
+        Preset {
+            inner: UnprocessedPreset {
+                name: name.to_owned(),
+                width: 1,
+                height: 1,
+                source_img: vec![pixel; 3],
+            },
+            assignments: vec![0],
+            stats: Vec::new(),
+            quality: None,
+            meta: PresetMeta {
+                id,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn names(presets: &[Preset]) -> Vec<&str> {
+        presets.iter().map(|p| p.inner.name.as_str()).collect()
+    }
+
+    #[test]
+    fn merging_spots_duplicates_and_conflicts() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let here = vec![preset("a", a, 1), preset("b", b, 2)];
+        let incoming = vec![
+            preset("a", Uuid::new_v4(), 1), // the same pictures under the same name
+            preset("renamed b", b, 3),      // same id
+            preset("a", Uuid::new_v4(), 4), // same name
+            preset("a", Uuid::nil(), 5),    // no id, same name
+            preset("new", Uuid::new_v4(), 6),
+        ];
+        let import = Import::new(Backup::new(incoming, String::new()), &here);
+        assert_eq!(import.duplicates, 1);
+        let conflicts = import
+            .conflicts
+            .iter()
+            .map(|c| (c.incoming, c.existing.clone()))
+            .collect::<Vec<_>>();
+        let by_name = Existing::Name("a".to_owned());
+        assert_eq!(
+            conflicts,
+            [(0, Existing::Id(b)), (1, by_name.clone()), (2, by_name)]
+        );
+    }
+
+    #[test]
+    fn resolutions_find_their_preset_again() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut here = vec![
+            preset("a", a, 1),
+            preset("b", b, 2),
+            preset("a (2)", Uuid::new_v4(), 3),
+        ];
+        let incoming = vec![preset("new b", b, 4), preset("a", Uuid::new_v4(), 5)];
+        let mut import = Import::new(Backup::new(incoming, "config".to_owned()), &here);
+        import.conflicts[0].resolution = Resolution::Replace;
+        import.import_config = true;
+
+        // deleting a preset before importing moves the one being replaced
+        here.remove(0);
+        assert_eq!(import.apply(&mut here).as_deref(), Some("config"));
+        assert_eq!(names(&here), ["new b", "a (2)", "a (3)"]);
+        assert_eq!(here[0].meta.id, b);
+        assert_eq!(here[0].inner.source_img, [4; 3]);
+        assert_ne!(here[2].meta.id, a); // kept both, under a new id
+
+        let skipped = vec![preset("new b", Uuid::new_v4(), 6)];
+        let mut import = Import::new(Backup::new(skipped, String::new()), &here);
+        import.conflicts[0].resolution = Resolution::Skip;
+        assert_eq!(import.apply(&mut here), None);
+        assert_eq!(names(&here), ["new b", "a (2)", "a (3)"]);
+        assert_eq!(here[0].inner.source_img, [4; 3]);
+    }
+}
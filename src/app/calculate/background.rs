@@ -0,0 +1,216 @@
+//! Guessing which part of the source is background, for the source mask.
+//!
+//! The image border seeds the background colors and the middle seeds the subject, like a
+//! chroma key. The guess is then refined a few times, grabcut style: fit a handful of
+//! colors to each side, relabel every pixel by which side its color fits better, smoothed
+//! toward its neighbors' labels, and refit. It's tuned for portraits, where the subject
+//! sits in the middle and touches little of the border.
+
+use image::{GrayImage, Luma, imageops};
+
+use crate::app::calculate::util::SourceImg;
+
+const WORK_SIZE: u32 = 160; // the longer side the guess is made at
+const CLUSTERS: usize = 5;
+const ROUNDS: usize = 4;
+const SMOOTHING_SWEEPS: usize = 3;
+const BORDER: u32 = 2; // pixels at the edge always counted as background
+
+/// A mask the size of `img`, 255 on the subject and 0 on the background.
+pub fn remove_background(img: &SourceImg) -> GrayImage {
+    let _span = tracing::info_span!("remove_background").entered();
+    let (w, h) = img.dimensions();
+    let scale = WORK_SIZE as f32 / w.max(h) as f32;
+    let (sw, sh) = if scale < 1.0 {
+        (
+            ((w as f32 * scale).round() as u32).max(1),
+            ((h as f32 * scale).round() as u32).max(1),
+        )
+    } else {
+        (w, h)
+    };
+    let small = imageops::resize(img, sw, sh, imageops::FilterType::Triangle);
+    let colors = small
+        .pixels()
+        .map(|p| p.0.map(|c| c as f32))
+        .collect::<Vec<_>>();
+    let (sw, sh) = (sw as usize, sh as usize);
+
+    let border = |i: usize| {
+        let (x, y) = (i % sw, i / sw);
+        let b = BORDER as usize;
+        x < b || y < b || x + b >= sw || y + b >= sh
+    };
+    // seed the subject with an ellipse in the middle, the background with everything else
+    let mut fg = (0..colors.len())
+        .map(|i| {
+            let dx = ((i % sw) as f32 + 0.5) / sw as f32 - 0.5;
+            let dy = ((i / sw) as f32 + 0.5) / sh as f32 - 0.5;
+            (dx / 0.3).powi(2) + (dy / 0.4).powi(2) <= 1.0
+        })
+        .collect::<Vec<_>>();
+
+    for _ in 0..ROUNDS {
+        let fg_model = kmeans(&colors, &fg, true);
+        let bg_model = kmeans(&colors, &fg, false);
+        if fg_model.is_empty() || bg_model.is_empty() {
+            break;
+        }
+        // positive where the subject's colors fit better
+        let data = colors
+            .iter()
+            .map(|c| nearest(&bg_model, c) - nearest(&fg_model, c))
+            .collect::<Vec<_>>();
+        let spread = data.iter().map(|d| d.abs()).sum::<f32>() / data.len() as f32;
+
+        for _ in 0..SMOOTHING_SWEEPS {
+            let mut next = fg.clone();
+            for (i, label) in next.iter_mut().enumerate() {
+                if border(i) {
+                    *label = false;
+                    continue;
+                }
+                let (x, y) = (i % sw, i / sw);
+                let mut vote = 0.0;
+                for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                    let n = ny * sw + nx;
+                    // similar colored neighbors pull harder, like grabcut's edge term
+                    let similarity = (-dist2(&colors[i], &colors[n]) / 900.0).exp();
+                    vote += if fg[n] { similarity } else { -similarity };
+                }
+                *label = data[i] + vote * spread * 0.5 > 0.0;
+            }
+            fg = next;
+        }
+    }
+
+    keep_largest_component(&mut fg, sw, sh);
+    fill_holes(&mut fg, sw, sh);
+
+    let small_mask = GrayImage::from_fn(sw as u32, sh as u32, |x, y| {
+        Luma([if fg[y as usize * sw + x as usize] {
+            255
+        } else {
+            0
+        }])
+    });
+    let mut mask = imageops::resize(&small_mask, w, h, imageops::FilterType::Triangle);
+    for p in mask.pixels_mut() {
+        p.0[0] = if p.0[0] >= 128 { 255 } else { 0 };
+    }
+    mask
+}
+
+fn dist2(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    (0..3).map(|c| (a[c] - b[c]).powi(2)).sum()
+}
+
+fn nearest(centers: &[[f32; 3]], color: &[f32; 3]) -> f32 {
+    centers
+        .iter()
+        .map(|c| dist2(c, color))
+        .fold(f32::MAX, f32::min)
+}
+
+/// A few representative colors of the pixels labelled `side`.
+fn kmeans(colors: &[[f32; 3]], labels: &[bool], side: bool) -> Vec<[f32; 3]> {
+    let members = colors
+        .iter()
+        .zip(labels)
+        .filter(|&(_, &l)| l == side)
+        .map(|(c, _)| *c)
+        .collect::<Vec<_>>();
+    if members.is_empty() {
+        return Vec::new();
+    }
+    let mut centers = (0..CLUSTERS)
+        .map(|k| members[k * members.len() / CLUSTERS])
+        .collect::<Vec<_>>();
+    for _ in 0..8 {
+        let mut sums = vec![([0.0f32; 3], 0usize); CLUSTERS];
+        for c in &members {
+            let k = (0..CLUSTERS)
+                .min_by(|&a, &b| dist2(&centers[a], c).total_cmp(&dist2(&centers[b], c)))
+                .unwrap();
+            for (sum, v) in sums[k].0.iter_mut().zip(c) {
+                *sum += v;
+            }
+            sums[k].1 += 1;
+        }
+        for (center, (sum, count)) in centers.iter_mut().zip(sums) {
+            if count > 0 {
+                *center = sum.map(|s| s / count as f32);
+            }
+        }
+    }
+    centers
+}
+
+/// Labels each 4-connected region of cells equal to `value`, returning the labels and
+/// the size of each region.
+pub(crate) fn components(
+    cells: &[bool],
+    value: bool,
+    w: usize,
+    h: usize,
+) -> (Vec<usize>, Vec<usize>) {
+    let mut labels = vec![usize::MAX; cells.len()];
+    let mut sizes = Vec::new();
+    let mut stack = Vec::new();
+    for start in 0..cells.len() {
+        if cells[start] != value || labels[start] != usize::MAX {
+            continue;
+        }
+        let label = sizes.len();
+        let mut size = 0;
+        labels[start] = label;
+        stack.push(start);
+        while let Some(i) = stack.pop() {
+            size += 1;
+            let (x, y) = (i % w, i / w);
+            let neighbors = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < w).then(|| i + 1),
+                (y > 0).then(|| i - w),
+                (y + 1 < h).then(|| i + w),
+            ];
+            for n in neighbors.into_iter().flatten() {
+                if cells[n] == value && labels[n] == usize::MAX {
+                    labels[n] = label;
+                    stack.push(n);
+                }
+            }
+        }
+        sizes.push(size);
+    }
+    (labels, sizes)
+}
+
+/// Drops specks of subject that aren't part of the biggest one.
+fn keep_largest_component(fg: &mut [bool], w: usize, h: usize) {
+    let (labels, sizes) = components(fg, true, w, h);
+    let Some(largest) = (0..sizes.len()).max_by_key(|&l| sizes[l]) else {
+        return;
+    };
+    for (cell, label) in fg.iter_mut().zip(labels) {
+        *cell = *cell && label == largest;
+    }
+}
+
+/// Counts background enclosed by the subject as subject, like eyes or teeth whose colors
+/// happened to match the background.
+fn fill_holes(fg: &mut [bool], w: usize, h: usize) {
+    let (labels, sizes) = components(fg, false, w, h);
+    let mut touches_edge = vec![false; sizes.len()];
+    for (i, &label) in labels.iter().enumerate() {
+        let (x, y) = (i % w, i / w);
+        if label != usize::MAX && (x == 0 || y == 0 || x + 1 == w || y + 1 == h) {
+            touches_edge[label] = true;
+        }
+    }
+    for (cell, label) in fg.iter_mut().zip(labels) {
+        if label != usize::MAX && !touches_edge[label] {
+            *cell = true;
+        }
+    }
+}
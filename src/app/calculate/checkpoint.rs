@@ -0,0 +1,156 @@
+//! Saving long fast solves as they go, so an interrupted one can carry on where it was.
+//!
+//! A checkpoint holds the source, the finalized settings and the solver's state, as
+//! gzipped ron like backups. It's rewritten every `checkpoint_minutes` while the solve
+//! runs and removed once it finishes or is cancelled.
+//!
+//! A population file is the same thing saved on request instead, wherever the user
+//! likes, and kept: loading one queues a new solve from that point, so a promising run
+//! can be branched into several experiments.
+
+use std::error::Error;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::calculate::SolverState;
+use crate::app::calculate::util::{GenerationSettings, SourceImg};
+use crate::app::preset::{self, UnprocessedPreset};
+
+const CHECKPOINT_VERSION: u32 = 1;
+pub const CHECKPOINT_EXTENSION: &str = "obcheckpoint";
+pub const POPULATION_EXTENSION: &str = "obpopulation";
+
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub version: u32,
+    pub unprocessed: UnprocessedPreset,
+    pub settings: GenerationSettings,
+    pub state: SolverState,
+}
+
+impl Checkpoint {
+    pub fn encode(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let text = ron::to_string(self)?;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(text.as_bytes())?;
+        Ok(encoder.finish()?)
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(data).read_to_string(&mut text)?;
+        let checkpoint: Self = ron::from_str(&text)?;
+        if checkpoint.version > CHECKPOINT_VERSION {
+            return Err("this checkpoint was made by a newer version of obamify".into());
+        }
+        Ok(checkpoint)
+    }
+
+    /// The settings to queue the solve with again, set to carry on from the saved state.
+    pub fn into_job(self) -> Option<(SourceImg, GenerationSettings)> {
+        let img = image::ImageBuffer::from_raw(
+            self.unprocessed.width,
+            self.unprocessed.height,
+            self.unprocessed.source_img,
+        )?;
+        let mut settings = self.settings;
+        settings.resume = Some(self.state);
+        Some((img, settings))
+    }
+}
+
+/// Where checkpoints are kept.
+pub fn dir() -> Option<PathBuf> {
+    eframe::storage_dir("obamify").map(|dir| dir.join("checkpoints"))
+}
+
+/// Saves a running solve every so often, and whenever a population file is asked for.
+pub struct Checkpointer {
+    path: Option<PathBuf>, // None if checkpoints are turned off, or there's nowhere to keep them
+    interval: u64,         // seconds
+    last_saved: u64,
+    unprocessed: UnprocessedPreset,
+    settings: GenerationSettings,
+    snapshot: Arc<AtomicBool>, // set to ask for a population file, cleared once it's made
+}
+
+impl Checkpointer {
+    pub fn new(
+        unprocessed: &UnprocessedPreset,
+        settings: &GenerationSettings,
+        snapshot: Arc<AtomicBool>,
+    ) -> Self {
+        let path = dir()
+            .filter(|_| settings.checkpoint_minutes > 0)
+            .map(|dir| dir.join(format!("{}.{}", settings.id, CHECKPOINT_EXTENSION)));
+        let mut settings = settings.clone();
+        settings.resume = None;
+        Self {
+            path,
+            interval: settings.checkpoint_minutes as u64 * 60,
+            last_saved: preset::now_unix(),
+            unprocessed: unprocessed.clone(),
+            settings,
+            snapshot,
+        }
+    }
+
+    fn checkpoint(&self, state: SolverState) -> Checkpoint {
+        Checkpoint {
+            version: CHECKPOINT_VERSION,
+            unprocessed: self.unprocessed.clone(),
+            settings: self.settings.clone(),
+            state,
+        }
+    }
+
+    /// Saves the state `state` gives if it's been long enough since the last save.
+    pub fn tick(&mut self, state: impl FnOnce() -> SolverState) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let now = preset::now_unix();
+        if now < self.last_saved + self.interval {
+            return;
+        }
+        self.last_saved = now;
+        let _span = tracing::info_span!("save_checkpoint").entered();
+        if let Err(err) = write(path, &self.checkpoint(state())) {
+            tracing::warn!("failed to save checkpoint: {}", err);
+        }
+    }
+
+    /// The contents of a population file with the state `state` gives, if one has been
+    /// asked for since the last call.
+    pub fn snapshot(&mut self, state: impl FnOnce() -> SolverState) -> Option<Vec<u8>> {
+        if !self.snapshot.swap(false, Ordering::Relaxed) {
+            return None;
+        }
+        let _span = tracing::info_span!("save_population").entered();
+        self.checkpoint(state())
+            .encode()
+            .inspect_err(|err| tracing::warn!("failed to save population: {}", err))
+            .ok()
+    }
+
+    pub fn remove(self) {
+        if let Some(path) = self.path.filter(|path| path.exists()) {
+            std::fs::remove_file(path).ok();
+        }
+    }
+}
+
+/// Writes next to the old checkpoint first, so a crash mid-write keeps the old one.
+fn write(path: &Path, checkpoint: &Checkpoint) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, checkpoint.encode()?)?;
+    std::fs::rename(partial, path)?;
+    Ok(())
+}
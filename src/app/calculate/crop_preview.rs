@@ -0,0 +1,84 @@
+//! A rough solve of the current settings, for judging crops by what they turn into.
+//!
+//! The grid is only 32 cells across, so a whole solve takes a fraction of a second, and
+//! it's run a few generations per frame on the UI thread instead of as a job. That shows
+//! the pixels settling while it goes, the same on the web as natively.
+
+use std::error::Error;
+
+use crate::app::calculate::util::{self, GenerationSettings, GridPixel, SourceImg, WeightedPixel};
+use crate::app::calculate::{
+    GENETIC_SEED, Pixel, SolverState, genetic_converged, genetic_generation, genetic_pixels,
+    make_new_img, next_max_dist,
+};
+
+pub const PREVIEW_SIDELEN: u32 = 32;
+const GENERATIONS_PER_STEP: u32 = 2;
+
+pub struct CropPreview {
+    settings: GenerationSettings,
+    source: Vec<GridPixel>,
+    target: Vec<WeightedPixel>,
+    pixels: Vec<Pixel>,
+    pinned: Option<Vec<bool>>,
+    state: SolverState,
+    done: bool,
+}
+
+impl CropPreview {
+    /// Starts a solve of `source` onto the target with `settings`, at preview size.
+    pub fn new(source: &SourceImg, settings: &GenerationSettings) -> Result<Self, Box<dyn Error>> {
+        let _span = tracing::info_span!("crop_preview_init").entered();
+        let mut settings = settings.clone();
+        settings.sidelen = PREVIEW_SIDELEN;
+        settings.block_size = 1;
+        settings.resume = None;
+        let settings = settings.finalized();
+
+        let (source, target) = util::get_images(source.clone(), &settings)?;
+        let state = SolverState::identity(&source, PREVIEW_SIDELEN, GENETIC_SEED);
+        let pixels = genetic_pixels(&source, &target, &state.assignments, settings.wrap());
+        Ok(Self {
+            pinned: settings.pinned_cells()?,
+            settings,
+            source,
+            target,
+            pixels,
+            state,
+            done: false,
+        })
+    }
+
+    /// Runs a few more generations, unless the solve has already settled.
+    pub fn step(&mut self) {
+        let _span = tracing::info_span!("crop_preview_step").entered();
+        for _ in 0..GENERATIONS_PER_STEP {
+            if self.done {
+                return;
+            }
+            let state = &mut self.state;
+            let swaps = genetic_generation(
+                &mut self.pixels,
+                &mut state.assignments,
+                &self.target,
+                &self.settings,
+                self.pinned.as_deref(),
+                state.max_dist,
+                state.seed,
+                state.generation,
+            );
+            state.generation += 1;
+            self.done = genetic_converged(state.max_dist, swaps);
+            state.max_dist = next_max_dist(state.max_dist);
+        }
+    }
+
+    pub fn done(&self) -> bool {
+        self.done
+    }
+
+    /// The result so far, as RGB rows of `PREVIEW_SIDELEN` pixels.
+    pub fn image(&self) -> Vec<u8> {
+        make_new_img(&self.source, &self.state.assignments, PREVIEW_SIDELEN)
+    }
+}
@@ -74,6 +74,7 @@ impl DrawingPixel {
             target_col,
             weight,
             proximity_importance,
+            0,
         )
     }
 }
@@ -156,7 +157,7 @@ pub fn drawing_process_genetic(
                     target_pixels[i].rgb_tuple(),
                     target_pixels[i].weight,
                     &read_colors,
-                    settings.proximity_importance,
+                    target_pixels[i].proximity,
                     // &read_pixel_data,
                 ) + STROKE_REWARD;
                 p.update_heuristic(h);
@@ -212,7 +213,7 @@ pub fn drawing_process_genetic(
                 t_b.rgb_tuple(),
                 t_b.weight,
                 &colors,
-                settings.proximity_importance,
+                t_b.proximity,
             ) + stroke_reward(bpos, apos, &pixel_data, &pixels, frame_count);
 
             let b_on_a_h = pixels[bpos].calc_drawing_heuristic(
@@ -220,7 +221,7 @@ pub fn drawing_process_genetic(
                 t_a.rgb_tuple(),
                 t_a.weight,
                 &colors,
-                settings.proximity_importance,
+                t_a.proximity,
             ) + stroke_reward(apos, bpos, &pixel_data, &pixels, frame_count);
 
             let improvement_a = pixels[apos].h - b_on_a_h;
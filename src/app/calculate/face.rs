@@ -0,0 +1,104 @@
+//! Finding the face in a source, to crop to it.
+//!
+//! There's no detection model to lean on, so this looks for skin: pixels in the usual
+//! YCbCr skin range, grouped into blobs, with the biggest roughly face shaped blob taken
+//! as the face. That's enough for the typical selfie, a lit face filling a fair part of
+//! the frame.
+
+use image::imageops;
+
+use crate::app::calculate::background::components;
+use crate::app::calculate::util::SourceImg;
+
+const WORK_SIZE: u32 = 200; // the longer side faces are looked for at
+const MIN_FACE_AREA: f32 = 0.01; // fraction of the image
+const MIN_FILL: f32 = 0.35; // of the face's bounding box that has to be skin
+const MAX_FACE_HEIGHT: f32 = 1.4; // times the width; anything below is neck and shoulders
+
+/// Center and side length of the square around a face, in source pixels.
+pub struct Face {
+    pub x: f32,
+    pub y: f32,
+    pub size: f32,
+}
+
+pub fn find_face(img: &SourceImg) -> Option<Face> {
+    let _span = tracing::info_span!("find_face").entered();
+    let (w, h) = img.dimensions();
+    let scale = (WORK_SIZE as f32 / w.max(h) as f32).min(1.0);
+    let (sw, sh) = (
+        ((w as f32 * scale).round() as u32).max(1),
+        ((h as f32 * scale).round() as u32).max(1),
+    );
+    let small = imageops::resize(img, sw, sh, imageops::FilterType::Triangle);
+    let (sw, sh) = (sw as usize, sh as usize);
+
+    let skin = small.pixels().map(|p| is_skin(p.0)).collect::<Vec<_>>();
+    // a majority vote over each 3x3 neighborhood closes up speckle from noise and pores
+    let skin = (0..skin.len())
+        .map(|i| {
+            let (x, y) = (i % sw, i / sw);
+            let mut count = 0;
+            for ny in y.saturating_sub(1)..(y + 2).min(sh) {
+                for nx in x.saturating_sub(1)..(x + 2).min(sw) {
+                    count += skin[ny * sw + nx] as u32;
+                }
+            }
+            count >= 5
+        })
+        .collect::<Vec<_>>();
+
+    let (labels, sizes) = components(&skin, true, sw, sh);
+    let mut bounds = vec![(usize::MAX, usize::MAX, 0, 0); sizes.len()];
+    for (i, &label) in labels.iter().enumerate() {
+        if label == usize::MAX {
+            continue;
+        }
+        let (x, y) = (i % sw, i / sw);
+        let b = &mut bounds[label];
+        *b = (b.0.min(x), b.1.min(y), b.2.max(x + 1), b.3.max(y + 1));
+    }
+
+    let mut best: Option<(usize, (f32, f32, f32))> = None;
+    for (label, &(x0, y0, x1, y1)) in bounds.iter().enumerate() {
+        if (sizes[label] as f32) < MIN_FACE_AREA * (sw * sh) as f32 {
+            continue;
+        }
+        let face_w = x1 - x0;
+        let face_h = (y1 - y0).min((face_w as f32 * MAX_FACE_HEIGHT).ceil() as usize);
+        let aspect = face_h as f32 / face_w as f32;
+        if !(0.7..=MAX_FACE_HEIGHT).contains(&aspect) {
+            continue;
+        }
+        let area = (y0..y0 + face_h)
+            .flat_map(|y| (x0..x1).map(move |x| y * sw + x))
+            .filter(|&i| labels[i] == label)
+            .count();
+        if (area as f32) < MIN_FILL * (face_w * face_h) as f32 {
+            continue;
+        }
+        if best.is_none_or(|(best_area, _)| area > best_area) {
+            let center = (
+                (x0 + x1) as f32 / 2.0,
+                y0 as f32 + face_h as f32 / 2.0,
+                face_w.max(face_h) as f32,
+            );
+            best = Some((area, center));
+        }
+    }
+
+    best.map(|(_, (x, y, size))| Face {
+        x: x / scale,
+        y: y / scale,
+        size: size / scale,
+    })
+}
+
+/// The Chai and Ngan skin range, with very dark pixels left out.
+fn is_skin([r, g, b]: [u8; 3]) -> bool {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let y = 0.299 * r + 0.587 * g + 0.114 * b;
+    let cb = 128.0 - 0.168736 * r - 0.331264 * g + 0.5 * b;
+    let cr = 128.0 + 0.5 * r - 0.418688 * g - 0.081312 * b;
+    y > 40.0 && (77.0..=127.0).contains(&cb) && (133.0..=173.0).contains(&cr)
+}
@@ -0,0 +1,470 @@
+//! Scoring many assignments at once on the GPU.
+//!
+//! Every cell's cost is computed in parallel and summed per workgroup in shared memory,
+//! leaving a few hundred partial sums per assignment to add up here. Without a usable
+//! adapter, or for settings whose costs don't fit the shader's 32 bit inputs, scoring
+//! falls back to the CPU.
+
+use std::error::Error;
+use std::sync::Mutex;
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::app::calculate::util::{GenerationSettings, GridPixel, WeightedPixel};
+
+const GROUP_SIZE: u32 = 256; // matches the shader's workgroup size
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Params {
+    count: u32,
+    wrap: u32,
+    _pad: [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Cell {
+    pos: u32,
+    rgb: u32,
+    weight: u32,
+    proximity: u32,
+}
+
+impl Cell {
+    fn new(p: GridPixel, weight: u32, proximity: u32) -> Self {
+        Self {
+            pos: p.x as u32 | ((p.y as u32) << 16),
+            rgb: p.rgb[0] as u32 | ((p.rgb[1] as u32) << 8) | ((p.rgb[2] as u32) << 16),
+            weight,
+            proximity,
+        }
+    }
+}
+
+pub struct FitnessEvaluator {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    params: wgpu::Buffer,
+    source: wgpu::Buffer,
+    target: wgpu::Buffer,
+    count: usize,
+    scratch: Mutex<Option<Scratch>>, // kept between calls, grown when a batch needs more
+}
+
+/// The per-call buffers of `FitnessEvaluator::costs`, for up to `capacity` assignments.
+struct Scratch {
+    capacity: usize,
+    input: wgpu::Buffer,
+    partials: wgpu::Buffer,
+    readback: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl FitnessEvaluator {
+    /// Uploads the cells for scoring assignments between them. Fails when there's no
+    /// adapter, or the costs could overflow the shader's arithmetic.
+    pub fn new(
+        source: &[GridPixel],
+        target: &[WeightedPixel],
+        settings: &GenerationSettings,
+    ) -> Result<Self, Box<dyn Error>> {
+        let _span = tracing::info_span!("gpu_fitness_init").entered();
+        let sidelen = settings.grid_sidelen() as u64;
+        let max_spatial = 2 * sidelen * sidelen;
+        if target.iter().any(|t| {
+            !(0..=u32::MAX as i64).contains(&t.weight)
+                || t.proximity < 0
+                || max_spatial * t.proximity as u64 > u32::MAX as u64
+        }) {
+            return Err("costs are out of range for the gpu".into());
+        }
+
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))?;
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+                label: Some("fitness_device"),
+                required_limits:
+                    wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+                ..Default::default()
+            }))?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("fitness.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../shaders/fitness.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("fitness_pipeline"),
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let params = Params {
+            count: target.len() as u32,
+            wrap: settings.wrap() as u32,
+            _pad: [0; 2],
+        };
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("fitness_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let source_cells = source
+            .iter()
+            .map(|&p| Cell::new(p, 0, 0))
+            .collect::<Vec<_>>();
+        let source = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("fitness_source"),
+            contents: bytemuck::cast_slice(&source_cells),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let target_cells = target
+            .iter()
+            .map(|t| Cell::new(t.pixel, t.weight as u32, t.proximity as u32))
+            .collect::<Vec<_>>();
+        let target = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("fitness_target"),
+            contents: bytemuck::cast_slice(&target_cells),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        Ok(Self {
+            device,
+            queue,
+            pipeline,
+            params,
+            source,
+            target,
+            count: target_cells.len(),
+            scratch: Mutex::new(None),
+        })
+    }
+
+    fn scratch(&self, capacity: usize) -> Scratch {
+        let groups = (self.count as u32).div_ceil(GROUP_SIZE) as u64;
+        let partials_size = capacity as u64 * groups * 8;
+        let input = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("fitness_assignments"),
+            size: (capacity * self.count * 4) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let partials = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("fitness_partials"),
+            size: partials_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("fitness_readback"),
+            size: partials_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("fitness_bg"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.source.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.target.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: input.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: partials.as_entire_binding(),
+                },
+            ],
+        });
+        Scratch {
+            capacity,
+            input,
+            partials,
+            readback,
+            bind_group,
+        }
+    }
+
+    /// The total cost of each assignment, where `assignment[target] = source`.
+    pub fn costs(&self, assignments: &[&[usize]]) -> Result<Vec<i64>, Box<dyn Error>> {
+        let _span = tracing::info_span!("gpu_fitness", count = assignments.len()).entered();
+        let groups = (self.count as u32).div_ceil(GROUP_SIZE);
+        let limits = self.device.limits();
+        let per_batch = (limits.max_storage_buffer_binding_size as usize / (self.count * 4))
+            .min(limits.max_compute_workgroups_per_dimension as usize)
+            .max(1);
+
+        let mut scratch = self.scratch.lock().unwrap();
+        let needed = assignments.len().min(per_batch);
+        let scratch = match scratch.take_if(|s| s.capacity >= needed) {
+            Some(kept) => scratch.insert(kept),
+            None => scratch.insert(self.scratch(needed)),
+        };
+
+        let mut costs = Vec::with_capacity(assignments.len());
+        for batch in assignments.chunks(per_batch) {
+            let data = batch
+                .iter()
+                .flat_map(|a| a.iter().map(|&i| i as u32))
+                .collect::<Vec<_>>();
+            self.queue
+                .write_buffer(&scratch.input, 0, bytemuck::cast_slice(&data));
+            let partials_size = (batch.len() * groups as usize * 8) as u64;
+
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("fitness_encoder"),
+                });
+            {
+                let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("fitness_pass"),
+                    timestamp_writes: None,
+                });
+                pass.set_pipeline(&self.pipeline);
+                pass.set_bind_group(0, &scratch.bind_group, &[]);
+                pass.dispatch_workgroups(groups, batch.len() as u32, 1);
+            }
+            encoder.copy_buffer_to_buffer(
+                &scratch.partials,
+                0,
+                &scratch.readback,
+                0,
+                partials_size,
+            );
+            self.queue.submit(Some(encoder.finish()));
+
+            let slice = scratch.readback.slice(..partials_size);
+            let (tx, rx) = std::sync::mpsc::channel();
+            slice.map_async(wgpu::MapMode::Read, move |res| {
+                tx.send(res).ok();
+            });
+            self.device.poll(wgpu::PollType::Wait)?;
+            rx.recv()??;
+            costs.extend(add_partials(
+                bytemuck::cast_slice(&slice.get_mapped_range()),
+                groups as usize,
+            ));
+            scratch.readback.unmap();
+        }
+        Ok(costs)
+    }
+}
+
+/// The total of each assignment from the shader's partial sums: `groups` (low, high)
+/// pairs per assignment, one after another.
+fn add_partials(sums: &[[u32; 2]], groups: usize) -> Vec<i64> {
+    sums.chunks(groups)
+        .map(|per_assignment| {
+            let total = per_assignment.iter().fold(0u64, |acc, [lo, hi]| {
+                acc.wrapping_add(*lo as u64 | ((*hi as u64) << 32))
+            });
+            total as i64
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::calculate::assignment_cost;
+
+    /// Random cells on a `sidelen` grid, and a shuffled assignment between them.
+    fn fixture(
+        sidelen: u32,
+        max_weight: u32,
+        max_proximity: u32,
+    ) -> (
+        GenerationSettings,
+        Vec<GridPixel>,
+        Vec<WeightedPixel>,
+        Vec<usize>,
+    ) {
+        let mut settings = GenerationSettings::default(uuid::Uuid::nil(), String::new());
+        (settings.sidelen, settings.block_size) = (sidelen, 1);
+        let side = settings.grid_sidelen();
+        let mut seed = 7u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            seed >> 8
+        };
+        let cells = (0..side * side).map(|i| (i % side, i / side));
+        let source = cells
+            .clone()
+            .map(|(x, y)| GridPixel::new(x, y, [next() as u8, next() as u8, next() as u8]))
+            .collect::<Vec<_>>();
+        let target = cells
+            .map(|(x, y)| WeightedPixel {
+                pixel: GridPixel::new(x, y, [next() as u8, next() as u8, next() as u8]),
+                weight: (next() % max_weight) as i64,
+                proximity: (next() % max_proximity) as i64,
+            })
+            .collect::<Vec<_>>();
+        let mut shuffled = (0..source.len()).collect::<Vec<_>>();
+        for i in (1..shuffled.len()).rev() {
+            shuffled.swap(i, next() as usize % (i + 1));
+        }
+        (settings, source, target, shuffled)
+    }
+
+    /// What fitness.wgsl writes to `partials`, step for step in wrapping u32 arithmetic.
+    fn shader_partials(
+        params: Params,
+        source: &[Cell],
+        target: &[Cell],
+        assignments: &[u32],
+    ) -> Vec<[u32; 2]> {
+        let add64 = |a: [u32; 2], b: [u32; 2]| {
+            let lo = a[0].wrapping_add(b[0]);
+            [lo, a[1].wrapping_add(b[1]).wrapping_add((lo < a[0]) as u32)]
+        };
+        let mul64 = |a: u32, b: u32| {
+            let (a0, a1, b0, b1) = (a & 0xffff, a >> 16, b & 0xffff, b >> 16);
+            let mid1 = a1.wrapping_mul(b0);
+            let mid = mid1.wrapping_add(a0.wrapping_mul(b1));
+            let mid_carry = if mid < mid1 { 1 << 16 } else { 0 };
+            let low = a0.wrapping_mul(b0);
+            let lo = low.wrapping_add(mid << 16);
+            let carry = (lo < low) as u32;
+            let hi = a1.wrapping_mul(b1).wrapping_add(mid >> 16);
+            [lo, hi.wrapping_add(mid_carry).wrapping_add(carry)]
+        };
+        let axis_dist = |a: u32, b: u32| {
+            let d = a.max(b) - a.min(b);
+            if params.wrap > 0 {
+                d.min(params.wrap - d)
+            } else {
+                d
+            }
+        };
+        let channel_diff = |a: u32, b: u32, shift: u32| {
+            let (ca, cb) = ((a >> shift) & 0xff, (b >> shift) & 0xff);
+            (ca.max(cb) - ca.min(cb)).pow(2)
+        };
+        let cell_cost = |s: Cell, t: Cell| {
+            let dx = axis_dist(s.pos & 0xffff, t.pos & 0xffff);
+            let dy = axis_dist(s.pos >> 16, t.pos >> 16);
+            let weighted_spatial = (dx * dx + dy * dy).wrapping_mul(t.proximity);
+            let color = (0..3).map(|c| channel_diff(s.rgb, t.rgb, c * 8)).sum();
+            add64(
+                mul64(color, t.weight),
+                mul64(weighted_spatial, weighted_spatial),
+            )
+        };
+
+        let count = params.count as usize;
+        let groups = count.div_ceil(GROUP_SIZE as usize);
+        let rows = assignments.len() / count;
+        let mut partials = vec![[0; 2]; rows * groups];
+        for row in 0..rows {
+            for group in 0..groups {
+                let mut sums = (0..GROUP_SIZE as usize)
+                    .map(|lid| {
+                        let cell = group * GROUP_SIZE as usize + lid;
+                        if cell < count {
+                            cell_cost(
+                                source[assignments[row * count + cell] as usize],
+                                target[cell],
+                            )
+                        } else {
+                            [0; 2]
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                let mut stride = GROUP_SIZE as usize / 2;
+                while stride > 0 {
+                    for lid in 0..stride {
+                        sums[lid] = add64(sums[lid], sums[lid + stride]);
+                    }
+                    stride /= 2;
+                }
+                partials[row * groups + group] = sums[0];
+            }
+        }
+        partials
+    }
+
+    /// Runs without a GPU: the shader's arithmetic and partial sum layout, mirrored on the
+    /// CPU and added up by `add_partials`, give the CPU's costs. The weights are big enough
+    /// to carry into the high words, and the grid isn't a whole number of workgroups.
+    #[test]
+    fn partial_sums_add_up_to_the_cpus_costs() {
+        let (mut settings, source, target, shuffled) = fixture(40, u32::MAX, 1 << 12);
+        let identity = (0..source.len()).collect::<Vec<_>>();
+        let source_cells = source
+            .iter()
+            .map(|&p| Cell::new(p, 0, 0))
+            .collect::<Vec<_>>();
+        let target_cells = target
+            .iter()
+            .map(|t| Cell::new(t.pixel, t.weight as u32, t.proximity as u32))
+            .collect::<Vec<_>>();
+        let assignments = [&shuffled, &identity]
+            .iter()
+            .flat_map(|a| a.iter().map(|&i| i as u32))
+            .collect::<Vec<_>>();
+        let groups = source.len().div_ceil(GROUP_SIZE as usize);
+        assert!(groups > 1 && source.len() % GROUP_SIZE as usize != 0);
+
+        for tileable in [false, true] {
+            settings.tileable = tileable;
+            let params = Params {
+                count: target.len() as u32,
+                wrap: settings.wrap() as u32,
+                _pad: [0; 2],
+            };
+            let partials = shader_partials(params, &source_cells, &target_cells, &assignments);
+            let expected =
+                [&shuffled, &identity].map(|a| assignment_cost(&source, &target, a, &settings));
+            assert_eq!(
+                add_partials(&partials, groups),
+                expected,
+                "tileable {tileable}"
+            );
+        }
+    }
+
+    /// Scores on whatever adapter there is, and is skipped without one.
+    #[test]
+    fn gpu_costs_match_the_cpus() {
+        let (mut settings, source, target, shuffled) = fixture(24, 256, 16);
+        let identity = (0..source.len()).collect::<Vec<_>>();
+
+        for tileable in [false, true] {
+            settings.tileable = tileable;
+            let Ok(gpu) = FitnessEvaluator::new(&source, &target, &settings) else {
+                eprintln!("no adapter, skipped");
+                return;
+            };
+            let expected =
+                [&shuffled, &identity].map(|a| assignment_cost(&source, &target, a, &settings));
+            assert_eq!(gpu.costs(&[&shuffled, &identity]).unwrap(), expected);
+            // again, through the buffers kept from the first call
+            assert_eq!(
+                gpu.costs(&[&identity]).unwrap(),
+                expected[1..],
+                "tileable {tileable}"
+            );
+        }
+    }
+}
@@ -1,8 +1,24 @@
 #[cfg(not(target_arch = "wasm32"))]
 use std::sync::{Arc, atomic::AtomicBool};
+pub mod background;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod checkpoint;
+pub mod crop_preview;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod drawing_process;
+pub mod face;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod gpu_fitness;
+pub mod preprocess;
+pub mod queue;
+pub mod stats;
+pub mod svg_mask;
+pub mod text_target;
 pub mod util;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod video;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod watch;
 
 #[cfg(target_arch = "wasm32")]
 pub mod worker;
@@ -16,8 +32,8 @@ fn _debug_print(s: String) {
 
 use crate::app::calculate::util::Algorithm;
 use crate::app::{
-    calculate::util::{GenerationSettings, GridPixel, ProgressSink, WeightedPixel},
-    preset::{Preset, UnprocessedPreset},
+    calculate::util::{GenerationSettings, GridPixel, ProgressSink, Symmetry, WeightedPixel},
+    preset::{self, Preset, PresetMeta, UnprocessedPreset},
 };
 use egui::ahash::AHasher;
 use pathfinding::prelude::Weights;
@@ -31,9 +47,14 @@ fn heuristic(
     b: (u8, u8, u8),
     color_weight: i64,
     spatial_weight: i64,
+    wrap: i64, // measure distance around the grid's edges, on a grid this wide; 0: don't
 ) -> i64 {
-    let dx = apos.0 as i64 - bpos.0 as i64;
-    let dy = apos.1 as i64 - bpos.1 as i64;
+    let mut dx = (apos.0 as i64 - bpos.0 as i64).abs();
+    let mut dy = (apos.1 as i64 - bpos.1 as i64).abs();
+    if wrap > 0 {
+        dx = dx.min(wrap - dx);
+        dy = dy.min(wrap - dy);
+    }
     let spatial = dx * dx + dy * dy;
 
     let dr = a.0 as i64 - b.0 as i64;
@@ -45,10 +66,15 @@ fn heuristic(
     color * color_weight + weighted_spatial * weighted_spatial
 }
 
+// large enough that the optimal solver never moves a pixel across halves in swap mode,
+// or a pinned one at all
+const SWAP_CROSSING_PENALTY: i64 = 1 << 50;
+
 struct ImgDiffWeights<'a> {
     source: &'a [GridPixel],
     target: &'a [WeightedPixel],
     settings: &'a GenerationSettings,
+    pinned: Option<Vec<bool>>, // cells outside the source mask
 }
 
 // const TARGET_IMAGE_PATH: &str = "./target.png";
@@ -67,13 +93,22 @@ impl Weights<i64> for ImgDiffWeights<'_> {
     fn at(&self, row: usize, col: usize) -> i64 {
         let target = self.target[row];
         let source = self.source[col];
+        if !self.settings.allows_move(source.x, target.pixel.x) {
+            return -SWAP_CROSSING_PENALTY;
+        }
+        if let Some(pinned) = &self.pinned {
+            if (pinned[row] || pinned[col]) && row != col {
+                return -SWAP_CROSSING_PENALTY;
+            }
+        }
         -heuristic(
             target.coords(),
             source.coords(),
             target.rgb_tuple(),
             source.rgb_tuple(),
             target.weight,
-            self.settings.proximity_importance,
+            target.proximity,
+            self.settings.wrap(),
         )
     }
 
@@ -82,29 +117,77 @@ impl Weights<i64> for ImgDiffWeights<'_> {
     }
 }
 
+/// What the calculation is currently doing.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum Stage {
+    Preparing,
+    Matching,   // optimal algorithm
+    Optimizing, // genetic algorithm
+}
+
+impl Stage {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Stage::Preparing => "preparing...",
+            Stage::Matching => "matching pixels...",
+            Stage::Optimizing => "optimizing...",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Preview {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>, // rgb
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ProgressUpdate {
+    pub stage: Stage,
+    pub fraction: f32,
+    pub generation: Option<stats::GenerationStats>, // includes the current cost
+    pub preview: Option<Preview>,
+}
+
+impl ProgressUpdate {
+    pub fn stage(stage: Stage) -> Self {
+        Self {
+            stage,
+            fraction: 0.0,
+            generation: None,
+            preview: None,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub enum ProgressMsg {
-    Progress(f32),
-    UpdatePreview {
-        width: u32,
-        height: u32,
-        data: Vec<u8>,
-    },
+    Progress(ProgressUpdate),
     UpdateAssignments(Vec<usize>),
-    Done(Preset), // result directory
+    Warning(String), // worth telling the user, but the calculation carries on
+    Done(Box<Preset>),
+    #[cfg(not(target_arch = "wasm32"))]
+    FrameDone(Box<Preset>), // one frame of a sequence; the rest are still coming
     Error(String),
     Cancelled,
+    #[cfg(not(target_arch = "wasm32"))]
+    Population(Vec<u8>), // a population file that was asked for; the solve carries on
 }
 
 impl ProgressMsg {
     pub fn typ(&self) -> &'static str {
         match self {
             ProgressMsg::Progress(_) => "progress",
-            ProgressMsg::UpdatePreview { .. } => "update_preview",
             ProgressMsg::UpdateAssignments(_) => "update_assignments",
+            ProgressMsg::Warning(_) => "warning",
             ProgressMsg::Done(_) => "done",
+            #[cfg(not(target_arch = "wasm32"))]
+            ProgressMsg::FrameDone(_) => "frame_done",
             ProgressMsg::Error(_) => "error",
             ProgressMsg::Cancelled => "cancelled",
+            #[cfg(not(target_arch = "wasm32"))]
+            ProgressMsg::Population(_) => "population",
         }
     }
 }
@@ -124,16 +207,66 @@ pub fn process_optimal<S: ProgressSink>(
     )
     .unwrap();
     // let start_time = std::time::Instant::now();
-    let (source_pixels, target_pixels) = util::get_images(source_img, &settings)?;
+    let (full_source, full_target) = util::get_images(source_img, &settings)?;
+    let (source_pixels, target_pixels) = util::to_blocks(
+        &full_source,
+        &full_target,
+        settings.sidelen,
+        settings.block_size,
+    );
+
+    let Some(assignments) = optimal_solve(
+        &source_pixels,
+        &target_pixels,
+        &settings,
+        (0.0, 1.0),
+        tx,
+        #[cfg(not(target_arch = "wasm32"))]
+        &cancel,
+    ) else {
+        tx.send(ProgressMsg::Cancelled);
+        return Ok(());
+    };
+
+    //let img = make_new_img(&source_pixels, &assignments, target.width());
+
+    //let dir_name = util::save_result(target, "todo".to_string(), source, assignments, img)?;
 
+    tx.send(ProgressMsg::Done(make_preset(
+        unprocessed.name,
+        &settings,
+        &full_source,
+        &full_target,
+        &assignments,
+    )));
+
+    // println!(
+    //     "finished in {:.2?} seconds",
+    //     std::time::Instant::now().duration_since(start_time)
+    // );
+    Ok(())
+}
+
+/// Finds the optimal assignment with the hungarian algorithm. Progress is mapped into
+/// `progress_range`. Returns `None` when cancelled.
+pub(crate) fn optimal_solve<S: ProgressSink>(
+    source_pixels: &[GridPixel],
+    target_pixels: &[WeightedPixel],
+    settings: &GenerationSettings,
+    progress_range: (f32, f32),
+    tx: &mut S,
+    #[cfg(not(target_arch = "wasm32"))] cancel: &AtomicBool,
+) -> Option<Vec<usize>> {
+    let (lo, hi) = progress_range;
     let weights = ImgDiffWeights {
-        source: &source_pixels,
-        target: &target_pixels,
-        settings: &settings,
+        source: source_pixels,
+        target: target_pixels,
+        settings,
+        pinned: settings.pinned_cells().unwrap_or_default(), // get_images checked it
     };
 
     // pathfinding::kuhn_munkres, inlined to allow for progress bar and cancelling
-    let (_total_diff, assignments) = {
+    let (_total_diff, mut assignments) = {
         // We call x the rows and y the columns. (nx, ny) is the size of the matrix.
         let nx = weights.rows();
         let ny = weights.columns();
@@ -147,15 +280,19 @@ pub fn process_optimal<S: ProgressSink>(
         // lx is the labelling for x nodes, ly the labelling for y nodes. We start
         // with an acceptable labelling with the maximum possible values for lx
         // and 0 for ly.
-        let mut lx: Vec<i64> = (0..nx)
-            .map(|row| (0..ny).map(|col| weights.at(row, col)).max().unwrap())
-            .collect::<Vec<_>>();
+        let mut lx: Vec<i64> = {
+            let _span = tracing::info_span!("build_costs", nx, ny).entered();
+            (0..nx)
+                .map(|row| (0..ny).map(|col| weights.at(row, col)).max().unwrap())
+                .collect::<Vec<_>>()
+        };
         let mut ly: Vec<i64> = vec![0; ny];
         // s, augmenting, and slack will be reset every time they are reused. augmenting
         // contains Some(prev) when the corresponding node belongs to the augmenting path.
         let mut s = FxIndexSet::<usize>::default();
         let mut alternating = Vec::with_capacity(ny);
         let mut slack = vec![0; ny];
+        let _span = tracing::info_span!("optimal_matching").entered();
         let mut slackx = Vec::with_capacity(ny);
         let mut assignment_preview = vec![0usize; nx];
         for root in 0..nx {
@@ -241,23 +378,26 @@ pub fn process_optimal<S: ProgressSink>(
                 #[cfg(not(target_arch = "wasm32"))]
                 {
                     if cancel.load(std::sync::atomic::Ordering::Relaxed) {
-                        tx.send(ProgressMsg::Cancelled);
-                        return Ok(());
+                        return None;
                     }
                 }
 
-                tx.send(ProgressMsg::Progress(root as f32 / nx as f32));
-
                 for (slot, value) in assignment_preview.iter_mut().zip(xy.iter()) {
                     *slot = value.unwrap_or(0);
                 }
-                let data = make_new_img(&source_pixels, &assignment_preview, settings.sidelen);
+                let grid_sidelen = settings.grid_sidelen();
+                let data = make_new_img(source_pixels, &assignment_preview, grid_sidelen);
 
-                tx.send(ProgressMsg::UpdatePreview {
-                    width: settings.sidelen,
-                    height: settings.sidelen,
-                    data,
-                });
+                tx.send(ProgressMsg::Progress(ProgressUpdate {
+                    stage: Stage::Matching,
+                    fraction: lo + (hi - lo) * root as f32 / nx as f32,
+                    generation: None,
+                    preview: Some(Preview {
+                        width: grid_sidelen,
+                        height: grid_sidelen,
+                        data,
+                    }),
+                }));
             }
         }
         (
@@ -265,26 +405,62 @@ pub fn process_optimal<S: ProgressSink>(
             xy.into_iter().map(Option::unwrap).collect::<Vec<_>>(),
         )
     };
+    if settings.symmetry == Symmetry::Enforce {
+        util::mirror_assignments(&mut assignments, settings.grid_sidelen());
+    }
+    Some(assignments)
+}
 
-    //let img = make_new_img(&source_pixels, &assignments, target.width());
-
-    //let dir_name = util::save_result(target, "todo".to_string(), source, assignments, img)?;
+/// The target's weights at the side kept with presets.
+fn weight_map(sidelen: u32, full_target: &[WeightedPixel]) -> Vec<u8> {
+    if full_target.len() != (sidelen * sidelen) as usize {
+        return Vec::new();
+    }
+    let map = image::ImageBuffer::from_fn(sidelen, sidelen, |x, y| {
+        let weight = full_target[(y * sidelen + x) as usize].weight.clamp(0, 255) as u8;
+        image::Rgb([weight; 3])
+    });
+    util::resample_weights(&map, preset::WEIGHT_MAP_SIDE)
+        .into_iter()
+        .map(|w| w as u8)
+        .collect()
+}
 
-    tx.send(ProgressMsg::Done(Preset {
+/// Builds the finished preset, expanding block assignments back to full resolution.
+fn make_preset(
+    name: String,
+    settings: &GenerationSettings,
+    full_source: &[GridPixel],
+    full_target: &[WeightedPixel],
+    assignments: &[usize],
+) -> Box<Preset> {
+    let assignments =
+        util::expand_block_assignments(assignments, settings.sidelen, settings.block_size);
+    let quality = stats::QualityReport::measure(full_source, full_target, &assignments, settings);
+    Box::new(Preset {
         inner: UnprocessedPreset {
-            name: unprocessed.name,
+            name,
             width: settings.sidelen,
             height: settings.sidelen,
-            source_img: source_pixels.into_iter().flat_map(|p| p.rgb).collect(),
+            source_img: full_source.iter().flat_map(|p| p.rgb).collect(),
         },
         assignments,
-    }));
-
-    // println!(
-    //     "finished in {:.2?} seconds",
-    //     std::time::Instant::now().duration_since(start_time)
-    // );
-    Ok(())
+        stats: Vec::new(),
+        quality: Some(quality),
+        meta: PresetMeta {
+            id: settings.id,
+            created: preset::now_unix(),
+            algorithm: Some(settings.algorithm),
+            aspect: if settings.mutual_swap {
+                util::Aspect::Square
+            } else {
+                settings.aspect
+            },
+            tileable: settings.tileable,
+            weights: weight_map(settings.sidelen, full_target),
+            ..Default::default()
+        },
+    })
 }
 
 fn make_new_img(source_pixels: &[GridPixel], assignments: &[usize], sidelen: u32) -> Vec<u8> {
@@ -315,25 +491,67 @@ impl Pixel {
     }
 
     #[inline(always)]
-    fn calc_heuristic(&self, target: WeightedPixel, proximity_importance: i64) -> i64 {
+    fn calc_heuristic(&self, target: WeightedPixel, wrap: i64) -> i64 {
         heuristic(
             self.tile.coords(),
             target.coords(),
             self.tile.rgb_tuple(),
             target.rgb_tuple(),
             target.weight,
-            proximity_importance,
+            target.proximity,
+            wrap,
         )
     }
 }
 
 const SWAPS_PER_GENERATION_PER_PIXEL: usize = 128;
+pub(crate) const GENETIC_SEED: u64 = 12345;
+// how much a color mismatch with the mirrored cell counts, relative to the target color
+const SYMMETRY_STRENGTH_DIVISOR: i64 = 2;
+
+/// How much swapping the pixels at `a` and `b` reduces the color difference between
+/// each of them and the cell mirrored across the vertical axis.
+fn symmetry_gain(
+    pixels: &[Pixel],
+    target_pixels: &[WeightedPixel],
+    a: usize,
+    b: usize,
+    sidelen: u32,
+) -> i64 {
+    let sidelen = sidelen as usize;
+    let mirror = |i: usize| {
+        let x = i % sidelen;
+        i - x + (sidelen - 1 - x)
+    };
+    let rgb_after = |i: usize| {
+        if i == a {
+            pixels[b].tile.rgb
+        } else if i == b {
+            pixels[a].tile.rgb
+        } else {
+            pixels[i].tile.rgb
+        }
+    };
+    let cost = |rgb: [u8; 3], partner: [u8; 3], at: usize| {
+        let color = (0..3)
+            .map(|c| (rgb[c] as i64 - partner[c] as i64).pow(2))
+            .sum::<i64>();
+        color * target_pixels[at].weight / SYMMETRY_STRENGTH_DIVISOR
+    };
+
+    let (pa, pb) = (pixels[a].tile.rgb, pixels[b].tile.rgb);
+    let (ma, mb) = (mirror(a), mirror(b));
+    let before = cost(pa, pixels[ma].tile.rgb, a) + cost(pb, pixels[mb].tile.rgb, b);
+    let after = cost(pb, rgb_after(ma), a) + cost(pa, rgb_after(mb), b);
+    before - after
+}
 
 pub fn process_genetic<S: ProgressSink>(
     unprocessed: UnprocessedPreset,
     settings: GenerationSettings,
     tx: &mut S,
     #[cfg(not(target_arch = "wasm32"))] cancel: Arc<AtomicBool>,
+    #[cfg(not(target_arch = "wasm32"))] snapshot: Arc<AtomicBool>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let source_img = image::ImageBuffer::from_vec(
         unprocessed.width,
@@ -342,95 +560,554 @@ pub fn process_genetic<S: ProgressSink>(
     )
     .unwrap();
     // let start_time = std::time::Instant::now();
-    let (source_pixels, target_pixels) = util::get_images(source_img, &settings)?;
+    let (full_source, full_target) = util::get_images(source_img, &settings)?;
+    let (source_pixels, target_pixels) = util::to_blocks(
+        &full_source,
+        &full_target,
+        settings.sidelen,
+        settings.block_size,
+    );
+
+    let start = match settings.resume.clone() {
+        Some(state) => state,
+        None => SolverState::identity(&source_pixels, settings.grid_sidelen(), GENETIC_SEED),
+    };
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut checkpoint = checkpoint::Checkpointer::new(&unprocessed, &settings, snapshot);
+    let result = genetic_solve(
+        &source_pixels,
+        &target_pixels,
+        &settings,
+        start,
+        (0.0, 1.0),
+        tx,
+        #[cfg(not(target_arch = "wasm32"))]
+        &cancel,
+        #[cfg(not(target_arch = "wasm32"))]
+        Some(&mut checkpoint),
+    );
+    #[cfg(not(target_arch = "wasm32"))]
+    checkpoint.remove();
+    let Some(assignments) = result else {
+        tx.send(ProgressMsg::Cancelled);
+        return Ok(());
+    };
+
+    //let dir_name = util::save_result(target, base_name, source, assignments, img)?;
+    tx.send(ProgressMsg::Done(make_preset(
+        unprocessed.name,
+        &settings,
+        &full_source,
+        &full_target,
+        &assignments,
+    )));
+    Ok(())
+}
+
+/// Largest grid, in cells, the optimal algorithm is among the tries of a best of solve;
+/// beyond it one optimal solve would take the whole budget.
+const BEST_OF_OPTIMAL_MAX_CELLS: usize = 64 * 64;
+
+/// Solves several times, the optimal algorithm first if the grid is small enough and then
+/// the fast one with different seeds, and keeps whichever assignment costs least.
+pub fn process_best_of<S: ProgressSink>(
+    unprocessed: UnprocessedPreset,
+    settings: GenerationSettings,
+    tx: &mut S,
+    #[cfg(not(target_arch = "wasm32"))] cancel: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let source_img = image::ImageBuffer::from_vec(
+        unprocessed.width,
+        unprocessed.height,
+        unprocessed.source_img.clone(),
+    )
+    .unwrap();
+    let (full_source, full_target) = util::get_images(source_img, &settings)?;
+    let (source_pixels, target_pixels) = util::to_blocks(
+        &full_source,
+        &full_target,
+        settings.sidelen,
+        settings.block_size,
+    );
 
-    let mut pixels = source_pixels
+    #[cfg(not(target_arch = "wasm32"))]
+    let fitness = fitness_evaluator(&source_pixels, &target_pixels, &settings);
+    let Some(assignments) = best_of_solve(
+        &source_pixels,
+        &target_pixels,
+        &settings,
+        (0.0, 1.0),
+        tx,
+        #[cfg(not(target_arch = "wasm32"))]
+        &cancel,
+        #[cfg(not(target_arch = "wasm32"))]
+        fitness.as_ref(),
+    ) else {
+        tx.send(ProgressMsg::Cancelled);
+        return Ok(());
+    };
+    tx.send(ProgressMsg::Done(make_preset(
+        unprocessed.name,
+        &settings,
+        &full_source,
+        &full_target,
+        &assignments,
+    )));
+    Ok(())
+}
+
+/// The solves of a best of run, and the cheapest of their assignments. A new solve isn't
+/// started when one more as long as the average so far would overrun `best_of_seconds`.
+/// The results are scored together at the end, on `fitness` if there is one. Progress is
+/// mapped into `progress_range`. Returns `None` when cancelled.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn best_of_solve<S: ProgressSink>(
+    source_pixels: &[GridPixel],
+    target_pixels: &[WeightedPixel],
+    settings: &GenerationSettings,
+    progress_range: (f32, f32),
+    tx: &mut S,
+    #[cfg(not(target_arch = "wasm32"))] cancel: &AtomicBool,
+    #[cfg(not(target_arch = "wasm32"))] fitness: Option<&gpu_fitness::FitnessEvaluator>,
+) -> Option<Vec<usize>> {
+    let runs = settings.best_of_runs.max(1);
+    let try_optimal = source_pixels.len() <= BEST_OF_OPTIMAL_MAX_CELLS;
+    let threads = settings.solver_threads() as u32;
+    let started = preset::now_unix();
+    let (lo, hi) = progress_range;
+    let part = |run: u32| lo + (hi - lo) * run as f32 / runs as f32;
+    let mut results = Vec::new();
+    let mut rounds = 0; // solves that were started together count once
+    let mut run = 0;
+    while run < runs {
+        let elapsed = preset::now_unix() - started;
+        if rounds > 0 && elapsed + elapsed / rounds > settings.best_of_seconds as u64 {
+            break;
+        }
+        rounds += 1;
+        if run == 0 && try_optimal {
+            results.push(optimal_solve(
+                source_pixels,
+                target_pixels,
+                settings,
+                (part(0), part(1)),
+                tx,
+                #[cfg(not(target_arch = "wasm32"))]
+                cancel,
+            )?);
+            run += 1;
+            continue;
+        }
+        // fast solves don't depend on each other, so one can run on each thread
+        let batch = threads.min(runs - run);
+        let seeds = (run..run + batch)
+            .map(|run| GENETIC_SEED + run as u64)
+            .collect::<Vec<_>>();
+        results.extend(genetic_batch(
+            source_pixels,
+            target_pixels,
+            settings,
+            &seeds,
+            (part(run), part(run + batch)),
+            tx,
+            #[cfg(not(target_arch = "wasm32"))]
+            cancel,
+        )?);
+        run += batch;
+    }
+
+    let costs = score_assignments(
+        source_pixels,
+        target_pixels,
+        &results,
+        settings,
+        #[cfg(not(target_arch = "wasm32"))]
+        fitness,
+    );
+    tracing::info!("best of costs {:?}", costs);
+    let best = (0..results.len()).min_by_key(|&i| costs[i]).unwrap(); // one run always happens
+    Some(results.swap_remove(best))
+}
+
+/// Fast solves from the identity with each of `seeds`, side by side on their own
+/// threads. Progress is their average mapped into `progress_range`, with previews and
+/// statistics from the first only, so the chart follows one solve. Returns `None` when
+/// cancelled.
+fn genetic_batch<S: ProgressSink>(
+    source_pixels: &[GridPixel],
+    target_pixels: &[WeightedPixel],
+    settings: &GenerationSettings,
+    seeds: &[u64],
+    progress_range: (f32, f32),
+    tx: &mut S,
+    #[cfg(not(target_arch = "wasm32"))] cancel: &AtomicBool,
+) -> Option<Vec<Vec<usize>>> {
+    let start = |seed| SolverState::identity(source_pixels, settings.grid_sidelen(), seed);
+    #[cfg(not(target_arch = "wasm32"))]
+    if seeds.len() > 1 {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        return std::thread::scope(|scope| {
+            let solves = seeds
+                .iter()
+                .enumerate()
+                .map(|(i, &seed)| {
+                    let sender = sender.clone();
+                    scope.spawn(move || {
+                        let mut sink = |msg| {
+                            sender.send((i, msg)).ok();
+                        };
+                        genetic_solve(
+                            source_pixels,
+                            target_pixels,
+                            settings,
+                            start(seed),
+                            (0.0, 1.0),
+                            &mut sink,
+                            cancel,
+                            None,
+                        )
+                    })
+                })
+                .collect::<Vec<_>>();
+            drop(sender);
+
+            let mut fractions = vec![0.0; seeds.len()];
+            for (i, msg) in receiver {
+                let ProgressMsg::Progress(mut update) = msg else {
+                    continue;
+                };
+                fractions[i] = update.fraction;
+                let (lo, hi) = progress_range;
+                update.fraction =
+                    lo + (hi - lo) * fractions.iter().sum::<f32>() / seeds.len() as f32;
+                if i > 0 {
+                    update.generation = None;
+                    update.preview = None;
+                }
+                tx.send(ProgressMsg::Progress(update));
+            }
+            solves
+                .into_iter()
+                .map(|solve| solve.join().unwrap())
+                .collect()
+        });
+    }
+    seeds
+        .iter()
+        .map(|&seed| {
+            genetic_solve(
+                source_pixels,
+                target_pixels,
+                settings,
+                start(seed),
+                progress_range,
+                tx,
+                #[cfg(not(target_arch = "wasm32"))]
+                cancel,
+                #[cfg(not(target_arch = "wasm32"))]
+                None,
+            )
+        })
+        .collect()
+}
+
+/// What scores a solve's assignments on the GPU, set up once for the whole solve since
+/// that opens a device. `None` without a usable adapter, and they're scored here.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn fitness_evaluator(
+    source: &[GridPixel],
+    target: &[WeightedPixel],
+    settings: &GenerationSettings,
+) -> Option<gpu_fitness::FitnessEvaluator> {
+    gpu_fitness::FitnessEvaluator::new(source, target, settings)
+        .inspect_err(|err| tracing::info!("scoring on the cpu instead: {}", err))
+        .ok()
+}
+
+/// The total cost of each candidate assignment, on the GPU when there is one.
+fn score_assignments(
+    source: &[GridPixel],
+    target: &[WeightedPixel],
+    candidates: &[Vec<usize>],
+    settings: &GenerationSettings,
+    #[cfg(not(target_arch = "wasm32"))] fitness: Option<&gpu_fitness::FitnessEvaluator>,
+) -> Vec<i64> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(fitness) = fitness {
+        let candidates = candidates.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        match fitness.costs(&candidates) {
+            Ok(costs) => return costs,
+            Err(err) => tracing::info!("scoring on the cpu instead: {}", err),
+        }
+    }
+    candidates
+        .iter()
+        .map(|a| assignment_cost(source, target, a, settings))
+        .collect()
+}
+
+/// The cost function summed over an assignment, where `assignments[target] = source`.
+fn assignment_cost(
+    source: &[GridPixel],
+    target: &[WeightedPixel],
+    assignments: &[usize],
+    settings: &GenerationSettings,
+) -> i64 {
+    let wrap = settings.wrap();
+    target
+        .iter()
+        .zip(assignments)
+        .map(|(t, &s)| {
+            let s = source[s];
+            heuristic(
+                s.coords(),
+                t.coords(),
+                s.rgb_tuple(),
+                t.rgb_tuple(),
+                t.weight,
+                t.proximity,
+                wrap,
+            )
+        })
+        .sum()
+}
+
+/// Where a genetic solve is. Each generation's swaps are drawn from `seed` and the
+/// generation number, so a solve carried on from a saved state goes exactly as it would
+/// have without stopping.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SolverState {
+    pub generation: u32,
+    pub max_dist: u32,
+    pub start_dist: u32,
+    pub seed: u64,
+    #[serde(with = "crate::app::assignments::compact")]
+    pub assignments: Vec<usize>,
+}
+
+impl SolverState {
+    /// A solve starting from `assignments`, with swaps up to `start_dist` cells apart.
+    pub(crate) fn new(assignments: Vec<usize>, start_dist: u32, seed: u64) -> Self {
+        Self {
+            generation: 0,
+            max_dist: start_dist.max(2),
+            start_dist,
+            seed,
+            assignments,
+        }
+    }
+
+    /// A solve starting from every pixel where it already is.
+    pub(crate) fn identity(source_pixels: &[GridPixel], sidelen: u32, seed: u64) -> Self {
+        let assignments = source_pixels
+            .iter()
+            .map(|p| p.linear_index(sidelen))
+            .collect();
+        Self::new(assignments, sidelen, seed)
+    }
+}
+
+/// Each cell's source pixel along with its cost where `assignments` puts it.
+fn genetic_pixels(
+    source_pixels: &[GridPixel],
+    target_pixels: &[WeightedPixel],
+    assignments: &[usize],
+    wrap: i64,
+) -> Vec<Pixel> {
+    assignments
         .iter()
         .enumerate()
-        .map(|(i, &tile)| {
-            let mut p = Pixel::new(tile, 0);
-            let h = p.calc_heuristic(target_pixels[i], settings.proximity_importance);
+        .map(|(i, &source_idx)| {
+            let mut p = Pixel::new(source_pixels[source_idx], 0);
+            let h = p.calc_heuristic(target_pixels[i], wrap);
             p.update_heuristic(h);
             p
         })
-        .collect::<Vec<_>>();
+        .collect()
+}
 
-    let mut rng = frand::Rand::with_seed(12345);
+/// One generation of random swaps between cells up to `max_dist` apart, drawn from `seed`
+/// and `generation`. Returns how many swaps were made.
+#[allow(clippy::too_many_arguments)]
+fn genetic_generation(
+    pixels: &mut [Pixel],
+    assignments: &mut [usize],
+    target_pixels: &[WeightedPixel],
+    settings: &GenerationSettings,
+    pinned: Option<&[bool]>,
+    max_dist: u32,
+    seed: u64,
+    generation: u32,
+) -> u32 {
+    let sidelen = settings.grid_sidelen();
+    let wrap = settings.wrap();
     let swaps_per_generation = SWAPS_PER_GENERATION_PER_PIXEL * pixels.len();
-    let sidelen = settings.sidelen;
     let sidelen_u16 = sidelen as u16;
     let sidelen_i16 = sidelen_u16 as i16;
 
-    let mut assignments = pixels
-        .iter()
-        .map(|p| p.tile.linear_index(sidelen))
-        .collect::<Vec<_>>();
+    let mut rng = frand::Rand::with_seed(seed);
+    rng.mix(generation as u64);
+    let mut swaps_made = 0;
+    for _ in 0..swaps_per_generation {
+        let apos = rng.gen_range(0..pixels.len() as u32) as usize;
+        let ax = apos as u16 % sidelen_u16;
+        let ay = apos as u16 / sidelen_u16;
+        let range = -(max_dist as i16)..(max_dist as i16 + 1);
+        let (bx, by) = (
+            ax as i16 + rng.gen_range(range.clone()),
+            ay as i16 + rng.gen_range(range),
+        );
+        // tileable grids can trade pixels across the edges too
+        let (bx, by) = if settings.tileable {
+            (
+                bx.rem_euclid(sidelen_i16) as u16,
+                by.rem_euclid(sidelen_i16) as u16,
+            )
+        } else {
+            (
+                bx.clamp(0, sidelen_i16 - 1) as u16,
+                by.clamp(0, sidelen_i16 - 1) as u16,
+            )
+        };
+        let bpos = by as usize * sidelen as usize + bx as usize;
+        if !settings.allows_move(ax, bx) {
+            continue;
+        }
+        if pinned.is_some_and(|p| p[apos] || p[bpos]) {
+            continue;
+        }
 
-    let mut max_dist = sidelen;
-    loop {
-        let mut swaps_made = 0;
-        for _ in 0..swaps_per_generation {
-            let apos = rng.gen_range(0..pixels.len() as u32) as usize;
-            let ax = apos as u16 % sidelen_u16;
-            let ay = apos as u16 / sidelen_u16;
-            let range = -(max_dist as i16)..(max_dist as i16 + 1);
-            let bx = (ax as i16 + rng.gen_range(range.clone())).clamp(0, sidelen_i16 - 1) as u16;
-            let by = (ay as i16 + rng.gen_range(range)).clamp(0, sidelen_i16 - 1) as u16;
-            let bpos = by as usize * sidelen as usize + bx as usize;
-
-            let t_a = target_pixels[apos];
-            let t_b = target_pixels[bpos];
-
-            let a_on_b_h = pixels[apos].calc_heuristic(t_b, settings.proximity_importance);
-
-            let b_on_a_h = pixels[bpos].calc_heuristic(t_a, settings.proximity_importance);
-
-            let improvement_a = pixels[apos].h - b_on_a_h;
-            let improvement_b = pixels[bpos].h - a_on_b_h;
-            if improvement_a + improvement_b > 0 {
-                // swap
-                pixels.swap(apos, bpos);
-                assignments.swap(apos, bpos);
-                pixels[apos].update_heuristic(b_on_a_h);
-                pixels[bpos].update_heuristic(a_on_b_h);
-                swaps_made += 1;
-            }
+        let t_a = target_pixels[apos];
+        let t_b = target_pixels[bpos];
+
+        let a_on_b_h = pixels[apos].calc_heuristic(t_b, wrap);
+
+        let b_on_a_h = pixels[bpos].calc_heuristic(t_a, wrap);
+
+        let improvement_a = pixels[apos].h - b_on_a_h;
+        let improvement_b = pixels[bpos].h - a_on_b_h;
+        let mut improvement = improvement_a + improvement_b;
+        if settings.symmetry == Symmetry::Encourage {
+            improvement += symmetry_gain(pixels, target_pixels, apos, bpos, sidelen);
+        }
+        if improvement > 0 {
+            // swap
+            pixels.swap(apos, bpos);
+            assignments.swap(apos, bpos);
+            pixels[apos].update_heuristic(b_on_a_h);
+            pixels[bpos].update_heuristic(a_on_b_h);
+            swaps_made += 1;
         }
+    }
+    swaps_made
+}
+
+/// Whether a genetic solve has settled: swaps are short and hardly any are made.
+fn genetic_converged(max_dist: u32, swaps_made: u32) -> bool {
+    max_dist < 4 && swaps_made < 10
+}
+
+/// The swap distance for the generation after one at `max_dist`.
+fn next_max_dist(max_dist: u32) -> u32 {
+    (max_dist as f32 * 0.99).max(2.0) as u32
+}
+
+/// Improves an assignment by random pair swaps, carrying on from `start`. Progress is
+/// mapped into `progress_range`. Returns `None` when cancelled.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn genetic_solve<S: ProgressSink>(
+    source_pixels: &[GridPixel],
+    target_pixels: &[WeightedPixel],
+    settings: &GenerationSettings,
+    start: SolverState,
+    progress_range: (f32, f32),
+    tx: &mut S,
+    #[cfg(not(target_arch = "wasm32"))] cancel: &AtomicBool,
+    #[cfg(not(target_arch = "wasm32"))] mut checkpoint: Option<&mut checkpoint::Checkpointer>,
+) -> Option<Vec<usize>> {
+    let sidelen = settings.grid_sidelen();
+    let wrap = settings.wrap();
+    let SolverState {
+        mut generation,
+        mut max_dist,
+        start_dist,
+        seed,
+        mut assignments,
+    } = start;
+
+    let mut pixels = genetic_pixels(source_pixels, target_pixels, &assignments, wrap);
+    let pinned = settings.pinned_cells().unwrap_or_default(); // get_images checked it
+
+    let _span = tracing::info_span!("genetic_solve", sidelen, start_dist).entered();
+    loop {
+        let _generation_span = tracing::info_span!("generation", generation).entered();
+        let swaps_made = genetic_generation(
+            &mut pixels,
+            &mut assignments,
+            target_pixels,
+            settings,
+            pinned.as_deref(),
+            max_dist,
+            seed,
+            generation,
+        );
 
         #[cfg(not(target_arch = "wasm32"))]
         {
             if cancel.load(std::sync::atomic::Ordering::Relaxed) {
                 println!("cancelled");
-                tx.send(ProgressMsg::Cancelled);
-                return Ok(());
+                return None;
             }
         }
 
+        // the swaps keep each cell's cost up to date, so the total is already at hand
+        let cost = pixels.iter().map(|p| p.h).sum::<i64>();
+        let stats = stats::GenerationStats {
+            generation,
+            cost,
+            mean_cost: cost as f64 / pixels.len().max(1) as f64,
+            swaps: swaps_made,
+            max_dist,
+        };
+        generation += 1;
+
         //debug_print(format!("max_dist = {max_dist}, swaps made = {swaps_made}"));
-        if max_dist < 4 && swaps_made < 10 {
-            //let dir_name = util::save_result(target, base_name, source, assignments, img)?;
-            tx.send(ProgressMsg::Done(Preset {
-                inner: UnprocessedPreset {
-                    name: unprocessed.name,
-                    width: settings.sidelen,
-                    height: settings.sidelen,
-                    source_img: source_pixels.iter().flat_map(|p| p.rgb).collect(),
-                },
-                assignments,
-            }));
-            return Ok(());
+        if genetic_converged(max_dist, swaps_made) {
+            if settings.symmetry == Symmetry::Enforce {
+                util::mirror_assignments(&mut assignments, sidelen);
+            }
+            return Some(assignments);
         }
-        let data = make_new_img(&source_pixels, &assignments, settings.sidelen);
-        tx.send(ProgressMsg::UpdatePreview {
-            width: settings.sidelen,
-            height: settings.sidelen,
-            data,
-        });
-        tx.send(ProgressMsg::Progress(
-            1.0 - max_dist as f32 / settings.sidelen as f32,
-        ));
+        let data = make_new_img(source_pixels, &assignments, sidelen);
+        let (lo, hi) = progress_range;
+        let local = 1.0 - max_dist as f32 / start_dist.max(2) as f32;
+        tx.send(ProgressMsg::Progress(ProgressUpdate {
+            stage: Stage::Optimizing,
+            fraction: lo + (hi - lo) * local,
+            generation: Some(stats),
+            preview: Some(Preview {
+                width: sidelen,
+                height: sidelen,
+                data,
+            }),
+        }));
+
+        max_dist = next_max_dist(max_dist);
 
-        max_dist = (max_dist as f32 * 0.99).max(2.0) as u32;
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(checkpoint) = checkpoint.as_mut() {
+            let state = || SolverState {
+                generation,
+                max_dist,
+                start_dist,
+                seed,
+                assignments: assignments.clone(),
+            };
+            checkpoint.tick(state);
+            if let Some(population) = checkpoint.snapshot(state) {
+                tx.send(ProgressMsg::Population(population));
+            }
+        }
     }
 }
 
@@ -444,16 +1121,37 @@ pub fn process_genetic<S: ProgressSink>(
 //             .join(",")
 //     )
 // }
+
+/// Announces the calculation and warns about inputs that will give poor results.
+pub(crate) fn report_start<S: ProgressSink>(
+    unprocessed: &UnprocessedPreset,
+    settings: &GenerationSettings,
+    tx: &mut S,
+) {
+    tx.send(ProgressMsg::Progress(ProgressUpdate::stage(
+        Stage::Preparing,
+    )));
+    if unprocessed.width.min(unprocessed.height) < settings.sidelen {
+        tx.send(ProgressMsg::Warning(format!(
+            "the source image is smaller than {}x{} and will be upscaled",
+            settings.sidelen, settings.sidelen
+        )));
+    }
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 pub fn process<S: ProgressSink>(
     unprocessed: UnprocessedPreset,
     settings: GenerationSettings,
     tx: &mut S,
     cancel: Arc<AtomicBool>,
+    snapshot: Arc<AtomicBool>, // set to ask a fast solve for a population file
 ) -> Result<(), Box<dyn std::error::Error>> {
+    report_start(&unprocessed, &settings, tx);
     match settings.algorithm {
         Algorithm::Optimal => process_optimal(unprocessed, settings, tx, cancel),
-        Algorithm::Genetic => process_genetic(unprocessed, settings, tx, cancel),
+        Algorithm::Genetic => process_genetic(unprocessed, settings, tx, cancel, snapshot),
+        Algorithm::BestOf => process_best_of(unprocessed, settings, tx, cancel),
     }
 }
 
@@ -463,8 +1161,10 @@ pub fn process<S: ProgressSink>(
     settings: GenerationSettings,
     tx: &mut S,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    report_start(&unprocessed, &settings, tx);
     match settings.algorithm {
         Algorithm::Optimal => process_optimal(unprocessed, settings, tx),
         Algorithm::Genetic => process_genetic(unprocessed, settings, tx),
+        Algorithm::BestOf => process_best_of(unprocessed, settings, tx),
     }
 }
@@ -0,0 +1,370 @@
+use image::imageops;
+use serde::{Deserialize, Serialize};
+
+use crate::app::calculate::util::{SourceImg, WideImg};
+
+/// How float (HDR) images are brought into 0..1 before they're cut down to 8 bits.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+pub enum Tonemap {
+    Clip,
+    Reinhard,
+    #[default]
+    Aces,
+}
+
+impl Tonemap {
+    fn apply(self, v: f32) -> f32 {
+        match self {
+            Tonemap::Clip => v,
+            Tonemap::Reinhard => v / (1.0 + v),
+            // Narkowicz's fit of the ACES filmic curve
+            Tonemap::Aces => (v * (2.51 * v + 0.03)) / (v * (2.43 * v + 0.59) + 0.14),
+        }
+    }
+}
+
+/// Options for loading HDR images, configurable in the config file.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default)]
+pub struct HdrSettings {
+    pub tonemap: Tonemap,
+    pub exposure: f32, // stops, 0: unchanged
+}
+
+impl Default for HdrSettings {
+    fn default() -> Self {
+        Self {
+            tonemap: Tonemap::Aces,
+            exposure: 0.0,
+        }
+    }
+}
+
+impl HdrSettings {
+    /// Converts a loaded image to 8-bit sRGB. Float images (EXR, Radiance HDR) hold linear
+    /// light that can go well past 1, so they're exposed and tonemapped instead of clipped.
+    pub fn convert(&self, img: &image::DynamicImage) -> SourceImg {
+        use image::DynamicImage::{ImageRgb32F, ImageRgba32F};
+        if !matches!(img, ImageRgb32F(_) | ImageRgba32F(_)) {
+            return img.to_rgb8();
+        }
+        let linear = img.to_rgb32f();
+        let scale = 2f32.powf(self.exposure);
+        SourceImg::from_fn(linear.width(), linear.height(), |x, y| {
+            image::Rgb(linear.get_pixel(x, y).0.map(|v| {
+                let v = self.tonemap.apply(v.max(0.0) * scale).clamp(0.0, 1.0);
+                (srgb_from_linear(v) * 255.0).round() as u8
+            }))
+        })
+    }
+}
+
+pub fn srgb_from_linear(v: f32) -> f32 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Adjustments applied to the cropped source before it's split into pixels.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct SourceFilters {
+    pub brightness: f32, // -1..1, 0: unchanged
+    pub contrast: f32,   // 0..2, 1: unchanged
+    pub saturation: f32, // 0..2, 1: unchanged
+    pub grayscale: bool,
+    pub posterize: u8, // levels per channel, 0: off
+    pub blur: f32,     // gaussian sigma in pixels, 0: off
+}
+
+impl Default for SourceFilters {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            grayscale: false,
+            posterize: 0,
+            blur: 0.0,
+        }
+    }
+}
+
+impl SourceFilters {
+    pub fn apply(&self, img: SourceImg) -> SourceImg {
+        self.apply_generic(img, 255.0)
+    }
+
+    pub fn apply_wide(&self, img: WideImg) -> WideImg {
+        self.apply_generic(img, 65535.0)
+    }
+
+    fn apply_generic<S>(
+        &self,
+        img: image::ImageBuffer<image::Rgb<S>, Vec<S>>,
+        max: f32,
+    ) -> image::ImageBuffer<image::Rgb<S>, Vec<S>>
+    where
+        S: image::Primitive + Into<f32> + 'static,
+        image::Rgb<S>: image::Pixel<Subpixel = S>,
+    {
+        if *self == Self::default() {
+            return img;
+        }
+
+        let mut img = if self.blur > 0.0 {
+            imageops::blur(&img, self.blur)
+        } else {
+            img
+        };
+
+        for pixel in img.pixels_mut() {
+            let mut rgb = pixel.0.map(|c| c.into() / max);
+
+            let luma = 0.299 * rgb[0] + 0.587 * rgb[1] + 0.114 * rgb[2];
+            let saturation = if self.grayscale { 0.0 } else { self.saturation };
+            for c in rgb.iter_mut() {
+                *c = luma + (*c - luma) * saturation;
+                *c = (*c - 0.5) * self.contrast + 0.5 + self.brightness;
+                *c = c.clamp(0.0, 1.0);
+                if self.posterize >= 2 {
+                    let levels = (self.posterize - 1) as f32;
+                    *c = (*c * levels).round() / levels;
+                }
+            }
+
+            pixel.0 = rgb.map(|c| S::from((c * max).round()).unwrap());
+        }
+        img
+    }
+}
+
+/// Gives fully transparent pixels the color of the nearest opaque one. A source keeps all
+/// its pixels, so whatever color hides under the transparency (usually black) would
+/// otherwise end up in the picture; this way cut-out subjects only contribute their own.
+pub fn fill_transparent(img: image::DynamicImage) -> image::DynamicImage {
+    use image::DynamicImage::*;
+    match img {
+        ImageLumaA8(_) => ImageRgba8(fill_transparent_generic(img.to_rgba8())),
+        ImageLumaA16(_) => ImageRgba16(fill_transparent_generic(img.to_rgba16())),
+        ImageRgba8(rgba) => ImageRgba8(fill_transparent_generic(rgba)),
+        ImageRgba16(rgba) => ImageRgba16(fill_transparent_generic(rgba)),
+        ImageRgba32F(rgba) => ImageRgba32F(fill_transparent_generic(rgba)),
+        other => other,
+    }
+}
+
+fn fill_transparent_generic<S>(
+    mut img: image::ImageBuffer<image::Rgba<S>, Vec<S>>,
+) -> image::ImageBuffer<image::Rgba<S>, Vec<S>>
+where
+    S: image::Primitive + 'static,
+    image::Rgba<S>: image::Pixel<Subpixel = S>,
+{
+    let (w, h) = (img.width() as usize, img.height() as usize);
+    let mut filled = img
+        .pixels()
+        .map(|p| p[3] != S::DEFAULT_MIN_VALUE)
+        .collect::<Vec<_>>();
+    // breadth first from every opaque pixel at once, so each hole gets the closest color
+    let mut queue = (0..w * h)
+        .filter(|&i| filled[i])
+        .collect::<std::collections::VecDeque<_>>();
+    if queue.is_empty() || queue.len() == w * h {
+        return img;
+    }
+    while let Some(i) = queue.pop_front() {
+        let (x, y) = (i % w, i / w);
+        let color = *img.get_pixel(x as u32, y as u32);
+        let neighbors = [
+            (x > 0).then(|| i - 1),
+            (x + 1 < w).then(|| i + 1),
+            (y > 0).then(|| i - w),
+            (y + 1 < h).then(|| i + w),
+        ];
+        for n in neighbors.into_iter().flatten() {
+            if !filled[n] {
+                filled[n] = true;
+                let p = img.get_pixel_mut((n % w) as u32, (n / w) as u32);
+                p.0[..3].copy_from_slice(&color.0[..3]);
+                queue.push_back(n);
+            }
+        }
+    }
+    img
+}
+
+/// A 16-bit copy of `img` if it has more than 8 bits per channel, for `set_wide_source`.
+pub fn wide_source(img: &image::DynamicImage) -> Option<WideImg> {
+    use image::ColorType::{L16, La16, Rgb16, Rgba16};
+    matches!(img.color(), L16 | La16 | Rgb16 | Rgba16).then(|| img.to_rgb16())
+}
+
+/// Rounds a 16-bit image to 8 bits per channel.
+pub fn narrow(img: &WideImg) -> SourceImg {
+    SourceImg::from_fn(img.width(), img.height(), |x, y| {
+        image::Rgb(
+            img.get_pixel(x, y)
+                .0
+                .map(|c| ((c as u32 * 255 + 32767) / 65535) as u8),
+        )
+    })
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum HistogramMatch {
+    Off,
+    SourceToTarget,
+    TargetToSource,
+}
+
+impl HistogramMatch {
+    pub const ALL: [HistogramMatch; 3] = [
+        HistogramMatch::Off,
+        HistogramMatch::SourceToTarget,
+        HistogramMatch::TargetToSource,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            HistogramMatch::Off => "no histogram matching",
+            HistogramMatch::SourceToTarget => "match source to target",
+            HistogramMatch::TargetToSource => "match target to source",
+        }
+    }
+
+    pub fn apply(&self, source: &mut SourceImg, target: &mut SourceImg) {
+        match self {
+            HistogramMatch::Off => {}
+            HistogramMatch::SourceToTarget => match_histogram(source, target),
+            HistogramMatch::TargetToSource => match_histogram(target, source),
+        }
+    }
+}
+
+/// Remaps each channel of `img` so its histogram follows `reference`'s.
+pub fn match_histogram(img: &mut SourceImg, reference: &SourceImg) {
+    let cdf = |img: &SourceImg, channel: usize| {
+        let mut hist = [0u64; 256];
+        for p in img.pixels() {
+            hist[p[channel] as usize] += 1;
+        }
+        let total = hist.iter().sum::<u64>().max(1) as f64;
+        let mut sum = 0;
+        hist.map(|count| {
+            sum += count;
+            sum as f64 / total
+        })
+    };
+
+    for channel in 0..3 {
+        let img_cdf = cdf(img, channel);
+        let ref_cdf = cdf(reference, channel);
+        let mut lut = [0u8; 256];
+        let mut u = 0;
+        for (v, entry) in lut.iter_mut().enumerate() {
+            while u < 255 && ref_cdf[u] < img_cdf[v] {
+                u += 1;
+            }
+            *entry = u as u8;
+        }
+        for p in img.pixels_mut() {
+            p[channel] = lut[p[channel] as usize];
+        }
+    }
+}
+
+pub const MAX_PALETTE_COLORS: usize = 256;
+
+/// Colors the source may use, so the result (and its exported gif) share one palette.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum Palette {
+    Full,
+    Reduced(u16), // quantize to this many colors
+    Custom(Vec<[u8; 3]>),
+}
+
+impl Palette {
+    /// Reduces a set of colors to at most `max` representative ones.
+    pub fn from_colors(colors: impl Iterator<Item = [u8; 3]>, max: usize) -> Vec<[u8; 3]> {
+        let rgba = colors
+            .flat_map(|[r, g, b]| [r, g, b, 255])
+            .collect::<Vec<_>>();
+        if rgba.is_empty() {
+            return Vec::new();
+        }
+        let nq = color_quant::NeuQuant::new(10, max.clamp(2, MAX_PALETTE_COLORS), &rgba);
+        let mut palette = nq
+            .color_map_rgb()
+            .chunks_exact(3)
+            .map(|c| [c[0], c[1], c[2]])
+            .collect::<Vec<_>>();
+        palette.sort();
+        palette.dedup();
+        palette
+    }
+
+    pub fn apply(&self, img: &mut SourceImg) {
+        let palette = match self {
+            Palette::Full => return,
+            Palette::Reduced(n) => Self::from_colors(img.pixels().map(|p| p.0), *n as usize),
+            Palette::Custom(colors) => colors.clone(),
+        };
+        if palette.is_empty() {
+            return;
+        }
+
+        for p in img.pixels_mut() {
+            p.0 = nearest(&palette, p.0.map(|c| c as f32));
+        }
+    }
+}
+
+fn nearest(palette: &[[u8; 3]], rgb: [f32; 3]) -> [u8; 3] {
+    *palette
+        .iter()
+        .min_by(|a, b| {
+            let dist = |c: &[u8; 3]| (0..3).map(|i| (c[i] as f32 - rgb[i]).powi(2)).sum::<f32>();
+            dist(a).total_cmp(&dist(b))
+        })
+        .unwrap()
+}
+
+/// Floyd-Steinberg dithers `img` to the `colors` most dominant colors of `target`, so
+/// the solver gets pixels that already match what the target is made of.
+pub fn dither_to_target(img: &mut SourceImg, target: &SourceImg, colors: u16) {
+    let palette = Palette::from_colors(target.pixels().map(|p| p.0), colors as usize);
+    if palette.is_empty() {
+        return;
+    }
+
+    let (w, h) = img.dimensions();
+    let mut buf = img
+        .pixels()
+        .map(|p| p.0.map(|c| c as f32))
+        .collect::<Vec<_>>();
+    for y in 0..h {
+        for x in 0..w {
+            let i = (y * w + x) as usize;
+            let old = buf[i];
+            let new = nearest(&palette, old);
+            img.put_pixel(x, y, image::Rgb(new));
+            let err = [0, 1, 2].map(|c| old[c] - new[c] as f32);
+            let mut spread = |dx: i32, dy: u32, factor: f32| {
+                let nx = x as i32 + dx;
+                if nx < 0 || nx >= w as i32 || y + dy >= h {
+                    return;
+                }
+                let j = ((y + dy) * w + nx as u32) as usize;
+                for (value, err) in buf[j].iter_mut().zip(err) {
+                    *value = (*value + err * factor).clamp(0.0, 255.0);
+                }
+            };
+            spread(1, 0, 7.0 / 16.0);
+            spread(-1, 1, 3.0 / 16.0);
+            spread(0, 1, 5.0 / 16.0);
+            spread(1, 1, 1.0 / 16.0);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+
+use uuid::Uuid;
+
+use crate::app::calculate::util::{GenerationSettings, SourceImg};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::app::calculate::video::{GifTarget, SourceSequence};
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Priority {
+    Low,
+    Normal,
+    High,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 3] = [Priority::High, Priority::Normal, Priority::Low];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+        }
+    }
+}
+
+/// A generation waiting to run, with everything it needs to start.
+pub struct Job {
+    pub img: SourceImg,
+    pub settings: GenerationSettings, // not finalized yet
+    pub priority: Priority,
+    pub record_timelapse: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub video: Option<(GifTarget, PathBuf)>, // frames and where to save the result
+    #[cfg(not(target_arch = "wasm32"))]
+    pub sequence: Option<(SourceSequence, PathBuf)>, // source frames, likewise
+}
+
+impl Job {
+    pub fn id(&self) -> Uuid {
+        self.settings.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.settings.name
+    }
+}
+
+/// Generations run one at a time; the rest wait here, highest priority first.
+#[derive(Default)]
+pub struct JobQueue {
+    jobs: Vec<Job>,
+}
+
+impl JobQueue {
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    pub fn jobs(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    /// Adds a job after every job of the same or higher priority.
+    pub fn push(&mut self, job: Job) {
+        let index = self
+            .jobs
+            .iter()
+            .position(|j| j.priority < job.priority)
+            .unwrap_or(self.jobs.len());
+        self.jobs.insert(index, job);
+    }
+
+    /// Takes the job that should run next.
+    pub fn pop(&mut self) -> Option<Job> {
+        (!self.jobs.is_empty()).then(|| self.jobs.remove(0))
+    }
+
+    pub fn remove(&mut self, id: Uuid) -> Option<Job> {
+        let index = self.jobs.iter().position(|j| j.id() == id)?;
+        Some(self.jobs.remove(index))
+    }
+
+    pub fn set_priority(&mut self, id: Uuid, priority: Priority) {
+        if let Some(mut job) = self.remove(id) {
+            job.priority = priority;
+            self.push(job);
+        }
+    }
+
+    /// Moves a job one place towards the front. Passing a job of higher priority raises
+    /// it to that priority, so the queue stays ordered.
+    pub fn move_up(&mut self, id: Uuid) {
+        let Some(index) = self.jobs.iter().position(|j| j.id() == id) else {
+            return;
+        };
+        if index == 0 {
+            return;
+        }
+        let passed = self.jobs[index - 1].priority;
+        let job = &mut self.jobs[index];
+        if job.priority < passed {
+            job.priority = passed;
+        }
+        self.jobs.swap(index, index - 1);
+    }
+
+    /// Moves a job one place towards the back, lowering its priority if needed.
+    pub fn move_down(&mut self, id: Uuid) {
+        let Some(index) = self.jobs.iter().position(|j| j.id() == id) else {
+            return;
+        };
+        if index + 1 >= self.jobs.len() {
+            return;
+        }
+        let passed = self.jobs[index + 1].priority;
+        let job = &mut self.jobs[index];
+        if job.priority > passed {
+            job.priority = passed;
+        }
+        self.jobs.swap(index, index + 1);
+    }
+}
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+
+use crate::app::calculate::util::{GenerationSettings, GridPixel, WeightedPixel};
+use crate::app::json;
+
+/// Cost of the assignment after one generation of the genetic solver.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct GenerationStats {
+    pub generation: u32,
+    pub cost: i64,      // sum of every cell's heuristic, lower is better
+    pub mean_cost: f64, // per cell
+    pub swaps: u32,
+    pub max_dist: u32, // how far apart swapped cells could be
+}
+
+/// Distances, in cells, that `QualityReport::within` counts pixels staying inside of.
+pub const WITHIN_CELLS: [u32; 3] = [1, 4, 16];
+/// Which percentiles of the color error `QualityReport::color_error` holds.
+pub const ERROR_PERCENTILES: [u32; 5] = [10, 25, 50, 75, 90];
+
+/// How good a finished assignment is, kept with the preset so results can be compared.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QualityReport {
+    pub total_cost: i64, // the solver's heuristic over every pixel, lower is better
+    pub mean_displacement: f32, // cells between where a pixel starts and ends
+    pub max_displacement: f32,
+    pub color_error: [f32; 5], // rgb distance to the target, at `ERROR_PERCENTILES`
+    pub within: [f32; 3],      // fraction of pixels ending up within `WITHIN_CELLS`
+}
+
+impl QualityReport {
+    /// Measures per pixel assignments, where `assignments[target] = source`.
+    pub(crate) fn measure(
+        source: &[GridPixel],
+        target: &[WeightedPixel],
+        assignments: &[usize],
+        settings: &GenerationSettings,
+    ) -> Self {
+        let _span = tracing::info_span!("quality_report").entered();
+        let wrap = if settings.tileable {
+            settings.sidelen as i64
+        } else {
+            0
+        };
+        let mut total_cost = 0;
+        let mut displacements = Vec::with_capacity(assignments.len());
+        let mut errors = Vec::with_capacity(assignments.len());
+        for (t, &s) in target.iter().zip(assignments) {
+            let s = source[s];
+            total_cost += super::heuristic(
+                s.coords(),
+                t.coords(),
+                s.rgb_tuple(),
+                t.rgb_tuple(),
+                t.weight,
+                t.proximity,
+                wrap,
+            );
+            let mut dx = (s.x as i64 - t.pixel.x as i64).abs();
+            let mut dy = (s.y as i64 - t.pixel.y as i64).abs();
+            if wrap > 0 {
+                dx = dx.min(wrap - dx);
+                dy = dy.min(wrap - dy);
+            }
+            displacements.push(((dx * dx + dy * dy) as f32).sqrt());
+            let error = (0..3)
+                .map(|c| (s.rgb[c] as f32 - t.pixel.rgb[c] as f32).powi(2))
+                .sum::<f32>();
+            errors.push(error.sqrt());
+        }
+
+        let n = displacements.len().max(1) as f32;
+        errors.sort_by(f32::total_cmp);
+        let percentile = |p: u32| {
+            let i = (p as usize * errors.len() / 100).min(errors.len().saturating_sub(1));
+            errors.get(i).copied().unwrap_or(0.0)
+        };
+        Self {
+            total_cost,
+            mean_displacement: displacements.iter().sum::<f32>() / n,
+            max_displacement: displacements.iter().copied().fold(0.0, f32::max),
+            color_error: ERROR_PERCENTILES.map(percentile),
+            within: WITHIN_CELLS
+                .map(|k| displacements.iter().filter(|&&d| d <= k as f32).count() as f32 / n),
+        }
+    }
+}
+
+pub fn to_csv(stats: &[GenerationStats]) -> String {
+    let mut csv = "generation,cost,mean_cost,swaps,max_dist\n".to_owned();
+    for s in stats {
+        csv += &format!(
+            "{},{},{},{},{}\n",
+            s.generation, s.cost, s.mean_cost, s.swaps, s.max_dist
+        );
+    }
+    csv
+}
+
+pub fn to_json(stats: &[GenerationStats]) -> String {
+    let rows = stats
+        .iter()
+        .map(|s| {
+            format!(
+                "  {{\"generation\": {}, \"cost\": {}, \"mean_cost\": {}, \"swaps\": {}, \"max_dist\": {}}}",
+                s.generation,
+                s.cost,
+                json::number(s.mean_cost),
+                s.swaps,
+                s.max_dist
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("[\n{}\n]\n", rows.join(",\n"))
+}
@@ -0,0 +1,45 @@
+//! Weight maps drawn as vectors: an svg with what matters filled white on black, so masks
+//! authored in a vector tool come out exactly at the grid's resolution.
+
+use resvg::{tiny_skia, usvg};
+
+use crate::app::calculate::util::SourceImg;
+
+/// Draws `data`, an svg, stretched over `width` × `height` on black, with the brightness
+/// of each pixel as its weight.
+pub fn rasterize(data: &[u8], width: u32, height: u32) -> Result<SourceImg, String> {
+    let tree = usvg::Tree::from_data(data, &usvg::Options::default())
+        .map_err(|e| format!("couldn't read the svg: {}", e))?;
+    let mut pixmap = tiny_skia::Pixmap::new(width, height).ok_or("the weight map is empty")?;
+    pixmap.fill(tiny_skia::Color::BLACK);
+    let size = tree.size();
+    let transform = tiny_skia::Transform::from_scale(
+        width as f32 / size.width(),
+        height as f32 / size.height(),
+    );
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+    // drawn over opaque black, so the colors are as they are, not premultiplied
+    Ok(SourceImg::from_fn(width, height, |x, y| {
+        let p = pixmap.pixels()[(y * width + x) as usize];
+        let luma =
+            (p.red() as u32 * 2126 + p.green() as u32 * 7152 + p.blue() as u32 * 722) / 10000;
+        image::Rgb([luma as u8; 3])
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn svg_is_stretched_over_the_map() {
+        let svg = br#"<svg xmlns="http://www.w3.org/2000/svg" width="2" height="2">
+            <rect width="1" height="2" fill="white"/>
+        </svg>"#;
+        let map = rasterize(svg, 4, 2).unwrap();
+        assert_eq!(map.dimensions(), (4, 2));
+        let row = (0..4).map(|x| map.get_pixel(x, 1)[0]).collect::<Vec<_>>();
+        assert_eq!(row, [255, 255, 0, 0]);
+        assert!(rasterize(b"not an svg", 4, 2).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont, point};
+use image::imageops;
+
+use crate::app::calculate::util::SourceImg;
+
+const CANVAS_SIZE: u32 = 512;
+const BACKGROUND_WEIGHT: f32 = 24.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TextFont {
+    Proportional,
+    Monospace,
+}
+
+impl TextFont {
+    pub const ALL: [TextFont; 2] = [TextFont::Proportional, TextFont::Monospace];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TextFont::Proportional => "proportional",
+            TextFont::Monospace => "monospace",
+        }
+    }
+
+    fn font_name(&self) -> &'static str {
+        match self {
+            TextFont::Proportional => "Ubuntu-Light",
+            TextFont::Monospace => "Hack",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TextTarget {
+    pub text: String,
+    pub font: TextFont,
+    pub size: f32, // fraction of the canvas height per line
+    pub color: [u8; 3],
+    pub background: [u8; 3],
+}
+
+impl Default for TextTarget {
+    fn default() -> Self {
+        Self {
+            text: "obama".to_owned(),
+            font: TextFont::Proportional,
+            size: 0.3,
+            color: [255, 255, 255],
+            background: [0, 0, 0],
+        }
+    }
+}
+
+impl TextTarget {
+    /// Renders the text centered on a square canvas, returning the target image and a
+    /// weight map that is strongest on the glyph strokes.
+    pub fn render(&self) -> Result<(SourceImg, SourceImg), Box<dyn std::error::Error>> {
+        let fonts = egui::FontDefinitions::default();
+        let data = fonts
+            .font_data
+            .get(self.font.font_name())
+            .ok_or("font not available")?;
+        let font = FontRef::try_from_slice(&data.font)?;
+        let scaled = font.as_scaled(PxScale::from(CANVAS_SIZE as f32 * self.size));
+
+        let lines = self.text.lines().collect::<Vec<_>>();
+        let line_height = scaled.height() + scaled.line_gap();
+        let widths = lines
+            .iter()
+            .map(|line| {
+                line.chars()
+                    .map(|c| scaled.h_advance(font.glyph_id(c)))
+                    .sum::<f32>()
+            })
+            .collect::<Vec<_>>();
+
+        // shrink to fit if the text doesn't fit the canvas
+        let max_width = widths.iter().copied().fold(0.0, f32::max);
+        let total_height = line_height * lines.len() as f32;
+        let fit = (CANVAS_SIZE as f32 * 0.9 / max_width)
+            .min(CANVAS_SIZE as f32 * 0.9 / total_height)
+            .min(1.0);
+        let scale = PxScale::from(CANVAS_SIZE as f32 * self.size * fit);
+        let scaled = font.as_scaled(scale);
+        let line_height = line_height * fit;
+
+        let mut coverage = image::GrayImage::new(CANVAS_SIZE, CANVAS_SIZE);
+        let mut y = (CANVAS_SIZE as f32 - line_height * lines.len() as f32) * 0.5 + scaled.ascent();
+        for (line, width) in lines.iter().zip(widths) {
+            let mut x = (CANVAS_SIZE as f32 - width * fit) * 0.5;
+            for c in line.chars() {
+                let glyph_id = font.glyph_id(c);
+                let glyph = glyph_id.with_scale_and_position(scale, point(x, y));
+                x += scaled.h_advance(glyph_id);
+                let Some(outlined) = font.outline_glyph(glyph) else {
+                    continue;
+                };
+                let bounds = outlined.px_bounds();
+                outlined.draw(|gx, gy, c| {
+                    let px = bounds.min.x as i32 + gx as i32;
+                    let py = bounds.min.y as i32 + gy as i32;
+                    if px >= 0 && py >= 0 && (px as u32) < CANVAS_SIZE && (py as u32) < CANVAS_SIZE
+                    {
+                        let p = coverage.get_pixel_mut(px as u32, py as u32);
+                        p[0] = p[0].max((c * 255.0) as u8);
+                    }
+                });
+            }
+            y += line_height;
+        }
+
+        let mix = |a: u8, b: u8, t: f32| (a as f32 + (b as f32 - a as f32) * t) as u8;
+        let target = SourceImg::from_fn(CANVAS_SIZE, CANVAS_SIZE, |x, y| {
+            let t = coverage.get_pixel(x, y)[0] as f32 / 255.0;
+            image::Rgb([
+                mix(self.background[0], self.color[0], t),
+                mix(self.background[1], self.color[1], t),
+                mix(self.background[2], self.color[2], t),
+            ])
+        });
+
+        // a soft halo around the strokes keeps the edges crisp too
+        let halo = imageops::blur(&coverage, CANVAS_SIZE as f32 / 128.0);
+        let weights = SourceImg::from_fn(CANVAS_SIZE, CANVAS_SIZE, |x, y| {
+            let stroke = coverage.get_pixel(x, y)[0] as f32;
+            let near = halo.get_pixel(x, y)[0] as f32;
+            let w = stroke.max(near * 0.7).max(BACKGROUND_WEIGHT) as u8;
+            image::Rgb([w, w, w])
+        });
+
+        Ok((target, weights))
+    }
+}
@@ -1,4 +1,9 @@
 use crate::app::calculate::ProgressMsg;
+use crate::app::calculate::SolverState;
+use crate::app::calculate::preprocess;
+use crate::app::calculate::preprocess::HistogramMatch;
+use crate::app::calculate::preprocess::Palette;
+use crate::app::calculate::preprocess::SourceFilters;
 
 use image::imageops;
 use serde::Deserialize;
@@ -88,6 +93,7 @@ impl GridPixel {
 pub struct WeightedPixel {
     pub pixel: GridPixel,
     pub weight: i64,
+    pub proximity: i64, // proximity importance at this cell
 }
 
 impl WeightedPixel {
@@ -106,8 +112,46 @@ pub(crate) fn get_images(
     source: SourceImg,
     settings: &GenerationSettings,
 ) -> Result<(Vec<GridPixel>, Vec<WeightedPixel>), Box<dyn Error>> {
-    let source = settings.source_crop_scale.apply(&source, settings.sidelen);
-    let (target, weights) = settings.get_target()?;
+    let _span = tracing::info_span!("prepare_images").entered();
+    let (source, mut target, weights, locality) = if settings.mutual_swap {
+        let (source, target, weights) = settings.get_swap_images(&source)?;
+        let locality = vec![PROXIMITY_AS_SET; weights.len()];
+        (source, target, weights, locality)
+    } else {
+        let (target, weights) = settings.get_target()?;
+        let locality = settings.get_locality(&weights)?;
+        let (target, weights) = settings.aspect.pad(target, weights, settings.pad_color);
+        let locality = settings
+            .aspect
+            .pad_map(locality, settings.sidelen, PROXIMITY_AS_SET);
+        (settings.compose_source(&source)?, target, weights, locality)
+    };
+    let mut source = reconcile_size(source, &target)?;
+    if weights.len() != locality.len() || weights.len() != target.pixels().len() {
+        return Err(format!(
+            "the weight map has {} cells and the proximity map {}, but the target has {} pixels",
+            weights.len(),
+            locality.len(),
+            target.pixels().len()
+        )
+        .into());
+    }
+    settings.histogram_match.apply(&mut source, &mut target);
+    if settings.target_dither_colors > 0 {
+        preprocess::dither_to_target(&mut source, &target, settings.target_dither_colors);
+    }
+    settings.palette.apply(&mut source);
+    let pinned = settings.pinned_cells()?; // the solvers can then count on it
+    if settings.unmasked == Unmasked::Hidden {
+        if let Some(pinned) = pinned {
+            let (grid, block) = (settings.grid_sidelen(), settings.block_size.max(1));
+            for (x, y, pixel) in source.enumerate_pixels_mut() {
+                if pinned[((y / block) * grid + x / block) as usize] {
+                    pixel.0 = settings.pad_color;
+                }
+            }
+        }
+    }
 
     let source_pixels = source
         .enumerate_pixels()
@@ -116,17 +160,132 @@ pub(crate) fn get_images(
 
     let target_pixels = target
         .enumerate_pixels()
-        .zip(weights.into_iter())
-        .map(|((x, y, pixel), weight)| WeightedPixel {
+        .zip(weights.into_iter().zip(locality))
+        .map(|((x, y, pixel), (weight, locality))| WeightedPixel {
             pixel: GridPixel::new(x, y, pixel.0),
             weight,
+            proximity: settings.proximity_importance * locality / PROXIMITY_AS_SET,
         })
         .collect::<Vec<_>>();
 
-    assert_eq!(source_pixels.len(), target_pixels.len());
     Ok((source_pixels, target_pixels))
 }
 
+/// Most pixels source and target sides may differ by and still be matched up, by
+/// resizing the source; anything more means something upstream went wrong.
+const MAX_SIZE_MISMATCH: u32 = 4;
+
+/// The source at the target's size, if it's only slightly off.
+fn reconcile_size(source: SourceImg, target: &SourceImg) -> Result<SourceImg, Box<dyn Error>> {
+    let (sw, sh) = source.dimensions();
+    let (tw, th) = target.dimensions();
+    if (sw, sh) == (tw, th) {
+        return Ok(source);
+    }
+    if sw.abs_diff(tw) > MAX_SIZE_MISMATCH || sh.abs_diff(th) > MAX_SIZE_MISMATCH {
+        return Err(format!(
+            "the source came out {}x{} but the target {}x{}",
+            sw, sh, tw, th
+        )
+        .into());
+    }
+    tracing::warn!(
+        "resizing the {}x{} source to the {}x{} target",
+        sw,
+        sh,
+        tw,
+        th
+    );
+    Ok(imageops::resize(
+        &source,
+        tw,
+        th,
+        imageops::FilterType::Triangle,
+    ))
+}
+
+/// Groups the grid into `block_size`×`block_size` blocks, averaging colors, weights and
+/// proximity, so the solver can assign whole blocks instead of individual pixels.
+pub(crate) fn to_blocks(
+    source: &[GridPixel],
+    target: &[WeightedPixel],
+    sidelen: u32,
+    block_size: u32,
+) -> (Vec<GridPixel>, Vec<WeightedPixel>) {
+    let _span = tracing::info_span!("build_blocks", block_size).entered();
+    if block_size <= 1 {
+        return (source.to_vec(), target.to_vec());
+    }
+    assert_eq!(
+        sidelen % block_size,
+        0,
+        "sidelen must be a multiple of block size"
+    );
+    let grid = sidelen / block_size;
+    let area = (block_size * block_size) as u64;
+
+    let mut source_blocks = Vec::with_capacity((grid * grid) as usize);
+    let mut target_blocks = Vec::with_capacity((grid * grid) as usize);
+    for by in 0..grid {
+        for bx in 0..grid {
+            let mut src_sum = [0u64; 3];
+            let mut tgt_sum = [0u64; 3];
+            let mut weight_sum = 0i64;
+            let mut proximity_sum = 0i64;
+            for dy in 0..block_size {
+                for dx in 0..block_size {
+                    let idx = ((by * block_size + dy) * sidelen + bx * block_size + dx) as usize;
+                    let t = target[idx];
+                    for c in 0..3 {
+                        src_sum[c] += source[idx].rgb[c] as u64;
+                        tgt_sum[c] += t.pixel.rgb[c] as u64;
+                    }
+                    weight_sum += t.weight;
+                    proximity_sum += t.proximity;
+                }
+            }
+            let avg = |sum: [u64; 3]| sum.map(|c| (c / area) as u8);
+            source_blocks.push(GridPixel::new(bx, by, avg(src_sum)));
+            target_blocks.push(WeightedPixel {
+                pixel: GridPixel::new(bx, by, avg(tgt_sum)),
+                weight: weight_sum / area as i64,
+                proximity: proximity_sum / area as i64,
+            });
+        }
+    }
+    (source_blocks, target_blocks)
+}
+
+/// Expands block-level assignments back to per-pixel assignments, keeping each pixel's
+/// offset within its block.
+pub(crate) fn expand_block_assignments(
+    assignments: &[usize],
+    sidelen: u32,
+    block_size: u32,
+) -> Vec<usize> {
+    if block_size <= 1 {
+        return assignments.to_vec();
+    }
+    let grid = (sidelen / block_size) as usize;
+    let block_size = block_size as usize;
+    let sidelen = sidelen as usize;
+    let mut expanded = vec![0; sidelen * sidelen];
+    for (target_block, &source_block) in assignments.iter().enumerate() {
+        let (tbx, tby) = (target_block % grid, target_block / grid);
+        let (sbx, sby) = (source_block % grid, source_block / grid);
+        for dy in 0..block_size {
+            for dx in 0..block_size {
+                let target_idx = (tby * block_size + dy) * sidelen + tbx * block_size + dx;
+                let source_idx = (sby * block_size + dy) * sidelen + sbx * block_size + dx;
+                expanded[target_idx] = source_idx;
+            }
+        }
+    }
+    expanded
+}
+
+pub const MAX_CROP_ZOOM: f32 = 5.0;
+
 #[derive(Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub struct CropScale {
     pub x: f32,     // -1: all left, 0: center, 1: all right
@@ -143,9 +302,55 @@ impl CropScale {
         }
     }
 
-    pub fn apply(&self, img: &SourceImg, sidelen: u32) -> SourceImg {
-        let (w, h) = img.dimensions();
+    pub fn apply<P>(
+        &self,
+        img: &image::ImageBuffer<P, Vec<P::Subpixel>>,
+        sidelen: u32,
+    ) -> image::ImageBuffer<P, Vec<P::Subpixel>>
+    where
+        P: image::Pixel + 'static,
+    {
+        let (x0, y0, cs) = self.region(img.width(), img.height());
+        let cropped = imageops::crop_imm(img, x0, y0, cs, cs).to_image();
 
+        if cs == sidelen {
+            cropped
+        } else {
+            imageops::resize(&cropped, sidelen, sidelen, imageops::FilterType::Lanczos3)
+        }
+    }
+
+    /// Crops a weight map like `apply` and brings its red channel to `sidelen` by area
+    /// averaging, which unlike Lanczos keeps weights within 0..=255 and their total as
+    /// it was.
+    pub fn apply_weights(&self, map: &SourceImg, sidelen: u32) -> Vec<i64> {
+        let (x0, y0, cs) = self.region(map.width(), map.height());
+        resample_weights(&imageops::crop_imm(map, x0, y0, cs, cs).to_image(), sidelen)
+    }
+
+    /// The crop closest to a square of `side` centered on (`cx`, `cy`) in a `w`×`h` image,
+    /// as far as the zoom range and the image edges allow.
+    pub fn around(w: u32, h: u32, cx: f32, cy: f32, side: f32) -> Self {
+        let base_side = w.min(h) as f32;
+        let scale = (base_side / side.max(1.0)).clamp(1.0, MAX_CROP_ZOOM);
+        let crop_side = (base_side / scale).floor().max(1.0);
+        let offset = |center: f32, len: u32| {
+            let max_off = len as f32 - crop_side;
+            if max_off <= 0.0 {
+                0.0
+            } else {
+                ((center - crop_side / 2.0) / max_off).clamp(0.0, 1.0) * 2.0 - 1.0
+            }
+        };
+        Self {
+            x: offset(cx, w),
+            y: offset(cy, h),
+            scale,
+        }
+    }
+
+    /// The square this crops out of a `w`×`h` image: left, top and side length.
+    pub fn region(&self, w: u32, h: u32) -> (u32, u32, u32) {
         let s = self.scale.max(1.0);
 
         let base_side = w.min(h) as f32;
@@ -161,14 +366,7 @@ impl CropScale {
 
         let x0 = (xn * max_x_off).floor() as u32;
         let y0 = (yn * max_y_off).floor() as u32;
-        let cs = crop_side as u32;
-        let cropped = imageops::crop_imm(img, x0, y0, cs, cs).to_image();
-
-        if cs == sidelen {
-            cropped
-        } else {
-            imageops::resize(&cropped, sidelen, sidelen, imageops::FilterType::Lanczos3)
-        }
+        (x0, y0, crop_side as u32)
     }
 }
 
@@ -176,23 +374,217 @@ impl CropScale {
 pub enum Algorithm {
     Optimal,
     Genetic,
+    BestOf, // several solves within a time budget, keeping the cheapest
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum Symmetry {
+    Off,
+    Encourage, // penalize mirrored cells with different colors (fast algorithm only)
+    Enforce,   // solve the left half and mirror it
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 3] = [Symmetry::Off, Symmetry::Encourage, Symmetry::Enforce];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Symmetry::Off => "no symmetry",
+            Symmetry::Encourage => "encourage symmetry",
+            Symmetry::Enforce => "enforce symmetry",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub enum Aspect {
+    #[default]
+    Square,
+    Landscape, // 16:9
+    Portrait,  // 9:16
+}
+
+impl Aspect {
+    pub const ALL: [Aspect; 3] = [Aspect::Square, Aspect::Landscape, Aspect::Portrait];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Aspect::Square => "square",
+            Aspect::Landscape => "16:9 landscape",
+            Aspect::Portrait => "9:16 portrait",
+        }
+    }
+
+    /// Width and height of the shown frame as fractions of the square grid, which it sits
+    /// in the middle of.
+    pub fn frame(&self) -> (f32, f32) {
+        match self {
+            Aspect::Square => (1.0, 1.0),
+            Aspect::Landscape => (1.0, 9.0 / 16.0),
+            Aspect::Portrait => (9.0 / 16.0, 1.0),
+        }
+    }
+
+    /// Shrinks the target to fit the frame without stretching it, and fills the rest of
+    /// the grid with `fill` at zero weight, so it takes whatever pixels are left over.
+    pub fn pad(
+        &self,
+        target: SourceImg,
+        weights: Vec<i64>,
+        fill: [u8; 3],
+    ) -> (SourceImg, Vec<i64>) {
+        if *self == Aspect::Square {
+            return (target, weights);
+        }
+        let sidelen = target.width();
+        let (fw, fh) = self.frame();
+        let inner = ((fw.min(fh) * sidelen as f32).round() as u32).clamp(1, sidelen);
+        let offset = (sidelen - inner) / 2;
+
+        let shrunk = imageops::resize(&target, inner, inner, imageops::FilterType::Lanczos3);
+        let mut padded = SourceImg::from_pixel(sidelen, sidelen, image::Rgb(fill));
+        imageops::replace(&mut padded, &shrunk, offset as i64, offset as i64);
+
+        (padded, self.pad_map(weights, sidelen, 0))
+    }
+
+    /// Shrinks a per-cell map the way `pad` shrinks the target, filling around it with
+    /// `fill`.
+    pub fn pad_map(&self, values: Vec<i64>, sidelen: u32, fill: i64) -> Vec<i64> {
+        if *self == Aspect::Square {
+            return values;
+        }
+        let (fw, fh) = self.frame();
+        let inner = ((fw.min(fh) * sidelen as f32).round() as u32).clamp(1, sidelen);
+        let offset = (sidelen - inner) / 2;
+
+        let mut padded = vec![fill; (sidelen * sidelen) as usize];
+        for y in 0..inner {
+            for x in 0..inner {
+                let (sx, sy) = (x * sidelen / inner, y * sidelen / inner);
+                padded[((y + offset) * sidelen + x + offset) as usize] =
+                    values[(sy * sidelen + sx) as usize];
+            }
+        }
+        padded
+    }
+}
+
+/// What happens to source pixels outside the source mask.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub enum Unmasked {
+    #[default]
+    Still, // they stay where they are
+    Hidden, // they stay where they are, painted over in the pad color
+}
+
+impl Unmasked {
+    pub const ALL: [Unmasked; 2] = [Unmasked::Still, Unmasked::Hidden];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Unmasked::Still => "keep unmasked pixels still",
+            Unmasked::Hidden => "hide unmasked pixels",
+        }
+    }
+}
+
+/// The proximity map value that keeps proximity importance as set; 0 lets pixels go
+/// anywhere and 255 holds them about twice as close.
+pub const PROXIMITY_AS_SET: i64 = 128;
+
+/// How proximity importance varies over the target.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub enum ProximityMap {
+    #[default]
+    Uniform,
+    FromWeights, // pixels roam freely where detail matters and stay put elsewhere
+    Custom(u32, u32, Vec<u8>), // red channel, over the whole uncropped target
+}
+
+impl ProximityMap {
+    pub const CHOICES: [ProximityMap; 2] = [ProximityMap::Uniform, ProximityMap::FromWeights];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProximityMap::Uniform => "uniform proximity",
+            ProximityMap::FromWeights => "proximity from weights",
+            ProximityMap::Custom(..) => "custom proximity map",
+        }
+    }
+}
+
+/// An extra source image whose pixels are merged into the pool alongside the main source.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BlendSource {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+    pub share: f32, // relative to the other sources' shares
+}
+
+impl BlendSource {
+    pub fn new(img: SourceImg) -> Self {
+        let (width, height) = img.dimensions();
+        Self {
+            width,
+            height,
+            data: img.into_raw(),
+            share: 1.0,
+        }
+    }
+
+    pub fn image(&self) -> Result<SourceImg, Box<dyn Error>> {
+        Ok(
+            image::ImageBuffer::from_vec(self.width, self.height, self.data.clone())
+                .ok_or("blend source doesn't match its size")?,
+        )
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct GenerationSettings {
     pub id: Uuid,
     pub name: String,
+    #[serde(default)]
+    pub parent: Option<Uuid>, // the preset these settings were tweaked from, for its history
 
     pub proximity_importance: i64,
     pub algorithm: Algorithm,
+    pub best_of_runs: u32,    // solves tried by Algorithm::BestOf
+    pub best_of_seconds: u32, // no new solve is started once this would be exceeded
 
     pub sidelen: u32,
+    pub block_size: u32, // 1: per-pixel matching, >1: match whole blocks
     custom_target: Option<(u32, u32, Vec<u8>)>,
+    custom_weights: Option<(u32, u32, Vec<u8>)>, // same shape as the target
+    wide_source: Option<(u32, u32, Vec<u16>)>,   // the source at 16 bits, if it had them
+    source_mask: Option<(u32, u32, Vec<u8>)>,    // same size as the source, 0: left out
     pub target_crop_scale: CropScale,
     pub source_crop_scale: CropScale,
+    pub source_share: f32,
+    pub blend_sources: Vec<BlendSource>,
+    pub mutual_swap: bool, // source and target trade pixels, side by side
+    pub source_filters: SourceFilters,
+    pub histogram_match: HistogramMatch,
+    pub palette: Palette,
+    pub target_dither_colors: u16, // 0: no dithering
+    pub symmetry: Symmetry,
+    pub aspect: Aspect,     // not used in mutual swap mode
+    pub pad_color: [u8; 3], // fills the grid around a non-square frame, and hidden pixels
+    pub unmasked: Unmasked,
+    pub tileable: bool, // proximity is measured around the edges, for repeating textures
+    pub checkpoint_minutes: u32, // how often long fast solves are saved to resume; 0: never
+    pub proximity_map: ProximityMap,
+    #[serde(skip)]
+    pub resume: Option<SolverState>, // carry on from a checkpoint instead of starting over
+    #[serde(skip)]
+    pub threads: u32, // from the config; see `solver_threads`
+    finalized: bool,
 }
 
 pub type SourceImg = image::RgbImage;
+pub type WideImg = image::ImageBuffer<image::Rgb<u16>, Vec<u16>>; // 16 bits per channel
 
 impl GenerationSettings {
     pub fn default(id: Uuid, name: String) -> Self {
@@ -200,29 +592,299 @@ impl GenerationSettings {
             name,
             proximity_importance: 13, // 20
             algorithm: Algorithm::Genetic,
+            best_of_runs: 4,
+            best_of_seconds: 60,
             id,
+            parent: None,
             sidelen: 128,
+            block_size: 1,
             custom_target: None,
+            custom_weights: None,
+            wide_source: None,
+            source_mask: None,
             target_crop_scale: CropScale::identity(),
             source_crop_scale: CropScale::identity(),
+            source_share: 1.0,
+            blend_sources: Vec::new(),
+            mutual_swap: false,
+            source_filters: SourceFilters::default(),
+            histogram_match: HistogramMatch::Off,
+            palette: Palette::Full,
+            target_dither_colors: 0,
+            symmetry: Symmetry::Off,
+            aspect: Aspect::Square,
+            pad_color: [0, 0, 0],
+            unmasked: Unmasked::Still,
+            tileable: false,
+            checkpoint_minutes: 10,
+            proximity_map: ProximityMap::Uniform,
+            resume: None,
+            threads: 0,
+            finalized: false,
         }
     }
 
+    /// The settings that shape the result, as names and readable values, for telling runs
+    /// apart in a preset's history. Images are only noted as used or not.
+    pub fn snapshot(&self) -> Vec<(String, String)> {
+        fn ron(value: &impl Serialize) -> String {
+            ron::to_string(value).unwrap_or_default()
+        }
+        let used = |image: bool| if image { "yes" } else { "no" }.to_owned();
+        let mut settings = vec![
+            ("algorithm", format!("{:?}", self.algorithm)),
+            ("proximity", self.proximity_importance.to_string()),
+            ("resolution", self.sidelen.to_string()),
+            ("block size", self.block_size.to_string()),
+            ("custom target", used(self.custom_target.is_some())),
+            ("custom weights", used(self.custom_weights.is_some())),
+            ("source mask", used(self.source_mask.is_some())),
+            ("target crop", ron(&self.target_crop_scale)),
+            ("source crop", ron(&self.source_crop_scale)),
+            ("source share", self.source_share.to_string()),
+            ("extra sources", self.blend_sources.len().to_string()),
+            ("mutual swap", self.mutual_swap.to_string()),
+            ("source filters", ron(&self.source_filters)),
+            ("histogram match", format!("{:?}", self.histogram_match)),
+            (
+                "palette",
+                match &self.palette {
+                    Palette::Custom(colors) => format!("{} custom colors", colors.len()),
+                    palette => format!("{:?}", palette),
+                },
+            ),
+            ("dither colors", self.target_dither_colors.to_string()),
+            ("symmetry", format!("{:?}", self.symmetry)),
+            ("aspect", format!("{:?}", self.aspect)),
+            ("pad color", format!("{:?}", self.pad_color)),
+            ("unmasked", format!("{:?}", self.unmasked)),
+            ("tileable", self.tileable.to_string()),
+            ("proximity map", self.proximity_map.label().to_owned()),
+        ];
+        if matches!(self.algorithm, Algorithm::BestOf) {
+            settings.push(("best of runs", self.best_of_runs.to_string()));
+            settings.push(("best of seconds", self.best_of_seconds.to_string()));
+        }
+        settings
+            .into_iter()
+            .map(|(name, value)| (name.to_owned(), value))
+            .collect()
+    }
+
+    /// Side length of the grid the solver works on (in blocks).
+    pub fn grid_sidelen(&self) -> u32 {
+        self.sidelen / self.block_size.max(1)
+    }
+
+    /// The width distances wrap around at, for the cost function; 0 unless tileable.
+    pub(crate) fn wrap(&self) -> i64 {
+        if self.tileable {
+            self.grid_sidelen() as i64
+        } else {
+            0
+        }
+    }
+
+    fn cropped_source(&self, source: &SourceImg) -> Result<SourceImg, Box<dyn Error>> {
+        // with a 16-bit copy, resizing and filtering happen at full precision and only
+        // the result is cut down to 8 bits, so smooth gradients don't band
+        if let Some((w, h, data)) = &self.wide_source {
+            if (*w, *h) == source.dimensions() {
+                let wide = WideImg::from_vec(*w, *h, data.clone())
+                    .ok_or("16-bit source doesn't match its size")?;
+                let cropped = self.source_crop_scale.apply(&wide, self.sidelen);
+                return Ok(preprocess::narrow(&self.source_filters.apply_wide(cropped)));
+            }
+        }
+        let cropped = self.source_crop_scale.apply(source, self.sidelen);
+        Ok(self.source_filters.apply(cropped))
+    }
+
+    /// Keeps a 16-bit copy of the source, the same size as the 8-bit one it goes with.
+    pub(crate) fn set_wide_source(&mut self, img: Option<WideImg>) {
+        self.wide_source = img.map(|img| {
+            let (w, h) = img.dimensions();
+            (w, h, img.into_raw())
+        });
+    }
+
+    pub(crate) fn source_mask(&self) -> Result<Option<image::GrayImage>, Box<dyn Error>> {
+        let Some((w, h, data)) = &self.source_mask else {
+            return Ok(None);
+        };
+        Ok(Some(
+            image::GrayImage::from_vec(*w, *h, data.clone())
+                .ok_or("source mask doesn't match its size")?,
+        ))
+    }
+
+    pub(crate) fn has_source_mask(&self) -> bool {
+        self.source_mask.is_some()
+    }
+
+    /// Limits which source pixels take part, by a mask the same size as the source.
+    pub(crate) fn set_source_mask(&mut self, mask: Option<image::GrayImage>) {
+        self.source_mask = mask.map(|mask| {
+            let (w, h) = mask.dimensions();
+            (w, h, mask.into_raw())
+        });
+    }
+
+    /// Whether the source mask is used. Blend sources, mutual swap and enforced symmetry
+    /// all rearrange the source before solving, so the mask wouldn't line up with them.
+    pub(crate) fn mask_applies(&self) -> bool {
+        self.has_source_mask()
+            && !self.mutual_swap
+            && self.blend_sources.is_empty()
+            && self.symmetry != Symmetry::Enforce
+    }
+
+    /// Grid cells whose pixels are outside the source mask, and so have to stay put.
+    pub(crate) fn pinned_cells(&self) -> Result<Option<Vec<bool>>, Box<dyn Error>> {
+        let Some(mask) = self.source_mask()?.filter(|_| self.mask_applies()) else {
+            return Ok(None);
+        };
+        let mask = self.source_crop_scale.apply(&mask, self.sidelen);
+        let grid = self.grid_sidelen();
+        let mask = if grid == self.sidelen {
+            mask
+        } else {
+            imageops::resize(&mask, grid, grid, imageops::FilterType::Triangle)
+        };
+        Ok(Some(mask.pixels().map(|p| p[0] < 128).collect()))
+    }
+
+    /// Crops the main source and lays any blend sources next to it in vertical bands,
+    /// each as wide as its share of the pixel pool.
+    pub(crate) fn compose_source(&self, source: &SourceImg) -> Result<SourceImg, Box<dyn Error>> {
+        let primary = self.cropped_source(source)?;
+        let total = self.source_share + self.blend_sources.iter().map(|b| b.share).sum::<f32>();
+        if self.blend_sources.is_empty() || total <= 0.0 {
+            return Ok(primary);
+        }
+
+        let sidelen = self.sidelen;
+        let mut composite = SourceImg::new(sidelen, sidelen);
+        let mut x = 0;
+        let count = self.blend_sources.len() + 1;
+        for i in 0..count {
+            let share = if i == 0 {
+                self.source_share
+            } else {
+                self.blend_sources[i - 1].share
+            };
+            let w = if i == count - 1 {
+                sidelen - x
+            } else {
+                ((share.max(0.0) / total * sidelen as f32).round() as u32).min(sidelen - x)
+            };
+            if w == 0 {
+                continue;
+            }
+            let band = if i == 0 {
+                imageops::crop_imm(&primary, (sidelen - w) / 2, 0, w, sidelen).to_image()
+            } else {
+                image::DynamicImage::ImageRgb8(self.blend_sources[i - 1].image()?)
+                    .resize_to_fill(w, sidelen, imageops::FilterType::Lanczos3)
+                    .to_rgb8()
+            };
+            imageops::replace(&mut composite, &band, x as i64, 0);
+            x += w;
+        }
+        Ok(composite)
+    }
+
+    /// Column (in grid cells) splitting the two halves in mutual swap mode.
+    pub(crate) fn swap_split(&self) -> u32 {
+        self.grid_sidelen() / 2
+    }
+
+    /// Whether a cell may be assigned to a target cell; in mutual swap mode each image's
+    /// pixels have to stay in their own half, and with enforced symmetry the left half,
+    /// the middle column and the right half are solved separately.
+    #[inline(always)]
+    pub(crate) fn allows_move(&self, from_x: u16, to_x: u16) -> bool {
+        self.zone(from_x) == self.zone(to_x)
+    }
+
+    #[inline(always)]
+    fn zone(&self, x: u16) -> i32 {
+        if self.symmetry == Symmetry::Enforce {
+            (2 * x as i32 + 1 - self.grid_sidelen() as i32).signum()
+        } else if self.mutual_swap {
+            (x < self.swap_split() as u16) as i32
+        } else {
+            0
+        }
+    }
+
+    /// Side-by-side images for mutual swap mode: the source has the whole source image,
+    /// shrunk to fit a half, on the left and the target on the right, and the target has
+    /// them the other way round. Since pixels stay in their half, that's two assignments
+    /// solved at once, the source's pixels forming the target and the target's the source.
+    pub(crate) fn get_swap_images(
+        &self,
+        source: &SourceImg,
+    ) -> Result<(SourceImg, SourceImg, Vec<i64>), Box<dyn std::error::Error>> {
+        let (sidelen, block) = (self.sidelen, self.block_size.max(1));
+        let half = self.swap_split() * block;
+        if half == 0 {
+            return Err("the grid is too small to split in two".into());
+        }
+        let fit =
+            |img: &SourceImg| imageops::resize(img, half, half, imageops::FilterType::Lanczos3);
+        let a = fit(&self.cropped_source(source)?);
+        let b = fit(&self.get_target()?.0);
+
+        // centered vertically, on whole cells, with the pad color above and below
+        let top = (self.grid_sidelen() - self.swap_split()) / 2 * block;
+        let side_by_side = |left: &SourceImg, right: &SourceImg| {
+            let mut img = SourceImg::from_pixel(sidelen, sidelen, image::Rgb(self.pad_color));
+            imageops::replace(&mut img, left, 0, top as i64);
+            imageops::replace(&mut img, right, half as i64, top as i64);
+            img
+        };
+        let weights = vec![255; (sidelen * sidelen) as usize];
+        Ok((side_by_side(&a, &b), side_by_side(&b, &a), weights))
+    }
+
     pub fn get_target(&self) -> Result<(SourceImg, Vec<i64>), Box<dyn std::error::Error>> {
         let target = self.get_raw_target();
         let target = self.target_crop_scale.apply(&target, self.sidelen);
-        let weights = if self.custom_target.is_some() {
+        let weights = if self.custom_target.is_some() && self.custom_weights.is_none() {
             vec![255; (self.sidelen * self.sidelen) as usize] // uniform weights
         } else {
-            let target_weights =
-                image::load_from_memory(include_bytes!("weights256.png"))?.to_rgb8();
-            let target_weights = self.target_crop_scale.apply(&target_weights, self.sidelen);
-            load_weights(target_weights)
+            self.target_crop_scale
+                .apply_weights(&self.get_raw_weights()?, self.sidelen)
         };
 
         Ok((target, weights))
     }
 
+    /// How strongly each target cell holds on to nearby pixels, from 0 to 255 with
+    /// `PROXIMITY_AS_SET` keeping proximity importance as it is.
+    fn get_locality(&self, weights: &[i64]) -> Result<Vec<i64>, Box<dyn std::error::Error>> {
+        Ok(match &self.proximity_map {
+            ProximityMap::Uniform => vec![PROXIMITY_AS_SET; weights.len()],
+            ProximityMap::FromWeights => weights.iter().map(|w| 255 - w.clamp(&0, &255)).collect(),
+            ProximityMap::Custom(w, h, data) => {
+                let map: SourceImg = image::ImageBuffer::from_vec(*w, *h, data.clone())
+                    .ok_or("proximity map doesn't match its size")?;
+                self.target_crop_scale.apply_weights(&map, self.sidelen)
+            }
+        })
+    }
+
+    /// The weight map over the whole uncropped target, in the red channel.
+    pub(crate) fn get_raw_weights(&self) -> Result<SourceImg, Box<dyn std::error::Error>> {
+        Ok(match (&self.custom_target, &self.custom_weights) {
+            (_, Some((w, h, data))) => image::ImageBuffer::from_vec(*w, *h, data.clone())
+                .ok_or("weight map doesn't match its size")?,
+            (Some((w, h, _)), None) => image::ImageBuffer::from_pixel(*w, *h, image::Rgb([255; 3])),
+            (None, None) => default_weights(),
+        })
+    }
+
     pub(crate) fn get_raw_target(&self) -> SourceImg {
         if let Some((w, h, data)) = &self.custom_target {
             image::ImageBuffer::from_vec(*w, *h, data.clone()).unwrap()
@@ -237,11 +899,76 @@ impl GenerationSettings {
         let (w, h) = img.dimensions();
         let data = img.into_raw();
         self.custom_target = Some((w, h, data));
+        self.custom_weights = None;
+        if matches!(self.proximity_map, ProximityMap::Custom(..)) {
+            self.proximity_map = ProximityMap::Uniform;
+        }
+    }
+
+    /// Sets a proximity map over the current target, read from the red channel.
+    pub(crate) fn set_proximity_map(&mut self, map: SourceImg) {
+        let (w, h) = self.get_raw_target().dimensions();
+        let map = imageops::resize(&map, w, h, imageops::FilterType::Triangle);
+        self.proximity_map = ProximityMap::Custom(w, h, map.into_raw());
+    }
+
+    /// Sets a weight map over the current target, read from the red channel.
+    pub(crate) fn set_weight_map(&mut self, map: SourceImg) {
+        let (w, h) = map.dimensions();
+        self.custom_weights = Some((w, h, map.into_raw()));
+    }
+
+    /// The size to draw a weight map over the whole target at for the grid, as it's
+    /// cropped, to get exactly one of its pixels per cell.
+    pub(crate) fn weight_map_size(&self) -> (u32, u32) {
+        let (w, h) = self.get_raw_target().dimensions();
+        let (_, _, crop_side) = self.target_crop_scale.region(w, h);
+        let scale = self.sidelen as f64 / crop_side.max(1) as f64;
+        let scaled = |v: u32| ((v as f64 * scale).round() as u32).max(1);
+        (scaled(w), scaled(h))
+    }
+
+    /// Sets a custom target along with its weight map (red channel, 0..255).
+    pub(crate) fn set_raw_weighted_target(&mut self, img: SourceImg, weights: SourceImg) {
+        assert_eq!(img.dimensions(), weights.dimensions());
+        self.set_raw_target(img);
+        let (w, h) = weights.dimensions();
+        self.custom_weights = Some((w, h, weights.into_raw()));
+    }
+
+    /// How many solves may run at once: `threads`, or with 0 one per core but one, so
+    /// the interface stays responsive. Always 1 on the web, which has no threads.
+    pub fn solver_threads(&self) -> usize {
+        #[cfg(target_arch = "wasm32")]
+        {
+            1
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        match self.threads {
+            0 => std::thread::available_parallelism()
+                .map_or(1, |n| n.get().saturating_sub(1))
+                .max(1),
+            n => n as usize,
+        }
+    }
+
+    /// The settings as the solver should see them: whole blocks only, and proximity scaled
+    /// so results look alike across resolutions.
+    pub fn finalized(mut self) -> Self {
+        if self.finalized {
+            return self; // resumed from a checkpoint, which saves them finalized
+        }
+        self.finalized = true;
+        self.sidelen -= self.sidelen % self.block_size.max(1);
+        self.proximity_importance =
+            (self.proximity_importance as f32 / (self.grid_sidelen() as f32 / 128.0)) as i64;
+        self
     }
 
     pub fn clone_with_new_id(&self) -> Self {
         let mut new = self.clone();
         new.id = Uuid::new_v4();
+        new.parent = Some(self.id);
 
         new.name = if let Some(v_pos) = self.name.rfind(" v") {
             let potential_version = &self.name[v_pos + 2..];
@@ -259,6 +986,69 @@ impl GenerationSettings {
     }
 }
 
+/// Overwrites the right half of a grid assignment with the mirror image of the left half.
+pub(crate) fn mirror_assignments(assignments: &mut [usize], sidelen: u32) {
+    let sidelen = sidelen as usize;
+    let mirror = |i: usize| {
+        let x = i % sidelen;
+        i - x + (sidelen - 1 - x)
+    };
+    for y in 0..sidelen {
+        for x in sidelen.div_ceil(2)..sidelen {
+            let target = y * sidelen + x;
+            assignments[target] = mirror(assignments[mirror(target)]);
+        }
+    }
+}
+
+/// For each of `dst` cells along an axis of `src` cells, the source cells it covers and
+/// how much of it each makes up.
+fn area_spans(src: u32, dst: u32) -> Vec<Vec<(usize, f64)>> {
+    let scale = src as f64 / dst as f64;
+    (0..dst)
+        .map(|i| {
+            let (start, end) = (i as f64 * scale, (i + 1) as f64 * scale);
+            (start.floor() as u32..(end.ceil() as u32).min(src))
+                .filter_map(|j| {
+                    let cover = end.min(j as f64 + 1.0) - start.max(j as f64);
+                    (cover > 0.0).then_some((j as usize, cover / scale))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The red channel of `map` at `size`×`size`, each cell the average of the area it
+/// covers.
+/// The weight map of the default target.
+pub(crate) fn default_weights() -> SourceImg {
+    image::load_from_memory(include_bytes!("weights256.png"))
+        .unwrap()
+        .to_rgb8()
+}
+
+pub(crate) fn resample_weights(map: &SourceImg, size: u32) -> Vec<i64> {
+    let (width, height) = map.dimensions();
+    if (width, height) == (size, size) {
+        return load_weights(map.clone());
+    }
+    let (xs, ys) = (area_spans(width, size), area_spans(height, size));
+    let red = map.pixels().map(|p| p[0] as f64).collect::<Vec<_>>();
+    let mut weights = Vec::with_capacity((size * size) as usize);
+    for y_spans in &ys {
+        for x_spans in &xs {
+            let mut sum = 0.0;
+            for &(y, wy) in y_spans {
+                for &(x, wx) in x_spans {
+                    sum += red[y * width as usize + x] * wy * wx;
+                }
+            }
+            weights.push((sum.round() as i64).clamp(0, 255));
+        }
+    }
+    weights
+}
+
 pub fn load_weights(source: SourceImg) -> Vec<i64> {
     let (width, height) = source.dimensions();
     let mut weights = vec![0; (width * height) as usize];
@@ -268,3 +1058,102 @@ pub fn load_weights(source: SourceImg) -> Vec<i64> {
     }
     weights
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundled_weights() -> SourceImg {
+        image::load_from_memory(include_bytes!("weights256.png"))
+            .unwrap()
+            .to_rgb8()
+    }
+
+    fn mean(values: impl Iterator<Item = f64>) -> f64 {
+        let (sum, count) = values.fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+        sum / count as f64
+    }
+
+    #[test]
+    fn resampled_weights_stay_in_range() {
+        let map = bundled_weights();
+        for size in [37, 100, 128, 300, 512] {
+            let weights = resample_weights(&map, size);
+            assert_eq!(weights.len(), (size * size) as usize);
+            assert!(weights.iter().all(|w| (0..=255).contains(w)), "size {size}");
+        }
+    }
+
+    #[test]
+    fn resampled_weights_keep_their_total() {
+        let map = bundled_weights();
+        let before = mean(map.pixels().map(|p| p[0] as f64));
+        for size in [37, 100, 128, 300, 512] {
+            let after = mean(resample_weights(&map, size).into_iter().map(|w| w as f64));
+            assert!(
+                (before - after).abs() < 0.5,
+                "size {size}: {before} vs {after}"
+            );
+        }
+    }
+
+    #[test]
+    fn hard_edges_dont_ring() {
+        let map = SourceImg::from_fn(256, 256, |x, _| {
+            image::Rgb([if x < 128 { 255 } else { 0 }; 3])
+        });
+        let weights = resample_weights(&map, 100);
+        for (i, w) in weights.iter().enumerate() {
+            let x = i % 100;
+            match x {
+                0..50 => assert_eq!(*w, 255, "column {x}"),
+                50 => {}
+                _ => assert_eq!(*w, 0, "column {x}"),
+            }
+        }
+    }
+
+    #[test]
+    fn mutual_swap_trades_whole_images_between_halves() {
+        let mut settings = GenerationSettings::default(uuid::Uuid::nil(), String::new());
+        (settings.sidelen, settings.block_size) = (64, 1);
+        settings.mutual_swap = true;
+        let red = SourceImg::from_pixel(80, 60, image::Rgb([255, 0, 0]));
+        let (source, target, _) = settings.get_swap_images(&red).unwrap();
+
+        let slot = |img: &SourceImg, x| imageops::crop_imm(img, x, 16, 32, 32).to_image();
+        assert!(slot(&source, 0).pixels().all(|p| p.0 == [255, 0, 0]));
+        assert_eq!(slot(&source, 0), slot(&target, 32));
+        assert_eq!(slot(&source, 32), slot(&target, 0));
+        assert_eq!(source.get_pixel(0, 0).0, settings.pad_color);
+        assert_eq!(target.get_pixel(63, 63).0, settings.pad_color);
+
+        assert!(settings.allows_move(0, 31) && settings.allows_move(32, 63));
+        assert!(!settings.allows_move(31, 32));
+    }
+
+    #[test]
+    fn images_that_dont_match_their_size_are_errors() {
+        let mut settings = GenerationSettings::default(uuid::Uuid::nil(), String::new());
+        (settings.sidelen, settings.block_size) = (32, 1);
+        let source = SourceImg::new(32, 32);
+        assert!(get_images(source.clone(), &settings).is_ok());
+
+        let mut blended = settings.clone();
+        blended.blend_sources.push(BlendSource {
+            width: 4,
+            height: 4,
+            data: vec![0; 5],
+            share: 1.0,
+        });
+        assert!(get_images(source.clone(), &blended).is_err());
+
+        let mut masked = settings.clone();
+        masked.source_mask = Some((32, 32, vec![255; 7]));
+        assert!(get_images(source.clone(), &masked).is_err());
+
+        let mut wide = settings;
+        wide.wide_source = Some((32, 32, vec![0; 3]));
+        assert!(get_images(source, &wide).is_err());
+    }
+}
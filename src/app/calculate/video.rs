@@ -0,0 +1,405 @@
+use std::error::Error;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+use image::AnimationDecoder;
+
+use crate::app::calculate::util::{
+    Algorithm, GenerationSettings, GridPixel, ProgressSink, SourceImg, WeightedPixel,
+};
+use crate::app::calculate::{
+    GENETIC_SEED, ProgressMsg, SolverState, best_of_solve, fitness_evaluator, genetic_solve,
+    make_new_img, make_preset, optimal_solve, report_start, util, watch,
+};
+use crate::app::export_job::JobProgress;
+use crate::app::gif_sink::{GifSink, PartFile};
+use crate::app::preset::UnprocessedPreset;
+use crate::app::video_recorder::VideoEncoder;
+
+const MAX_FRAMES: usize = 120;
+pub const SEQUENCE_FRAME_DELAY: u16 = 8; // hundredths of a second, about stop motion's 12 fps
+
+/// Frames of an animated gif used as a sequence of targets. Only gifs are read; other
+/// video formats would need a decoder this doesn't have.
+pub struct GifTarget {
+    pub frames: Vec<SourceImg>,
+    pub delays: Vec<u16>, // in hundredths of a second, like gif frame delays
+    pub truncated: bool,  // had more than `MAX_FRAMES` frames
+}
+
+impl GifTarget {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        let decoder = image::codecs::gif::GifDecoder::new(file)?;
+        let mut frames = Vec::new();
+        let mut delays = Vec::new();
+        let mut truncated = false;
+        for (i, frame) in decoder.into_frames().enumerate() {
+            if i == MAX_FRAMES {
+                truncated = true;
+                break;
+            }
+            let frame = frame?;
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            delays.push(((numer / denom.max(1)) / 10).max(2) as u16);
+            frames.push(image::DynamicImage::ImageRgba8(frame.into_buffer()).to_rgb8());
+        }
+        if frames.is_empty() {
+            return Err("video has no frames".into());
+        }
+        Ok(Self {
+            frames,
+            delays,
+            truncated,
+        })
+    }
+}
+
+/// A folder of numbered images, like `frame_001.png`, `frame_002.png`, ..., used as a
+/// sequence of sources for stop motion. Images without a number are left out.
+pub struct SourceSequence {
+    pub frames: Vec<SourceImg>,
+    pub numbers: Vec<u64>, // of each frame's file
+    pub truncated: bool,   // had more than `MAX_FRAMES` frames
+}
+
+/// The last run of digits in a file's name, which numbers it in its sequence.
+fn frame_number(path: &Path) -> Option<u64> {
+    let stem = path.file_stem()?.to_str()?;
+    let end = stem.rfind(|c: char| c.is_ascii_digit())? + 1;
+    let start = stem[..end]
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map_or(0, |i| i + 1);
+    stem[start..end].parse().ok()
+}
+
+/// The numbered images among `paths`, in the order their numbers give.
+fn numbered_frames(paths: Vec<PathBuf>) -> Vec<(u64, PathBuf)> {
+    let mut numbered = paths
+        .into_iter()
+        .filter_map(|path| Some((frame_number(&path)?, path)))
+        .collect::<Vec<_>>();
+    numbered.sort();
+    numbered
+}
+
+impl SourceSequence {
+    pub fn load(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let numbered = numbered_frames(watch::list_images(dir));
+        if numbered.is_empty() {
+            return Err("the folder has no numbered images".into());
+        }
+        let truncated = numbered.len() > MAX_FRAMES;
+        let mut frames = Vec::new();
+        let mut numbers = Vec::new();
+        for (number, path) in numbered.into_iter().take(MAX_FRAMES) {
+            let img = image::open(&path)
+                .map_err(|e| format!("failed to load {}: {}", path.display(), e))?;
+            frames.push(img.to_rgb8());
+            numbers.push(number);
+        }
+        Ok(Self {
+            frames,
+            numbers,
+            truncated,
+        })
+    }
+}
+
+/// Obamifies every frame of the gif `video`, each solve seeded with the previous frame's
+/// assignment so pixels stay coherent over time, and writes the result to `output`.
+pub fn process_video<S: ProgressSink>(
+    unprocessed: UnprocessedPreset,
+    settings: GenerationSettings,
+    video: GifTarget,
+    output: PathBuf,
+    tx: &mut S,
+    cancel: &AtomicBool,
+) -> Result<(), Box<dyn Error>> {
+    let source_img = image::ImageBuffer::from_vec(
+        unprocessed.width,
+        unprocessed.height,
+        unprocessed.source_img.clone(),
+    )
+    .unwrap();
+
+    report_start(&unprocessed, &settings, tx);
+    if video.truncated {
+        tx.send(ProgressMsg::Warning(format!(
+            "only the first {} frames of the video are used",
+            MAX_FRAMES
+        )));
+    }
+
+    let frames = video.frames.into_iter().map(|frame| {
+        let mut frame_settings = settings.clone();
+        frame_settings.set_raw_target(frame);
+        (source_img.clone(), frame_settings)
+    });
+    let presets = Presets::Last(unprocessed.name);
+    obamify_frames(frames, &video.delays, presets, &output, tx, cancel)
+}
+
+/// Obamifies every frame of `sequence` with the same settings, each solve seeded with the
+/// previous frame's assignment so pixels stay coherent over time. Every frame becomes a
+/// preset of its own, and together they're written to `output`.
+pub fn process_sequence<S: ProgressSink>(
+    settings: GenerationSettings,
+    sequence: SourceSequence,
+    output: PathBuf,
+    tx: &mut S,
+    cancel: &AtomicBool,
+) -> Result<(), Box<dyn Error>> {
+    let name = settings.name.clone();
+    let first = &sequence.frames[0];
+    let unprocessed = UnprocessedPreset {
+        name: name.clone(),
+        width: first.width(),
+        height: first.height(),
+        source_img: first.as_raw().clone(),
+    };
+    report_start(&unprocessed, &settings, tx);
+    if sequence.truncated {
+        tx.send(ProgressMsg::Warning(format!(
+            "only the first {} frames of the sequence are used",
+            MAX_FRAMES
+        )));
+    }
+
+    let delays = vec![SEQUENCE_FRAME_DELAY; sequence.frames.len()];
+    let names = sequence
+        .numbers
+        .iter()
+        .map(|number| format!("{} {}", name, number))
+        .collect();
+    let frames = sequence.frames.into_iter().enumerate().map(|(i, frame)| {
+        let mut frame_settings = settings.clone();
+        if i > 0 {
+            frame_settings.id = uuid::Uuid::new_v4(); // each frame is a preset of its own
+        }
+        (frame, frame_settings)
+    });
+    obamify_frames(frames, &delays, Presets::Each(names), &output, tx, cancel)
+}
+
+/// Which of a run's frames become presets.
+enum Presets {
+    Last(String),      // only the last, with this name
+    Each(Vec<String>), // every frame, with these
+}
+
+/// Solves each of `frames`, a source and the settings holding its target, and writes them
+/// to `output`, each shown for its delay in `delays`. Fast solves start from the frame
+/// before's assignment. The file is only moved into place once the last frame is in, so
+/// cancelling or failing leaves nothing half written.
+fn obamify_frames<S: ProgressSink>(
+    frames: impl ExactSizeIterator<Item = (SourceImg, GenerationSettings)>,
+    delays: &[u16],
+    presets: Presets,
+    output: &Path,
+    tx: &mut S,
+    cancel: &AtomicBool,
+) -> Result<(), Box<dyn Error>> {
+    let frame_count = frames.len();
+    let mut writer = None;
+    let mut previous: Option<Vec<usize>> = None;
+    for (i, ((source, settings), &delay)) in frames.zip(delays).enumerate() {
+        let _span = tracing::info_span!("video_frame", frame = i).entered();
+        let sidelen = settings.sidelen;
+        // made with the first frame's settings, which the rest share
+        let frame_writer = match &mut writer {
+            Some(writer) => writer,
+            none => none.insert(FrameWriter::create(output, sidelen, delays)?),
+        };
+        let (full_source, full_target) = util::get_images(source, &settings)?;
+        let (source_pixels, target_pixels) =
+            util::to_blocks(&full_source, &full_target, sidelen, settings.block_size);
+
+        let progress_range = (
+            i as f32 / frame_count as f32,
+            (i + 1) as f32 / frame_count as f32,
+        );
+        let Some(assignments) = solve_frame(
+            &source_pixels,
+            &target_pixels,
+            &settings,
+            previous.take(),
+            progress_range,
+            tx,
+            cancel,
+        ) else {
+            tx.send(ProgressMsg::Cancelled);
+            return Ok(()); // the writer's part file goes with it
+        };
+
+        let expanded = util::expand_block_assignments(&assignments, sidelen, settings.block_size);
+        frame_writer.write_frame(&make_new_img(&full_source, &expanded, sidelen), delay)?;
+
+        let last = i + 1 == frame_count;
+        let name = match &presets {
+            Presets::Each(names) => Some(&names[i]),
+            Presets::Last(name) => last.then_some(name),
+        };
+        if let Some(name) = name {
+            let preset = make_preset(
+                name.clone(),
+                &settings,
+                &full_source,
+                &full_target,
+                &assignments,
+            );
+            if last {
+                if let Some(writer) = writer.take() {
+                    writer.finish()?;
+                }
+                tx.send(ProgressMsg::Done(preset));
+            } else {
+                tx.send(ProgressMsg::FrameDone(preset));
+            }
+        }
+        previous = Some(assignments);
+    }
+    Ok(())
+}
+
+/// One frame's assignment with the chosen algorithm. The fast one carries on from
+/// `previous`, since later frames only need small corrections; the others solve each
+/// frame from scratch. Returns `None` when cancelled.
+fn solve_frame<S: ProgressSink>(
+    source_pixels: &[GridPixel],
+    target_pixels: &[WeightedPixel],
+    settings: &GenerationSettings,
+    previous: Option<Vec<usize>>,
+    progress_range: (f32, f32),
+    tx: &mut S,
+    cancel: &AtomicBool,
+) -> Option<Vec<usize>> {
+    match settings.algorithm {
+        Algorithm::Optimal => optimal_solve(
+            source_pixels,
+            target_pixels,
+            settings,
+            progress_range,
+            tx,
+            cancel,
+        ),
+        Algorithm::BestOf => {
+            let fitness = fitness_evaluator(source_pixels, target_pixels, settings);
+            best_of_solve(
+                source_pixels,
+                target_pixels,
+                settings,
+                progress_range,
+                tx,
+                cancel,
+                fitness.as_ref(),
+            )
+        }
+        Algorithm::Genetic => {
+            let grid = settings.grid_sidelen();
+            let start = match previous {
+                Some(previous) => SolverState::new(previous, grid / 8, GENETIC_SEED),
+                None => SolverState::identity(source_pixels, grid, GENETIC_SEED),
+            };
+            let range = progress_range;
+            genetic_solve(
+                source_pixels,
+                target_pixels,
+                settings,
+                start,
+                range,
+                tx,
+                cancel,
+                None,
+            )
+        }
+    }
+}
+
+/// Where obamified frames go: a webm, or a gif when the output ends in `.gif`. Both are
+/// written beside it under a `.part` name until they're finished.
+enum FrameWriter {
+    Gif(gif::Encoder<GifSink>, u16), // and the frames' side
+    Webm(VideoEncoder, GifSink),
+}
+
+impl FrameWriter {
+    fn create(output: &Path, sidelen: u32, delays: &[u16]) -> Result<Self, Box<dyn Error>> {
+        let file = GifSink::File(PartFile::create(output)?);
+        let is_gif = output
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+        if is_gif {
+            let mut encoder = gif::Encoder::new(file, sidelen as u16, sidelen as u16, &[])?;
+            encoder.set_repeat(gif::Repeat::Infinite)?;
+            return Ok(FrameWriter::Gif(encoder, sidelen as u16));
+        }
+        // videos have one frame rate, so frames get the delays' average
+        let mean_delay = delays.iter().map(|&d| d as f64).sum::<f64>() / delays.len() as f64;
+        let encoder = VideoEncoder::new(sidelen, sidelen, mean_delay * 10.0)?;
+        Ok(FrameWriter::Webm(encoder, file))
+    }
+
+    fn write_frame(&mut self, rgb: &[u8], delay: u16) -> Result<(), Box<dyn Error>> {
+        let _span = tracing::info_span!("video_encode_frame").entered();
+        match self {
+            FrameWriter::Gif(encoder, side) => {
+                let mut frame = gif::Frame::from_rgb_speed(*side, *side, rgb, 10);
+                frame.delay = delay;
+                encoder.write_frame(&frame)?;
+            }
+            FrameWriter::Webm(encoder, _) => {
+                let rgba = rgb
+                    .chunks_exact(3)
+                    .flat_map(|c| [c[0], c[1], c[2], 255])
+                    .collect::<Vec<_>>();
+                encoder.write_frame(&rgba)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Finishes the file and moves it into place.
+    fn finish(self) -> Result<(), Box<dyn Error>> {
+        let mut file = match self {
+            FrameWriter::Gif(encoder, _) => encoder.into_inner()?,
+            FrameWriter::Webm(encoder, mut file) => {
+                file.write_all(&encoder.finish(None, &JobProgress::default())?)?;
+                file
+            }
+        };
+        file.flush()?;
+        file.finish()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequences_go_by_their_last_number() {
+        let paths = [
+            "shot2_frame10.png",
+            "shot2_frame9.png",
+            "cover.png",
+            "0001.jpg",
+        ]
+        .map(PathBuf::from)
+        .to_vec();
+        let order = numbered_frames(paths)
+            .into_iter()
+            .map(|(n, path)| (n, path.to_string_lossy().into_owned()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            order,
+            [
+                (1, "0001.jpg".to_owned()),
+                (9, "shot2_frame9.png".to_owned()),
+                (10, "shot2_frame10.png".to_owned()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, mpsc};
+use std::time::{Duration, Instant};
+
+use crate::app::assignments;
+use crate::app::calculate::preprocess::{self, HdrSettings};
+use crate::app::calculate::util::{CropScale, GenerationSettings};
+use crate::app::calculate::{self, ProgressMsg};
+use crate::app::icc;
+use crate::app::preset::{Preset, UnprocessedPreset};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const IMAGE_EXTENSIONS: [&str; 10] = [
+    "png", "jpg", "jpeg", "webp", "bmp", "gif", "tif", "tiff", "exr", "hdr",
+];
+
+/// Processes every image that appears in a folder with the same settings, writing each
+/// result to its own folder in `results`. Runs until dropped.
+pub struct FolderWatch {
+    pub dir: PathBuf,
+    pub results: PathBuf,
+    pub processed: u32,
+    pub current: Option<String>, // name of the image being processed
+    pub last_error: Option<String>,
+    rx: mpsc::Receiver<WatchMsg>,
+    stop: Arc<AtomicBool>,
+}
+
+pub enum WatchMsg {
+    Started(String),
+    Done(Box<Preset>),
+    Error(String),
+}
+
+impl FolderWatch {
+    /// Starts watching `dir`. Images already in it are left alone; only new ones count.
+    pub fn start(
+        dir: PathBuf,
+        results: PathBuf,
+        settings: GenerationSettings,
+        hdr: HdrSettings,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        std::thread::spawn({
+            let (dir, results, stop) = (dir.clone(), results.clone(), stop.clone());
+            move || watch(&dir, &results, settings, hdr, &tx, &stop)
+        });
+        Self {
+            dir,
+            results,
+            processed: 0,
+            current: None,
+            last_error: None,
+            rx,
+            stop,
+        }
+    }
+
+    /// Returns the presets finished since the last call.
+    pub fn poll(&mut self) -> Vec<Preset> {
+        let mut done = Vec::new();
+        while let Ok(msg) = self.rx.try_recv() {
+            match msg {
+                WatchMsg::Started(name) => self.current = Some(name),
+                WatchMsg::Done(preset) => {
+                    self.current = None;
+                    self.processed += 1;
+                    done.push(*preset);
+                }
+                WatchMsg::Error(err) => {
+                    self.current = None;
+                    self.last_error = Some(err);
+                }
+            }
+        }
+        done
+    }
+}
+
+impl Drop for FolderWatch {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+pub(crate) fn list_images(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut images = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && is_image(path))
+        .collect::<Vec<_>>();
+    images.sort();
+    images
+}
+
+/// Wakes the watch loop when a file in the folder is created, written or moved in. Uses
+/// inotify on Linux; elsewhere, or if that can't be set up, the loop lists the folder
+/// every `POLL_INTERVAL` instead.
+struct Changes {
+    #[cfg(target_os = "linux")]
+    inotify: Option<nix::sys::inotify::Inotify>,
+}
+
+impl Changes {
+    fn watch(dir: &Path) -> Self {
+        #[cfg(target_os = "linux")]
+        {
+            use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+            let flags = AddWatchFlags::IN_CREATE
+                | AddWatchFlags::IN_CLOSE_WRITE
+                | AddWatchFlags::IN_MOVED_TO;
+            let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)
+                .and_then(|inotify| inotify.add_watch(dir, flags).map(|_| inotify))
+                .inspect_err(|err| log::warn!("can't watch {}, polling: {}", dir.display(), err))
+                .ok();
+            Self { inotify }
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = dir;
+            Self {}
+        }
+    }
+
+    /// Waits up to `POLL_INTERVAL`, returning early if the folder changes. Returns
+    /// whether it may have changed; without events that's always.
+    fn wait(&self) -> bool {
+        #[cfg(target_os = "linux")]
+        if let Some(inotify) = &self.inotify {
+            use nix::poll::{PollFd, PollFlags, poll};
+            use std::os::fd::AsFd;
+            let mut fds = [PollFd::new(inotify.as_fd(), PollFlags::POLLIN)];
+            let timeout = POLL_INTERVAL.as_millis() as u16;
+            match poll(&mut fds, timeout) {
+                Ok(0) => return false,
+                Ok(_) => {
+                    inotify.read_events().ok(); // only that there were any matters
+                    return true;
+                }
+                Err(nix::errno::Errno::EINTR) => return true,
+                Err(_) => {} // fall back to polling for this round
+            }
+        }
+        std::thread::sleep(POLL_INTERVAL);
+        true
+    }
+}
+
+fn watch(
+    dir: &Path,
+    results: &Path,
+    settings: GenerationSettings,
+    hdr: HdrSettings,
+    tx: &mpsc::Sender<WatchMsg>,
+    stop: &Arc<AtomicBool>,
+) {
+    let changes = Changes::watch(dir);
+    let mut seen = list_images(dir).into_iter().collect::<HashSet<_>>();
+    // a file still being copied in has a changing size, so wait until it has held still
+    // for a whole interval
+    let mut pending = Vec::<(PathBuf, u64, Instant)>::new();
+
+    while !stop.load(Ordering::Relaxed) {
+        // files that are settling are looked at again either way
+        if !changes.wait() && pending.is_empty() {
+            continue;
+        }
+
+        let mut ready = Vec::new();
+        for path in list_images(dir) {
+            if seen.contains(&path) {
+                continue;
+            }
+            let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            match pending.iter_mut().find(|(p, _, _)| *p == path) {
+                Some((_, last, since)) if *last == size && size > 0 => {
+                    if since.elapsed() >= POLL_INTERVAL {
+                        ready.push(path);
+                    }
+                }
+                Some((_, last, since)) => (*last, *since) = (size, Instant::now()),
+                None => pending.push((path, size, Instant::now())),
+            }
+        }
+
+        for path in ready {
+            pending.retain(|(p, _, _)| *p != path);
+            seen.insert(path.clone());
+            let name = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| "image".to_owned());
+            tx.send(WatchMsg::Started(name.clone())).ok();
+            let msg = match process_file(&path, &name, results, &settings, &hdr, stop) {
+                Ok(Some(preset)) => WatchMsg::Done(Box::new(preset)),
+                Ok(None) => return, // stopped
+                Err(err) => WatchMsg::Error(format!("{}: {}", name, err)),
+            };
+            if tx.send(msg).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn process_file(
+    path: &Path,
+    name: &str,
+    results: &Path,
+    settings: &GenerationSettings,
+    hdr: &HdrSettings,
+    stop: &Arc<AtomicBool>,
+) -> Result<Option<Preset>, Box<dyn Error>> {
+    let loaded = preprocess::fill_transparent(icc::open(path)?);
+    let img = hdr.convert(&loaded);
+    let mut settings = settings.clone();
+    settings.set_wide_source(preprocess::wide_source(&loaded));
+    settings.name = name.to_owned();
+    settings.source_crop_scale = CropScale::identity();
+
+    let unprocessed = UnprocessedPreset {
+        name: name.to_owned(),
+        width: img.width(),
+        height: img.height(),
+        source_img: img.into_raw(),
+    };
+    let mut result = None;
+    let mut error = None;
+    let mut sink = |msg: ProgressMsg| match msg {
+        ProgressMsg::Done(preset) => result = Some(preset),
+        ProgressMsg::Error(err) => error = Some(err),
+        _ => {}
+    };
+    let snapshot = Arc::new(AtomicBool::new(false)); // nobody to ask for one
+    calculate::process(unprocessed, settings, &mut sink, stop.clone(), snapshot)?;
+    if let Some(err) = error {
+        return Err(err.into());
+    }
+    let Some(preset) = result else {
+        return Ok(None);
+    };
+
+    write_result(&preset, &result_dir(results, path))?;
+    Ok(Some(*preset))
+}
+
+/// A folder in `results` for the result of `path` that isn't there yet: named after the
+/// whole file name, so `photo.png` and `photo.jpg` get one each, and numbered if an earlier
+/// watch already has that name.
+fn result_dir(results: &Path, path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "image".to_owned());
+    (1..)
+        .map(|i| match i {
+            1 => results.join(&file_name),
+            i => results.join(format!("{} ({})", file_name, i)),
+        })
+        .find(|dir| !dir.exists())
+        .unwrap() // there's only so many folders
+}
+
+/// Writes the preset like the bundled ones (`source.png` and `assignments.bin`), plus the
+/// obamified image as `result.png`.
+fn write_result(preset: &Preset, dir: &Path) -> Result<(), Box<dyn Error>> {
+    std::fs::create_dir_all(dir)?;
+    let inner = &preset.inner;
+    image::save_buffer(
+        dir.join("source.png"),
+        &inner.source_img,
+        inner.width,
+        inner.height,
+        image::ExtendedColorType::Rgb8,
+    )?;
+
+    std::fs::write(
+        dir.join("assignments.bin"),
+        assignments::encode(&preset.assignments),
+    )?;
+
+    let mut result = vec![0; inner.source_img.len()];
+    for (target_idx, &source_idx) in preset.assignments.iter().enumerate() {
+        result[target_idx * 3..target_idx * 3 + 3]
+            .copy_from_slice(&inner.source_img[source_idx * 3..source_idx * 3 + 3]);
+    }
+    image::save_buffer(
+        dir.join("result.png"),
+        &result,
+        inner.width,
+        inner.height,
+        image::ExtendedColorType::Rgb8,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_of_same_named_images_get_their_own_folders() {
+        let results = std::env::temp_dir().join(format!("obamify_watch_{}", std::process::id()));
+        let dir = |name: &str| {
+            let dir = result_dir(&results, Path::new(name));
+            std::fs::create_dir_all(&dir).unwrap();
+            dir.file_name().unwrap().to_string_lossy().to_string()
+        };
+        assert_eq!(dir("in/photo.png"), "photo.png");
+        assert_eq!(dir("in/photo.jpg"), "photo.jpg");
+        assert_eq!(dir("in/photo.png"), "photo.png (2)");
+        assert_eq!(dir("in/photo.png"), "photo.png (3)");
+        std::fs::remove_dir_all(&results).ok();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn new_files_wake_the_watch() {
+        let dir = std::env::temp_dir().join(format!("obamify_changes_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let changes = Changes::watch(&dir);
+        assert!(changes.inotify.is_some());
+        let started = Instant::now();
+        assert!(!changes.wait()); // nothing happened
+        assert!(started.elapsed() >= POLL_INTERVAL);
+
+        std::fs::write(dir.join("new.png"), b"not really").unwrap();
+        let started = Instant::now();
+        assert!(changes.wait());
+        assert!(started.elapsed() < POLL_INTERVAL);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,46 @@
+//! Copying finished gifs, for pasting them into chats and documents without saving them
+//! first. Clipboards have no animated image format, so the gif goes on as html holding it
+//! inline, which browsers, chat apps and office suites paste as the animation.
+
+use base64::Engine as _;
+
+/// The system clipboard, opened the first time something's copied. Kept open, since on
+/// Linux what's copied is gone once whoever copied it lets go.
+#[derive(Default)]
+pub struct GifClipboard(Option<arboard::Clipboard>);
+
+impl GifClipboard {
+    /// Puts `gif` on the clipboard, with `alt` for where only text can be pasted.
+    pub fn copy_gif(&mut self, gif: &[u8], alt: &str) -> Result<(), String> {
+        let clipboard = match &mut self.0 {
+            Some(clipboard) => clipboard,
+            none => none.insert(arboard::Clipboard::new().map_err(|e| e.to_string())?),
+        };
+        clipboard
+            .set_html(html(gif, alt), Some(alt.to_owned()))
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// An image tag holding `gif` inline.
+fn html(gif: &[u8], alt: &str) -> String {
+    format!(
+        "<img src=\"data:image/gif;base64,{}\" alt=\"{}\">",
+        base64::engine::general_purpose::STANDARD.encode(gif),
+        alt.replace('&', "&amp;").replace('"', "&quot;")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html_holds_the_gif_inline() {
+        assert_eq!(
+            html(b"GIF89a", "\"tom & jerry\""),
+            "<img src=\"data:image/gif;base64,R0lGODlh\" alt=\"&quot;tom &amp; jerry&quot;\">"
+        );
+        assert!(html(b"g", "").contains("base64,Zw==\""));
+    }
+}
@@ -0,0 +1,421 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::app::calculate::preprocess::HdrSettings;
+use crate::app::calculate::util::Algorithm;
+use crate::app::gif_recorder::GifLimits;
+use crate::app::json;
+use crate::app::power::LowPower;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum Theme {
+    System,
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub fn preference(&self) -> egui::ThemePreference {
+        match self {
+            Theme::System => egui::ThemePreference::System,
+            Theme::Dark => egui::ThemePreference::Dark,
+            Theme::Light => egui::ThemePreference::Light,
+        }
+    }
+}
+
+/// Key names as understood by `egui::Key::from_name`, e.g. "Space" or "R".
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Keybinds {
+    pub play: String,
+    pub reverse: String,
+    pub save_gif: String,
+}
+
+impl Default for Keybinds {
+    fn default() -> Self {
+        Self {
+            play: "Space".to_owned(),
+            reverse: "R".to_owned(),
+            save_gif: "G".to_owned(),
+        }
+    }
+}
+
+/// Where exports are saved: asks with a file dialog, or writes straight into the output
+/// directory when dialogs are disabled for scripted runs.
+#[derive(Clone, Debug, Default)]
+pub struct Output {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub dir: Option<PathBuf>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub no_dialog: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Output {
+    /// A file dialog that opens in the output directory, if one is set.
+    fn file_dialog(&self) -> rfd::FileDialog {
+        let dialog = rfd::FileDialog::new();
+        match &self.dir {
+            Some(dir) => dialog.set_directory(dir),
+            None => dialog,
+        }
+    }
+
+    fn path_in_dir(&self, file_name: &str) -> PathBuf {
+        self.dir
+            .clone()
+            .unwrap_or_else(|| ".".into())
+            .join(file_name)
+    }
+
+    /// Path to save `file_name` to, or `None` if the user cancelled.
+    pub fn save_path(&self, title: &str, file_name: &str, extension: &str) -> Option<PathBuf> {
+        self.save_path_as(title, file_name, &[extension])
+    }
+
+    /// Like `save_path`, offering each of `extensions` to save as.
+    pub fn save_path_as(
+        &self,
+        title: &str,
+        file_name: &str,
+        extensions: &[&str],
+    ) -> Option<PathBuf> {
+        if self.no_dialog {
+            return Some(self.path_in_dir(file_name));
+        }
+        extensions
+            .iter()
+            .fold(self.file_dialog(), |dialog, &ext| {
+                dialog.add_filter(ext, &[ext])
+            })
+            .set_title(title)
+            .set_file_name(file_name)
+            .save_file()
+    }
+
+    /// Folder to write a sequence of files into, or `None` if the user cancelled.
+    pub fn folder(&self, title: &str, name: &str) -> Option<PathBuf> {
+        if self.no_dialog {
+            let dir = self.path_in_dir(name);
+            return match std::fs::create_dir_all(&dir) {
+                Ok(()) => Some(dir),
+                Err(err) => {
+                    eprintln!("failed to create {}: {}", dir.display(), err);
+                    None
+                }
+            };
+        }
+        self.file_dialog().set_title(title).pick_folder()
+    }
+}
+
+/// Application defaults, read from `config.toml` at startup. Missing entries keep their
+/// default, so the file only needs what the user wants to change.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub output_dir: Option<PathBuf>, // where save dialogs start
+    pub sidelen: u32,
+    pub algorithm: Algorithm,
+    pub solver_threads: u32, // 0: one per core but one
+    pub low_power: LowPower,
+    pub theme: Theme,
+    pub gif: GifLimits,
+    pub hdr: HdrSettings,
+    pub keybinds: Keybinds,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    pub no_dialog: bool, // only settable from the command line or environment
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            output_dir: None,
+            sidelen: 128,
+            algorithm: Algorithm::Genetic,
+            solver_threads: 0,
+            low_power: LowPower::Auto,
+            theme: Theme::System,
+            gif: GifLimits::default(),
+            hdr: HdrSettings::default(),
+            keybinds: Keybinds::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            no_dialog: false,
+        }
+    }
+}
+
+/// Config settings given as `--flags`, which `Config::load` applies over the file and the
+/// `OBAMIFY_*` environment variables.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+pub struct Overrides {
+    output_dir: Option<PathBuf>,
+    sidelen: Option<u32>,
+    algorithm: Option<Algorithm>,
+    threads: Option<u32>,
+    low_power: bool,
+    no_dialog: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Overrides {
+    /// Takes the config flags out of `args`, leaving the rest for the caller.
+    pub fn from_args(args: &mut pico_args::Arguments) -> Result<Self, pico_args::Error> {
+        Ok(Self {
+            output_dir: args.opt_value_from_str("--output-dir")?,
+            sidelen: args.opt_value_from_str("--sidelen")?,
+            algorithm: args.opt_value_from_fn("--algorithm", parse_algorithm)?,
+            threads: args.opt_value_from_str("--threads")?,
+            low_power: args.contains("--low-power"),
+            no_dialog: args.contains("--no-dialog"),
+        })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_algorithm(name: &str) -> Result<Algorithm, String> {
+    match name.to_lowercase().as_str() {
+        "optimal" => Ok(Algorithm::Optimal),
+        "genetic" | "fast" => Ok(Algorithm::Genetic),
+        "bestof" | "best-of" | "best" => Ok(Algorithm::BestOf),
+        _ => Err(format!("unknown algorithm {:?}", name)),
+    }
+}
+
+impl Config {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn path() -> Option<PathBuf> {
+        eframe::storage_dir("obamify").map(|dir| dir.join("config.toml"))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+
+    /// Reads the config file, falling back to the defaults if it's missing or invalid,
+    /// then applies overrides from the environment and command line.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(overrides: &Overrides) -> Self {
+        let mut config = Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .map(|text| {
+                Self::parse(&text).unwrap_or_else(|err| {
+                    eprintln!("invalid config file, using defaults: {}", err);
+                    Self::default()
+                })
+            })
+            .unwrap_or_default();
+        if let Err(err) = config.apply_overrides(overrides) {
+            eprintln!("ignoring invalid override: {}", err);
+        }
+        config
+    }
+
+    /// Applies `OBAMIFY_*` environment variables, then `--flags`, which take precedence.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn apply_overrides(&mut self, flags: &Overrides) -> Result<(), Box<dyn std::error::Error>> {
+        let env = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+
+        if let Some(dir) = &flags.output_dir {
+            self.output_dir = Some(dir.clone());
+        } else if let Some(dir) = env("OBAMIFY_OUTPUT_DIR") {
+            self.output_dir = Some(dir.into());
+        }
+
+        if let Some(sidelen) = flags.sidelen {
+            self.sidelen = sidelen;
+        } else if let Some(sidelen) = env("OBAMIFY_SIDELEN") {
+            self.sidelen = sidelen.parse()?;
+        }
+
+        if let Some(algorithm) = flags.algorithm {
+            self.algorithm = algorithm;
+        } else if let Some(name) = env("OBAMIFY_ALGORITHM") {
+            self.algorithm = parse_algorithm(&name)?;
+        }
+
+        if let Some(threads) = flags.threads {
+            self.solver_threads = threads;
+        } else if let Some(threads) = env("OBAMIFY_THREADS") {
+            self.solver_threads = threads.parse()?;
+        }
+
+        if flags.low_power || env("OBAMIFY_LOW_POWER").is_some() {
+            self.low_power = LowPower::Always;
+        }
+
+        self.no_dialog = flags.no_dialog || env("OBAMIFY_NO_DIALOG").is_some();
+        Ok(())
+    }
+
+    pub fn output(&self) -> Output {
+        Output {
+            #[cfg(not(target_arch = "wasm32"))]
+            dir: self.output_dir.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            no_dialog: self.no_dialog,
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = Self::path().ok_or("no config directory")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_toml())?;
+        Ok(())
+    }
+
+    /// Writes the config with a comment per entry, so the file documents itself.
+    pub fn to_toml(&self) -> String {
+        let quoted = json::quote;
+        let mut toml = String::new();
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            toml += "# folder the save dialogs open in\n";
+            match &self.output_dir {
+                Some(dir) => toml += &format!("output_dir = {}\n", quoted(&dir.to_string_lossy())),
+                None => toml += "# output_dir = \"/path/to/folder\"\n",
+            }
+        }
+        toml += &format!(
+            "# default resolution of new obamifications\nsidelen = {}\n",
+            self.sidelen
+        );
+        toml += &format!(
+            "# \"Optimal\", \"Genetic\" or \"BestOf\"\nalgorithm = \"{:?}\"\n",
+            self.algorithm
+        );
+        toml += &format!(
+            "# solves run side by side in best of mode; 0 uses all cores but one\nsolver_threads = {}\n",
+            self.solver_threads
+        );
+        toml += &format!(
+            "# one solver thread and slower previews: \"Auto\" while on battery, \"Always\" or \"Never\"\nlow_power = \"{:?}\"\n",
+            self.low_power
+        );
+        toml += &format!(
+            "# \"System\", \"Dark\" or \"Light\"\ntheme = \"{:?}\"\n",
+            self.theme
+        );
+
+        let gif = &self.gif;
+        toml += "\n[gif]\n";
+        toml += &format!(
+            "# frames captured per second of animation, up to 50\nframerate = {}\n",
+            gif.framerate
+        );
+        toml += &format!("resolution = {}\n", gif.resolution);
+        toml += &format!("min_frames = {}\n", gif.min_frames);
+        toml += &format!("max_frames = {}\n", gif.max_frames);
+        toml += &format!("max_size_mb = {}\n", gif.max_size_mb);
+        toml += &format!("speed = {:?}\n", gif.speed);
+        toml += &format!(
+            "# 2 keeps every other frame, so twice as much fits under the limits\ncapture_every = {}\n",
+            gif.capture_every
+        );
+        toml += &format!(
+            "# how rendered frames are encoded: \"Srgb\", or \"Linear\" for linear light\ncolor_space = \"{:?}\"\n",
+            gif.color_space
+        );
+        toml += &format!(
+            "# \"Forever\", \"Once\" or {{ Repeat = 3 }} to stop on the last frame, or \"Boomerang\"\n# to play backwards after forwards\nloop_mode = {}\n",
+            gif.loop_mode.toml()
+        );
+        toml += &format!(
+            "# 1 to 10; lower builds big grids' palettes faster, 10 learns from every color\npalette_quality = {}\n",
+            gif.palette_quality
+        );
+        toml += &format!(
+            "# seconds of playback to record, or 0 to stop on the frame limits\nmax_seconds = {:?}\n",
+            gif.max_seconds
+        );
+        toml += &format!(
+            "# spread each pixel's color error onto its neighbors, trading banding for grain\ndither = {}\n",
+            gif.dither
+        );
+        toml += &format!(
+            "# give each frame a palette of its own colors instead of the seeds'; slower and bigger\nlocal_palettes = {}\n",
+            gif.local_palettes
+        );
+        toml += &format!(
+            "# lower the framerate, colors and then resolution as needed to fit the whole animation\n# under max_size_mb, instead of stopping the recording there\nfit_size = {}\n",
+            gif.fit_size
+        );
+        let stop = &gif.stop;
+        toml += "\n# what ends a recording on its own; with none of them it records until stopped\n[gif.stop]\n";
+        toml += &format!(
+            "# at max_frames, or max_seconds of playback if set\nmax_frames = {}\n",
+            stop.max_frames
+        );
+        toml += &format!(
+            "# once the next frame wouldn't fit in max_size_mb\nmax_size = {}\n",
+            stop.max_size
+        );
+        toml += &format!(
+            "# once every cell has reached its place\narrived = {}\n",
+            stop.arrived
+        );
+        toml += &format!(
+            "# \"Any\" stops at the first met, \"All\" once they all are\ncombine = \"{:?}\"\n",
+            stop.combine
+        );
+
+        let hdr = &self.hdr;
+        toml += "\n# how exr and hdr images are brought down to normal brightness\n[hdr]\n";
+        toml += &format!(
+            "# \"Aces\", \"Reinhard\" or \"Clip\"\ntonemap = \"{:?}\"\n",
+            hdr.tonemap
+        );
+        toml += &format!("# in stops\nexposure = {:?}\n", hdr.exposure);
+
+        let keys = &self.keybinds;
+        toml += "\n# key names like \"Space\", \"R\" or \"F5\"\n[keybinds]\n";
+        toml += &format!("play = {}\n", quoted(&keys.play));
+        toml += &format!("reverse = {}\n", quoted(&keys.reverse));
+        toml += &format!("save_gif = {}\n", quoted(&keys.save_gif));
+        toml
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::gif_recorder::LoopMode;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn written_configs_read_back_the_same() {
+        for loop_mode in [LoopMode::Repeat(3), LoopMode::Boomerang] {
+            let config = Config {
+                output_dir: Some(PathBuf::from(r#"C:\Users\"me"\obamify out"#)),
+                sidelen: 96,
+                algorithm: Algorithm::Genetic,
+                gif: GifLimits {
+                    loop_mode,
+                    dither: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+
+            let toml = config.to_toml();
+            let parsed = Config::parse(&toml).unwrap();
+            assert_eq!(parsed.output_dir, config.output_dir);
+            assert_eq!(parsed.gif.loop_mode, loop_mode);
+            assert_eq!((parsed.sidelen, parsed.gif.dither), (96, true));
+            assert!(matches!(parsed.algorithm, Algorithm::Genetic));
+            assert_eq!(parsed.to_toml(), toml); // and everything else too
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Slow export work, like encoding stills, re-encoding gifs and finishing videos, run off
+//! the UI thread with progress to show and a way to cancel. The web has no threads, so
+//! there a job runs to the end as soon as it's started.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, AtomicU32, Ordering},
+};
+
+/// Finished files, as contents and names without the extension.
+pub type Files = Vec<(Vec<u8>, String)>;
+
+pub const CANCELLED: &str = "cancelled";
+
+/// What a running job shares with whoever waits on it.
+#[derive(Default)]
+pub struct JobProgress {
+    done: AtomicU32, // fraction done, as f32 bits
+    cancelled: AtomicBool,
+}
+
+impl JobProgress {
+    pub fn set(&self, fraction: f32) {
+        self.done
+            .store(fraction.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn fraction(&self) -> f32 {
+        f32::from_bits(self.done.load(Ordering::Relaxed))
+    }
+
+    /// Whether the job should give up, which it does by returning `CANCELLED`.
+    pub fn cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Notes that `done` of `total` steps are done, and gives `Err(CANCELLED)` once the
+    /// job should give up, for `?` in loops.
+    pub fn step(&self, done: usize, total: usize) -> Result<(), String> {
+        self.set(done as f32 / total.max(1) as f32);
+        if self.cancelled() {
+            Err(CANCELLED.to_owned())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+pub struct ExportJob {
+    pub label: &'static str, // what's being exported, for the progress and saved messages
+    pub ext: &'static str,   // of the files it makes
+    progress: Arc<JobProgress>,
+    #[cfg(not(target_arch = "wasm32"))]
+    result: std::sync::mpsc::Receiver<Result<Files, String>>,
+    #[cfg(target_arch = "wasm32")]
+    result: Option<Result<Files, String>>,
+}
+
+impl ExportJob {
+    pub fn spawn(
+        label: &'static str,
+        ext: &'static str,
+        work: impl FnOnce(&JobProgress) -> Result<Files, String> + Send + 'static,
+    ) -> Self {
+        let progress = Arc::new(JobProgress::default());
+        #[cfg(not(target_arch = "wasm32"))]
+        let result = {
+            let (tx, rx) = std::sync::mpsc::channel();
+            let progress = progress.clone();
+            std::thread::spawn(move || {
+                let _span = tracing::info_span!("export_job", label).entered();
+                tx.send(work(&progress)).ok(); // nobody's waiting if it was dropped
+            });
+            rx
+        };
+        #[cfg(target_arch = "wasm32")]
+        let result = Some(work(&progress));
+        Self {
+            label,
+            ext,
+            progress,
+            result,
+        }
+    }
+
+    pub fn fraction(&self) -> f32 {
+        self.progress.fraction()
+    }
+
+    /// Asks the job to stop; it ends with `CANCELLED` soon after.
+    pub fn cancel(&self) {
+        self.progress.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// The job's files or error, once it's done.
+    pub fn poll(&mut self) -> Option<Result<Files, String>> {
+        #[cfg(not(target_arch = "wasm32"))]
+        return match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(std::sync::mpsc::TryRecvError::Empty) => None,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                Some(Err("the export stopped unexpectedly".to_owned()))
+            }
+        };
+        #[cfg(target_arch = "wasm32")]
+        self.result.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancelled_job_stops_at_its_next_step() {
+        let (go, wait) = std::sync::mpsc::channel::<()>();
+        let mut job = ExportJob::spawn("test", "bin", move |job| {
+            wait.recv().ok();
+            for i in 0..10 {
+                job.step(i, 10)?;
+            }
+            Ok(vec![])
+        });
+        job.cancel();
+        go.send(()).unwrap();
+        let result = loop {
+            if let Some(result) = job.poll() {
+                break result;
+            }
+            std::thread::yield_now();
+        };
+        assert_eq!(result, Err(CANCELLED.to_owned()));
+        assert_eq!(job.fraction(), 0.0);
+    }
+}
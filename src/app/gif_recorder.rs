@@ -1,28 +1,709 @@
-#[cfg(not(target_arch = "wasm32"))]
 use std::borrow::Cow;
+use std::collections::VecDeque;
+#[cfg(not(target_arch = "wasm32"))]
 use std::path::PathBuf;
 use std::sync::{Arc, atomic::AtomicBool};
 
 use color_quant::NeuQuant;
 
-use crate::{ObamifyApp, app::SeedColor};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::app::clipboard::GifClipboard;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::app::gif_sink::PartFile;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::app::gpu_quantize;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::app::video_recorder::{OpusAudio, VideoEncoder};
+use crate::{
+    ObamifyApp,
+    app::{
+        DEFAULT_RESOLUTION, SeedColor,
+        calculate::preprocess::srgb_from_linear,
+        config::Output,
+        export_job::{CANCELLED, ExportJob, Files, JobProgress},
+        gif_sink::GifSink,
+        lut::Lut,
+        overlay::GridOverlay,
+        sprite_sheet::SpriteSheet,
+        zip::ZipWriter,
+    },
+};
+
+const GIF_FRAMERATE: u32 = 8;
+pub const MAX_FRAMERATE: u32 = 50;
+const MIN_FRAME_DELAY: f64 = 2.0; // hundredths; most viewers slow anything shorter down
+const GIF_RESOLUTION: u32 = 400;
+const GIF_MAX_FRAMES: u32 = 140;
+const GIF_MIN_FRAMES: u32 = 100;
+const GIF_MAX_SIZE_MB: u32 = 45;
+const GIF_SPEED: f32 = 1.5;
+const GIF_CAPTURE_EVERY: u32 = 1;
+const GIF_PALETTE_QUALITY: u32 = 5;
+pub const MAX_PALETTE_QUALITY: u32 = 10; // learns from every color
+const PALETTE_SAMPLES_PER_QUALITY: usize = 25_000;
+const MAX_PALETTE_SAMPLEFAC: usize = 30; // NeuQuant's coarsest
+pub const MAX_TAKES: u32 = 6;
+const TAKE_PREVIEW_FRAME: u32 = 30; // mid-transition, where takes differ the most
+const TIMELAPSE_MAX_FRAMES: usize = 120;
+const TIMELAPSE_MIN_SIZE: u32 = 256;
+const TIMELAPSE_FRAME_DELAY: u16 = 8; // hundredths of a second
+const TIMELAPSE_END_DELAY: u16 = 200;
+const STILL_BANDS: usize = 32; // of rows, each a step of a still's progress
+
+/// How the rendered frames' channel values relate to what's on screen. The renderer
+/// writes seed colors, which are sRGB, into a plain (non-sRGB) texture that egui shows
+/// as is, so captured bytes are already sRGB. Linear is for renders in linear light,
+/// which get the sRGB transfer function applied before quantizing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize)]
+pub enum ColorSpace {
+    #[default]
+    Srgb,
+    Linear,
+}
+
+impl ColorSpace {
+    /// The byte a 0..1 channel value ends up as in the gif, rounded like the GPU's unorm
+    /// conversion so palette entries match the captured pixels exactly.
+    pub fn encode(self, v: f32) -> u8 {
+        // the texture holds 8 bits either way, so convert what was actually stored
+        let stored = (v.clamp(0.0, 1.0) * 255.0).round();
+        match self {
+            ColorSpace::Srgb => stored as u8,
+            ColorSpace::Linear => (srgb_from_linear(stored / 255.0) * 255.0).round() as u8,
+        }
+    }
+
+    /// The gif byte of each byte a captured frame can store.
+    pub fn encoding(self) -> [u8; 256] {
+        std::array::from_fn(|i| self.encode(i as f32 / 255.0))
+    }
+
+    /// Converts captured RGBA bytes to sRGB in place; alpha is left alone.
+    pub fn to_srgb(self, rgba: &mut [u8]) {
+        if self == ColorSpace::Srgb {
+            return;
+        }
+        let lut = self.encoding();
+        for pixel in rgba.chunks_exact_mut(4) {
+            for c in &mut pixel[..3] {
+                *c = lut[*c as usize];
+            }
+        }
+    }
+}
+
+/// NeuQuant's sample factor for learning from about as many of `colors` as `quality` is
+/// worth, so big grids don't stall on building the palette.
+fn palette_samplefac(colors: usize, quality: u32) -> i32 {
+    if quality >= MAX_PALETTE_QUALITY {
+        return 1;
+    }
+    let budget = PALETTE_SAMPLES_PER_QUALITY * quality.max(1) as usize;
+    colors.div_ceil(budget).clamp(1, MAX_PALETTE_SAMPLEFAC) as i32
+}
+
+/// Builds the gif palette from the colors that can appear in the frames.
+pub fn build_palette(colors: &[SeedColor], color_space: ColorSpace, quality: u32) -> NeuQuant {
+    let bytes = colors
+        .iter()
+        .flat_map(|c| {
+            let [r, g, b, a] = c.rgba;
+            // alpha is coverage, not a color, so it's never transformed
+            [
+                color_space.encode(r),
+                color_space.encode(g),
+                color_space.encode(b),
+                ColorSpace::Srgb.encode(a),
+            ]
+        })
+        .collect::<Vec<u8>>();
+    let _span = tracing::info_span!("build_palette", colors = colors.len()).entered();
+    NeuQuant::new(palette_samplefac(colors.len(), quality), 256, &bytes)
+}
+
+/// Writes the palette indices of an rgba frame into `out`, spreading each pixel's error
+/// onto the neighbors not yet written (Floyd–Steinberg), so gradients come out as fine
+/// grain instead of flat bands. Alpha is indexed as is.
+pub(crate) fn dither_indices(palette: &NeuQuant, rgba: &[u8], width: usize, out: &mut [u8]) {
+    let width = width.max(1);
+    let height = rgba.len() / 4 / width;
+    let mut wanted = rgba
+        .chunks_exact(4)
+        .map(|c| [c[0], c[1], c[2]].map(|v| v as f32))
+        .collect::<Vec<_>>();
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            let [r, g, b] = wanted[i].map(|v| v.round().clamp(0.0, 255.0) as u8);
+            let index = palette.index_of(&[r, g, b, rgba[i * 4 + 3]]);
+            out[i] = index as u8;
+            let chosen = palette.lookup(index).unwrap_or_default();
+            let error: [f32; 3] = std::array::from_fn(|c| wanted[i][c] - chosen[c] as f32);
+            let mut spread = |x: usize, y: usize, share: f32| {
+                if x < width && y < height {
+                    let pixel = &mut wanted[y * width + x];
+                    for c in 0..3 {
+                        pixel[c] += error[c] * share;
+                    }
+                }
+            };
+            spread(x + 1, y, 7.0 / 16.0);
+            if x > 0 {
+                spread(x - 1, y + 1, 3.0 / 16.0);
+            }
+            spread(x, y + 1, 5.0 / 16.0);
+            spread(x + 1, y + 1, 1.0 / 16.0);
+        }
+    }
+}
+
+/// The shape of recorded frames: the square animation, or the preview's shape with the
+/// animation fitted inside it or cut to fill it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FrameShape {
+    #[default]
+    Square,
+    Pad,  // bars where the animation doesn't reach
+    Fill, // the animation's edges cut off
+}
+
+/// How a gif plays: looping, a number of times before stopping on its last frame, or
+/// forwards and then backwards on every loop.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum LoopMode {
+    #[default]
+    Forever,
+    Once,
+    Repeat(u16),
+    Boomerang, // obamify then unobamify, seamlessly, from a single recording
+}
+
+impl LoopMode {
+    pub const ALL: [LoopMode; 4] = [
+        LoopMode::Forever,
+        LoopMode::Once,
+        LoopMode::Repeat(3),
+        LoopMode::Boomerang,
+    ];
+
+    pub fn label(self) -> String {
+        match self {
+            LoopMode::Forever => "loop".to_owned(),
+            LoopMode::Once => "play once".to_owned(),
+            LoopMode::Repeat(n) => format!("play {} times", n),
+            LoopMode::Boomerang => "boomerang".to_owned(),
+        }
+    }
+
+    pub fn is_boomerang(self) -> bool {
+        self == LoopMode::Boomerang
+    }
+
+    /// Whether `other` is the same mode, whatever its repeat count.
+    pub fn same_kind(self, other: LoopMode) -> bool {
+        std::mem::discriminant(&self) == std::mem::discriminant(&other)
+    }
+
+    /// How the mode is written in the config file.
+    pub fn toml(self) -> String {
+        match self {
+            LoopMode::Repeat(n) => format!("{{ Repeat = {} }}", n),
+            _ => format!("\"{:?}\"", self),
+        }
+    }
+
+    pub(crate) fn repeat(self) -> gif::Repeat {
+        match self {
+            LoopMode::Forever | LoopMode::Boomerang => gif::Repeat::Infinite,
+            LoopMode::Once => gif::Repeat::Finite(0),
+            LoopMode::Repeat(n) => gif::Repeat::Finite(n.saturating_sub(1)), // counts replays
+        }
+    }
+
+    /// The apng's play count, where 0 is forever.
+    fn plays(self) -> u32 {
+        match self {
+            LoopMode::Forever | LoopMode::Boomerang => 0,
+            LoopMode::Once => 1,
+            LoopMode::Repeat(n) => n.max(1) as u32,
+        }
+    }
+}
+
+/// The frames a boomerang plays backwards after `count` forwards ones, leaving out both
+/// ends so neither is shown twice in a row.
+fn boomerang_frames(count: usize) -> impl Iterator<Item = usize> {
+    (1..count.saturating_sub(1)).rev()
+}
+
+/// Palette indices, any local palette and the delay of a written gif frame.
+pub(crate) type WrittenFrame = (Vec<u8>, Option<Vec<u8>>, u16);
+
+/// Writes the way back of a boomerang recorded as `frames` into its gif.
+pub(crate) fn append_boomerang<W: std::io::Write>(
+    encoder: &mut gif::Encoder<W>,
+    frames: &[WrittenFrame],
+    (width, height): (u32, u32),
+    job: &JobProgress,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let back = boomerang_frames(frames.len()).collect::<Vec<_>>();
+    for (i, &frame) in back.iter().enumerate() {
+        job.step(i, back.len())?;
+        let (pixels, palette, delay) = &frames[frame];
+        encoder.write_frame(&gif::Frame {
+            width: width as u16,
+            height: height as u16,
+            buffer: Cow::Borrowed(pixels),
+            palette: palette.clone(),
+            delay: *delay,
+            ..Default::default()
+        })?;
+    }
+    Ok(())
+}
+
+/// Encodes written gif frames again as a gif played backwards, which is the opposite
+/// transformation.
+fn encode_backwards(
+    frames: Vec<WrittenFrame>,
+    (width, height): (u32, u32),
+    color_map: &[u8],
+    repeat: gif::Repeat,
+    job: &JobProgress,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let _span = tracing::info_span!("gif_backwards", frames = frames.len()).entered();
+    let mut encoder = gif::Encoder::new(vec![], width as u16, height as u16, color_map)?;
+    encoder.set_repeat(repeat)?;
+    let count = frames.len();
+    for (i, (pixels, local_palette, delay)) in frames.into_iter().rev().enumerate() {
+        job.step(i, count)?;
+        encoder.write_frame(&gif::Frame {
+            width: width as u16,
+            height: height as u16,
+            buffer: Cow::Owned(pixels),
+            palette: local_palette,
+            delay,
+            ..Default::default()
+        })?;
+    }
+    Ok(encoder.into_inner()?)
+}
+
+/// Writes `others` next to the file saved at `path`, named after themselves.
+#[cfg(not(target_arch = "wasm32"))]
+fn save_beside(path: PathBuf, others: &[(Vec<u8>, String)], ext: &str) -> GifStatus {
+    for (data, name) in others {
+        let other = path.with_file_name(format!("{}.{}", name, ext));
+        if let Err(err) = std::fs::write(&other, data) {
+            return GifStatus::Error(format!("failed to save {}: {}", other.display(), err));
+        }
+    }
+    GifStatus::Complete(path)
+}
+
+/// Encodes full color frames as an apng.
+fn encode_apng(
+    frames: &[&Vec<u8>],
+    (width, height): (u32, u32),
+    limits: &GifLimits,
+    job: &JobProgress,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let _span = tracing::info_span!("apng_encode", frames = frames.len()).entered();
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(frames.len() as u32, limits.loop_mode.plays())?;
+    let mut writer = encoder.write_header()?;
+    for (i, frame) in frames.iter().enumerate() {
+        job.step(i, frames.len())?;
+        let delay = rounded_step(limits.frame_ms(), i as u32);
+        writer.set_frame_delay(delay.min(u16::MAX as f64) as u16, 1000)?;
+        writer.write_image_data(frame)?;
+    }
+    writer.finish()?;
+    Ok(data)
+}
+
+/// Grades a read back still, burns in `overlay` and encodes it as a png, a band of rows
+/// at a time so it can be cancelled.
+fn encode_still(
+    mut rgba: Vec<u8>,
+    (width, height): (u32, u32),
+    lut: Option<&Lut>,
+    overlay: Option<GridOverlay>,
+    job: &JobProgress,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let _span = tracing::info_span!("still_encode", width, height).entered();
+    let band = (width as usize * 4 * (height as usize).div_ceil(STILL_BANDS)).max(4);
+    let bands = rgba.len().div_ceil(band);
+    if let Some(lut) = lut {
+        for (i, rows) in rgba.chunks_mut(band).enumerate() {
+            job.step(i, bands * 2)?;
+            lut.apply_rgba8(rows);
+        }
+    }
+    if let Some(overlay) = overlay {
+        overlay.draw_rgba(&mut rgba, (width, height), (0, 0), width);
+    }
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    let mut stream = writer.stream_writer()?;
+    for (i, rows) in rgba.chunks(band).enumerate() {
+        job.step(bands + i, bands * 2)?;
+        std::io::Write::write_all(&mut stream, rows)?;
+    }
+    stream.finish()?;
+    drop(writer);
+    Ok(data)
+}
+
+/// Quality given up step by step, so a recording fits its size limit as a whole instead
+/// of getting cut off: one frame kept in every so many, palette colors used, and the side
+/// of the blocks pixels are merged into.
+const FIT_STEPS: [(u32, usize, u32); 7] = [
+    (1, 256, 1),
+    (2, 256, 1),
+    (2, 64, 1),
+    (2, 64, 2),
+    (4, 64, 2),
+    (4, 16, 2),
+    (4, 16, 4),
+];
+/// Frames written at a step before their size is trusted to predict the rest.
+const FIT_WARMUP: u32 = 4;
+
+/// How far a recording has stepped down to fit, and the bytes it took since the last step.
+#[derive(Default)]
+struct Fit {
+    step: usize,
+    since_bytes: usize,
+    since_frames: u32,
+    remap: Option<Vec<u8>>, // global palette index to the nearest of the colors still used
+}
+
+impl Fit {
+    fn settings(&self) -> (u32, usize, u32) {
+        FIT_STEPS[self.step]
+    }
+
+    /// Takes the next step down if the gif, at `bytes` now with `frames_left` frames still
+    /// to capture, would end up over `budget` at this one. Returns whether it stepped.
+    fn after_frame(&mut self, bytes: usize, budget: usize, frames_left: u32) -> bool {
+        self.since_frames += 1;
+        if self.since_frames < FIT_WARMUP || self.step + 1 >= FIT_STEPS.len() {
+            return false;
+        }
+        let per_frame = bytes.saturating_sub(self.since_bytes) / self.since_frames as usize;
+        let written_left = frames_left.div_ceil(self.settings().0) as usize;
+        if bytes + per_frame * written_left <= budget {
+            return false;
+        }
+        self.step += 1;
+        self.since_bytes = bytes;
+        self.since_frames = 0;
+        true
+    }
+}
+
+/// Maps each entry of `palette` to the nearest of `colors` colors learned from it, so
+/// frames use fewer distinct indices and compress better. `None` keeps every color.
+fn reduced_palette(palette: &NeuQuant, colors: usize) -> Option<Vec<u8>> {
+    if colors >= 256 {
+        return None;
+    }
+    let full = palette.color_map_rgba();
+    let small = NeuQuant::new(1, colors, &full);
+    Some(
+        full.chunks_exact(4)
+            .map(|c| {
+                let near = small.lookup(small.index_of(c)).unwrap_or_default();
+                palette.index_of(&near) as u8
+            })
+            .collect(),
+    )
+}
+
+/// `square`, an rgba frame `side` pixels across, centered in a black frame of `size`.
+fn pad_rgba(square: &[u8], side: u32, (width, height): (u32, u32)) -> Vec<u8> {
+    let mut frame = [0, 0, 0, 255].repeat((width * height) as usize);
+    let (left, top) = ((width - side) / 2, (height - side) / 2);
+    let row = (side * 4) as usize;
+    for (y, src) in square.chunks_exact(row).enumerate() {
+        let start = ((top as usize + y) * width as usize + left as usize) * 4;
+        frame[start..start + row].copy_from_slice(src);
+    }
+    frame
+}
+
+/// Gives every `size` × `size` block of palette indices its top left one, halving (or
+/// more) the resolution without changing the frame's size.
+fn merge_blocks(pixels: &mut [u8], width: usize, size: usize) {
+    let width = width.max(1);
+    for i in 0..pixels.len() {
+        let (x, y) = (i % width, i / width);
+        pixels[i] = pixels[(y - y % size) * width + x - x % size];
+    }
+}
+
+/// Recording limits, configurable in the config file.
+#[derive(Clone, Copy, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct GifLimits {
+    pub framerate: u32,
+    pub resolution: u32,
+    pub min_frames: u32,
+    pub max_frames: u32,
+    pub max_size_mb: u32,
+    pub speed: f32,              // playback speed relative to the animation
+    pub capture_every: u32,      // keep every nth frame, for longer gifs at a lower framerate
+    pub color_space: ColorSpace, // of the rendered frames
+    pub loop_mode: LoopMode,
+    pub palette_quality: u32, // 1 is fastest to build, MAX_PALETTE_QUALITY the most faithful
+    pub max_seconds: f32,     // of playback; 0 stops on the frame limits instead
+    pub dither: bool,         // Floyd–Steinberg, trading the palette's banding for grain
+    pub local_palettes: bool, // each frame gets a palette of its own pixels
+    pub fit_size: bool,       // lower quality as needed to fit under max_size_mb
+    pub stop: StopRules,
+}
+
+impl Default for GifLimits {
+    fn default() -> Self {
+        Self {
+            framerate: GIF_FRAMERATE,
+            resolution: GIF_RESOLUTION,
+            min_frames: GIF_MIN_FRAMES,
+            max_frames: GIF_MAX_FRAMES,
+            max_size_mb: GIF_MAX_SIZE_MB,
+            speed: GIF_SPEED,
+            capture_every: GIF_CAPTURE_EVERY,
+            color_space: ColorSpace::Srgb,
+            loop_mode: LoopMode::Forever,
+            palette_quality: GIF_PALETTE_QUALITY,
+            max_seconds: 0.0,
+            dither: false,
+            local_palettes: false,
+            fit_size: false,
+            stop: StopRules::default(),
+        }
+    }
+}
+
+/// How a recording's stop conditions add up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize)]
+pub enum StopCombine {
+    #[default]
+    Any, // the first one met stops it
+    All, // it stops once they've all been met
+}
+
+/// What ends a recording without pressing stop. With none of them it goes on until it's
+/// stopped by hand. None stop it before `min_frames`, unless it's limited to `max_seconds`.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize)]
+#[serde(default)]
+pub struct StopRules {
+    pub max_frames: bool, // at max_frames, or max_seconds of playback if set
+    pub max_size: bool,   // once the next frame wouldn't fit in max_size_mb
+    pub arrived: bool,    // once every cell has reached its destination
+    pub combine: StopCombine,
+}
+
+impl Default for StopRules {
+    fn default() -> Self {
+        Self {
+            max_frames: true,
+            max_size: true,
+            arrived: false,
+            combine: StopCombine::Any,
+        }
+    }
+}
 
-pub const GIF_FRAMERATE: u32 = 8;
-pub const GIF_RESOLUTION: u32 = 400;
-pub const GIF_MAX_FRAMES: u32 = 140;
-pub const GIF_MIN_FRAMES: u32 = 100;
-pub const GIF_MAX_SIZE: usize = 45 * 1024 * 1024; // 45 MB
-pub const GIF_SPEED: f32 = 1.5;
-pub const GIF_PALETTE_SAMPLEFAC: i32 = 1;
+impl StopRules {
+    pub fn manual(&self) -> bool {
+        !(self.max_frames || self.max_size || self.arrived)
+    }
+
+    /// Whether the rules say to stop, given which conditions are met. `arrived` is only
+    /// asked when it matters, since it looks at every cell.
+    fn met(&self, frames: bool, size: bool, arrived: impl FnOnce() -> bool) -> bool {
+        if self.manual() {
+            return false;
+        }
+        let arrived = self.arrived && arrived();
+        let conditions = [
+            (self.max_frames, frames),
+            (self.max_size, size),
+            (self.arrived, arrived),
+        ];
+        match self.combine {
+            StopCombine::Any => conditions.iter().any(|&(on, met)| on && met),
+            StopCombine::All => conditions.iter().all(|&(on, met)| !on || met),
+        }
+    }
+
+    /// Whether a frame that doesn't fit in the size limit ends the recording without
+    /// being written, rather than going over the limit for the other conditions.
+    fn size_is_hard(&self) -> bool {
+        self.max_size && (self.combine == StopCombine::Any || !(self.max_frames || self.arrived))
+    }
+}
+
+impl GifLimits {
+    fn framerate(&self) -> f64 {
+        self.framerate.clamp(1, MAX_FRAMERATE) as f64
+    }
+
+    /// Simulation steps (at 60 per second) between recorded frames `frame` and `frame + 1`.
+    pub fn steps_after(&self, frame: u32) -> u32 {
+        let per_frame = 60.0 * self.capture_every.max(1) as f64 / self.framerate();
+        rounded_step(per_frame, frame) as u32
+    }
+
+    /// Delay of gif frame `frame` in hundredths of a second, longer when frames are skipped
+    /// so the animation still plays at the same speed. Gifs can't play faster than 50 fps,
+    /// so high speeds at high framerates play slower than the preview.
+    pub fn frame_delay(&self, frame: u32) -> u16 {
+        let per_frame = 100.0 * self.capture_every.max(1) as f64
+            / self.framerate()
+            / self.speed.max(0.01) as f64;
+        rounded_step(per_frame.max(MIN_FRAME_DELAY), frame) as u16
+    }
+
+    /// How long each frame is shown in a video, in milliseconds. Videos have no minimum
+    /// delay, so they always play at the preview's speed.
+    pub fn frame_ms(&self) -> f64 {
+        1000.0 * self.capture_every.max(1) as f64 / self.framerate() / self.speed.max(0.01) as f64
+    }
+
+    /// How many frames make `max_seconds` of playback, if recordings are limited to a
+    /// duration rather than a frame count.
+    pub fn duration_frames(&self) -> Option<u32> {
+        (self.max_seconds > 0.0)
+            .then(|| ((self.max_seconds as f64 * 1000.0 / self.frame_ms()).round() as u32).max(1))
+    }
+}
+
+/// Step `n` of a fractional step size, rounded off the running total rather than on its
+/// own, so rounding errors never add up over long recordings.
+fn rounded_step(step: f64, n: u32) -> f64 {
+    ((n + 1) as f64 * step).round() - (n as f64 * step).round()
+}
+
+/// What an export profile records to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum ExportFormat {
+    #[default]
+    Gif,
+    PngFrames, // native only; the web records a gif instead
+    Video,     // native only, as webm
+    Apng,
+    PngZip,      // numbered pngs in a zip, for video editors
+    SpriteSheet, // laid out as the recorder's sprite sheet settings say
+}
+
+impl ExportFormat {
+    pub fn label(self) -> &'static str {
+        match self {
+            ExportFormat::Gif => "gif",
+            ExportFormat::PngFrames => "png frames",
+            ExportFormat::Video => "video",
+            ExportFormat::Apng => "apng",
+            ExportFormat::PngZip => "png frames (zip)",
+            ExportFormat::SpriteSheet => "sprite sheet",
+        }
+    }
+}
+
+/// A named way to export a preset, saved with it, so each of its usual exports is a
+/// single click.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ExportProfile {
+    pub name: String,
+    pub format: ExportFormat,
+    pub resolution: u32,
+    pub framerate: u32,
+    pub max_size_mb: u32,
+    pub loop_mode: LoopMode,
+}
+
+impl Default for ExportProfile {
+    fn default() -> Self {
+        Self {
+            name: "new profile".to_owned(),
+            format: ExportFormat::Gif,
+            resolution: GIF_RESOLUTION,
+            framerate: GIF_FRAMERATE,
+            max_size_mb: GIF_MAX_SIZE_MB,
+            loop_mode: LoopMode::Forever,
+        }
+    }
+}
+
+impl ExportProfile {
+    /// The profiles a preset has until it's given its own.
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self {
+                name: "Discord GIF".to_owned(),
+                resolution: 320,
+                framerate: 15,
+                max_size_mb: 10,
+                ..Default::default()
+            },
+            Self {
+                name: "Twitter GIF".to_owned(),
+                resolution: 480,
+                framerate: 15,
+                max_size_mb: 15,
+                ..Default::default()
+            },
+            Self {
+                name: "HQ archive".to_owned(),
+                format: ExportFormat::PngFrames,
+                resolution: DEFAULT_RESOLUTION,
+                framerate: 30,
+                ..Default::default()
+            },
+        ]
+    }
+
+    /// `base` with this profile's settings, recording as much of the animation as `base`
+    /// would at its framerate.
+    pub fn limits(&self, base: GifLimits) -> GifLimits {
+        let frames = |n: u32| {
+            (n as u64 * self.framerate as u64 / base.framerate.max(1) as u64).max(1) as u32
+        };
+        GifLimits {
+            framerate: self.framerate,
+            resolution: self.resolution.clamp(1, DEFAULT_RESOLUTION),
+            min_frames: frames(base.min_frames),
+            max_frames: frames(base.max_frames),
+            max_size_mb: self.max_size_mb,
+            loop_mode: self.loop_mode,
+            ..base
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub enum GifStatus {
     None,
     Recording,
+    Paused,    // recording, but not capturing until resumed
+    Exporting, // encoding the finished recording in the background
     #[cfg(not(target_arch = "wasm32"))]
     Complete(PathBuf),
     #[cfg(target_arch = "wasm32")]
     Complete,
+    #[cfg(target_arch = "wasm32")]
+    Saving, // the browser's save dialogs are open
+    Unsaved, // finished, but the save dialog was cancelled
+    PickTake,
     Error(String),
 }
 impl GifStatus {
@@ -35,113 +716,773 @@ impl GifStatus {
     }
 }
 
+/// A finished recording kept in memory until the user picks one to save.
+pub struct Take {
+    pub data: Vec<u8>,
+    pub preview: egui::ColorImage,
+    pub preview_texture: Option<egui::TextureHandle>,
+}
+
+/// Solver previews collected while a calculation runs, so the convergence can be
+/// exported as a gif afterwards.
+#[derive(Default)]
+pub struct Timelapse {
+    width: u32,
+    height: u32,
+    frames: Vec<Vec<u8>>,
+    stride: usize, // keep every nth preview
+    seen: usize,
+}
+
+impl Timelapse {
+    pub fn push(&mut self, width: u32, height: u32, data: &[u8]) {
+        if (width, height) != (self.width, self.height) {
+            *self = Self {
+                width,
+                height,
+                ..Default::default()
+            };
+        }
+        let stride = self.stride.max(1);
+        self.seen += 1;
+        if (self.seen - 1) % stride != 0 {
+            return;
+        }
+        self.frames.push(data.to_vec());
+
+        // thin out evenly instead of truncating, so long runs still show the whole solve
+        if self.frames.len() > TIMELAPSE_MAX_FRAMES {
+            let mut i = 0;
+            self.frames.retain(|_| {
+                i += 1;
+                i % 2 == 1
+            });
+            self.stride = stride * 2;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let _span = tracing::info_span!("timelapse_encode", frames = self.frames.len()).entered();
+        let scale = TIMELAPSE_MIN_SIZE.div_ceil(self.width.max(1)).max(1);
+        let (width, height) = (self.width * scale, self.height * scale);
+        let mut encoder = gif::Encoder::new(vec![], width as u16, height as u16, &[])?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        for (i, frame) in self.frames.iter().enumerate() {
+            let upscaled = image::imageops::resize(
+                &image::RgbImage::from_raw(self.width, self.height, frame.clone())
+                    .ok_or("invalid timelapse frame")?,
+                width,
+                height,
+                image::imageops::FilterType::Nearest,
+            );
+            let mut gif_frame =
+                gif::Frame::from_rgb_speed(width as u16, height as u16, &upscaled, 10);
+            gif_frame.delay = if i + 1 == self.frames.len() {
+                TIMELAPSE_END_DELAY
+            } else {
+                TIMELAPSE_FRAME_DELAY
+            };
+            encoder.write_frame(&gif_frame)?;
+        }
+        Ok(encoder.into_inner()?)
+    }
+}
+
+/// Readbacks of recorded frames that may be mapping at once, so capturing keeps pace
+/// with rendering instead of waiting for each one before the animation moves on.
+const READBACK_RING: usize = 3;
+
 struct InFlight {
     buffer: wgpu::Buffer,
     ready: Arc<AtomicBool>,
+    id: u32,               // of the recording it was read back for
+    size: (u32, u32),      // of the frame it holds
+    arrived: Option<bool>, // the animation was done in it, if that was asked of it
+    indexed: bool,         // holds palette indices, a byte per pixel, instead of rgba
+}
+
+impl InFlight {
+    fn is_ready(&self) -> bool {
+        self.ready.load(std::sync::atomic::Ordering::Acquire)
+    }
+
+    /// Copies the mapped frame into `rgba` without the rows' padding, and unmaps it.
+    /// Indexed frames have no padding, and are copied as they are.
+    fn read_into(&self, rgba: &mut Vec<u8>) {
+        let (width, height) = self.size;
+        let mapped = self.buffer.slice(..).get_mapped_range();
+        if self.indexed {
+            rgba.clear();
+            rgba.extend_from_slice(&mapped[..(width * height) as usize]);
+            drop(mapped);
+            self.buffer.unmap();
+            return;
+        }
+        let row = (width * 4) as usize; // RGBA8
+        let padded_row = padded_bytes_per_row(width) as usize;
+        rgba.resize(row * height as usize, 0);
+        for (y, dst) in rgba.chunks_exact_mut(row).enumerate() {
+            dst.copy_from_slice(&mapped[y * padded_row..y * padded_row + row]);
+        }
+        drop(mapped);
+        self.buffer.unmap();
+    }
+}
+
+/// Bytes per row of a readback `width` pixels wide, padded as copies need.
+fn padded_bytes_per_row(width: u32) -> u32 {
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT; // 256
+    (width * 4).div_ceil(align) * align
 }
 
 pub struct GifRecorder {
     pub id: u32,
+    pub limits: GifLimits,
     pub status: GifStatus,
-    pub encoder: Option<gif::Encoder<Vec<u8>>>,
+    pub encoder: Option<gif::Encoder<GifSink>>,
     pub palette: Option<NeuQuant>,
     pub frame_count: u32,
-    inflight: Option<InFlight>,
-    should_stop: bool,
+    inflight: VecDeque<InFlight>,     // oldest first
+    spare_buffers: Vec<wgpu::Buffer>, // staging buffers frames were read from, to reuse
+    pub requested: u32,               // frames asked for in this take
+    frame_arrived: Option<bool>,      // whether the last written frame was the end, if known
+    gpu_indices: Option<Vec<u8>>,     // the last read back frame's palette indices, if indexed
+    over_size: bool,                  // a frame didn't fit in max_size_mb
     rgba_buffer: Vec<u8>,
     quantized_buffer: Vec<u8>,
+    pub capture_region: Option<egui::Rect>, // part of the animation to record, in 0..1 coords
+    pub shape: FrameShape,
+    pub shape_scale: f32,     // of the preview's pixels, for shaped frames
+    pub viewport: (u32, u32), // the preview's size in pixels, followed between recordings
+    pub takes: Vec<Take>,
+    takes_planned: u32,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub png_dir: Option<PathBuf>, // export an rgba png sequence here instead of a gif
+    #[cfg(not(target_arch = "wasm32"))]
+    pub record_video: bool, // record a webm instead of a gif
+    #[cfg(not(target_arch = "wasm32"))]
+    video: Option<VideoEncoder>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub audio: Option<OpusAudio>, // music for videos, kept between recordings
+    pub apng: Option<Vec<Vec<u8>>>, // rgba frames, while recording an apng instead of a gif
+    pub png_zip: Option<ZipWriter>, // a png of each frame, while recording those instead
+    pub sprite_sheet: SpriteSheet,
+    pub sprite_frames: Option<Vec<Vec<u8>>>, // rgba frames, while recording a sprite sheet
+    pub output: Output,
+    take_preview: Option<egui::ColorImage>,
+    pub timelapse: Option<Timelapse>, // collected during the next or last calculation
+    pub show_quantized: bool,
+    pub quantized_frame: Option<egui::ColorImage>, // the last frame as written, if shown
+    pub both_directions: bool,                     // single gifs also get saved played backwards
+    written_frames: Vec<WrittenFrame>,             // kept for the backwards gif
+    fit: Fit,
+    stopped_early: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub window: WindowCapture,
+    pub start_delay: f32, // seconds between asking for a recording and it starting
+    starts_at: Option<f64>, // egui time a delayed recording starts at
+    pub lut: Option<Arc<Lut>>, // grading applied to exported frames
+    pub lut_preview: bool, // also grade the preview, so it shows what gets exported
+    frames_graded: bool,  // captured frames come from a graded preview already
+    pub overlay: GridOverlay,
+    pub snapshot_wanted: bool, // a still was asked for, read back once at full detail
+    snapshot: Option<(InFlight, bool)>, // the still, and whether the preview graded it
+    still: Option<ExportJob>,  // encoding the still once it's read back
+    job: Option<ExportJob>,    // encoding the finished recording
+    pub exported: &'static str, // what was saved last, for telling the user
+    finished: Option<(Files, &'static str)>, // and their extension, to save again or copy
+    #[cfg(not(target_arch = "wasm32"))]
+    clipboard: GifClipboard,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub copied: bool, // the finished gif is on the clipboard
+    #[cfg(not(target_arch = "wasm32"))]
+    pub stream: bool, // choose where gifs go first and write them there while recording
+    #[cfg(not(target_arch = "wasm32"))]
+    pub stream_to: Option<PathBuf>, // where the next gif gets written while it's recorded
+    #[cfg(not(target_arch = "wasm32"))]
+    streamed: Option<PathBuf>, // where the current one is being written, if it is
+    #[cfg(not(target_arch = "wasm32"))]
+    pub quantizer: Option<gpu_quantize::GpuQuantizer>, // indexes gif frames, if the GPU can
+    #[cfg(target_arch = "wasm32")]
+    saving: Option<std::sync::mpsc::Receiver<Result<bool, String>>>, // whether any got saved
+}
+
+/// Recording the whole window, interface and all, for tutorials, instead of just the
+/// animation. Frames are screenshots egui hands back the frame after they're asked for;
+/// the gif's size and palette come from the first one.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+pub struct WindowCapture {
+    pub enabled: bool,
+    pub requested: bool, // a screenshot is on its way
+    frame: Option<Arc<egui::ColorImage>>,
+    size: Option<(u32, u32)>, // of the recorded frames, once the first one is in
 }
 
 impl GifRecorder {
     pub fn new() -> Self {
         Self {
             id: 0,
+            limits: GifLimits::default(),
             status: GifStatus::None,
             encoder: None,
             palette: None,
             frame_count: 0,
-            inflight: None,
-            should_stop: false,
+            inflight: VecDeque::new(),
+            spare_buffers: Vec::new(),
+            requested: 0,
+            frame_arrived: None,
+            gpu_indices: None,
+            over_size: false,
             rgba_buffer: Vec::new(),
             quantized_buffer: Vec::new(),
+            capture_region: None,
+            shape: FrameShape::Square,
+            shape_scale: 1.0,
+            viewport: (0, 0),
+            takes: Vec::new(),
+            takes_planned: 1,
+            #[cfg(not(target_arch = "wasm32"))]
+            png_dir: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            record_video: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            video: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            audio: None,
+            apng: None,
+            png_zip: None,
+            sprite_sheet: SpriteSheet::default(),
+            sprite_frames: None,
+            output: Output::default(),
+            take_preview: None,
+            timelapse: None,
+            show_quantized: false,
+            quantized_frame: None,
+            both_directions: false,
+            written_frames: Vec::new(),
+            fit: Fit::default(),
+            stopped_early: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            window: WindowCapture::default(),
+            start_delay: 0.0,
+            starts_at: None,
+            lut: None,
+            lut_preview: false,
+            frames_graded: false,
+            overlay: GridOverlay::default(),
+            snapshot_wanted: false,
+            snapshot: None,
+            still: None,
+            job: None,
+            exported: "gif",
+            finished: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            clipboard: GifClipboard::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            copied: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            stream: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            stream_to: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            streamed: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            quantizer: None,
+            #[cfg(target_arch = "wasm32")]
+            saving: None,
+        }
+    }
+
+    /// Holds off the recording asked for at `now` if there's a start delay, returning
+    /// whether it's waiting. Recordings always begin at the start of the animation; the
+    /// delay is for getting the rest of the window ready.
+    pub fn delay_start(&mut self, now: f64) -> bool {
+        if self.start_delay <= 0.0 {
+            return false;
+        }
+        self.starts_at = Some(now + self.start_delay as f64);
+        self.status = GifStatus::Recording;
+        true
+    }
+
+    /// Seconds until a delayed recording starts.
+    pub fn seconds_until_start(&self, now: f64) -> Option<f64> {
+        self.starts_at.map(|at| (at - now).max(0.0))
+    }
+
+    /// Whether a delayed recording is due to start now. Only says so once.
+    pub fn start_due(&mut self, now: f64) -> bool {
+        if self.starts_at.is_some_and(|at| now >= at) {
+            self.starts_at = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Prepares to record `count` takes in a row (1 records and saves a single gif).
+    pub fn start_takes(&mut self, count: u32) {
+        self.takes.clear();
+        self.takes_planned = count.clamp(1, MAX_TAKES);
+    }
+
+    pub fn is_multi_take(&self) -> bool {
+        self.takes_planned > 1
+    }
+
+    pub fn takes_remaining(&self) -> u32 {
+        self.takes_planned.saturating_sub(self.takes.len() as u32)
+    }
+
+    /// Keeps the current recording as a take instead of saving it.
+    pub fn finish_take(&mut self) {
+        if let Err(err) = self.append_boomerang() {
+            self.status = GifStatus::Error(err.to_string());
+            return;
+        }
+        let data = self
+            .encoder
+            .take()
+            .map(|e| e.into_inner().and_then(GifSink::finish));
+        let Some(Ok(Some(data))) = data else {
+            self.status = GifStatus::Error("failed to finish take".to_owned());
+            return;
+        };
+        let (width, height) = self.frame_size();
+        let preview = self.take_preview.take().unwrap_or_else(|| {
+            egui::ColorImage::filled([width as usize, height as usize], egui::Color32::BLACK)
+        });
+        self.takes.push(Take {
+            data,
+            preview,
+            preview_texture: None,
+        });
+        self.over_size = false;
+    }
+
+    pub fn save_take(&mut self, index: usize, name: String) -> bool {
+        let data = self.takes[index].data.clone();
+        self.save(
+            vec![(data, format!("{}_take{}", name, index + 1))],
+            "gif",
+            "gif",
+        )
+    }
+
+    /// Saves the collected solver timelapse. Returns false if the user cancelled.
+    pub fn save_timelapse(&mut self, name: String) -> bool {
+        let Some(timelapse) = self.timelapse.take() else {
+            return false;
+        };
+        match timelapse.encode() {
+            Ok(data) => {
+                let saved = self.save(
+                    vec![(data, format!("{}_timelapse", name))],
+                    "gif",
+                    "timelapse",
+                );
+                if !saved {
+                    self.timelapse = Some(timelapse);
+                }
+                saved
+            }
+            Err(err) => {
+                self.status = GifStatus::Error(err.to_string());
+                true
+            }
+        }
+    }
+
+    /// Side length the animation is rendered at while recording, chosen so the capture
+    /// region comes out at the configured resolution.
+    pub fn render_size(&self) -> u32 {
+        if let Some((width, height)) = self.shaped_size() {
+            return match self.shape {
+                FrameShape::Fill => width.max(height),
+                _ => width.min(height),
+            };
+        }
+        match self.capture_region {
+            None => self.limits.resolution,
+            Some(region) => {
+                let side = region.width().max(region.height()).max(0.01);
+                ((self.limits.resolution as f32 / side).round() as u32).min(DEFAULT_RESOLUTION)
+            }
+        }
+    }
+
+    /// Size of the recorded frames.
+    pub fn frame_size(&self) -> (u32, u32) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(size) = self.window.size {
+            return size;
+        }
+        if let Some(size) = self.shaped_size() {
+            return size;
+        }
+        match self.capture_region {
+            None => (self.limits.resolution, self.limits.resolution),
+            Some(region) => {
+                let size = self.render_size();
+                let scale = |v: f32| ((v * size as f32).round() as u32).clamp(1, size);
+                (scale(region.width()), scale(region.height()))
+            }
+        }
+    }
+
+    /// The frame's size when it takes the preview's shape: the preview's pixels scaled by
+    /// `shape_scale`, and shrunk if the animation would render bigger than it can.
+    fn shaped_size(&self) -> Option<(u32, u32)> {
+        if self.shape == FrameShape::Square || self.capture_region.is_some() {
+            return None; // a region has a shape of its own
+        }
+        let (width, height) = self.viewport;
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let scale = self.shape_scale.max(0.01);
+        let (width, height) = (width as f32 * scale, height as f32 * scale);
+        let side = match self.shape {
+            FrameShape::Fill => width.max(height),
+            _ => width.min(height),
+        };
+        let shrink = (DEFAULT_RESOLUTION as f32 / side).min(1.0);
+        let scaled = |v: f32| ((v * shrink).round() as u32).max(1);
+        Some((scaled(width), scaled(height)))
+    }
+
+    /// Size of the part of the rendered animation read back for each frame, which padded
+    /// frames only get their bars around afterwards.
+    fn readback_size(&self) -> (u32, u32) {
+        match self.shape {
+            FrameShape::Pad if self.shaped_size().is_some() => {
+                let side = self.render_size();
+                (side, side)
+            }
+            _ => self.frame_size(),
+        }
+    }
+
+    fn capture_origin(&self) -> (u32, u32) {
+        if self.shape == FrameShape::Fill {
+            if let Some((w, h)) = self.shaped_size() {
+                let side = self.render_size();
+                return ((side - w) / 2, (side - h) / 2);
+            }
+        }
+        match self.capture_region {
+            None => (0, 0),
+            Some(region) => {
+                let size = self.render_size();
+                let (w, h) = self.frame_size();
+                let scale = |v: f32| (v * size as f32).floor() as u32;
+                (
+                    scale(region.min.x).min(size - w),
+                    scale(region.min.y).min(size - h),
+                )
+            }
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.status.is_recording()
+    }
+
+    pub fn not_recording(&self) -> bool {
+        self.status.not_recording()
+    }
+
+    /// Takes the oldest frame being read back, once it's mapped; they come in the order
+    /// they were asked for.
+    fn poll_inflight(&mut self) -> Result<bool, Box<dyn std::error::Error>> {
+        if !self.inflight.front().is_some_and(InFlight::is_ready) {
+            return Ok(false);
+        }
+        let Some(inflight) = self.inflight.pop_front() else {
+            return Ok(false);
+        };
+        // indices are of whole frames, indexed as read back or once prepared here
+        let size = match inflight.indexed {
+            true => self.frame_size(),
+            false => self.readback_size(),
+        };
+        if (inflight.id, inflight.size) != (self.id, size) {
+            // the frame belongs to settings or a preset that's since changed
+            self.inflight.clear();
+            return Err("the recording was stopped because its preset or size changed".into());
+        }
+        let _span = tracing::info_span!("gpu_readback").entered();
+        self.frame_arrived = inflight.arrived;
+        if inflight.indexed {
+            let mut indices = std::mem::take(&mut self.quantized_buffer);
+            inflight.read_into(&mut indices);
+            self.gpu_indices = Some(indices);
+        } else {
+            self.gpu_indices = None;
+            inflight.read_into(&mut self.rgba_buffer);
+        }
+        if self.spare_buffers.len() < READBACK_RING {
+            self.spare_buffers.push(inflight.buffer);
+        }
+        if self.gpu_indices.is_some() {
+            return Ok(true); // nothing is drawn over or graded in frames indexed there
+        }
+        self.limits.color_space.to_srgb(&mut self.rgba_buffer);
+        self.grade_frame();
+        let (size, origin) = (self.readback_size(), self.capture_origin());
+        let side = self.render_size();
+        if self.overlay.enabled {
+            self.overlay
+                .draw_rgba(&mut self.rgba_buffer, size, origin, side);
+        }
+        if size != self.frame_size() {
+            self.rgba_buffer = pad_rgba(&self.rgba_buffer, side, self.frame_size());
+        }
+        Ok(true)
+    }
+
+    /// Hands over a screenshot of the window, to be recorded if window capture is on.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn receive_window_frame(&mut self, image: Arc<egui::ColorImage>) {
+        if self.window.requested {
+            self.window.requested = false;
+            self.window.frame = Some(image);
+        }
+    }
+
+    /// Scales a received screenshot into the rgba buffer, setting up the encoder on the
+    /// first one, since only then is the window's size and look known.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn poll_window(&mut self) -> Result<bool, Box<dyn std::error::Error>> {
+        let Some(image) = self.window.frame.take() else {
+            return Ok(false);
+        };
+        let [w, h] = image.size.map(|v| v.max(1) as u32);
+        self.frame_arrived = None; // asked of the animation as it is, screenshots being current
+        self.gpu_indices = None;
+        let first = self.window.size.is_none();
+        let (width, height) = *self.window.size.get_or_insert_with(|| {
+            let scale = (self.limits.resolution as f32 / w.max(h) as f32).min(1.0);
+            let scaled = |v: u32| ((v as f32 * scale).round() as u32).max(1);
+            (scaled(w), scaled(h))
+        });
+        let rgba = image::RgbaImage::from_raw(w, h, image.as_raw().to_vec())
+            .ok_or("invalid window screenshot")?;
+        let rgba = if (width, height) == (w, h) {
+            rgba
+        } else {
+            image::imageops::resize(&rgba, width, height, image::imageops::FilterType::Triangle)
+        };
+        self.rgba_buffer = rgba.into_raw();
+        self.grade_frame();
+        if first && self.record_video {
+            self.start_video()?;
+        } else if first && self.apng.is_none() {
+            let colors = self
+                .rgba_buffer
+                .chunks_exact(4)
+                .map(|c| SeedColor {
+                    rgba: [c[0], c[1], c[2], 255].map(|v| v as f32 / 255.0),
+                })
+                .collect::<Vec<_>>();
+            // screenshots are sRGB whatever the renderer uses
+            let palette = build_palette(&colors, ColorSpace::Srgb, self.limits.palette_quality);
+            self.start_encoder(palette)?;
+        }
+        Ok(true)
+    }
+
+    fn grade_frame(&mut self) {
+        if let Some(lut) = self.lut.as_ref().filter(|_| !self.frames_graded) {
+            let _span = tracing::info_span!("lut_grade").entered();
+            lut.apply_rgba8(&mut self.rgba_buffer);
+        }
+    }
+
+    pub fn try_write_frame(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        #[cfg(not(target_arch = "wasm32"))]
+        let polled = self.poll_inflight()? || self.poll_window()?;
+        #[cfg(target_arch = "wasm32")]
+        let polled = self.poll_inflight()?;
+        if !polled {
+            return Ok(false);
+        }
+
+        let _span = tracing::info_span!("gif_encode_frame", frame = self.frame_count).entered();
+        let (width, height) = self.frame_size();
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(dir) = &self.png_dir {
+            image::save_buffer(
+                dir.join(format!("frame_{:04}.png", self.frame_count)),
+                &self.rgba_buffer,
+                width,
+                height,
+                image::ExtendedColorType::Rgba8,
+            )?;
+            return Ok(true);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(video) = &mut self.video {
+            video.write_frame(&self.rgba_buffer)?;
+            return Ok(true);
+        }
+        if let Some(frames) = self.apng.as_mut().or(self.sprite_frames.as_mut()) {
+            frames.push(self.rgba_buffer.clone());
+            return Ok(true);
+        }
+        if let Some(zip) = &mut self.png_zip {
+            let mut png = Vec::new();
+            image::ImageEncoder::write_image(
+                image::codecs::png::PngEncoder::new(&mut png),
+                &self.rgba_buffer,
+                width,
+                height,
+                image::ExtendedColorType::Rgba8,
+            )?;
+            zip.add(&format!("frame_{:04}.png", self.frame_count), &png)?;
+            return Ok(true);
         }
-    }
-
-    pub fn is_recording(&self) -> bool {
-        self.status.is_recording()
-    }
 
-    pub fn not_recording(&self) -> bool {
-        self.status.not_recording()
-    }
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.gpu_indices.is_none() && self.index_prepared(device, queue) {
+            return Ok(false); // written once its indices are back
+        }
+        #[cfg(target_arch = "wasm32")]
+        let _ = (device, queue);
 
-    fn poll_inflight(&mut self) -> bool {
-        if let Some(inflight) = &self.inflight {
-            if inflight.ready.load(std::sync::atomic::Ordering::Acquire) {
-                let slice = inflight.buffer.slice(..);
-                let mapped = slice.get_mapped_range();
-                // Remove row padding
-                let width = GIF_RESOLUTION;
-                let height = GIF_RESOLUTION;
-                let bpp = 4u32; // RGBA8
-                let unpadded_bytes_per_row = width * bpp;
-                let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT; // 256
-                let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(align) * align;
-
-                let total_bytes = (width * height * bpp) as usize;
-                self.rgba_buffer.resize(total_bytes, 0);
-                for y in 0..height as usize {
-                    let start = y * padded_bytes_per_row as usize;
-                    let end = start + unpadded_bytes_per_row as usize;
-                    let dst_start = y * unpadded_bytes_per_row as usize;
-                    let dst_end = dst_start + unpadded_bytes_per_row as usize;
-                    self.rgba_buffer[dst_start..dst_end].copy_from_slice(&mapped[start..end]);
+        let Some(encoder) = &mut self.encoder else {
+            return Err("No encoder".into());
+        };
+        let (keep_every, colors, block) = self.fit.settings();
+        if self.frame_count % keep_every != 0 {
+            return Ok(true); // shown by the frame before, for longer
+        }
+        let pixel_count = (width * height) as usize;
+        // a palette learned from the frame itself, for targets with more colors than the
+        // seeds' palette covers; it's heavier to build, and adds 768 bytes to each frame
+        let local = self.limits.local_palettes.then(|| {
+            let _span = tracing::info_span!("local_palette").entered();
+            let samplefac = palette_samplefac(pixel_count, self.limits.palette_quality);
+            NeuQuant::new(samplefac, colors, &self.rgba_buffer)
+        });
+        let nq = local.as_ref().or(self.palette.as_ref()).unwrap();
+        let indexed = self.gpu_indices.is_some(); // and rgba_buffer holds an older frame
+        let mut pixels = match self.gpu_indices.take() {
+            Some(indices) => indices,
+            None => {
+                let mut pixels = std::mem::take(&mut self.quantized_buffer);
+                pixels.resize(pixel_count, 0);
+                if self.limits.dither {
+                    dither_indices(nq, &self.rgba_buffer, width as usize, &mut pixels);
+                } else {
+                    for (dst, chunk) in pixels.iter_mut().zip(self.rgba_buffer.chunks_exact(4)) {
+                        *dst = nq.index_of(chunk) as u8;
+                    }
                 }
-                drop(mapped);
-                inflight.buffer.unmap();
-                self.inflight = None;
-                true
-            } else {
-                false
+                pixels
+            }
+        };
+        if let Some(remap) = self.fit.remap.as_ref().filter(|_| local.is_none()) {
+            for index in &mut pixels {
+                *index = remap[*index as usize];
             }
-        } else {
-            false
         }
-    }
-
-    pub fn try_write_frame(&mut self) -> Result<bool, Box<dyn std::error::Error>> {
-        if !self.poll_inflight() {
-            return Ok(false);
+        if block > 1 {
+            merge_blocks(&mut pixels, width as usize, block as usize);
         }
 
-        let Some(encoder) = &mut self.encoder else {
-            return Err("No encoder".into());
+        let frame = gif::Frame {
+            width: width as u16,
+            height: height as u16,
+            buffer: Cow::Owned(pixels),
+            delay: (0..keep_every)
+                .map(|k| self.limits.frame_delay(self.frame_count + k) as u32)
+                .sum::<u32>()
+                .min(u16::MAX as u32) as u16,
+            palette: local.as_ref().map(|nq| nq.color_map_rgb()),
+            ..Default::default()
+        };
+
+        let frame_size = encoder.get_ref().written()
+            + frame.buffer.len()
+            + frame.palette.as_ref().map_or(0, |p| p.len())
+            + 32; // conservative estimate
+        // a boomerang writes every frame but its ends twice
+        let budget = if self.limits.loop_mode.is_boomerang() {
+            self.limits.max_size_mb as usize * 512 * 1024
+        } else {
+            self.limits.max_size_mb as usize * 1024 * 1024
         };
-        let nq = self.palette.as_ref().unwrap();
-        let pixel_count = (GIF_RESOLUTION * GIF_RESOLUTION) as usize;
-        let mut pixels = std::mem::take(&mut self.quantized_buffer);
-        pixels.resize(pixel_count, 0);
-        for (dst, chunk) in pixels.iter_mut().zip(self.rgba_buffer.chunks_exact(4)) {
-            *dst = nq.index_of(chunk) as u8;
-        }
-
-        let mut frame = gif::Frame::default();
-        frame.width = GIF_RESOLUTION as u16;
-        frame.height = GIF_RESOLUTION as u16;
-        frame.buffer = Cow::Owned(pixels);
-        frame.delay = ((100.0 / GIF_FRAMERATE as f32) / GIF_SPEED) as u16; // delay in 1/100 sec
-
-        let frame_size = encoder.get_ref().len() + frame.buffer.len() + 32; // conservative estimate
-        if frame_size > GIF_MAX_SIZE {
+        self.over_size |= frame_size > budget;
+        if self.over_size && self.limits.stop.size_is_hard() {
             self.quantized_buffer = match frame.buffer {
                 Cow::Owned(buf) => buf,
                 Cow::Borrowed(_) => Vec::new(),
             };
-            self.should_stop = true;
             return Ok(true);
         }
 
         encoder.write_frame(&frame)?;
+        if self.limits.fit_size {
+            let total = self
+                .limits
+                .duration_frames()
+                .unwrap_or(self.limits.max_frames.max(1));
+            let frames_left = total.saturating_sub(self.frame_count + 1);
+            if self
+                .fit
+                .after_frame(encoder.get_ref().written(), budget, frames_left)
+            {
+                let (_, colors, _) = self.fit.settings();
+                tracing::info!("gif stepping down to fit: {:?}", self.fit.settings());
+                self.fit.remap = self
+                    .palette
+                    .as_ref()
+                    .and_then(|palette| reduced_palette(palette, colors));
+            }
+        }
+        if self.keeps_frames() {
+            self.written_frames
+                .push((frame.buffer.to_vec(), frame.palette.clone(), frame.delay));
+        }
+        let as_written = || {
+            let palette = nq.color_map_rgb();
+            let pixels = frame
+                .buffer
+                .iter()
+                .map(|&i| {
+                    let c = &palette[i as usize * 3..i as usize * 3 + 3];
+                    egui::Color32::from_rgb(c[0], c[1], c[2])
+                })
+                .collect();
+            egui::ColorImage {
+                size: [width as usize, height as usize],
+                pixels,
+                source_size: egui::vec2(width as f32, height as f32),
+            }
+        };
+        if self.show_quantized {
+            self.quantized_frame = Some(as_written());
+        }
+        if self.takes_planned > 1 && self.frame_count == TAKE_PREVIEW_FRAME {
+            self.take_preview = Some(if indexed {
+                as_written()
+            } else {
+                egui::ColorImage::from_rgba_unmultiplied(
+                    [width as usize, height as usize],
+                    &self.rgba_buffer,
+                )
+            });
+        }
         self.quantized_buffer = match frame.buffer {
             Cow::Owned(buf) => buf,
             Cow::Borrowed(_) => Vec::new(),
@@ -150,101 +1491,658 @@ impl GifRecorder {
         Ok(true)
     }
 
+    /// Gets ready to record. `preview_graded` says whether the preview, and so
+    /// `active_colors` and the captured frames, already have the LUT's grading.
     pub fn init_encoder(
         &mut self,
         active_colors: &[SeedColor],
+        preview_graded: bool,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let colors = active_colors
+        self.frames_graded = preview_graded;
+        self.written_frames.clear();
+        self.fit = Fit::default();
+        self.frame_count = 0;
+        self.requested = 0;
+        self.inflight.clear(); // frames read back past the end of the last take
+        self.frame_arrived = None;
+        self.gpu_indices = None;
+        self.stopped_early = false;
+        self.status = GifStatus::Recording;
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.window.enabled {
+            self.window.size = None;
+            return Ok(()); // started on the first screenshot
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.record_video {
+            return self.start_video();
+        }
+        if let Some(frames) = &mut self.apng {
+            frames.clear();
+            return Ok(());
+        }
+        // learn the palette from the colors as they'll be captured: sRGB, graded and
+        // with the grid drawn over them
+        let space = self.limits.color_space;
+        let lut = self.lut.as_ref().filter(|_| !preview_graded);
+        let mut colors = active_colors
             .iter()
-            .flat_map(|s| {
-                s.rgba
-                    .map(|f| (if f == 1.0 { 255.0 } else { f * 256.0 }) as u8)
+            .map(|c| {
+                let [r, g, b, a] = c.rgba;
+                let mut rgb = [r, g, b].map(|v| space.encode(v) as f32 / 255.0);
+                if let Some(lut) = lut {
+                    rgb = lut.apply(rgb);
+                }
+                SeedColor {
+                    rgba: [rgb[0], rgb[1], rgb[2], a],
+                }
             })
-            .collect::<Vec<u8>>();
-        let gif_palette = NeuQuant::new(GIF_PALETTE_SAMPLEFAC, 256, &colors);
-        let mut encoder = gif::Encoder::new(
-            vec![],
-            GIF_RESOLUTION as u16,
-            GIF_RESOLUTION as u16,
-            &gif_palette.color_map_rgb(),
-        )?;
-        self.palette = Some(gif_palette);
-        encoder.set_repeat(gif::Repeat::Infinite)?;
+            .collect::<Vec<_>>();
+        if self.overlay.enabled {
+            // enough copies that the lines get an entry of their own
+            let [r, g, b, _] = self.overlay.color.to_srgba_unmultiplied();
+            let line = SeedColor {
+                rgba: [r, g, b, 255].map(|v| v as f32 / 255.0),
+            };
+            colors.extend(std::iter::repeat_n(line, colors.len() / 64 + 1));
+        }
+        if self.readback_size() != self.frame_size() {
+            // and the bars around padded frames
+            let bar = SeedColor {
+                rgba: [0.0, 0.0, 0.0, 1.0],
+            };
+            colors.extend(std::iter::repeat_n(bar, colors.len() / 64 + 1));
+        }
+        let gif_palette = build_palette(&colors, ColorSpace::Srgb, self.limits.palette_quality);
+        self.start_encoder(gif_palette)
+    }
+
+    fn start_encoder(&mut self, palette: NeuQuant) -> Result<(), Box<dyn std::error::Error>> {
+        let (width, height) = self.frame_size();
+        self.encoder = None; // a file left from the last take goes before one replaces it
+        let sink = GifSink::Memory(vec![]);
+        #[cfg(not(target_arch = "wasm32"))]
+        let sink = match self.stream_to.clone().filter(|_| !self.is_multi_take()) {
+            Some(path) => {
+                let file = PartFile::create(&path)
+                    .map_err(|e| format!("can't write {}: {}", path.display(), e))?;
+                self.streamed = Some(path);
+                GifSink::File(file)
+            }
+            None => sink,
+        };
+        let mut encoder =
+            gif::Encoder::new(sink, width as u16, height as u16, &palette.color_map_rgb())?;
+        self.palette = Some(palette);
+        encoder.set_repeat(self.limits.loop_mode.repeat())?;
         self.encoder = Some(encoder);
-        self.frame_count = 0;
-        self.status = GifStatus::Recording;
         Ok(())
     }
 
-    pub fn finish(&mut self, name: String) -> bool {
-        match (
-            self.status.clone(),
-            self.encoder.take().unwrap().into_inner(),
-        ) {
-            (GifStatus::Recording, Ok(data)) => {
-                #[cfg(not(target_arch = "wasm32"))]
-                {
-                    let file = rfd::FileDialog::new()
-                        .set_title("save gif")
-                        .add_filter("gif", &["gif"])
-                        .set_file_name(format!("{}.gif", name))
-                        .save_file();
-                    if let Some(path) = file {
-                        std::fs::write(&path, data).unwrap();
-                        self.status = GifStatus::Complete(path);
-                    } else {
-                        return false;
+    #[cfg(not(target_arch = "wasm32"))]
+    fn start_video(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let (width, height) = self.frame_size();
+        self.video = Some(VideoEncoder::new(width, height, self.limits.frame_ms())?);
+        Ok(())
+    }
+
+    /// Whether this recording's frames are kept to also save it played backwards. A
+    /// boomerang already plays both ways.
+    fn saves_both_directions(&self) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.png_dir.is_some() || self.record_video {
+            return false;
+        }
+        self.both_directions
+            && !self.is_multi_take()
+            && self.apng.is_none()
+            && self.png_zip.is_none()
+            && self.sprite_frames.is_none()
+            && !self.limits.loop_mode.is_boomerang()
+    }
+
+    /// Whether written gif frames are kept, for playing them back again.
+    fn keeps_frames(&self) -> bool {
+        self.saves_both_directions() || self.limits.loop_mode.is_boomerang()
+    }
+
+    /// Writes the boomerang's way back into the gif, if it's one.
+    fn append_boomerang(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.limits.loop_mode.is_boomerang() {
+            return Ok(());
+        }
+        let size = self.frame_size();
+        let frames = std::mem::take(&mut self.written_frames);
+        let encoder = self.encoder.as_mut().ok_or("no encoder")?;
+        append_boomerang(encoder, &frames, size, &JobProgress::default())
+    }
+
+    /// Saves the recording of `sim_name`, which played backwards if `reverse`, encoding
+    /// what's left of it in the background. Returns false if the user cancelled.
+    pub fn finish(&mut self, sim_name: String, reverse: bool) -> bool {
+        let _span = tracing::info_span!("gif_finish").entered();
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(dir) = self.png_dir.clone() {
+            self.exported = "frames";
+            self.status = GifStatus::Complete(dir);
+            return true;
+        }
+        let size = self.frame_size();
+        let limits = self.limits;
+        let names = [
+            self.get_name(sim_name.clone(), reverse),
+            self.get_name(sim_name, !reverse),
+        ];
+        let job = if let Some(zip) = self.png_zip.take() {
+            let [name, _] = names;
+            ExportJob::spawn("frames", "zip", move |_| {
+                Ok(vec![(zip.finish()?, format!("{}_frames", name))])
+            })
+        } else if let Some(frames) = self.sprite_frames.take() {
+            let sheet = self.sprite_sheet;
+            let [name, _] = names;
+            ExportJob::spawn("sprite sheet", "png", move |job| {
+                let data = sheet
+                    .encode(&frames, size, job)
+                    .map_err(|e| e.to_string())?;
+                Ok(vec![(data, format!("{}_sheet", name))])
+            })
+        } else if let Some(frames) = self.apng.take() {
+            let both_directions = self.both_directions && !limits.loop_mode.is_boomerang();
+            ExportJob::spawn("apng", "png", move |job| {
+                let mut forwards = frames.iter().collect::<Vec<_>>();
+                if limits.loop_mode.is_boomerang() {
+                    forwards.extend(boomerang_frames(frames.len()).map(|i| &frames[i]));
+                }
+                let data = encode_apng(&forwards, size, &limits, job).map_err(|e| e.to_string())?;
+                let [name, backwards_name] = names;
+                let mut files = vec![(data, name)];
+                if both_directions {
+                    let backwards = frames.iter().rev().collect::<Vec<_>>();
+                    let data =
+                        encode_apng(&backwards, size, &limits, job).map_err(|e| e.to_string())?;
+                    files.push((data, backwards_name));
+                }
+                Ok(files)
+            })
+        } else {
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(video) = self.video.take() {
+                let audio = self.audio.clone();
+                self.job = Some(ExportJob::spawn("video", "webm", move |job| {
+                    let data = video
+                        .finish(audio.as_ref(), job)
+                        .map_err(|e| e.to_string())?;
+                    let [name, _] = names;
+                    Ok(vec![(data, name)])
+                }));
+                self.status = GifStatus::Exporting;
+                return true;
+            }
+            let (Some(mut encoder), Some(palette)) = (self.encoder.take(), self.palette.as_ref())
+            else {
+                self.status = GifStatus::Error("no recording to finish".to_owned());
+                return true;
+            };
+            if !self.status.is_recording() {
+                self.status =
+                    GifStatus::Error(format!("Something weird happened: {:?}", self.status));
+                return true;
+            }
+            let backwards = self.saves_both_directions();
+            let color_map = palette.color_map_rgb();
+            let frames = std::mem::take(&mut self.written_frames);
+            ExportJob::spawn("gif", "gif", move |job| {
+                let finish = || -> Result<Files, Box<dyn std::error::Error>> {
+                    if limits.loop_mode.is_boomerang() {
+                        append_boomerang(&mut encoder, &frames, size, job)?;
+                    }
+                    let [name, backwards_name] = names;
+                    let mut files = Vec::new();
+                    if let Some(data) = encoder.into_inner()?.finish()? {
+                        files.push((data, name)); // unless it's been written where it goes
+                    }
+                    if backwards {
+                        let repeat = limits.loop_mode.repeat();
+                        let data = encode_backwards(frames, size, &color_map, repeat, job)?;
+                        files.push((data, backwards_name));
                     }
+                    Ok(files)
+                };
+                finish().map_err(|e| e.to_string())
+            })
+        };
+        self.job = Some(job);
+        self.status = GifStatus::Exporting;
+        true
+    }
+
+    /// Saves the recording once its background encoding is done. Returns whether it
+    /// finished, and if so, false if the user cancelled saving it.
+    pub fn poll_job(&mut self) -> Option<bool> {
+        #[cfg(target_arch = "wasm32")]
+        if let Some(saved) = self.poll_saving() {
+            return Some(saved);
+        }
+        let result = self.job.as_mut()?.poll()?;
+        let job = self.job.take()?;
+        match result {
+            Ok(files) => {
+                if !self.save(files, job.ext, job.label) {
+                    self.status = GifStatus::Unsaved;
                 }
-                #[cfg(target_arch = "wasm32")]
-                {
-                    self.status = GifStatus::None;
-                    use wasm_bindgen_futures::spawn_local;
-                    let status_ptr: *mut GifStatus = &mut self.status;
-
-                    spawn_local(async move {
-                        if let Some(handle) = rfd::AsyncFileDialog::new()
-                            .set_title("Recording complete!")
-                            .set_file_name(format!("{}.gif", name))
-                            .save_file()
-                            .await
-                        {
-                            handle.write(&data).await.ok();
-                            // SAFETY: We ensure the app outlives the async task (eframe app is long-lived).
-                            unsafe {
-                                *status_ptr = GifStatus::Complete;
-                            }
+                Some(true)
+            }
+            Err(err) if err == CANCELLED => Some(false),
+            Err(err) => {
+                self.status = GifStatus::Error(err);
+                Some(true)
+            }
+        }
+    }
+
+    /// The background encoding of the finished recording, while it runs.
+    pub fn job(&self) -> Option<&ExportJob> {
+        self.job.as_ref()
+    }
+
+    /// Keeps finished recordings, given as contents and name, with file extension `ext`,
+    /// and asks where to save them; `exported` says what they are. Returns false if the
+    /// user cancelled.
+    fn save(&mut self, files: Files, ext: &'static str, exported: &'static str) -> bool {
+        self.exported = exported;
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = self.streamed.take() {
+            self.status = save_beside(path, &files, ext);
+            return true;
+        }
+        self.finished = Some((files, ext));
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.copied = false;
+        }
+        self.save_finished()
+    }
+
+    /// The finished recording's file, once it's encoded, and its extension. Kept after
+    /// it's saved, so it can be used again.
+    pub fn finished_bytes(&self) -> Option<(&[u8], &'static str)> {
+        let (files, ext) = self.finished.as_ref()?;
+        Some((&files.first()?.0, ext))
+    }
+
+    /// Asks where to save the finished recording. Any files after the first go next to it.
+    /// Returns false if the user cancelled.
+    pub fn save_finished(&mut self) -> bool {
+        let Some((files, ext)) = &self.finished else {
+            return false;
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let mut files = files.iter();
+            let Some((data, name)) = files.next() else {
+                return false;
+            };
+            let file =
+                self.output
+                    .save_path(&format!("save {}", ext), &format!("{}.{}", name, ext), ext);
+            let Some(path) = file else {
+                return false;
+            };
+            self.status = match std::fs::write(&path, data) {
+                Ok(()) => save_beside(path, files.as_slice(), ext),
+                Err(err) => GifStatus::Error(format!("failed to save {}: {}", path.display(), err)),
+            };
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            // the dialogs finish after this returns, so how they went comes back through a
+            // channel that poll_job reads
+            let (tx, rx) = std::sync::mpsc::channel();
+            self.saving = Some(rx);
+            self.status = GifStatus::Saving;
+            let files = files.clone();
+            let ext = ext.to_owned();
+            wasm_bindgen_futures::spawn_local(async move {
+                let mut saved = false;
+                for (data, name) in files {
+                    let file_name = format!("{}.{}", name, ext);
+                    if let Some(handle) = rfd::AsyncFileDialog::new()
+                        .set_title("Recording complete!")
+                        .set_file_name(&file_name)
+                        .save_file()
+                        .await
+                    {
+                        if let Err(err) = handle.write(&data).await {
+                            tx.send(Err(format!("failed to save {}: {}", file_name, err)))
+                                .ok();
+                            return;
                         }
-                    });
+                        saved = true;
+                    }
                 }
+                tx.send(Ok(saved)).ok(); // nobody's waiting if the recording was stopped
+            });
+        }
+        true
+    }
+
+    /// How the browser's save dialogs went, once they're closed: whether the recording
+    /// finished, and if so, false if the user cancelled saving it.
+    #[cfg(target_arch = "wasm32")]
+    fn poll_saving(&mut self) -> Option<bool> {
+        let result = match self.saving.as_ref()?.try_recv() {
+            Ok(result) => result,
+            Err(std::sync::mpsc::TryRecvError::Empty) => return None,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                Err("saving stopped unexpectedly".to_owned())
+            }
+        };
+        self.saving = None;
+        match result {
+            Ok(true) => {
+                self.status = GifStatus::Complete;
+                Some(true)
             }
-            (a, b) => {
-                self.status = GifStatus::Error(format!("Something weird happened: {:?}", (a, b)));
+            Ok(false) => {
+                self.status = GifStatus::Unsaved;
+                Some(true)
             }
+            Err(err) => {
+                self.status = GifStatus::Error(err);
+                Some(true)
+            }
+        }
+    }
+
+    /// Puts the finished gif on the clipboard.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn copy_gif(&mut self) -> Result<(), String> {
+        let Some((files, "gif")) = &self.finished else {
+            return Err("there's no finished gif to copy".to_owned());
+        };
+        let Some((data, name)) = files.first() else {
+            return Err("there's no finished gif to copy".to_owned());
+        };
+        let alt = match &self.status {
+            GifStatus::Complete(path) => path.display().to_string(),
+            _ => format!("{}.gif", name),
+        };
+        self.clipboard.copy_gif(data, &alt)?;
+        self.copied = true;
+        Ok(())
+    }
+
+    /// A staging buffer of `size` bytes to read a frame back into, one a frame was read
+    /// from before if any fits.
+    fn staging_buffer(&mut self, device: &wgpu::Device, size: u64) -> wgpu::Buffer {
+        let spare = &mut self.spare_buffers;
+        match spare.iter().position(|b| b.size() == size) {
+            Some(i) => spare.swap_remove(i),
+            None => device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("color readback"),
+                size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+        }
+    }
+
+    /// Maps `readback` once the copies into it are done, for a frame of `size`.
+    fn map_in_flight(&self, readback: wgpu::Buffer, size: (u32, u32), indexed: bool) -> InFlight {
+        let ready = Arc::new(AtomicBool::new(false));
+        let ready_in_cb = Arc::clone(&ready);
+        readback
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |res| {
+                if res.is_ok() {
+                    ready_in_cb.store(true, std::sync::atomic::Ordering::Release);
+                }
+            });
+        InFlight {
+            buffer: readback,
+            ready,
+            id: self.id,
+            size,
+            arrived: None,
+            indexed,
+        }
+    }
+
+    /// Whether frames go into a gif with one palette and no dithering, so each pixel's
+    /// index can be looked up on its own, on the GPU.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn indexes_pixels_alone(&self) -> bool {
+        self.encoder.is_some()
+            && self.png_dir.is_none()
+            && self.apng.is_none()
+            && self.png_zip.is_none()
+            && self.sprite_frames.is_none()
+            && !self.limits.dither
+            && !self.limits.local_palettes
+    }
+
+    /// Whether frames can have their palette indices worked out on the GPU as they're read
+    /// back: those of the animation, with nothing drawn over or graded in them on the way.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn indexes_on_gpu(&self) -> bool {
+        self.indexes_pixels_alone()
+            && !self.window.enabled
+            && (self.lut.is_none() || self.frames_graded)
+            && !self.overlay.enabled
+            && self.readback_size() == self.frame_size()
+    }
+
+    /// Hands the frame just prepared here, drawn over, graded, padded or screenshotted, to
+    /// the GPU to be indexed, returning whether it went. It's queued where it was taken
+    /// from the ring, or behind the screenshots before it, so frames keep their order.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn index_prepared(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> bool {
+        let size = self.frame_size();
+        let (keep_every, _, _) = self.fit.settings();
+        let fits = self
+            .quantizer
+            .as_ref()
+            .is_some_and(|q| q.fits(device, size));
+        // frames left out aren't indexed at all
+        if !fits || !self.indexes_pixels_alone() || self.frame_count % keep_every != 0 {
+            return false;
+        }
+        let Some(palette) = self.palette.as_ref().map(NeuQuant::color_map_rgba) else {
+            return false;
+        };
+        let readback = self.staging_buffer(device, gpu_quantize::indexed_bytes(size));
+        let Some(quantizer) = self.quantizer.as_mut() else {
+            return false;
+        };
+        quantizer.index_rgba(device, queue, &self.rgba_buffer, size, &palette, &readback);
+        let mut inflight = self.map_in_flight(readback, size, true);
+        inflight.arrived = self.frame_arrived;
+        if self.window.enabled {
+            self.inflight.push_back(inflight);
+        } else {
+            self.inflight.push_front(inflight);
         }
         true
     }
 
-    pub fn no_inflight(&self) -> bool {
-        self.inflight.is_none()
+    /// Whether another frame can be asked for: one screenshot at a time, or while there's
+    /// room in the ring of readbacks.
+    pub fn wants_frame(&self) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.window.enabled {
+            // the ring only holds screenshots being indexed then
+            return !self.window.requested
+                && self.window.frame.is_none()
+                && self.inflight.len() < READBACK_RING;
+        }
+        self.inflight.len() < READBACK_RING
+    }
+
+    /// Marks frames already being read back as stale, for when what's recorded changes.
+    pub fn invalidate(&mut self) {
+        self.id += 1;
+    }
+
+    /// Ends the recording at the next frame and saves what there is so far.
+    pub fn stop_early(&mut self) {
+        self.stopped_early = true;
+        self.resume(); // the next frame is what finishes it
+    }
+
+    /// Stops capturing frames without ending the recording, so a dull stretch of the
+    /// animation can play out unrecorded.
+    pub fn pause(&mut self) {
+        if self.status.is_recording() {
+            self.status = GifStatus::Paused;
+        }
+    }
+
+    /// Goes back to capturing, carrying on from the last frame captured.
+    pub fn resume(&mut self) {
+        if self.is_paused() {
+            self.status = GifStatus::Recording;
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        matches!(self.status, GifStatus::Paused)
     }
 
     pub fn stop(&mut self) {
+        if let Some(job) = self.job.take() {
+            job.cancel();
+        }
         self.status = GifStatus::None;
         self.encoder = None;
         self.palette = None;
         self.frame_count = 0;
-        self.inflight = None;
+        self.requested = 0;
+        self.inflight.clear();
+        self.spare_buffers.clear();
+        self.frame_arrived = None;
+        self.gpu_indices = None;
+        self.takes.clear();
+        self.take_preview = None;
+        self.quantized_frame = None;
+        self.written_frames = Vec::new();
+        self.apng = None;
+        self.png_zip = None;
+        self.sprite_frames = None;
+        self.finished = None;
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.saving = None;
+        }
+        self.stopped_early = false;
+        self.starts_at = None;
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.png_dir = None;
+            self.record_video = false;
+            self.video = None;
+            self.stream_to = None;
+            self.streamed = None;
+            self.window.requested = false;
+            self.window.frame = None;
+            self.window.size = None;
+        }
         self.id += 1;
     }
 
-    pub fn should_stop(&self) -> bool {
-        if self.frame_count < GIF_MIN_FRAMES {
-            false
-        } else if self.frame_count >= GIF_MAX_FRAMES {
-            true
-        } else {
-            self.should_stop
+    /// Seconds of playback still to record, when recording a set duration.
+    pub fn seconds_left(&self) -> Option<f64> {
+        let frames = self.limits.duration_frames()?;
+        Some(frames.saturating_sub(self.frame_count) as f64 * self.limits.frame_ms() / 1000.0)
+    }
+
+    /// Whether the recording is over, by the stop button or the stop rules. `arrived`
+    /// tells whether the animation has finished, if the rules ask.
+    pub fn should_stop(&self, arrived: impl FnOnce() -> bool) -> bool {
+        if self.stopped_early {
+            return true;
+        }
+        let (limit, floor) = match self.limits.duration_frames() {
+            Some(frames) => (frames, 0),
+            None => (self.limits.max_frames.max(1), self.limits.min_frames),
+        };
+        self.frame_count >= floor
+            && self
+                .limits
+                .stop
+                .met(self.frame_count >= limit, self.over_size, || {
+                    self.frame_arrived.unwrap_or_else(arrived)
+                })
+    }
+
+    /// Encodes the still being read back once it's in, in the background, and then saves
+    /// it as a png named after `name`. Returns whether one was saved, or the user
+    /// cancelled saving it.
+    pub fn poll_snapshot(&mut self, name: String) -> Result<bool, Box<dyn std::error::Error>> {
+        if let Some((inflight, graded)) = self.snapshot.take_if(|(inflight, _)| inflight.is_ready())
+        {
+            let size = inflight.size;
+            let mut rgba = Vec::new();
+            inflight.read_into(&mut rgba);
+            let color_space = self.limits.color_space;
+            let lut = self.lut.clone().filter(|_| !graded);
+            let overlay = Some(self.overlay).filter(|overlay| overlay.enabled);
+            self.still = Some(ExportJob::spawn("frame", "png", move |job| {
+                color_space.to_srgb(&mut rgba);
+                let data = encode_still(rgba, size, lut.as_deref(), overlay, job)
+                    .map_err(|e| e.to_string())?;
+                Ok(vec![(data, name)])
+            }));
+        }
+        let Some(result) = self.still.as_mut().and_then(|still| still.poll()) else {
+            return Ok(false);
+        };
+        self.still = None;
+        let Some((data, name)) = result
+            .or_else(|err| {
+                if err == CANCELLED {
+                    Ok(vec![])
+                } else {
+                    Err(err)
+                }
+            })?
+            .pop()
+        else {
+            return Ok(true);
+        };
+        let file_name = format!("{}.png", name);
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = self.output.save_path("save frame", &file_name, "png") {
+            std::fs::write(path, data)?;
+        }
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Some(handle) = rfd::AsyncFileDialog::new()
+                .set_title("save frame")
+                .set_file_name(file_name)
+                .save_file()
+                .await
+            {
+                handle.write(&data).await.ok();
+            }
+        });
+        Ok(true)
+    }
+
+    /// Whether a still is waiting to be read back.
+    pub fn snapshot_pending(&self) -> bool {
+        self.snapshot_wanted || self.snapshot.is_some()
+    }
+
+    /// The background encoding of a still, while it runs.
+    pub fn still_job(&self) -> Option<&ExportJob> {
+        self.still.as_ref()
+    }
+
+    /// Gives up on the still being encoded.
+    pub fn cancel_still(&mut self) {
+        if let Some(still) = self.still.take() {
+            still.cancel();
         }
     }
 
@@ -258,26 +2156,107 @@ impl GifRecorder {
 }
 
 impl ObamifyApp {
+    /// Asks for the next frame to record: a readback of the animation, or a screenshot
+    /// when recording the whole window.
+    pub fn request_frame(
+        &mut self,
+        ctx: &egui::Context,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.gif_recorder.requested += 1;
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.gif_recorder.window.enabled {
+            self.gif_recorder.window.requested = true;
+            ctx.send_viewport_cmd(egui::ViewportCommand::Screenshot(egui::UserData::default()));
+            return Ok(());
+        }
+        #[cfg(target_arch = "wasm32")]
+        let _ = ctx;
+        self.get_color_image_data(device, queue)
+    }
+
     pub fn get_color_image_data(
         &mut self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let width = self.size.0;
-        let height = self.size.1;
-        let bpp = 4u32; // RGBA8
-        let unpadded_bytes_per_row = width * bpp;
-        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT; // 256
-        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(align) * align;
+        let size = self.gif_recorder.readback_size();
+        let origin = self.gif_recorder.capture_origin();
+        // the animation runs ahead of frames still mapping, so whether it was done is
+        // asked now, of what's read back
+        let arrived = self.gif_recorder.limits.stop.arrived && self.animation_arrived();
+        #[cfg(not(target_arch = "wasm32"))]
+        let indexed = self.read_back_indices(device, queue, origin, size);
+        #[cfg(target_arch = "wasm32")]
+        let indexed = None;
+        let mut inflight = indexed.unwrap_or_else(|| self.read_back(device, queue, origin, size));
+        inflight.arrived = Some(arrived);
+        self.gif_recorder.inflight.push_back(inflight);
+        Ok(())
+    }
+
+    /// Reads the whole rendered frame back for a still, once the preview renders every
+    /// pixel; until then it's only asked for.
+    pub fn request_snapshot(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if !self.gif_recorder.snapshot_wanted || self.lod != 1 {
+            return;
+        }
+        self.gif_recorder.snapshot_wanted = false;
+        let still = self.read_back(device, queue, (0, 0), self.render_size());
+        self.gif_recorder.snapshot = Some((still, self.preview_graded()));
+    }
+
+    /// Starts working out the palette indices of the `size` part of the rendered frame at
+    /// `origin` on the GPU, and copying them to the cpu, if the recording and GPU can.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn read_back_indices(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        origin: (u32, u32),
+        size: (u32, u32),
+    ) -> Option<InFlight> {
+        let fits = self
+            .gif_recorder
+            .quantizer
+            .as_ref()
+            .is_some_and(|q| q.fits(device, size));
+        if !fits || !self.gif_recorder.indexes_on_gpu() {
+            return None;
+        }
+        let palette = self.gif_recorder.palette.as_ref()?.color_map_rgba();
+        let encoding = self.gif_recorder.limits.color_space.encoding();
+        let readback = self
+            .gif_recorder
+            .staging_buffer(device, gpu_quantize::indexed_bytes(size));
+        let quantizer = self.gif_recorder.quantizer.as_mut()?;
+        let part = (origin, size);
+        quantizer.index(
+            device,
+            queue,
+            &self.color_tex,
+            part,
+            &palette,
+            &encoding,
+            &readback,
+        );
+        Some(self.gif_recorder.map_in_flight(readback, size, true))
+    }
+
+    /// Starts copying the `size` part of the rendered frame at `origin` to the cpu.
+    fn read_back(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        (x, y): (u32, u32),
+        (width, height): (u32, u32),
+    ) -> InFlight {
+        let padded_bytes_per_row = padded_bytes_per_row(width);
         let buffer_size = padded_bytes_per_row as u64 * height as u64;
 
         // Staging buffer to receive the texture
-        let readback = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("color readback"),
-            size: buffer_size,
-            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+        let readback = self.gif_recorder.staging_buffer(device, buffer_size);
 
         // Encode copy
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
@@ -288,7 +2267,7 @@ impl ObamifyApp {
             wgpu::TexelCopyTextureInfo {
                 texture: &self.color_tex,
                 mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
+                origin: wgpu::Origin3d { x, y, z: 0 },
                 aspect: wgpu::TextureAspect::All,
             },
             wgpu::TexelCopyBufferInfo {
@@ -307,23 +2286,8 @@ impl ObamifyApp {
         );
 
         queue.submit(Some(encoder.finish()));
-
-        let ready = Arc::new(AtomicBool::new(false));
-        let slice = readback.slice(..);
-        let ready_in_cb = Arc::clone(&ready);
-
-        slice.map_async(wgpu::MapMode::Read, move |res| {
-            if res.is_ok() {
-                ready_in_cb.store(true, std::sync::atomic::Ordering::Release);
-            }
-        });
-
-        self.gif_recorder.inflight = Some(InFlight {
-            buffer: readback,
-            ready,
-        });
-
-        Ok(())
+        self.gif_recorder
+            .map_in_flight(readback, (width, height), false)
 
         // let slice = readback.slice(..);
         // let (tx, rx) = futures_intrusive::channel::shared::oneshot_channel();
@@ -351,3 +2315,135 @@ impl ObamifyApp {
         // Ok(rgba)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A frame of one color, as the shade pass writes it into the unorm texture.
+    fn render_solid(color: SeedColor, size: usize) -> Vec<u8> {
+        let texel = color
+            .rgba
+            .map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8);
+        texel.repeat(size * size)
+    }
+
+    fn palette_entry(palette: &NeuQuant, rgba: &[u8]) -> [u8; 3] {
+        let map = palette.color_map_rgb();
+        let i = palette.index_of(rgba) * 3;
+        [map[i], map[i + 1], map[i + 2]]
+    }
+
+    #[test]
+    fn solid_color_matches_its_palette_entry() {
+        // 0.3 and 0.7 land between two bytes, where truncating and rounding disagree
+        let color = SeedColor {
+            rgba: [0.3, 0.5, 0.7, 1.0],
+        };
+        let mut frame = render_solid(color, 4);
+        ColorSpace::Srgb.to_srgb(&mut frame);
+        let palette = build_palette(&[color], ColorSpace::Srgb, GIF_PALETTE_QUALITY);
+        for pixel in frame.chunks_exact(4) {
+            assert_eq!(palette_entry(&palette, pixel), [77, 128, 179]);
+            assert_eq!(&pixel[..3], &[77, 128, 179]);
+        }
+    }
+
+    #[test]
+    fn linear_frames_are_encoded_like_the_palette() {
+        let color = SeedColor {
+            rgba: [0.2, 0.5, 0.05, 1.0],
+        };
+        let mut frame = render_solid(color, 4);
+        ColorSpace::Linear.to_srgb(&mut frame);
+        let palette = build_palette(&[color], ColorSpace::Linear, GIF_PALETTE_QUALITY);
+        let expected = [124, 188, 64]; // sRGB of the stored linear bytes
+        assert_eq!(&frame[..4], &[124, 188, 64, 255]);
+        assert_eq!(palette_entry(&palette, &frame[..4]), expected);
+    }
+
+    #[test]
+    fn dithering_keeps_a_gradients_average() {
+        let black_and_white = [0.0, 1.0].map(|v| SeedColor {
+            rgba: [v, v, v, 1.0],
+        });
+        let palette = build_palette(&black_and_white, ColorSpace::Srgb, MAX_PALETTE_QUALITY);
+        let width = 64;
+        let frame = (0..width * 8)
+            .flat_map(|i| {
+                let v = (i % width * 255 / (width - 1)) as u8;
+                [v, v, v, 255]
+            })
+            .collect::<Vec<u8>>();
+        let mut indices = vec![0; width * 8];
+        dither_indices(&palette, &frame, width, &mut indices);
+        let shade = |i: u8| palette.lookup(i as usize).unwrap()[0] as f32;
+        // a quarter of the way along, about a quarter of the pixels are white
+        let column_mean = |x: usize| (0..8).map(|y| shade(indices[y * width + x])).sum::<f32>();
+        let quarter = (12..20).map(column_mean).sum::<f32>() / 64.0;
+        assert!((quarter - 255.0 / 4.0).abs() < 25.0, "{}", quarter);
+    }
+
+    #[test]
+    fn srgb_encoding_keeps_byte_colors() {
+        for byte in 0..=255u8 {
+            assert_eq!(ColorSpace::Srgb.encode(byte as f32 / 255.0), byte);
+        }
+    }
+
+    #[test]
+    fn fit_steps_down_when_the_rest_wont_fit() {
+        let mut fit = Fit::default();
+        // 1000 bytes a frame, 100 frames to go: 104 kB at this step
+        for frame in 1..=FIT_WARMUP {
+            assert!(!fit.after_frame(frame as usize * 1000, 200_000, 100));
+        }
+        let mut fit = Fit::default();
+        for frame in 1..FIT_WARMUP {
+            fit.after_frame(frame as usize * 1000, 50_000, 100);
+        }
+        assert!(fit.after_frame(FIT_WARMUP as usize * 1000, 50_000, 100));
+        assert_eq!(fit.settings().0, 2); // every other frame kept
+    }
+
+    #[test]
+    fn stop_rules_combine_conditions() {
+        let mut rules = StopRules {
+            max_frames: true,
+            max_size: false,
+            arrived: true,
+            combine: StopCombine::Any,
+        };
+        assert!(rules.met(true, false, || false));
+        assert!(!rules.met(false, true, || false)); // the size limit isn't one of them
+        rules.combine = StopCombine::All;
+        assert!(!rules.met(true, true, || false));
+        assert!(rules.met(true, false, || true));
+        assert!(!rules.size_is_hard());
+        let manual = StopRules {
+            max_frames: false,
+            max_size: false,
+            arrived: false,
+            ..rules
+        };
+        assert!(!manual.met(true, true, || panic!(
+            "arrival isn't asked without the rule"
+        )));
+    }
+
+    #[test]
+    fn shaped_frames_pad_or_crop_the_square() {
+        let mut recorder = GifRecorder::new();
+        recorder.viewport = (300, 100);
+        recorder.shape = FrameShape::Pad;
+        assert_eq!(recorder.frame_size(), (300, 100));
+        assert_eq!(recorder.readback_size(), (100, 100));
+        recorder.shape = FrameShape::Fill;
+        assert_eq!(recorder.render_size(), 300);
+        assert_eq!(recorder.readback_size(), (300, 100));
+        assert_eq!(recorder.capture_origin(), (0, 100));
+
+        let padded = pad_rgba(&[255, 0, 0, 255], 1, (3, 1));
+        assert_eq!(padded, [0, 0, 0, 255, 255, 0, 0, 255, 0, 0, 0, 255]);
+    }
+}
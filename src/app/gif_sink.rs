@@ -0,0 +1,132 @@
+//! Where a recording's gif is encoded to: memory, or natively straight into the file it's
+//! saved as, so a long recording isn't held in memory while it's encoded and again while
+//! it's saved.
+
+use std::io::{self, Write};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::{Path, PathBuf};
+
+pub enum GifSink {
+    Memory(Vec<u8>),
+    #[cfg(not(target_arch = "wasm32"))]
+    File(PartFile),
+}
+
+impl GifSink {
+    /// Bytes written so far.
+    pub fn written(&self) -> usize {
+        match self {
+            GifSink::Memory(data) => data.len(),
+            #[cfg(not(target_arch = "wasm32"))]
+            GifSink::File(file) => file.written,
+        }
+    }
+
+    /// The finished gif if it's in memory, or nothing once it's been moved into place.
+    pub fn finish(self) -> io::Result<Option<Vec<u8>>> {
+        match self {
+            GifSink::Memory(data) => Ok(Some(data)),
+            #[cfg(not(target_arch = "wasm32"))]
+            GifSink::File(file) => file.finish().map(|_| None),
+        }
+    }
+}
+
+impl Write for GifSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            GifSink::Memory(data) => data.write(buf),
+            #[cfg(not(target_arch = "wasm32"))]
+            GifSink::File(file) => {
+                let n = file
+                    .writer
+                    .as_mut()
+                    .ok_or(io::ErrorKind::NotConnected)?
+                    .write(buf)?;
+                file.written += n;
+                Ok(n)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            GifSink::Memory(_) => Ok(()),
+            #[cfg(not(target_arch = "wasm32"))]
+            GifSink::File(file) => file.writer.as_mut().map_or(Ok(()), |w| w.flush()),
+        }
+    }
+}
+
+/// A file written next to where it's going under a `.part` name, and only moved there once
+/// it's finished. Dropped unfinished, like when the recording is stopped, it's deleted.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct PartFile {
+    writer: Option<io::BufWriter<std::fs::File>>, // none once finished
+    part: PathBuf,
+    dest: PathBuf,
+    written: usize,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl PartFile {
+    pub fn create(dest: &Path) -> io::Result<Self> {
+        let mut name = dest.file_name().unwrap_or_default().to_owned();
+        name.push(".part");
+        let part = dest.with_file_name(name);
+        let file = std::fs::File::create(&part)?;
+        Ok(Self {
+            writer: Some(io::BufWriter::new(file)),
+            part,
+            dest: dest.to_owned(),
+            written: 0,
+        })
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        if let Some(writer) = self.writer.take() {
+            writer
+                .into_inner()
+                .map_err(|e| e.into_error())?
+                .sync_all()?;
+        }
+        std::fs::rename(&self.part, &self.dest)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for PartFile {
+    fn drop(&mut self) {
+        if self.writer.take().is_some() {
+            // unfinished; closed above first, since open files can't be removed everywhere
+            std::fs::remove_file(&self.part).ok();
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn part_file_is_moved_into_place_or_deleted() {
+        let dir = std::env::temp_dir().join(format!("obamify_gif_sink_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (kept, dropped) = (dir.join("kept.gif"), dir.join("dropped.gif"));
+
+        let mut sink = GifSink::File(PartFile::create(&kept).unwrap());
+        sink.write_all(b"GIF89a").unwrap();
+        assert_eq!(sink.written(), 6);
+        assert!(dir.join("kept.gif.part").exists());
+        assert_eq!(sink.finish().unwrap(), None);
+        assert_eq!(std::fs::read(&kept).unwrap(), b"GIF89a");
+        assert!(!dir.join("kept.gif.part").exists());
+
+        let mut sink = GifSink::File(PartFile::create(&dropped).unwrap());
+        sink.write_all(b"GIF89a").unwrap();
+        drop(sink);
+        assert!(!dir.join("dropped.gif.part").exists());
+        assert!(!dropped.exists());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,437 @@
+//! Palette indices worked out on the GPU, so a gif frame only has to be handed to the
+//! encoder instead of searching the palette for every pixel here: as recorded frames are
+//! read back, or for frames that were drawn over, graded or screenshotted on the cpu,
+//! after uploading them. Needs compute shaders, which WebGL doesn't have.
+
+use bytemuck::{Pod, Zeroable};
+
+const GROUP_SIZE: u32 = 256; // matches the shader's workgroup size
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Params {
+    origin: [u32; 2],
+    size: [u32; 2],
+    colors: u32,
+    _pad: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Tables {
+    palette: [[u32; 4]; 256],
+    encoding: [[u32; 4]; 64],
+    netindex: [[u32; 4]; 64],
+}
+
+impl Tables {
+    /// `palette` as NeuQuant's rgba bytes, and the gif byte of each byte the frame stores.
+    fn new(palette: &[u8], encoding: &[u8; 256]) -> Self {
+        let mut tables = Tables::zeroed();
+        for (entry, rgba) in tables.palette.iter_mut().zip(palette.chunks_exact(4)) {
+            *entry = [0, 1, 2, 3].map(|c| rgba[c] as u32);
+        }
+        for (i, &byte) in encoding.iter().enumerate() {
+            tables.encoding[i / 4][i % 4] = byte as u32;
+        }
+        for (g, start) in netindex(palette).into_iter().enumerate() {
+            tables.netindex[g / 4][g % 4] = start;
+        }
+        tables
+    }
+}
+
+/// Where NeuQuant's search starts for each green, worked out again from its palette the
+/// way `build_netindex` does, since it doesn't share its own. The palette comes sorted by
+/// green, so only the starts are left to find.
+fn netindex(palette: &[u8]) -> [u32; 256] {
+    let greens = palette
+        .chunks_exact(4)
+        .map(|c| c[1] as usize)
+        .collect::<Vec<_>>();
+    let mut netindex = [0; 256];
+    let Some(last) = greens.len().checked_sub(1) else {
+        return netindex;
+    };
+    let (mut previous, mut start) = (0, 0);
+    for (i, &g) in greens.iter().enumerate() {
+        if g != previous {
+            netindex[previous] = ((start + i) >> 1) as u32;
+            netindex[previous + 1..g].fill(i as u32);
+            (previous, start) = (g, i);
+        }
+    }
+    netindex[previous] = ((start + last) >> 1) as u32;
+    netindex[previous + 1..].fill(last as u32);
+    netindex
+}
+
+/// Bytes the indices of a `width` × `height` frame take up, rows back to back and
+/// rounded up to whole words.
+pub fn indexed_bytes((width, height): (u32, u32)) -> u64 {
+    (width as u64 * height as u64).div_ceil(4) * 4
+}
+
+pub struct GpuQuantizer {
+    pipeline: wgpu::ComputePipeline,
+    params: wgpu::Buffer,
+    tables: wgpu::Buffer,
+    indices: Option<wgpu::Buffer>, // written by the shader, kept while frames fit
+    upload: Option<wgpu::Texture>, // rgba frames indexed from the cpu, kept the same way
+}
+
+impl GpuQuantizer {
+    /// The quantizer, if the adapter can run compute shaders and `device` was made with
+    /// the limits they need; on GL the device gets WebGL2's, which allow none.
+    pub fn new(adapter: &wgpu::Adapter, device: &wgpu::Device) -> Option<Self> {
+        let flags = adapter.get_downlevel_capabilities().flags;
+        let limits = device.limits();
+        if !flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+            || limits.max_storage_buffers_per_shader_stage == 0
+            || limits.max_compute_workgroups_per_dimension == 0
+            || limits.max_compute_workgroup_size_x < GROUP_SIZE
+            || limits.max_compute_invocations_per_workgroup < GROUP_SIZE
+            || (limits.max_uniform_buffer_binding_size as usize) < size_of::<Tables>()
+        {
+            return None;
+        }
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("quantize.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/quantize.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("quantize_pipeline"),
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let uniform = |label, size| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+        Some(Self {
+            pipeline,
+            params: uniform("quantize_params", size_of::<Params>() as u64),
+            tables: uniform("quantize_tables", size_of::<Tables>() as u64),
+            indices: None,
+            upload: None,
+        })
+    }
+
+    /// Whether a frame of `size` can be indexed in one dispatch.
+    pub fn fits(&self, device: &wgpu::Device, size: (u32, u32)) -> bool {
+        let limits = device.limits();
+        let groups = (indexed_bytes(size) / 4).div_ceil(GROUP_SIZE as u64);
+        groups <= limits.max_compute_workgroups_per_dimension as u64
+            && size.0.max(size.1) <= limits.max_texture_dimension_2d
+    }
+
+    /// Queues indexing the `size` part of `frame` at `origin` against `palette`, rgba
+    /// bytes, into `readback`, which needs `indexed_bytes(size)` of room. `encoding` is
+    /// the gif byte of each byte the frame stores.
+    #[allow(clippy::too_many_arguments)]
+    pub fn index(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &wgpu::Texture,
+        (origin, size): ((u32, u32), (u32, u32)),
+        palette: &[u8],
+        encoding: &[u8; 256],
+        readback: &wgpu::Buffer,
+    ) {
+        let _span = tracing::info_span!("gpu_quantize").entered();
+        let bytes = indexed_bytes(size);
+        let params = Params {
+            origin: [origin.0, origin.1],
+            size: [size.0, size.1],
+            colors: (palette.len() / 4).min(256) as u32,
+            _pad: [0; 3],
+        };
+        queue.write_buffer(&self.params, 0, bytemuck::bytes_of(&params));
+        queue.write_buffer(
+            &self.tables,
+            0,
+            bytemuck::bytes_of(&Tables::new(palette, encoding)),
+        );
+        let indices = match self.indices.take().filter(|b| b.size() == bytes) {
+            Some(indices) => indices,
+            None => device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("quantize_indices"),
+                size: bytes,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+        };
+        let view = frame.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("quantize_bg"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.tables.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: indices.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("quantize_encoder"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("quantize_pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups((bytes / 4).div_ceil(GROUP_SIZE as u64) as u32, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&indices, 0, readback, 0, bytes);
+        queue.submit(Some(encoder.finish()));
+        self.indices = Some(indices);
+    }
+
+    /// Queues indexing an sRGB `rgba` frame of `size`, made on the cpu, against `palette`
+    /// into `readback`, like `index` does with one rendered here.
+    #[allow(clippy::too_many_arguments)]
+    pub fn index_rgba(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rgba: &[u8],
+        size: (u32, u32),
+        palette: &[u8],
+        readback: &wgpu::Buffer,
+    ) {
+        let extent = wgpu::Extent3d {
+            width: size.0,
+            height: size.1,
+            depth_or_array_layers: 1,
+        };
+        // the upload before is still read from, but writes wait for the work queued before
+        let upload = match self.upload.take().filter(|t| t.size() == extent) {
+            Some(upload) => upload,
+            None => device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("quantize_upload"),
+                size: extent,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            }),
+        };
+        queue.write_texture(
+            upload.as_image_copy(),
+            rgba,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(size.0 * 4),
+                rows_per_image: Some(size.1),
+            },
+            extent,
+        );
+        // the bytes are already what gets looked up, so each stands for itself
+        let encoding = std::array::from_fn(|i| i as u8);
+        let part = ((0, 0), size);
+        self.index(device, queue, &upload, part, palette, &encoding, readback);
+        self.upload = Some(upload);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_hold_a_word_per_channel() {
+        let encoding: [u8; 256] = std::array::from_fn(|i| 255 - i as u8);
+        let tables = Tables::new(&[1, 2, 3, 255, 4, 5, 6, 255], &encoding);
+        assert_eq!(tables.palette[..2], [[1, 2, 3, 255], [4, 5, 6, 255]]);
+        assert_eq!(tables.encoding[0], [255, 254, 253, 252]);
+        assert_eq!(tables.encoding[63][3], 0);
+        assert_eq!(indexed_bytes((3, 3)), 12);
+    }
+
+    /// quantize.wgsl's `nearest`, step for step, over the tables `Tables::new` fills in.
+    fn shader_nearest(tables: &Tables, colors: u32, rgba: [u8; 4]) -> u32 {
+        let entry = |i: u32| tables.palette[i as usize].map(|c| c as i32);
+        let distance = |i: u32| {
+            let e = entry(i);
+            (0..4).map(|c| (e[c] - rgba[c] as i32).pow(2)).sum::<i32>()
+        };
+        let (mut best, mut best_distance) = (0, 1 << 30);
+        let green = rgba[1] as usize;
+        let mut i = tables.netindex[green / 4][green % 4];
+        let mut j = i.saturating_sub(1);
+        while i < colors || j > 0 {
+            if i < colors {
+                if (entry(i)[1] - rgba[1] as i32).pow(2) >= best_distance {
+                    break;
+                }
+                let d = distance(i);
+                if d < best_distance {
+                    (best, best_distance) = (i, d);
+                }
+                i += 1;
+            }
+            if j > 0 {
+                if (entry(j)[1] - rgba[1] as i32).pow(2) >= best_distance {
+                    break;
+                }
+                let d = distance(j);
+                if d < best_distance {
+                    (best, best_distance) = (j, d);
+                }
+                j -= 1;
+            }
+        }
+        best
+    }
+
+    /// Runs without a GPU: the shader's search from the rebuilt `netindex` picks what
+    /// NeuQuant does, for palettes of many, few and repeated colors, and every green.
+    #[test]
+    fn rebuilt_netindex_searches_like_neuquant() {
+        let mut seed = 3u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            (seed >> 24) as u8
+        };
+        let random = (0..4096)
+            .flat_map(|_| [next(), next(), next(), 255])
+            .collect::<Vec<u8>>();
+        let gradient = (0..=255u8)
+            .flat_map(|v| [v, v / 2, 255 - v, 255])
+            .collect::<Vec<u8>>();
+        let few = [[250, 10, 10, 255], [10, 250, 10, 255], [10, 10, 250, 255]]
+            .iter()
+            .cycle()
+            .take(300)
+            .flatten()
+            .copied()
+            .collect::<Vec<u8>>();
+        let translucent = (0..2048)
+            .flat_map(|_| [next(), next() / 4, next(), next()])
+            .collect::<Vec<u8>>();
+        let probes = (0..=255u8)
+            .flat_map(|g| (0..8).map(move |k| [k * 32, g, 255 - k * 32, 255 - k]))
+            .chain((0..1024).map(|_| [next(), next(), next(), next()]))
+            .collect::<Vec<_>>();
+
+        let encoding = std::array::from_fn(|i| i as u8);
+        for (name, pixels, samplefac) in [
+            ("random", &random, 10),
+            ("gradient", &gradient, 1),
+            ("few colors", &few, 1),
+            ("translucent", &translucent, 30),
+        ] {
+            let nq = color_quant::NeuQuant::new(samplefac, 256, pixels);
+            let palette = nq.color_map_rgba();
+            let tables = Tables::new(&palette, &encoding);
+            let colors = (palette.len() / 4) as u32;
+            for rgba in &probes {
+                assert_eq!(
+                    shader_nearest(&tables, colors, *rgba) as usize,
+                    nq.index_of(rgba),
+                    "{name} palette, pixel {rgba:?}"
+                );
+            }
+        }
+    }
+
+    /// Runs the pass on whatever adapter there is, and is skipped without one.
+    #[test]
+    fn indices_match_neuquant() {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let Ok(adapter) = pollster::block_on(instance.request_adapter(&Default::default())) else {
+            eprintln!("no adapter, skipped");
+            return;
+        };
+        let limits = wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits());
+        let descriptor = wgpu::DeviceDescriptor {
+            required_limits: limits,
+            ..Default::default()
+        };
+        let Ok((device, queue)) = pollster::block_on(adapter.request_device(&descriptor)) else {
+            eprintln!("no device, skipped");
+            return;
+        };
+        let Some(mut quantizer) = GpuQuantizer::new(&adapter, &device) else {
+            eprintln!("no compute shaders, skipped");
+            return;
+        };
+
+        let (width, height) = (37, 23); // not a whole number of words
+        let mut seed = 1u32;
+        let rgba = (0..width * height)
+            .flat_map(|i| {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                [(seed >> 24) as u8, (i % 251) as u8, (seed >> 8) as u8, 255]
+            })
+            .collect::<Vec<u8>>();
+        let nq = color_quant::NeuQuant::new(10, 256, &rgba);
+        let frame = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            frame.as_image_copy(),
+            &rgba,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 4),
+                rows_per_image: None,
+            },
+            frame.size(),
+        );
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: indexed_bytes((width, height)),
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let encoding = std::array::from_fn(|i| i as u8);
+        let part = ((0, 0), (width, height));
+        let palette = nq.color_map_rgba();
+        quantizer.index(
+            &device, &queue, &frame, part, &palette, &encoding, &readback,
+        );
+        readback.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::PollType::Wait).unwrap();
+
+        let indices = readback.slice(..).get_mapped_range();
+        let expected = rgba.chunks_exact(4).map(|c| nq.index_of(c) as u8);
+        assert!(indices.iter().zip(expected).all(|(&got, want)| got == want));
+    }
+}
@@ -1,19 +1,64 @@
 #[cfg(not(target_arch = "wasm32"))]
 use super::DRAWING_ALPHA;
 
+use super::CvdFilter;
 use super::GuiMode;
 use super::ObamifyApp;
+use super::RenderMode;
+use super::live_source::LiveSource;
 use crate::app::DEFAULT_RESOLUTION;
+use crate::app::backup::BACKUP_EXTENSION;
+use crate::app::backup::Backup;
+use crate::app::backup::Import;
+use crate::app::backup::Resolution;
 use crate::app::calculate;
 use crate::app::calculate::ProgressMsg;
+use crate::app::calculate::Stage;
+use crate::app::calculate::crop_preview::{CropPreview, PREVIEW_SIDELEN};
+use crate::app::calculate::preprocess;
+use crate::app::calculate::preprocess::HistogramMatch;
+use crate::app::calculate::preprocess::MAX_PALETTE_COLORS;
+use crate::app::calculate::preprocess::Palette;
+use crate::app::calculate::queue::Job;
+use crate::app::calculate::queue::JobQueue;
+use crate::app::calculate::queue::Priority;
+use crate::app::calculate::stats;
+use crate::app::calculate::stats::GenerationStats;
+use crate::app::calculate::stats::QualityReport;
+use crate::app::calculate::text_target::TextFont;
+use crate::app::calculate::text_target::TextTarget;
+use crate::app::calculate::util::BlendSource;
 use crate::app::calculate::util::CropScale;
 use crate::app::calculate::util::GenerationSettings;
+use crate::app::calculate::util::MAX_CROP_ZOOM;
+use crate::app::calculate::util::ProximityMap;
 use crate::app::calculate::util::SourceImg;
-use crate::app::gif_recorder::GIF_FRAMERATE;
-use crate::app::gif_recorder::GIF_RESOLUTION;
+use crate::app::calculate::util::{Aspect, Symmetry, Unmasked};
+use crate::app::config::Config;
+use crate::app::gif_recorder::GifRecorder;
 use crate::app::gif_recorder::GifStatus;
+use crate::app::gif_recorder::Timelapse;
+use crate::app::gif_recorder::{
+    ExportFormat, ExportProfile, FrameShape, LoopMode, MAX_FRAMERATE, MAX_PALETTE_QUALITY,
+    MAX_TAKES, StopCombine, StopRules,
+};
+use crate::app::icc;
+use crate::app::lut;
+use crate::app::mosaic;
+use crate::app::mosaic::TileAtlas;
+use crate::app::overlay;
+use crate::app::power;
+use crate::app::preset;
+use crate::app::preset::BrokenPreset;
 use crate::app::preset::Preset;
+use crate::app::preset::PresetFilter;
+use crate::app::preset::PresetSort;
+use crate::app::preset::RunRecord;
 use crate::app::preset::UnprocessedPreset;
+use crate::app::sprite_sheet;
+use crate::app::uv_map;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::app::video_recorder;
 use eframe::App;
 use eframe::Frame;
 use egui::Color32;
@@ -30,11 +75,63 @@ use uuid::Uuid;
 // #[cfg(not(target_arch = "wasm32"))]
 // use std::thread as wasm_thread;
 
+const QUANTIZED_PREVIEW_SIZE: f32 = 160.0;
+const IMAGE_FILE_EXTENSIONS: [&str; 8] =
+    ["png", "jpg", "jpeg", "webp", "tif", "tiff", "exr", "hdr"];
+
+/// What to do with a file from the browser's picker. The picker finishes after the frame
+/// that opened it, so this comes back through a channel and `update` runs it.
+#[cfg(target_arch = "wasm32")]
+type PickedFile = Box<dyn FnOnce(&mut ObamifyApp)>;
+
 #[derive(Default)]
 struct GuiImageCache {
     source_preview: Option<egui::TextureHandle>,
     target_preview: Option<egui::TextureHandle>,
     overlap_preview: Option<egui::TextureHandle>,
+    mask: MaskEditor,
+    show_weights: bool, // over the target preview
+    result: ResultPreview,
+}
+
+const RESULT_PREVIEW_SIZE: f32 = 96.0;
+const RESULT_PREVIEW_REFRESH: f64 = 0.3; // seconds between restarts while cropping
+
+/// A quick solve of the current crops, restarted whenever they change.
+#[derive(Default)]
+struct ResultPreview {
+    solve: Option<CropPreview>,
+    texture: Option<egui::TextureHandle>,
+    stale: bool,
+    started: f64,
+}
+
+const MASK_EDITOR_SIZE: f32 = 256.0;
+
+#[derive(Clone, Copy, PartialEq)]
+enum MaskTool {
+    Brush,
+    Lasso,
+}
+
+struct MaskEditor {
+    tool: MaskTool,
+    erase: bool,
+    brush_radius: f32,       // in editor pixels
+    stroke: Vec<egui::Pos2>, // 0..1 across the cropped source, while dragging
+    preview: Option<egui::TextureHandle>,
+}
+
+impl Default for MaskEditor {
+    fn default() -> Self {
+        Self {
+            tool: MaskTool::Brush,
+            erase: false,
+            brush_radius: 12.0,
+            stroke: Vec::new(),
+            preview: None,
+        }
+    }
 }
 
 pub(crate) struct GuiState {
@@ -42,19 +139,62 @@ pub(crate) struct GuiState {
     pub last_mouse_pos: Option<(f32, f32)>,
     #[cfg(not(target_arch = "wasm32"))]
     pub drawing_color: [f32; 4],
-    mode: GuiMode,
+    pub mode: GuiMode,
     pub animate: bool,
     //pub fps_text: String,
     show_progress_modal: Option<Uuid>,
     last_progress: f32,
+    last_stage: Stage,
+    progress_warnings: Vec<String>,
     process_cancelled: Arc<AtomicBool>,
+    #[cfg(not(target_arch = "wasm32"))]
+    population_requested: Arc<AtomicBool>, // the running fast solve should save its state
+    #[cfg(not(target_arch = "wasm32"))]
+    population_saveable: bool, // the running job is a fast solve, which can save one
+    queue: JobQueue,
+    queue_priority: Priority,    // for the next job added
+    running_job: Option<String>, // name of the job in the progress modal
     //pub currently_processing: Option<Preset>,
     pub presets: Vec<Preset>,
+    broken_presets: Vec<BrokenPreset>,
+    show_broken_presets: bool,
+    preset_filter: PresetFilter,
+    preset_search: String,
+    preset_sort: PresetSort,
+    organizing_presets: bool,
+    editing_profiles: bool, // of the current preset
+    new_folder: String,
+    editing_tags: Option<(usize, String)>, // preset index, comma separated tags
+    import: Option<Import>,
     //pub current_settings: GenerationSettings,
     configuring_generation: Option<(SourceImg, GenerationSettings, GuiImageCache)>,
     saved_config: Option<(SourceImg, GenerationSettings)>,
+    pub selecting_capture_region: bool,
+    pub quantized_texture: Option<TextureHandle>, // last recorded frame, in the gif's colors
+    take_count: u32,
+    record_timelapse: bool,
+    convergence_stats: Vec<GenerationStats>, // of the calculation in progress
+    run_started: Option<(RunRecord, Option<Uuid>, f64)>, // its history entry, parent and start
+    show_stats_chart: bool,
+    show_history: bool,
+    config_editor: Option<(String, Option<String>)>, // toml being edited, parse error
+    pub capture_drag_start: Option<egui::Pos2>,
+    text_target: TextTarget,
+    #[cfg(not(target_arch = "wasm32"))]
+    gif_target: Option<calculate::video::GifTarget>,
+    #[cfg(not(target_arch = "wasm32"))]
+    source_sequence: Option<calculate::video::SourceSequence>,
+    #[cfg(not(target_arch = "wasm32"))]
+    folder_watch: Option<calculate::watch::FolderWatch>,
+    #[cfg(target_os = "linux")]
+    screen_capture: Option<std::sync::mpsc::Receiver<crate::app::screen_capture::Capture>>,
+    #[cfg(target_arch = "wasm32")]
+    picked_tx: std::sync::mpsc::Sender<PickedFile>, // for the browser's pickers to send back on
+    #[cfg(target_arch = "wasm32")]
+    picked_rx: std::sync::mpsc::Receiver<PickedFile>,
     pub current_preset: usize,
     error_message: Option<String>,
+    pub motion_focus: f32, // -1 to 1: how much earlier important cells arrive
 
     has_obamified_once: bool,
 }
@@ -62,18 +202,40 @@ pub(crate) struct GuiState {
 impl GuiState {
     pub fn default(
         presets: Vec<Preset>,
+        broken_presets: Vec<BrokenPreset>,
         current_preset: usize,
         has_obamified_once: bool,
     ) -> GuiState {
+        #[cfg(target_arch = "wasm32")]
+        let (picked_tx, picked_rx) = std::sync::mpsc::channel();
         GuiState {
+            show_broken_presets: broken_presets.iter().any(|b| !b.quarantined),
+            broken_presets,
             animate: true,
             //fps_text: String::new(),
             presets,
+            preset_filter: PresetFilter::All,
+            preset_search: String::new(),
+            preset_sort: PresetSort::Name,
+            organizing_presets: false,
+            editing_profiles: false,
+            new_folder: String::new(),
+            editing_tags: None,
+            import: None,
             mode: GuiMode::Transform,
             show_progress_modal: None,
             last_progress: 0.0,
+            last_stage: Stage::Preparing,
+            progress_warnings: Vec::new(),
             process_cancelled: Arc::new(AtomicBool::new(false)),
             #[cfg(not(target_arch = "wasm32"))]
+            population_requested: Arc::new(AtomicBool::new(false)),
+            #[cfg(not(target_arch = "wasm32"))]
+            population_saveable: false,
+            queue: JobQueue::default(),
+            queue_priority: Priority::Normal,
+            running_job: None,
+            #[cfg(not(target_arch = "wasm32"))]
             last_mouse_pos: None,
             #[cfg(not(target_arch = "wasm32"))]
             drawing_color: [0.0, 0.0, 0.0, DRAWING_ALPHA],
@@ -81,20 +243,48 @@ impl GuiState {
             //current_settings: GenerationSettings::default(),
             configuring_generation: None,
             saved_config: None,
+            selecting_capture_region: false,
+            quantized_texture: None,
+            take_count: 3,
+            record_timelapse: false,
+            convergence_stats: Vec::new(),
+            run_started: None,
+            show_stats_chart: false,
+            show_history: false,
+            config_editor: None,
+            capture_drag_start: None,
+            text_target: TextTarget::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            gif_target: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            source_sequence: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            folder_watch: None,
+            #[cfg(target_os = "linux")]
+            screen_capture: None,
+            #[cfg(target_arch = "wasm32")]
+            picked_tx,
+            #[cfg(target_arch = "wasm32")]
+            picked_rx,
             current_preset,
             error_message: None,
+            motion_focus: 0.0,
             has_obamified_once,
         }
     }
 
     fn show_progress_modal(&mut self, id: Uuid) {
         self.show_progress_modal = Some(id);
+        self.last_stage = Stage::Preparing;
+        self.progress_warnings.clear();
+        self.convergence_stats.clear();
         #[cfg(target_arch = "wasm32")]
         hide_icons();
     }
 
     fn hide_progress_modal(&mut self) {
         self.show_progress_modal = None;
+        self.running_job = None;
         #[cfg(target_arch = "wasm32")]
         show_icons();
     }
@@ -137,7 +327,11 @@ fn hide_icons() {
 impl App for ObamifyApp {
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
         eframe::set_value(storage, "presets", &self.gui.presets);
+        eframe::set_value(storage, "broken_presets", &self.gui.broken_presets);
         eframe::set_value(storage, "has_obamified_once", &self.gui.has_obamified_once);
+        eframe::set_value(storage, "telemetry", &self.telemetry);
+        #[cfg(target_arch = "wasm32")]
+        eframe::set_value(storage, "config", &self.config.to_toml());
     }
     fn update(&mut self, ctx: &egui::Context, frame: &mut Frame) {
         let Some(rs) = frame.wgpu_render_state() else {
@@ -155,6 +349,9 @@ impl App for ObamifyApp {
         //     self.resize(rs, target_size);
         // }
 
+        // small previews render coarser, which reallocates the textures
+        self.update_lod(device, &rs.queue);
+
         // Ensure texture is registered exactly once per allocation
         self.ensure_registered_texture(
             rs,
@@ -167,17 +364,24 @@ impl App for ObamifyApp {
 
         #[cfg(target_arch = "wasm32")]
         self.ensure_worker(ctx);
+        #[cfg(target_arch = "wasm32")]
+        while let Ok(picked) = self.gui.picked_rx.try_recv() {
+            picked(self);
+        }
+        self.power.poll(ctx.input(|i| i.time));
+        #[cfg(target_os = "linux")]
+        if self.gui.screen_capture.is_some() {
+            self.poll_screen_capture();
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+        self.start_next_job(device, ctx.input(|i| i.time));
 
         // Run GPU pipeline
         if let Some(img) = &self.preview_image {
             // show image
-            let img = if img.width() != self.size.0 || img.height() != self.size.1 {
-                &image::imageops::resize(
-                    img,
-                    self.size.0,
-                    self.size.1,
-                    image::imageops::FilterType::Nearest,
-                )
+            let (width, height) = self.render_size();
+            let img = if img.width() != width || img.height() != height {
+                &image::imageops::resize(img, width, height, image::imageops::FilterType::Nearest)
             } else {
                 img
             };
@@ -193,64 +397,133 @@ impl App for ObamifyApp {
                 &rgba,
                 wgpu::TexelCopyBufferLayout {
                     offset: 0,
-                    bytes_per_row: Some(4 * self.size.0),
-                    rows_per_image: Some(self.size.1),
+                    bytes_per_row: Some(4 * width),
+                    rows_per_image: Some(height),
                 },
                 wgpu::Extent3d {
-                    width: self.size.0,
-                    height: self.size.1,
+                    width,
+                    height,
                     depth_or_array_layers: 1,
                 },
             );
         } else {
+            if let Err(err) = self.poll_live_source(device, &rs.queue, ctx.input(|i| i.time)) {
+                self.gui
+                    .show_error(format!("stopped the live source: {}", err));
+            }
+            if self.live_source.is_some() {
+                ctx.request_repaint();
+            }
+            if std::mem::take(&mut self.color_lookup_stale) {
+                self.write_color_lookup(&rs.queue);
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if self.gif_recorder.is_recording() && self.gif_recorder.png_dir.is_some() {
+                self.write_motion_alpha(&rs.queue);
+            }
             self.run_gpu(rs);
+            self.request_snapshot(device, &rs.queue);
+            if self.gif_recorder.snapshot_pending() || self.gif_recorder.still_job().is_some() {
+                let name = format!(
+                    "{}_frame",
+                    self.gif_recorder.get_name(self.sim.name(), self.reverse)
+                );
+                match self.gif_recorder.poll_snapshot(name) {
+                    Ok(_) => ctx.request_repaint(),
+                    Err(err) => self
+                        .gui
+                        .show_error(format!("failed to save frame: {}", err)),
+                }
+            }
+
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(image) = ctx.input(|i| {
+                i.events.iter().find_map(|event| match event {
+                    egui::Event::Screenshot { image, .. } => Some(image.clone()),
+                    _ => None,
+                })
+            }) {
+                self.gif_recorder.receive_window_frame(image);
+            }
+
+            if self.gif_recorder.poll_job() == Some(false) {
+                // cancelled
+                self.stop_recording_gif(device, &rs.queue);
+            }
+
+            let now = ctx.input(|i| i.time);
+            if self.gif_recorder.start_due(now) {
+                self.start_recording_take(device, &rs.queue);
+            } else if let Some(wait) = self.gif_recorder.seconds_until_start(now) {
+                ctx.request_repaint_after(std::time::Duration::from_secs_f64(wait.min(0.25)));
+            }
 
             if self.gui.animate {
                 if self.gif_recorder.is_recording() {
-                    if self.gif_recorder.no_inflight() {
-                        if let Err(e) = self.get_color_image_data(device, &rs.queue) {
-                            self.gif_recorder.status = GifStatus::Error(e.to_string());
-                        }
-                    }
-                    match self.gif_recorder.try_write_frame() {
+                    let mut restarted = false; // the next take's first frame isn't rendered yet
+                    match self.gif_recorder.try_write_frame(device, &rs.queue) {
                         Err(e) => {
                             self.gif_recorder.status = GifStatus::Error(e.to_string());
                             self.gui.animate = false;
                         }
                         Ok(true) => {
-                            for _ in 0..(60 / GIF_FRAMERATE) {
-                                self.sim.update(&mut self.seeds, self.size.0);
-                            }
-
                             self.gif_recorder.frame_count += 1;
 
-                            if self.gif_recorder.should_stop() {
-                                // finish recording
-                                if !self.gif_recorder.finish(
-                                    self.gif_recorder.get_name(self.sim.name(), self.reverse),
-                                ) {
-                                    // cancelled
-                                    self.stop_recording_gif(device, &rs.queue);
-                                }
+                            if self.gif_recorder.should_stop(|| self.animation_arrived()) {
+                                if self.gif_recorder.is_multi_take() {
+                                    let frames = self.gif_recorder.frame_count;
+                                    self.gif_recorder.finish_take();
+                                    if self.gif_recorder.takes_remaining() > 0 {
+                                        self.start_recording_take(device, &rs.queue);
+                                        restarted = true;
+                                    } else {
+                                        self.telemetry.finish_export(frames, now);
+                                        self.gif_recorder.status = GifStatus::PickTake;
+                                        self.gui.animate = false;
+                                    }
+                                } else {
+                                    self.telemetry
+                                        .finish_export(self.gif_recorder.frame_count, now);
+                                    // finish recording
+                                    if !self.gif_recorder.finish(self.sim.name(), self.reverse) {
+                                        // cancelled
+                                        self.stop_recording_gif(device, &rs.queue);
+                                    }
 
-                                self.gui.animate = false;
-                            } else {
-                                // queue next frame
-                                if let Err(e) = self.get_color_image_data(device, &rs.queue) {
-                                    self.gif_recorder.status = GifStatus::Error(e.to_string());
+                                    self.gui.animate = false;
                                 }
                             }
                         }
 
                         Ok(false) => { /* not ready yet */ }
                     }
+                    // the animation moves on as soon as a frame is asked for, while earlier
+                    // ones are still being read back
+                    if self.gif_recorder.is_recording()
+                        && self.gif_recorder.wants_frame()
+                        && !restarted
+                    {
+                        match self.request_frame(ctx, device, &rs.queue) {
+                            Ok(()) => {
+                                let frame = self.gif_recorder.requested - 1;
+                                for _ in 0..self.gif_recorder.limits.steps_after(frame) {
+                                    self.step_sim();
+                                }
+                            }
+                            Err(e) => self.gif_recorder.status = GifStatus::Error(e.to_string()),
+                        }
+                    }
                 } else {
-                    self.sim.update(&mut self.seeds, self.size.0);
+                    for _ in 0..self.steps_per_repaint() {
+                        self.step_sim();
+                    }
+                }
+                if !self.instanced_motion() {
+                    rs.queue
+                        .write_buffer(&self.seed_buf, 0, bytemuck::cast_slice(&self.seeds));
+                    // Update seed texture for WebGL compatibility
+                    self.update_seed_texture_data(&rs.queue, &self.seeds);
                 }
-                rs.queue
-                    .write_buffer(&self.seed_buf, 0, bytemuck::cast_slice(&self.seeds));
-                // Update seed texture for WebGL compatibility
-                self.update_seed_texture_data(&rs.queue, &self.seeds);
             }
         }
 
@@ -287,14 +560,14 @@ impl App for ObamifyApp {
 
                             while let Some(msg) = self.get_latest_msg() {
                                 match msg {
-                                    ProgressMsg::UpdatePreview {
-                                        width,
-                                        height,
-                                        data,
-                                    } => {
-                                        let image =
-                                            image::ImageBuffer::from_vec(width, height, data);
-                                        self.preview_image = image;
+                                    ProgressMsg::Progress(update) => {
+                                        if let Some(preview) = update.preview {
+                                            self.preview_image = image::ImageBuffer::from_vec(
+                                                preview.width,
+                                                preview.height,
+                                                preview.data,
+                                            );
+                                        }
                                     }
                                     ProgressMsg::Cancelled => {
                                         self.gui.process_cancelled.store(false, Ordering::Relaxed);
@@ -305,9 +578,12 @@ impl App for ObamifyApp {
                                     ProgressMsg::UpdateAssignments(assignments) => {
                                         self.sim.set_assignments(assignments, self.size.0)
                                     }
-                                    ProgressMsg::Progress(_) => todo!(),
+                                    ProgressMsg::Warning(_) => {}
                                     ProgressMsg::Done(_) => todo!(),
+                                    // drawing solves a single image, never a sequence
+                                    ProgressMsg::FrameDone(_) => {}
                                     ProgressMsg::Error(_) => todo!(),
+                                    ProgressMsg::Population(_) => {}
                                 }
                             }
 
@@ -325,7 +601,30 @@ impl App for ObamifyApp {
                             ui.horizontal_wrapped(|ui| {
                                 if ui.add(egui::Button::new("play transformation")).clicked() {
                                     self.gui.animate = true;
-                                    self.sim.prepare_play(&mut self.seeds, self.reverse);
+                                    self.prepare_play(device);
+                                }
+                                let favorites = self
+                                    .gui
+                                    .presets
+                                    .iter()
+                                    .enumerate()
+                                    .filter(|(_, p)| p.meta.favorite)
+                                    .map(|(i, p)| (i, p.inner.name.clone()))
+                                    .collect::<Vec<_>>();
+                                if !favorites.is_empty() {
+                                    ui.menu_button("★", |ui| {
+                                        for (i, name) in favorites {
+                                            let selected = i == self.gui.current_preset;
+                                            if ui.selectable_label(selected, name).clicked() {
+                                                let preset = self.gui.presets[i].clone();
+                                                self.change_sim(device, &rs.queue, preset, i);
+                                                self.gui.animate = true;
+                                                ui.close();
+                                            }
+                                        }
+                                    })
+                                    .response
+                                    .on_hover_text("switch to a favorite");
                                 }
                                 if ui
                                     .add(egui::Checkbox::new(&mut self.reverse, "reverse"))
@@ -334,6 +633,165 @@ impl App for ObamifyApp {
                                     self.gui.animate = true;
                                     self.reset_sim(device, &rs.queue);
                                 }
+                                if ui
+                                    .add(
+                                        egui::Slider::new(&mut self.gui.motion_focus, -1.0..=1.0)
+                                            .text("features first"),
+                                    )
+                                    .on_hover_text(
+                                        "above 0 the parts of the target that matter most arrive first and the background after; below 0 they arrive last",
+                                    )
+                                    .drag_stopped()
+                                {
+                                    self.gui.animate = true;
+                                    self.reset_sim(device, &rs.queue);
+                                }
+
+                                let mut render_mode = self.render_mode;
+                                egui::ComboBox::from_id_salt("render_mode_select")
+                                    .selected_text(render_mode.label())
+                                    .show_ui(ui, |ui| {
+                                        for mode in RenderMode::ALL {
+                                            ui.selectable_value(&mut render_mode, mode, mode.label());
+                                        }
+                                    });
+                                if render_mode != self.render_mode {
+                                    self.set_render_mode(device, &rs.queue, render_mode);
+                                }
+                                let mut cvd_filter = self.cvd_filter;
+                                egui::ComboBox::from_id_salt("cvd_filter_select")
+                                    .selected_text(cvd_filter.label())
+                                    .show_ui(ui, |ui| {
+                                        for filter in CvdFilter::ALL {
+                                            ui.selectable_value(&mut cvd_filter, filter, filter.label());
+                                        }
+                                    })
+                                    .response
+                                    .on_hover_text(
+                                        "preview as seen with color blindness; recordings keep the real colors",
+                                    );
+                                if cvd_filter != self.cvd_filter {
+                                    self.set_cvd_filter(&rs.queue, cvd_filter);
+                                }
+                                let mut tiled = self.tiled_preview;
+                                if ui
+                                    .checkbox(&mut tiled, "tile 3x3")
+                                    .on_hover_text("preview the result repeated, to check the edges meet")
+                                    .changed()
+                                {
+                                    self.set_tiled_preview(&rs.queue, tiled);
+                                }
+                                ui.menu_button("grid", |ui| {
+                                    let grid = &mut self.gif_recorder.overlay;
+                                    ui.checkbox(&mut grid.enabled, "show grid")
+                                        .on_hover_text("lines and cell numbers over the animation, recorded too");
+                                    ui.add_enabled_ui(grid.enabled, |ui| {
+                                        ui.add(
+                                            egui::DragValue::new(&mut grid.divisions)
+                                                .range(2..=overlay::MAX_DIVISIONS)
+                                                .suffix(" cells across"),
+                                        );
+                                        ui.horizontal(|ui| {
+                                            ui.color_edit_button_srgba(&mut grid.color);
+                                            ui.label("line color");
+                                        });
+                                        ui.checkbox(&mut grid.labels, "number cells");
+                                    });
+                                });
+                                ui.menu_button("source", |ui| {
+                                    if let Some(live) = &self.live_source {
+                                        ui.label(format!("showing {}", live.label));
+                                    }
+                                    if ui
+                                        .button("swap image...")
+                                        .on_hover_text(
+                                            "play another picture through this morph, without solving again",
+                                        )
+                                        .clicked()
+                                    {
+                                        prompt_image("choose image to swap in", self, |name, img, app| {
+                                            app.set_live_source(LiveSource::still(img, name));
+                                        });
+                                    }
+                                    #[cfg(not(target_arch = "wasm32"))]
+                                    {
+                                        let clicked = ui
+                                            .button("play gif through...")
+                                            .on_hover_text("loop an animated gif through this morph")
+                                            .clicked();
+                                        if clicked {
+                                            if let Some(path) = rfd::FileDialog::new()
+                                                .set_title("choose gif to play through")
+                                                .add_filter("animated gif", &["gif"])
+                                                .pick_file()
+                                            {
+                                                match calculate::video::GifTarget::load(&path) {
+                                                    Ok(video) => {
+                                                        let name = path.file_name().unwrap_or_default();
+                                                        self.set_live_source(LiveSource::frames(
+                                                            video.frames,
+                                                            video.delays,
+                                                            get_default_preset_name(
+                                                                name.to_string_lossy().into_owned(),
+                                                            ),
+                                                        ));
+                                                    }
+                                                    Err(e) => self
+                                                        .gui
+                                                        .show_error(format!("failed to load gif: {}", e)),
+                                                }
+                                            }
+                                        }
+                                        let clicked = ui
+                                            .button("follow file...")
+                                            .on_hover_text(
+                                                "show an image file every time it changes, like a webcam's frames from ffmpeg -update 1 frame.png",
+                                            )
+                                            .clicked();
+                                        if clicked {
+                                            if let Some(path) = rfd::FileDialog::new()
+                                                .set_title("choose image to follow")
+                                                .add_filter("image files", &IMAGE_FILE_EXTENSIONS)
+                                                .pick_file()
+                                            {
+                                                self.set_live_source(LiveSource::file(path));
+                                            }
+                                        }
+                                    }
+                                    if ui
+                                        .add_enabled(self.live_source.is_some(), egui::Button::new("restore original"))
+                                        .clicked()
+                                    {
+                                        self.clear_live_source(device, &rs.queue);
+                                    }
+                                });
+                                let mut provenance = self.provenance_preview;
+                                if ui
+                                    .checkbox(&mut provenance, "show origins")
+                                    .on_hover_text(
+                                        "tint every pixel by where it started in the source: hue for the direction from the middle, strength for the distance; recordings keep the real colors",
+                                    )
+                                    .changed()
+                                {
+                                    self.set_provenance_preview(&rs.queue, provenance);
+                                }
+                                if self.render_mode == RenderMode::Photomosaic {
+                                    if ui.button("load tiles...").clicked() {
+                                        prompt_tile_library(self, |atlas, app| {
+                                            app.pending_tile_atlas = Some(atlas);
+                                        });
+                                    }
+                                    if ui
+                                        .checkbox(&mut self.unique_tiles, "unique tiles")
+                                        .on_hover_text("use each tile as few times as possible")
+                                        .changed()
+                                    {
+                                        self.refresh_tiles(device, &rs.queue);
+                                    }
+                                }
+                                if let Some(atlas) = self.pending_tile_atlas.take() {
+                                    self.set_photo_atlas(device, &rs.queue, atlas);
+                                }
                                 // if ui.button("reload").clicked() {
                                 //     self.reset_sim(device, &rs.queue);
                                 //     self.gui.animate = false;
@@ -349,26 +807,435 @@ impl App for ObamifyApp {
                                 })
                                 .clicked()
                             {
-                                self.gif_recorder.status = GifStatus::Recording;
-                                self.gif_recorder.encoder = None;
-                                if let Err(err) = self
-                                    .gif_recorder
-                                    .init_encoder(self.colors.read().unwrap().as_ref())
+                                self.start_gif(device, &rs.queue, ui.input(|i| i.time));
+                            }
+                            if ui
+                                .button("save frame as png")
+                                .on_hover_text("the frame shown now, at full resolution")
+                                .clicked()
+                            {
+                                self.gif_recorder.snapshot_wanted = true;
+                            }
+                            #[cfg(not(target_arch = "wasm32"))]
+                            if ui
+                                .button("save video")
+                                .on_hover_text("webm with every color and no size limit")
+                                .clicked()
+                            {
+                                self.start_video(device, &rs.queue, ui.input(|i| i.time));
+                            }
+                            #[cfg(not(target_arch = "wasm32"))]
+                            if self.gif_recorder.audio.is_some() {
+                                if ui
+                                    .button("🎵 remove music")
+                                    .on_hover_text("save videos silent again")
+                                    .clicked()
                                 {
-                                    self.gif_recorder.status = GifStatus::Error(err.to_string());
-                                } else {
-                                    self.resize_textures(
-                                        device,
-                                        (GIF_RESOLUTION, GIF_RESOLUTION),
-                                        false,
+                                    self.gif_recorder.audio = None;
+                                }
+                            } else if ui
+                                .button("add music...")
+                                .on_hover_text("an .opus file to play under videos, looped or cut to length")
+                                .clicked()
+                            {
+                                prompt_file("music for videos", "opus", self, |data, app| {
+                                    match video_recorder::OpusAudio::parse(&data) {
+                                        Ok(audio) => app.gif_recorder.audio = Some(audio),
+                                        Err(err) => app.gui.show_error(err.to_string()),
+                                    }
+                                });
+                            }
+                            if self.gif_recorder.lut.is_some() {
+                                if ui
+                                    .button("remove LUT")
+                                    .on_hover_text("export colors as they are again")
+                                    .clicked()
+                                {
+                                    self.gif_recorder.lut = None;
+                                    self.color_lookup_stale = true;
+                                }
+                                if ui
+                                    .checkbox(&mut self.gif_recorder.lut_preview, "grade preview")
+                                    .on_hover_text("show the LUT's grading in the preview too")
+                                    .changed()
+                                {
+                                    self.color_lookup_stale = true;
+                                }
+                            } else if ui
+                                .button("load LUT...")
+                                .on_hover_text("a .cube color grade applied to exported frames")
+                                .clicked()
+                            {
+                                prompt_file("color LUT", lut::LUT_EXTENSION, self, |data, app| {
+                                    match lut::Lut::parse(&String::from_utf8_lossy(&data)) {
+                                        Ok(lut) => {
+                                            app.gif_recorder.lut = Some(Arc::new(lut));
+                                            app.color_lookup_stale = true;
+                                        }
+                                        Err(err) => {
+                                            app.gui.show_error(format!("failed to load LUT: {}", err))
+                                        }
+                                    }
+                                });
+                            }
+                            ui.checkbox(&mut self.gif_recorder.both_directions, "both ways")
+                                .on_hover_text(
+                                    "also save the opposite transformation, from the same frames",
+                                );
+                            #[cfg(not(target_arch = "wasm32"))]
+                            ui.checkbox(&mut self.gif_recorder.window.enabled, "whole window")
+                                .on_hover_text(
+                                    "record the interface along with the animation, for tutorials",
+                                );
+                            #[cfg(not(target_arch = "wasm32"))]
+                            ui.checkbox(&mut self.gif_recorder.stream, "save as it records")
+                                .on_hover_text(
+                                    "choose where gifs go first and write them there while recording, so long ones don't fill memory",
+                                );
+                            if ui
+                                .add(
+                                    egui::DragValue::new(&mut self.config.gif.framerate)
+                                        .range(1..=MAX_FRAMERATE)
+                                        .suffix(" fps"),
+                                )
+                                .on_hover_text("frames captured per second of animation")
+                                .changed()
+                            {
+                                self.gif_recorder.limits.framerate = self.config.gif.framerate;
+                            }
+                            if ui
+                                .add(
+                                    egui::Slider::new(
+                                        &mut self.config.gif.palette_quality,
+                                        1..=MAX_PALETTE_QUALITY,
+                                    )
+                                    .text("palette"),
+                                )
+                                .on_hover_text(
+                                    "how closely gif colors follow the image; lower is faster for big grids",
+                                )
+                                .changed()
+                            {
+                                self.gif_recorder.limits.palette_quality =
+                                    self.config.gif.palette_quality;
+                            }
+                            ui.menu_button("gif size", |ui| {
+                                let gif = &mut self.config.gif;
+                                let mut changed = ui
+                                    .add(
+                                        egui::DragValue::new(&mut gif.resolution)
+                                            .range(16..=DEFAULT_RESOLUTION)
+                                            .prefix("resolution ")
+                                            .suffix(" px"),
+                                    )
+                                    .changed();
+                                changed |= ui
+                                    .add(
+                                        egui::DragValue::new(&mut gif.speed)
+                                            .range(0.25..=4.0)
+                                            .speed(0.05)
+                                            .prefix("speed ")
+                                            .suffix("x"),
+                                    )
+                                    .on_hover_text("playback speed relative to the preview")
+                                    .changed();
+                                changed |= ui
+                                    .add(
+                                        egui::DragValue::new(&mut gif.max_size_mb)
+                                            .range(1..=500)
+                                            .prefix("at most ")
+                                            .suffix(" MB"),
+                                    )
+                                    .changed();
+                                changed |= ui
+                                    .checkbox(&mut gif.fit_size, "shrink to fit")
+                                    .on_hover_text(
+                                        "lower the framerate, colors and then resolution as needed to fit the whole animation in, instead of cutting it off",
+                                    )
+                                    .changed();
+                                changed |= ui
+                                    .add(
+                                        egui::DragValue::new(&mut gif.max_seconds)
+                                            .range(0.0..=120.0)
+                                            .speed(0.1)
+                                            .custom_formatter(|v, _| {
+                                                if v > 0.0 {
+                                                    format!("{:.1} s long", v)
+                                                } else {
+                                                    "any length".to_owned()
+                                                }
+                                            }),
+                                    )
+                                    .on_hover_text(
+                                        "record this many seconds instead of a number of frames",
+                                    )
+                                    .changed();
+                                ui.add(
+                                    egui::DragValue::new(&mut self.gif_recorder.start_delay)
+                                        .range(0.0..=30.0)
+                                        .speed(0.1)
+                                        .prefix("start after ")
+                                        .suffix(" s"),
+                                )
+                                .on_hover_text("time to get the window ready before recording");
+                                ui.horizontal(|ui| {
+                                    changed |= loop_mode_ui(ui, "gif_loop", &mut gif.loop_mode);
+                                });
+                                changed |= ui
+                                    .checkbox(&mut gif.dither, "dither")
+                                    .on_hover_text("grain instead of banding in smooth gradients")
+                                    .changed();
+                                changed |= ui
+                                    .checkbox(&mut gif.local_palettes, "palette per frame")
+                                    .on_hover_text(
+                                        "learn each frame's colors, for targets with lots of them",
+                                    )
+                                    .changed();
+                                if changed {
+                                    let limits = &mut self.gif_recorder.limits;
+                                    limits.resolution = gif.resolution;
+                                    limits.speed = gif.speed;
+                                    limits.max_size_mb = gif.max_size_mb;
+                                    limits.max_seconds = gif.max_seconds;
+                                    limits.dither = gif.dither;
+                                    limits.loop_mode = gif.loop_mode;
+                                    limits.local_palettes = gif.local_palettes;
+                                    limits.fit_size = gif.fit_size;
+                                }
+                            })
+                            .response
+                            .on_hover_text("resolution, speed and size limit of saved gifs");
+                            ui.menu_button("stop when", |ui| {
+                                let stop = &mut self.config.gif.stop;
+                                let mut changed = ui
+                                    .checkbox(&mut stop.max_frames, "frame limit")
+                                    .on_hover_text(
+                                        "the most frames a recording takes, or its length if one is set",
+                                    )
+                                    .changed();
+                                changed |= ui
+                                    .checkbox(&mut stop.max_size, "size limit")
+                                    .on_hover_text("the next frame wouldn't fit in the gif size limit")
+                                    .changed();
+                                changed |= ui
+                                    .checkbox(&mut stop.arrived, "animation done")
+                                    .on_hover_text("every cell has reached its place")
+                                    .changed();
+                                let mut manual = stop.manual();
+                                if ui
+                                    .checkbox(&mut manual, "manual only")
+                                    .on_hover_text("record until stop is pressed")
+                                    .changed()
+                                {
+                                    *stop = StopRules {
+                                        max_frames: !manual,
+                                        max_size: !manual,
+                                        arrived: false,
+                                        combine: stop.combine,
+                                    };
+                                    changed = true;
+                                }
+                                ui.add_enabled_ui(!stop.manual(), |ui| {
+                                    ui.horizontal(|ui| {
+                                        ui.label("stop on");
+                                        changed |= ui
+                                            .radio_value(&mut stop.combine, StopCombine::Any, "any")
+                                            .changed();
+                                        changed |= ui
+                                            .radio_value(&mut stop.combine, StopCombine::All, "all")
+                                            .changed();
+                                    });
+                                });
+                                if changed {
+                                    self.gif_recorder.limits.stop = *stop;
+                                }
+                            })
+                            .response
+                            .on_hover_text("what ends a recording on its own");
+                            ui.add(
+                                egui::DragValue::new(&mut self.gui.take_count)
+                                    .range(2..=MAX_TAKES)
+                                    .suffix(" takes"),
+                            );
+                            if ui
+                                .button("record takes")
+                                .on_hover_text(
+                                    "record several variations in a row and pick the best one",
+                                )
+                                .clicked()
+                            {
+                                let now = ui.input(|i| i.time);
+                                self.gif_recorder.start_takes(self.gui.take_count);
+                                self.telemetry.start_export(
+                                    "gif takes",
+                                    self.gif_recorder.render_size(),
+                                    now,
+                                );
+                                self.begin_recording(device, &rs.queue, now);
+                            }
+                            if self
+                                .gif_recorder
+                                .timelapse
+                                .as_ref()
+                                .is_some_and(|t| !t.is_empty())
+                                && ui
+                                    .button("save timelapse")
+                                    .on_hover_text("gif of the last calculation converging")
+                                    .clicked()
+                            {
+                                let name = self.gif_recorder.get_name(self.sim.name(), false);
+                                self.gif_recorder.save_timelapse(name);
+                            }
+                            #[cfg(not(target_arch = "wasm32"))]
+                            if ui
+                                .button("export alpha frames")
+                                .on_hover_text(
+                                    "png sequence where only moving pixels are opaque, for compositing",
+                                )
+                                .clicked()
+                            {
+                                self.start_png_frames(device, &rs.queue, ui.input(|i| i.time));
+                            }
+                            let profiles = self
+                                .gui
+                                .presets
+                                .get(self.gui.current_preset)
+                                .map(|p| p.meta.export_profiles())
+                                .unwrap_or_default();
+                            ui.menu_button("export as", |ui| {
+                                for profile in &profiles {
+                                    if ui.button(&profile.name).clicked() {
+                                        let now = ui.input(|i| i.time);
+                                        self.start_profile_export(device, &rs.queue, profile, now);
+                                        ui.close();
+                                    }
+                                }
+                                ui.separator();
+                                if ui
+                                    .button("full color apng")
+                                    .on_hover_text("no palette, so gradients don't band")
+                                    .clicked()
+                                {
+                                    self.start_apng(device, &rs.queue, ui.input(|i| i.time));
+                                    ui.close();
+                                }
+                                if ui
+                                    .button("png frames (zip)")
+                                    .on_hover_text("every frame as a numbered png, for video editors")
+                                    .clicked()
+                                {
+                                    self.start_png_zip(device, &rs.queue, ui.input(|i| i.time));
+                                    ui.close();
+                                }
+                                ui.horizontal(|ui| {
+                                    let sheet = &mut self.gif_recorder.sprite_sheet;
+                                    let max = sprite_sheet::MAX_CELLS;
+                                    ui.add(egui::DragValue::new(&mut sheet.columns).range(1..=max))
+                                        .on_hover_text("columns");
+                                    ui.label("×");
+                                    ui.add(egui::DragValue::new(&mut sheet.rows).range(1..=max))
+                                        .on_hover_text("rows");
+                                    let cells = sheet.columns * sheet.rows;
+                                    ui.add(
+                                        egui::DragValue::new(&mut sheet.frames)
+                                            .range(1..=cells)
+                                            .suffix(" frames"),
                                     );
-                                    self.reset_sim(device, &rs.queue);
-                                    self.gui.animate = true;
-                                    for _ in 0..20 {
-                                        self.sim.update(&mut self.seeds, self.size.0);
+                                    if ui
+                                        .button("sprite sheet")
+                                        .on_hover_text("evenly spaced frames tiled into one png, for games and CSS")
+                                        .clicked()
+                                    {
+                                        self.start_sprite_sheet(device, &rs.queue, ui.input(|i| i.time));
+                                        ui.close();
+                                    }
+                                });
+                                ui.separator();
+                                let (mut error, mut export) = (None, None);
+                                if let Some(preset) = self.gui.presets.get(self.gui.current_preset) {
+                                    let side = preset.inner.width;
+                                    let name = self.sim.name();
+                                    if ui
+                                        .button("uv map (png)")
+                                        .on_hover_text(
+                                            "where each pixel comes from, as red and green, to redo the morph with your own footage",
+                                        )
+                                        .clicked()
+                                    {
+                                        match uv_map::to_png(&preset.assignments, side) {
+                                            Ok(data) => {
+                                                export = Some((format!("{}_uv.png", name), "png", data))
+                                            }
+                                            Err(err) => error = Some(format!(
+                                                "failed to export uv map: {}",
+                                                err
+                                            )),
+                                        }
+                                        ui.close();
                                     }
+                                    if ui
+                                        .button("pixel origins (json)")
+                                        .on_hover_text("the source x and y of every cell, row by row")
+                                        .clicked()
+                                    {
+                                        let json = uv_map::to_json(&preset.assignments, side);
+                                        export =
+                                            Some((format!("{}_origins.json", name), "json", json.into()));
+                                        ui.close();
+                                    }
+                                }
+                                if let Some(err) = error {
+                                    self.gui.show_error(err);
                                 }
+                                if let Some((file_name, extension, data)) = export {
+                                    save_file(&self.config, &mut self.gui, file_name, extension, data);
+                                }
+                                if ui.button("edit profiles...").clicked() {
+                                    self.gui.editing_profiles = true;
+                                    ui.close();
+                                }
+                            });
+                            if ui
+                                .selectable_label(
+                                    self.gui.selecting_capture_region,
+                                    "capture region",
+                                )
+                                .on_hover_text("drag over the animation to record only part of it")
+                                .clicked()
+                            {
+                                self.gui.selecting_capture_region =
+                                    !self.gui.selecting_capture_region;
+                            }
+                            if self.gif_recorder.capture_region.is_some()
+                                && ui.small_button("full view").clicked()
+                            {
+                                self.gif_recorder.capture_region = None;
+                                self.gui.selecting_capture_region = false;
                             }
+                            ui.add_enabled_ui(self.gif_recorder.capture_region.is_none(), |ui| {
+                                ui.menu_button("frame shape", |ui| {
+                                    let recorder = &mut self.gif_recorder;
+                                    let shape = &mut recorder.shape;
+                                    ui.radio_value(shape, FrameShape::Square, "square");
+                                    ui.radio_value(shape, FrameShape::Pad, "preview, padded")
+                                        .on_hover_text("the preview's shape, with bars beside it");
+                                    ui.radio_value(shape, FrameShape::Fill, "preview, filled")
+                                        .on_hover_text("the preview's shape, cutting off the edges");
+                                    ui.add_enabled(
+                                        recorder.shape != FrameShape::Square,
+                                        egui::DragValue::new(&mut recorder.shape_scale)
+                                            .range(0.1..=2.0)
+                                            .speed(0.01)
+                                            .prefix("scale ")
+                                            .suffix("x"),
+                                    )
+                                    .on_hover_text("of the preview's size on screen");
+                                    let (width, height) = recorder.frame_size();
+                                    ui.weak(format!("{} × {} px", width, height));
+                                })
+                                .response
+                                .on_hover_text("record the square animation or the preview's shape");
+                            });
 
                             ui.separator();
                             // choose preset
@@ -394,10 +1261,34 @@ impl App for ObamifyApp {
                                     .show_ui(ui, |ui| {
                                         let mut to_remove: Option<usize> = None;
                                         let mut close_menu = false;
+                                        let mut toggle_favorite: Option<usize> = None;
 
-                                        for (i, preset) in
-                                            self.gui.presets.clone().into_iter().enumerate()
-                                        {
+                                        ui.add(
+                                            egui::TextEdit::singleline(&mut self.gui.preset_search)
+                                                .hint_text("search names and tags"),
+                                        );
+                                        ui.horizontal(|ui| {
+                                            ui.label("sort:");
+                                            for sort in PresetSort::ALL {
+                                                ui.selectable_value(
+                                                    &mut self.gui.preset_sort,
+                                                    sort,
+                                                    sort.label(),
+                                                );
+                                            }
+                                        });
+                                        ui.separator();
+
+                                        let order = preset::search(
+                                            &self.gui.presets,
+                                            &self.gui.preset_search,
+                                            self.gui.preset_sort,
+                                        );
+                                        for i in order {
+                                            let preset = self.gui.presets[i].clone();
+                                            if !self.gui.preset_filter.matches(&preset) {
+                                                continue;
+                                            }
                                             ui.horizontal(|ui| {
                                                 let remove_enabled = self.gui.presets.len() > 5;
 
@@ -421,6 +1312,16 @@ impl App for ObamifyApp {
                                                     0.0
                                                 };
 
+                                                let star =
+                                                    if preset.meta.favorite { "★" } else { "☆" };
+                                                if ui
+                                                    .small_button(star)
+                                                    .on_hover_text("pin to the top")
+                                                    .clicked()
+                                                {
+                                                    toggle_favorite = Some(i);
+                                                }
+
                                                 let preset_width =
                                                     (ui.available_width() - del_width - spacing)
                                                         .max(0.0);
@@ -455,6 +1356,10 @@ impl App for ObamifyApp {
                                             });
                                         }
 
+                                        if let Some(idx) = toggle_favorite {
+                                            let meta = &mut self.gui.presets[idx].meta;
+                                            meta.favorite = !meta.favorite;
+                                        }
                                         if let Some(idx) = to_remove {
                                             let removed_current = idx == self.gui.current_preset;
                                             self.gui.presets.remove(idx);
@@ -477,6 +1382,76 @@ impl App for ObamifyApp {
                                         }
                                     });
 
+                                let (folders, tags) = preset::folders_and_tags(&self.gui.presets);
+                                let filters = std::iter::once(PresetFilter::All)
+                                    .chain(folders.into_iter().map(PresetFilter::Folder))
+                                    .chain(tags.into_iter().map(PresetFilter::Tag))
+                                    .collect::<Vec<_>>();
+                                if !filters.contains(&self.gui.preset_filter) {
+                                    self.gui.preset_filter = PresetFilter::All;
+                                }
+                                if filters.len() > 1 {
+                                    egui::ComboBox::from_id_salt("preset_filter")
+                                        .selected_text(self.gui.preset_filter.label())
+                                        .show_ui(ui, |ui| {
+                                            for filter in filters {
+                                                let label = filter.label();
+                                                ui.selectable_value(
+                                                    &mut self.gui.preset_filter,
+                                                    filter,
+                                                    label,
+                                                );
+                                            }
+                                        });
+                                }
+                                if ui.button("🗂").on_hover_text("organize presets").clicked() {
+                                    self.gui.organizing_presets = !self.gui.organizing_presets;
+                                }
+
+                                let current = self.gui.presets.get(self.gui.current_preset);
+                                // copied, so the exports below can report errors to the gui
+                                let current_stats =
+                                    current.map(|p| p.stats.clone()).unwrap_or_default();
+                                let has_quality = current.is_some_and(|p| p.quality.is_some());
+                                let has_history = current.is_some_and(|p| !p.meta.history.is_empty());
+                                if !current_stats.is_empty() || has_quality {
+                                    ui.menu_button("stats", |ui| {
+                                        ui.checkbox(&mut self.gui.show_stats_chart, "show stats");
+                                        if current_stats.is_empty() {
+                                            return;
+                                        }
+                                        let name = self.sim.name();
+                                        if ui.button("export csv").clicked() {
+                                            save_file(
+                                                &self.config,
+                                                &mut self.gui,
+                                                format!("{}_stats.csv", name),
+                                                "csv",
+                                                stats::to_csv(&current_stats),
+                                            );
+                                            ui.close();
+                                        }
+                                        if ui.button("export json").clicked() {
+                                            save_file(
+                                                &self.config,
+                                                &mut self.gui,
+                                                format!("{}_stats.json", name),
+                                                "json",
+                                                stats::to_json(&current_stats),
+                                            );
+                                            ui.close();
+                                        }
+                                    });
+                                }
+                                if has_history
+                                    && ui
+                                        .button("history")
+                                        .on_hover_text("the runs that led to this preset and what changed in each")
+                                        .clicked()
+                                {
+                                    self.gui.show_history = !self.gui.show_history;
+                                }
+
                                 // Make button glow if user hasn't obamified once
                                 let button_response = if !self.gui.has_obamified_once {
                                     // Create a glowing effect by animating the button outline
@@ -506,25 +1481,176 @@ impl App for ObamifyApp {
                                         #[cfg(target_arch = "wasm32")]
                                         hide_icons();
                                     } else {
-                                        prompt_image(
+                                        prompt_dynamic_image(
                                             "choose image to obamify",
                                             self,
-                                            |name: String, mut img: SourceImg, app: &mut ObamifyApp| {
-                                                img = ensure_reasonable_size(img);
-                                                app.gui.configuring_generation = Some((
-                                                    img,
-                                                    GenerationSettings::default(Uuid::new_v4(), name),
-                                                    GuiImageCache::default(),
-                                                ));
+                                            |name: String, img: image::DynamicImage, app: &mut ObamifyApp| {
+                                                let img = preprocess::fill_transparent(img);
+                                                let wide = preprocess::wide_source(&img)
+                                                    .map(ensure_reasonable_size);
+                                                let img =
+                                                    ensure_reasonable_size(app.config.hdr.convert(&img));
+                                                let mut settings =
+                                                    GenerationSettings::default(Uuid::new_v4(), name);
+                                                settings.sidelen = app.config.sidelen;
+                                                settings.algorithm = app.config.algorithm;
+                                                settings.set_wide_source(wide);
+                                                app.gui.configuring_generation =
+                                                    Some((img, settings, GuiImageCache::default()));
                                                 #[cfg(target_arch = "wasm32")]
                                                 hide_icons();
                                             },
                                         );
                                     }
                                 }
-                            });
+
+                                #[cfg(target_os = "linux")]
+                                if ui
+                                    .add_enabled(
+                                        self.gui.screen_capture.is_none(),
+                                        egui::Button::new("capture screen..."),
+                                    )
+                                    .on_hover_text("obamify a region of the screen")
+                                    .clicked()
+                                {
+                                    self.gui.screen_capture =
+                                        Some(crate::app::screen_capture::capture_region());
+                                }
+                                #[cfg(not(any(target_os = "linux", target_arch = "wasm32")))]
+                                ui.add_enabled(false, egui::Button::new("capture screen..."))
+                                    .on_disabled_hover_text(
+                                        "screen capture goes through the desktop portal, so it's only on linux for now",
+                                    );
+
+                                #[cfg(not(target_arch = "wasm32"))]
+                                if ui
+                                    .button("obamify image sequence...")
+                                    .on_hover_text(
+                                        "a folder of numbered frames, for stop motion: each becomes a preset, and all of them a gif",
+                                    )
+                                    .clicked()
+                                {
+                                    self.pick_source_sequence();
+                                }
+
+                                #[cfg(not(target_arch = "wasm32"))]
+                                self.folder_watch_ui(ui);
+                            });
                             ui.separator();
 
+                            if ui
+                                .button("⚙")
+                                .on_hover_text("preferences")
+                                .clicked()
+                            {
+                                self.gui.config_editor = Some((self.config.to_toml(), None));
+                            }
+                            ui.menu_button("💾", |ui| {
+                                if ui.button("export all data...").clicked() {
+                                    let backup = Backup::new(
+                                        self.gui.presets.clone(),
+                                        self.config.to_toml(),
+                                    );
+                                    match backup.encode() {
+                                        Ok(data) => save_file(
+                                            &self.config,
+                                            &mut self.gui,
+                                            format!("obamify_backup.{}", BACKUP_EXTENSION),
+                                            BACKUP_EXTENSION,
+                                            data,
+                                        ),
+                                        Err(err) => self
+                                            .gui
+                                            .show_error(format!("failed to export: {}", err)),
+                                    }
+                                    ui.close();
+                                }
+                                if ui.button("import data...").clicked() {
+                                    prompt_file(
+                                        "choose backup to import",
+                                        BACKUP_EXTENSION,
+                                        self,
+                                        |data, app| match Backup::decode(&data) {
+                                            Ok(backup) => {
+                                                app.gui.import =
+                                                    Some(Import::new(backup, &app.gui.presets));
+                                            }
+                                            Err(err) => app
+                                                .gui
+                                                .show_error(format!("failed to import: {}", err)),
+                                        },
+                                    );
+                                    ui.close();
+                                }
+                                #[cfg(not(target_arch = "wasm32"))]
+                                if ui.button("resume interrupted run...").clicked() {
+                                    self.resume_checkpoint();
+                                    ui.close();
+                                }
+                                #[cfg(not(target_arch = "wasm32"))]
+                                if ui
+                                    .button("branch from population...")
+                                    .on_hover_text("carry on from a saved population as a new run")
+                                    .clicked()
+                                {
+                                    self.branch_population();
+                                    ui.close();
+                                }
+                                if !self.gui.broken_presets.is_empty()
+                                    && ui
+                                        .button(format!(
+                                            "broken presets ({})...",
+                                            self.gui.broken_presets.len()
+                                        ))
+                                        .clicked()
+                                {
+                                    self.gui.show_broken_presets = true;
+                                    ui.close();
+                                }
+                            })
+                            .response
+                            .on_hover_text("backup");
+                            ui.menu_button("📊", |ui| {
+                                ui.checkbox(
+                                    &mut self.telemetry.enabled,
+                                    "collect performance stats",
+                                )
+                                .on_hover_text(
+                                    "solve and export times are kept on this device only, \
+                                     export them to attach to a bug report",
+                                );
+                                ui.label(format!("{} records", self.telemetry.len()));
+                                if ui.button("export json").clicked() {
+                                    save_file(
+&self.config,
+&mut self.gui,
+                                        "obamify_telemetry.json".to_owned(),
+                                        "json",
+                                        self.telemetry.to_json(),
+                                    );
+                                    ui.close();
+                                }
+                                if ui.button("clear").clicked() {
+                                    self.telemetry.clear();
+                                }
+                            })
+                            .response
+                            .on_hover_text("performance stats");
+                            if self.low_power() {
+                                ui.label("🔋").on_hover_text(if self.power.on_battery() == Some(true) {
+                                    "on battery: solves use one thread and previews repaint less often"
+                                } else {
+                                    "low power mode: solves use one thread and previews repaint less often"
+                                });
+                            } else if self.config.low_power == power::LowPower::Auto
+                                && self.power.on_battery().is_none()
+                            {
+                                ui.weak("🔋?").on_hover_text(
+                                    "battery state unknown, so low power mode stays off; \
+                                     set low_power = \"Always\" in the config to use it",
+                                );
+                            }
+
                             if ui
                                 .add(egui::Button::new(egui::RichText::new("✏")))
                                 .on_hover_text("drawing mode")
@@ -593,6 +1719,8 @@ impl App for ObamifyApp {
 
                             let mut change_source = false;
                             let mut change_target = false;
+                            let mut no_face_found = false;
+                            let mut pick_weights = false;
 
                             ui.allocate_ui_with_layout(
                                 egui::vec2(max_w, 0.0),
@@ -604,13 +1732,19 @@ impl App for ObamifyApp {
                                     if let Some((source_img, settings, cache)) =
                                         self.gui.configuring_generation.as_mut()
                                     {
-                                        change_source = image_crop_gui(
+                                        let crops =
+                                            (settings.source_crop_scale, settings.target_crop_scale);
+                                        let action = image_crop_gui(
                                             "source",
                                             ui,
                                             source_img,
                                             &mut settings.source_crop_scale,
                                             &mut cache.source_preview,
+                                            true,
+                                            None,
                                         );
+                                        change_source = action == CropGuiAction::ChangeImage;
+                                        no_face_found = action == CropGuiAction::NoFaceFound;
                                         if is_landscape {
                                             // ./arrow-right.svg
                                             ui.vertical(|ui| {
@@ -633,49 +1767,415 @@ impl App for ObamifyApp {
                                             });
                                         }
 
-                                        change_target = image_crop_gui(
+                                        // only loaded when the preview is rebuilt
+                                        let weights = (cache.show_weights
+                                            && cache.target_preview.is_none())
+                                        .then(|| settings.get_raw_weights().ok())
+                                        .flatten();
+                                        let action = image_crop_gui(
                                             "target",
                                             ui,
                                             &settings.get_raw_target(),
                                             &mut settings.target_crop_scale,
                                             &mut cache.target_preview,
+                                            false,
+                                            Some(WeightOverlay {
+                                                shown: &mut cache.show_weights,
+                                                weights: weights.as_ref(),
+                                            }),
                                         );
+                                        change_target = action == CropGuiAction::ChangeImage;
+                                        pick_weights = action == CropGuiAction::PickWeights;
+                                        if crops
+                                            != (settings.source_crop_scale, settings.target_crop_scale)
+                                        {
+                                            cache.result.stale = true;
+                                        }
+                                        result_preview_gui(ui, source_img, settings, &mut cache.result);
                                     }
                                 },
                             );
 
+                            if pick_weights {
+                                prompt_file("weight map", "svg", self, |data, app| {
+                                    let mut error = None;
+                                    if let Some((_, settings, cache)) =
+                                        &mut app.gui.configuring_generation
+                                    {
+                                        let (w, h) = settings.weight_map_size();
+                                        match calculate::svg_mask::rasterize(&data, w, h) {
+                                            Ok(map) => {
+                                                settings.set_weight_map(map);
+                                                cache.target_preview = None;
+                                                cache.result.stale = true;
+                                            }
+                                            Err(err) => error = Some(err),
+                                        }
+                                    }
+                                    if let Some(err) = error {
+                                        app.gui.show_error(err);
+                                    }
+                                });
+                            }
+                            if no_face_found {
+                                self.gui.show_error(
+                                    "couldn't find a face in the source, crop it with the sliders instead"
+                                        .to_owned(),
+                                );
+                            }
                             if change_source {
-                                prompt_image(
+                                prompt_dynamic_image(
                                     "choose image to obamify",
                                     self,
-                                    |_, mut img: SourceImg, app: &mut ObamifyApp| {
-                                        img = ensure_reasonable_size(img);
-                                        if let Some((src, _, cache)) =
+                                    |_, img: image::DynamicImage, app: &mut ObamifyApp| {
+                                        let img = preprocess::fill_transparent(img);
+                                        let wide =
+                                            preprocess::wide_source(&img).map(ensure_reasonable_size);
+                                        let img = ensure_reasonable_size(app.config.hdr.convert(&img));
+                                        if let Some((src, settings, cache)) =
                                             &mut app.gui.configuring_generation
                                         {
                                             *src = img;
+                                            settings.set_wide_source(wide);
+                                            settings.set_source_mask(None);
                                             cache.source_preview = None;
                                         }
                                     },
                                 );
                             } else if change_target {
-                                prompt_image(
+                                prompt_dynamic_image(
                                     "choose custom target image",
                                     self,
-                                    |_, mut img: SourceImg, app: &mut ObamifyApp| {
-                                        img = ensure_reasonable_size(img);
+                                    |_, img: image::DynamicImage, app: &mut ObamifyApp| {
+                                        // transparent areas don't matter, opaque ones do
+                                        let alpha_weights = img.color().has_alpha().then(|| {
+                                            let rgba = img.to_rgba8();
+                                            ensure_reasonable_size(SourceImg::from_fn(
+                                                rgba.width(),
+                                                rgba.height(),
+                                                |x, y| {
+                                                    let a = rgba.get_pixel(x, y)[3];
+                                                    image::Rgb([a, a, a])
+                                                },
+                                            ))
+                                        });
+                                        let img =
+                                            ensure_reasonable_size(app.config.hdr.convert(&img));
                                         if let Some((_, settings, cache)) =
                                             &mut app.gui.configuring_generation
                                         {
-                                            settings.set_raw_target(img);
+                                            match alpha_weights {
+                                                Some(weights) => {
+                                                    settings.set_raw_weighted_target(img, weights)
+                                                }
+                                                None => settings.set_raw_target(img),
+                                            }
                                             cache.target_preview = None;
                                         }
+                                        #[cfg(not(target_arch = "wasm32"))]
+                                        {
+                                            app.gui.gif_target = None;
+                                        }
+                                    },
+                                );
+                            }
+
+                            if let Some((_, settings, cache)) =
+                                self.gui.configuring_generation.as_mut()
+                            {
+                                let text_target = &mut self.gui.text_target;
+                                let mut error = None;
+                                egui::CollapsingHeader::new("text as target")
+                                    .default_open(false)
+                                    .show(ui, |ui| {
+                                        ui.text_edit_multiline(&mut text_target.text);
+                                        ui.horizontal_wrapped(|ui| {
+                                            egui::ComboBox::from_id_salt("text_font_select")
+                                                .selected_text(text_target.font.label())
+                                                .show_ui(ui, |ui| {
+                                                    for font in TextFont::ALL {
+                                                        ui.selectable_value(
+                                                            &mut text_target.font,
+                                                            font,
+                                                            font.label(),
+                                                        );
+                                                    }
+                                                });
+                                            ui.add(
+                                                egui::Slider::new(
+                                                    &mut text_target.size,
+                                                    0.05..=1.0,
+                                                )
+                                                .text("size"),
+                                            );
+                                        });
+                                        ui.horizontal_wrapped(|ui| {
+                                            ui.label("text:");
+                                            ui.color_edit_button_srgb(&mut text_target.color);
+                                            ui.label("background:");
+                                            ui.color_edit_button_srgb(&mut text_target.background);
+                                        });
+                                        if ui.button("use as target").clicked() {
+                                            match text_target.render() {
+                                                Ok((img, weights)) => {
+                                                    settings.set_raw_weighted_target(img, weights);
+                                                    #[cfg(not(target_arch = "wasm32"))]
+                                                    {
+                                                        self.gui.gif_target = None;
+                                                    }
+                                                    settings.target_crop_scale =
+                                                        CropScale::identity();
+                                                    cache.target_preview = None;
+                                                    cache.overlap_preview = None;
+                                                }
+                                                Err(e) => error = Some(e.to_string()),
+                                            }
+                                        }
+                                    });
+                                if let Some(e) = error {
+                                    self.gui.show_error(format!("failed to render text: {}", e));
+                                }
+                            }
+
+                            #[cfg(not(target_arch = "wasm32"))]
+                            if let Some((_, settings, cache)) =
+                                self.gui.configuring_generation.as_mut()
+                            {
+                                let mut error = None;
+                                ui.horizontal_wrapped(|ui| {
+                                    let clicked = ui
+                                        .button("use animated gif target...")
+                                        .on_hover_text(
+                                            "obamify every frame of an animated gif and save the result as a webm or gif",
+                                        )
+                                        .clicked();
+                                    if clicked {
+                                        if let Some(path) = rfd::FileDialog::new()
+                                            .set_title("choose animated gif target")
+                                            .add_filter("animated gif", &["gif"])
+                                            .pick_file()
+                                        {
+                                            match calculate::video::GifTarget::load(&path) {
+                                                Ok(video) => {
+                                                    settings.set_raw_target(ensure_reasonable_size(
+                                                        video.frames[0].clone(),
+                                                    ));
+                                                    cache.target_preview = None;
+                                                    self.gui.gif_target = Some(video);
+                                                }
+                                                Err(e) => error = Some(e.to_string()),
+                                            }
+                                        }
+                                    }
+                                    if let Some(video) = &self.gui.gif_target {
+                                        ui.label(format!("{} frames", video.frames.len()));
+                                        if ui.small_button("remove").clicked() {
+                                            self.gui.gif_target = None;
+                                        }
+                                    }
+                                    if let Some(sequence) = &self.gui.source_sequence {
+                                        ui.label(format!(
+                                            "source: {} frames of a sequence",
+                                            sequence.frames.len()
+                                        ));
+                                        if ui.small_button("use first frame only").clicked() {
+                                            self.gui.source_sequence = None;
+                                        }
+                                    }
+                                });
+                                if let Some(e) = error {
+                                    self.gui.show_error(format!("failed to load video: {}", e));
+                                }
+                            }
+
+                            let mut pick_palette_image = false;
+                            if let Some((_, settings, _)) =
+                                self.gui.configuring_generation.as_mut()
+                            {
+                                egui::CollapsingHeader::new("source filters")
+                                    .default_open(false)
+                                    .show(ui, |ui| {
+                                        let filters = &mut settings.source_filters;
+                                        ui.add(
+                                            egui::Slider::new(&mut filters.brightness, -1.0..=1.0)
+                                                .text("brightness"),
+                                        );
+                                        ui.add(
+                                            egui::Slider::new(&mut filters.contrast, 0.0..=2.0)
+                                                .text("contrast"),
+                                        );
+                                        ui.add_enabled(
+                                            !filters.grayscale,
+                                            egui::Slider::new(&mut filters.saturation, 0.0..=2.0)
+                                                .text("saturation"),
+                                        );
+                                        ui.add(
+                                            egui::Slider::new(&mut filters.posterize, 0..=16)
+                                                .text("posterize levels (0: off)"),
+                                        );
+                                        ui.add(
+                                            egui::Slider::new(&mut filters.blur, 0.0..=4.0)
+                                                .text("blur"),
+                                        );
+                                        egui::ComboBox::from_id_salt("histogram_match_select")
+                                            .selected_text(settings.histogram_match.label())
+                                            .show_ui(ui, |ui| {
+                                                for mode in HistogramMatch::ALL {
+                                                    ui.selectable_value(
+                                                        &mut settings.histogram_match,
+                                                        mode,
+                                                        mode.label(),
+                                                    );
+                                                }
+                                            })
+                                            .response
+                                            .on_hover_text(
+                                                "helps when source and target palettes differ a lot",
+                                            );
+                                        ui.horizontal_wrapped(|ui| {
+                                            let label = match &settings.palette {
+                                                Palette::Full => "full color".to_owned(),
+                                                Palette::Reduced(n) => format!("{} colors", n),
+                                                Palette::Custom(c) => {
+                                                    format!("custom palette ({})", c.len())
+                                                }
+                                            };
+                                            egui::ComboBox::from_id_salt("palette_select")
+                                                .selected_text(label)
+                                                .show_ui(ui, |ui| {
+                                                    if ui.button("full color").clicked() {
+                                                        settings.palette = Palette::Full;
+                                                    }
+                                                    if ui.button("reduced colors").clicked() {
+                                                        settings.palette = Palette::Reduced(16);
+                                                    }
+                                                    if ui.button("palette from image...").clicked()
+                                                    {
+                                                        pick_palette_image = true;
+                                                    }
+                                                    if ui.button("palette of current preset").clicked()
+                                                    {
+                                                        let colors = self.colors.read().unwrap();
+                                                        settings.palette =
+                                                            Palette::Custom(Palette::from_colors(
+                                                                colors.iter().map(|c| {
+                                                                    [0, 1, 2].map(|i| {
+                                                                        (c.rgba[i] * 255.0) as u8
+                                                                    })
+                                                                }),
+                                                                64,
+                                                            ));
+                                                    }
+                                                });
+                                            if let Palette::Reduced(n) = &mut settings.palette {
+                                                ui.add(
+                                                    egui::Slider::new(
+                                                        n,
+                                                        2..=MAX_PALETTE_COLORS as u16,
+                                                    )
+                                                    .text("colors"),
+                                                );
+                                            }
+                                        });
+                                        ui.add(
+                                            egui::Slider::new(
+                                                &mut settings.target_dither_colors,
+                                                0..=64,
+                                            )
+                                            .text("dither to target colors (0: off)"),
+                                        )
+                                        .on_hover_text(
+                                            "reads more clearly as the target at low resolutions",
+                                        );
+                                        let filters = &mut settings.source_filters;
+                                        ui.horizontal(|ui| {
+                                            ui.checkbox(&mut filters.grayscale, "grayscale");
+                                            if ui.button("reset").clicked() {
+                                                *filters = Default::default();
+                                            }
+                                        });
+                                    });
+                            }
+                            if pick_palette_image {
+                                prompt_image(
+                                    "choose palette image",
+                                    self,
+                                    |_, img: SourceImg, app: &mut ObamifyApp| {
+                                        if let Some((_, settings, _)) =
+                                            &mut app.gui.configuring_generation
+                                        {
+                                            settings.palette = Palette::Custom(
+                                                Palette::from_colors(img.pixels().map(|p| p.0), 64),
+                                            );
+                                        }
+                                    },
+                                );
+                            }
+
+                            if let Some((source_img, settings, cache)) =
+                                self.gui.configuring_generation.as_mut()
+                            {
+                                egui::CollapsingHeader::new("source mask")
+                                    .default_open(false)
+                                    .show(ui, |ui| {
+                                        source_mask_gui(ui, source_img, settings, cache);
+                                    });
+                            }
+
+                            ui.separator();
+
+                            let mut add_blend_source = false;
+                            if let Some((_img, settings, _)) =
+                                self.gui.configuring_generation.as_mut()
+                            {
+                                if !settings.blend_sources.is_empty() {
+                                    let slider_w = ui.available_width().min(260.0);
+                                    ui.add_sized(
+                                        [slider_w, 20.0],
+                                        egui::Slider::new(&mut settings.source_share, 0.0..=1.0)
+                                            .text("source share"),
+                                    );
+                                }
+                                let mut remove = None;
+                                for (i, blend) in settings.blend_sources.iter_mut().enumerate() {
+                                    ui.horizontal(|ui| {
+                                        ui.add(
+                                            egui::Slider::new(&mut blend.share, 0.0..=1.0)
+                                                .text(format!("extra source {} share", i + 1)),
+                                        );
+                                        if ui.small_button("remove").clicked() {
+                                            remove = Some(i);
+                                        }
+                                    });
+                                }
+                                if let Some(i) = remove {
+                                    settings.blend_sources.remove(i);
+                                }
+                                add_blend_source = ui
+                                    .button("blend in another source")
+                                    .on_hover_text(
+                                        "merge pixels from several photos into one result",
+                                    )
+                                    .clicked();
+                            }
+                            if add_blend_source {
+                                prompt_image(
+                                    "choose image to blend in",
+                                    self,
+                                    |_, mut img: SourceImg, app: &mut ObamifyApp| {
+                                        img = ensure_reasonable_size(img);
+                                        if let Some((_, settings, _)) =
+                                            &mut app.gui.configuring_generation
+                                        {
+                                            settings.blend_sources.push(BlendSource::new(img));
+                                        }
                                     },
                                 );
                             }
 
                             ui.separator();
 
+                            let mut pick_proximity_map = false;
                             if let Some((_img, settings, _)) =
                                 self.gui.configuring_generation.as_mut()
                             {
@@ -705,6 +2205,95 @@ impl App for ObamifyApp {
                                                     )
                                                     .text("proximity importance"),
                                                 );
+                                                egui::ComboBox::from_id_salt("proximity_map_select")
+                                                    .selected_text(settings.proximity_map.label())
+                                                    .show_ui(ui, |ui| {
+                                                        for map in ProximityMap::CHOICES {
+                                                            let label = map.label();
+                                                            ui.selectable_value(
+                                                                &mut settings.proximity_map,
+                                                                map,
+                                                                label,
+                                                            );
+                                                        }
+                                                        if ui.button("custom map...").clicked() {
+                                                            pick_proximity_map = true;
+                                                            ui.close();
+                                                        }
+                                                    })
+                                                    .response
+                                                    .on_hover_text(
+                                                        "a map over the target: bright areas keep pixels close, dark areas let them travel",
+                                                    );
+
+                                                egui::ComboBox::from_id_salt("block_size_select")
+                                                    .selected_text(block_size_label(
+                                                        settings.block_size,
+                                                    ))
+                                                    .show_ui(ui, |ui| {
+                                                        for size in [1, 2, 4, 8] {
+                                                            ui.selectable_value(
+                                                                &mut settings.block_size,
+                                                                size,
+                                                                block_size_label(size),
+                                                            );
+                                                        }
+                                                    });
+
+                                                ui.checkbox(&mut settings.tileable, "tileable")
+                                                    .on_hover_text(
+                                                        "pixels can wrap around the edges, for results that repeat as wallpapers or textures",
+                                                    );
+
+                                                ui.checkbox(
+                                                    &mut settings.mutual_swap,
+                                                    "mutual swap",
+                                                )
+                                                .on_hover_text(
+                                                    "source and target sit side by side and trade pixels: each half turns into the other image",
+                                                );
+
+                                                egui::ComboBox::from_id_salt("symmetry_select")
+                                                    .selected_text(settings.symmetry.label())
+                                                    .show_ui(ui, |ui| {
+                                                        for symmetry in Symmetry::ALL {
+                                                            ui.selectable_value(
+                                                                &mut settings.symmetry,
+                                                                symmetry,
+                                                                symmetry.label(),
+                                                            );
+                                                        }
+                                                    })
+                                                    .response
+                                                    .on_hover_text(
+                                                        "for symmetric targets like faces; encouraging only affects the fast algorithm",
+                                                    );
+
+                                                ui.add_enabled_ui(!settings.mutual_swap, |ui| {
+                                                    ui.horizontal(|ui| {
+                                                        egui::ComboBox::from_id_salt("aspect_select")
+                                                            .selected_text(settings.aspect.label())
+                                                            .show_ui(ui, |ui| {
+                                                                for aspect in Aspect::ALL {
+                                                                    ui.selectable_value(
+                                                                        &mut settings.aspect,
+                                                                        aspect,
+                                                                        aspect.label(),
+                                                                    );
+                                                                }
+                                                            })
+                                                            .response
+                                                            .on_hover_text(
+                                                                "pads the target into a wider or taller frame, so recordings aren't square",
+                                                            );
+                                                        if settings.aspect != Aspect::Square {
+                                                            ui.color_edit_button_srgb(
+                                                                &mut settings.pad_color,
+                                                            )
+                                                            .on_hover_text("padding color");
+                                                        }
+                                                    });
+                                                });
 
                                                 let mut algorithm = match settings.algorithm {
                                                     calculate::util::Algorithm::Optimal => {
@@ -713,6 +2302,9 @@ impl App for ObamifyApp {
                                                     calculate::util::Algorithm::Genetic => {
                                                         "fast algorithm"
                                                     }
+                                                    calculate::util::Algorithm::BestOf => {
+                                                        "best of several"
+                                                    }
                                                 };
 
                                                 egui::ComboBox::from_id_salt("algorithm_select")
@@ -729,79 +2321,135 @@ impl App for ObamifyApp {
                                                             settings.algorithm =
                                                                 calculate::util::Algorithm::Genetic;
                                                         }
+                                                        if ui.button("best of several").clicked() {
+                                                            algorithm = "best of several";
+                                                            settings.algorithm =
+                                                                calculate::util::Algorithm::BestOf;
+                                                        }
                                                     });
+                                                if matches!(
+                                                    settings.algorithm,
+                                                    calculate::util::Algorithm::BestOf
+                                                ) {
+                                                    ui.add(
+                                                        egui::DragValue::new(
+                                                            &mut settings.best_of_runs,
+                                                        )
+                                                        .range(1..=16)
+                                                        .suffix(" tries"),
+                                                    )
+                                                    .on_hover_text(
+                                                        "the optimal algorithm first on small grids, then the fast one with different seeds; the cheapest result is kept",
+                                                    );
+                                                    ui.add(
+                                                        egui::DragValue::new(
+                                                            &mut settings.best_of_seconds,
+                                                        )
+                                                        .range(1..=3600)
+                                                        .suffix(" s budget"),
+                                                    )
+                                                    .on_hover_text(
+                                                        "no new try is started once it would run past this",
+                                                    );
+                                                }
+                                                #[cfg(not(target_arch = "wasm32"))]
+                                                if matches!(
+                                                    settings.algorithm,
+                                                    calculate::util::Algorithm::Genetic
+                                                ) {
+                                                    ui.add(
+                                                        egui::DragValue::new(
+                                                            &mut settings.checkpoint_minutes,
+                                                        )
+                                                        .range(0..=240)
+                                                        .custom_formatter(|n, _| {
+                                                            if n == 0.0 {
+                                                                "no checkpoints".to_owned()
+                                                            } else {
+                                                                format!("checkpoint every {} min", n)
+                                                            }
+                                                        }),
+                                                    )
+                                                    .on_hover_text(
+                                                        "saves the run as it goes, so it can be resumed from the 💾 menu if obamify is closed or crashes",
+                                                    );
+                                                }
                                             },
                                         );
                                     });
                             }
+                            if pick_proximity_map {
+                                prompt_image(
+                                    "choose proximity map",
+                                    self,
+                                    |_, img: SourceImg, app: &mut ObamifyApp| {
+                                        if let Some((_, settings, _)) =
+                                            &mut app.gui.configuring_generation
+                                        {
+                                            settings.set_proximity_map(img);
+                                        }
+                                    },
+                                );
+                            }
                             ui.separator();
                             ui.horizontal_wrapped(|ui| {
+                                let busy = self.gui.show_progress_modal.is_some()
+                                    || !self.gui.queue.is_empty();
+                                let label = if busy { "add to queue" } else { "start!" };
                                 if ui
-                                    .add(egui::Button::new(egui::RichText::new("start!").strong()))
+                                    .add(egui::Button::new(egui::RichText::new(label).strong()))
                                     .clicked()
                                 {
-                                    if let Some((img, mut settings, _)) =
+                                    if let Some((img, settings, _)) =
                                         self.gui.configuring_generation.take()
                                     {
-                                        self.gui.show_progress_modal(settings.id);
                                         self.gui.saved_config =
                                             Some((img.clone(), settings.clone()));
-                                        //self.gui.currently_processing = Some(path.clone());
-                                        //self.change_sim(device, path.clone(), false);
-
-                                        // adjust for consistency across resolutions
-                                        settings.proximity_importance =
-                                            (settings.proximity_importance as f32
-                                                / (settings.sidelen as f32 / 128.0))
-                                                as i64;
-
-                                        self.gui
-                                            .process_cancelled
-                                            .store(false, std::sync::atomic::Ordering::Relaxed);
-
-                                        let unprocessed = UnprocessedPreset {
-                                            name: settings.name.clone(),
-                                            width: img.width(),
-                                            height: img.height(),
-                                            source_img: img.into_raw(),
-                                        };
-
-                                        self.resize_textures(
-                                            device,
-                                            (settings.sidelen, settings.sidelen),
-                                            false,
-                                        );
-
-                                        #[cfg(target_arch = "wasm32")]
-                                        {
-                                            self.start_job(unprocessed, settings);
-                                        }
-
                                         #[cfg(not(target_arch = "wasm32"))]
-                                        {
-                                            std::thread::spawn({
-                                                let tx = self.progress_tx.clone();
-                                                let cancelled = self.gui.process_cancelled.clone();
-                                                move || {
-                                                    let result = calculate::process(
-                                                        unprocessed,
-                                                        settings,
-                                                        &mut tx.clone(),
-                                                        cancelled,
-                                                    );
-                                                    if let Err(err) = result {
-                                                        tx.send(ProgressMsg::Error(
-                                                            err.to_string(),
-                                                        ))
-                                                        .ok();
-                                                    }
-                                                }
+                                        let video = self.gui.gif_target.take().and_then(|video| {
+                                            // without an output file only the first frame is processed
+                                            let output = self.config.output().save_path_as(
+                                                "save obamified video",
+                                                &format!("{}.webm", settings.name),
+                                                &["webm", "gif"],
+                                            )?;
+                                            Some((video, output))
+                                        });
+                                        #[cfg(not(target_arch = "wasm32"))]
+                                        let sequence =
+                                            self.gui.source_sequence.take().and_then(|sequence| {
+                                                let output = self.config.output().save_path_as(
+                                                    "save obamified sequence",
+                                                    &format!("{}.gif", settings.name),
+                                                    &["gif", "webm"],
+                                                )?;
+                                                Some((sequence, output))
                                             });
-                                        }
+                                        self.gui.queue.push(Job {
+                                            img,
+                                            settings,
+                                            priority: self.gui.queue_priority,
+                                            record_timelapse: self.gui.record_timelapse,
+                                            #[cfg(not(target_arch = "wasm32"))]
+                                            video,
+                                            #[cfg(not(target_arch = "wasm32"))]
+                                            sequence,
+                                        });
+                                        ui.ctx().request_repaint();
                                     }
                                 }
+                                if busy {
+                                    priority_select(ui, "new_job_priority", &mut self.gui.queue_priority);
+                                }
+                                ui.checkbox(&mut self.gui.record_timelapse, "record timelapse")
+                                    .on_hover_text("save a gif of the solution converging afterwards");
                                 if ui.button("cancel").clicked() {
                                     self.gui.configuring_generation = None;
+                                    #[cfg(not(target_arch = "wasm32"))]
+                                    {
+                                        self.gui.gif_target = None;
+                                        self.gui.source_sequence = None;
+                                    }
                                     #[cfg(target_arch = "wasm32")]
                                     show_icons();
                                 }
@@ -819,12 +2467,17 @@ impl App for ObamifyApp {
                 .resizable(false)
                 .anchor(egui::Align2::CENTER_BOTTOM, (0.0, 0.0))
                 .show(ctx, |ui| {
-                    let processing_label_message = "processing...";
                     ui.vertical(|ui| {
                         ui.set_min_width(ui.available_width().min(400.0));
                         while let Some(msg) = self.get_latest_msg() {
                             match msg {
                                 ProgressMsg::Done(new_preset) => {
+                                    let mut new_preset = *new_preset;
+                                    let now = ui.input(|i| i.time);
+                                    self.telemetry.finish_solve(now);
+                                    self.record_run(&mut new_preset, now);
+                                    new_preset.stats =
+                                        std::mem::take(&mut self.gui.convergence_stats);
                                     self.preview_image = None;
                                     self.resize_textures(
                                         device,
@@ -845,24 +2498,53 @@ impl App for ObamifyApp {
                                     ui.close();
                                     break;
                                 }
-                                ProgressMsg::Progress(p) => {
-                                    self.gui.last_progress = p;
+                                #[cfg(not(target_arch = "wasm32"))]
+                                ProgressMsg::FrameDone(preset) => {
+                                    let mut preset = *preset;
+                                    preset.stats = std::mem::take(&mut self.gui.convergence_stats);
+                                    self.gui.presets.push(preset);
                                 }
-                                ProgressMsg::Error(err) => {
-                                    ui.label(format!("error: {}", err));
-                                    if ui.button("close").clicked() {
-                                        ui.close();
+                                ProgressMsg::Progress(update) => {
+                                    self.gui.last_progress = update.fraction;
+                                    self.gui.last_stage = update.stage;
+                                    if let Some(stats) = update.generation {
+                                        self.gui.convergence_stats.push(stats);
+                                    }
+                                    if let Some(preview) = update.preview {
+                                        if let Some(timelapse) = &mut self.gif_recorder.timelapse {
+                                            timelapse.push(
+                                                preview.width,
+                                                preview.height,
+                                                &preview.data,
+                                            );
+                                        }
+                                        self.preview_image = image::ImageBuffer::from_vec(
+                                            preview.width,
+                                            preview.height,
+                                            preview.data,
+                                        );
                                     }
                                 }
-                                ProgressMsg::UpdatePreview {
-                                    width,
-                                    height,
-                                    data,
-                                } => {
-                                    let image = image::ImageBuffer::from_vec(width, height, data);
-                                    self.preview_image = image;
+                                ProgressMsg::Warning(warning) => {
+                                    self.gui.progress_warnings.push(warning);
+                                }
+                                ProgressMsg::Error(err) => {
+                                    self.telemetry.cancel_solve();
+                                    self.gif_recorder.timelapse = None;
+                                    self.preview_image = None;
+                                    self.resize_textures(
+                                        device,
+                                        (DEFAULT_RESOLUTION, DEFAULT_RESOLUTION),
+                                        false,
+                                    );
+                                    self.gui.hide_progress_modal();
+                                    self.gui.show_error(format!("error: {}", err));
+                                    ui.close();
+                                    break;
                                 }
                                 ProgressMsg::Cancelled => {
+                                    self.telemetry.cancel_solve();
+                                    self.gif_recorder.timelapse = None;
                                     self.preview_image = None;
                                     self.resize_textures(
                                         device,
@@ -875,84 +2557,400 @@ impl App for ObamifyApp {
                                 ProgressMsg::UpdateAssignments(assignments) => {
                                     self.sim.set_assignments(assignments, self.size.0)
                                 }
+                                #[cfg(not(target_arch = "wasm32"))]
+                                ProgressMsg::Population(population) => {
+                                    let generation = self
+                                        .gui
+                                        .convergence_stats
+                                        .last()
+                                        .map_or(0, |stats| stats.generation);
+                                    let name = format!(
+                                        "{} gen {}",
+                                        self.gui.running_job.as_deref().unwrap_or("obamify"),
+                                        generation
+                                    );
+                                    save_file(
+                                        &self.config,
+                                        &mut self.gui,
+                                        name,
+                                        calculate::checkpoint::POPULATION_EXTENSION,
+                                        population,
+                                    );
+                                }
                             }
                         }
 
                         if self.gui.process_cancelled.load(Ordering::Relaxed) {
                             ui.label("cancelling...");
-                        } else if self.gui.last_progress == 0.0 {
-                            ui.label("preparing...");
+                        } else if let Some(stats) = self.gui.convergence_stats.last() {
+                            ui.label(format!(
+                                "{} generation {}",
+                                self.gui.last_stage.label(),
+                                stats.generation
+                            ));
                         } else {
-                            ui.label(processing_label_message);
+                            ui.label(self.gui.last_stage.label());
+                        }
+                        for warning in &self.gui.progress_warnings {
+                            ui.colored_label(ui.visuals().warn_fg_color, warning);
                         }
                         ui.add(egui::ProgressBar::new(self.gui.last_progress).show_percentage());
+                        if !self.gui.convergence_stats.is_empty() {
+                            convergence_chart(
+                                ui,
+                                &self.gui.convergence_stats,
+                                egui::vec2(ui.available_width(), 60.0),
+                            );
+                        }
 
                         ui.horizontal(|ui| {
                             if ui.button("cancel").clicked() {
-                                #[cfg(target_arch = "wasm32")]
-                                {
-                                    if let Some(w) = &self.worker {
-                                        w.terminate();
-                                    }
-                                    self.worker = None;
-                                    self.preview_image = None;
-                                    self.resize_textures(
-                                        device,
-                                        (DEFAULT_RESOLUTION, DEFAULT_RESOLUTION),
-                                        false,
-                                    );
-                                    self.gui.hide_progress_modal();
-                                    ui.close();
-                                }
-                                self.gui.process_cancelled.store(true, Ordering::Relaxed);
-                                self.gui.last_progress = 0.0;
+                                self.cancel_running_job(device);
+                            }
+                            #[cfg(not(target_arch = "wasm32"))]
+                            if self.gui.population_saveable
+                                && ui
+                                    .add_enabled(
+                                        !self.gui.population_requested.load(Ordering::Relaxed),
+                                        egui::Button::new("save population..."),
+                                    )
+                                    .on_hover_text(
+                                        "save the solve as it is now, to branch off from later",
+                                    )
+                                    .clicked()
+                            {
+                                self.gui.population_requested.store(true, Ordering::Relaxed);
                             }
                         })
                     });
                 });
+        } else if self.recording_window() {
+            // a modal would get in the way of the interface being recorded
+            Window::new("recording_window")
+                .title_bar(false)
+                .resizable(false)
+                .anchor(egui::Align2::LEFT_BOTTOM, (8.0, -8.0))
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        let now = ui.input(|i| i.time);
+                        ui.label(match self.gif_recorder.seconds_until_start(now) {
+                            Some(wait) => format!("⏺ recording starts in {:.0} s", wait.ceil()),
+                            None if self.gif_recorder.is_paused() => {
+                                format!("⏸ paused at frame {}", self.gif_recorder.frame_count)
+                            }
+                            None => format!(
+                                "⏺ recording the window, frame {}{}",
+                                self.gif_recorder.frame_count,
+                                countdown(self.gif_recorder.seconds_left())
+                            ),
+                        });
+                        let started = self.gif_recorder.seconds_until_start(now).is_none();
+                        pause_button(ui, &mut self.gif_recorder, started);
+                        if ui.button("stop").clicked() {
+                            if self.gif_recorder.seconds_until_start(now).is_some() {
+                                self.stop_recording_gif(device, &rs.queue);
+                            } else {
+                                self.gif_recorder.stop_early();
+                            }
+                        }
+                    });
+                });
         } else if !self.gif_recorder.not_recording() {
             Modal::new(format!("recording_progress_{}", self.gif_recorder.id).into()).show(
                 ctx,
-                |ui| {
-                    match self.gif_recorder.status.clone() {
-                        GifStatus::Recording => {
-                            ui.label("recording gif...");
+                |ui| match self.gif_recorder.status.clone() {
+                    GifStatus::PickTake => {
+                        ui.label("pick a take to save");
+                        let name = self.gif_recorder.get_name(self.sim.name(), self.reverse);
+                        let mut save = None;
+                        ui.horizontal_wrapped(|ui| {
+                            for (i, take) in self.gif_recorder.takes.iter_mut().enumerate() {
+                                ui.vertical(|ui| {
+                                    let texture = take.preview_texture.get_or_insert_with(|| {
+                                        ui.ctx().load_texture(
+                                            format!("take_preview_{}", i),
+                                            take.preview.clone(),
+                                            egui::TextureOptions::LINEAR,
+                                        )
+                                    });
+                                    ui.add(
+                                        egui::Image::new(&*texture)
+                                            .max_size(egui::vec2(120.0, 120.0)),
+                                    );
+                                    if ui.button(format!("save take {}", i + 1)).clicked() {
+                                        save = Some(i);
+                                    }
+                                });
+                            }
+                        });
+                        if let Some(i) = save {
+                            self.gif_recorder.save_take(i, name);
+                        }
+                        if ui.button("discard all").clicked() {
+                            self.stop_recording_gif(device, &rs.queue);
+                        }
+                    }
+                    GifStatus::Recording => {
+                        let now = ui.input(|i| i.time);
+                        if let Some(wait) = self.gif_recorder.seconds_until_start(now) {
+                            ui.label(format!("recording starts in {:.0} s...", wait.ceil()));
+                        } else if self.gif_recorder.is_multi_take() {
+                            ui.label(format!(
+                                "recording take {}...{}",
+                                self.gif_recorder.takes.len() + 1,
+                                countdown(self.gif_recorder.seconds_left())
+                            ));
+                        } else {
+                            ui.label(format!(
+                                "recording gif...{}",
+                                countdown(self.gif_recorder.seconds_left())
+                            ));
+                        }
+                        ui.checkbox(&mut self.gif_recorder.show_quantized, "show gif colors")
+                            .on_hover_text("the last frame as the gif will contain it");
+                        if !self.gif_recorder.show_quantized {
+                            self.gui.quantized_texture = None;
+                        } else if let Some(frame) = self.gif_recorder.quantized_frame.take() {
+                            match &mut self.gui.quantized_texture {
+                                Some(texture) => texture.set(frame, egui::TextureOptions::NEAREST),
+                                None => {
+                                    self.gui.quantized_texture = Some(ctx.load_texture(
+                                        "quantized_frame",
+                                        frame,
+                                        egui::TextureOptions::NEAREST,
+                                    ))
+                                }
+                            }
+                        }
+                        if let Some(texture) = &self.gui.quantized_texture {
+                            let size = texture.size_vec2();
+                            let scale = (QUANTIZED_PREVIEW_SIZE / size.max_elem()).min(1.0);
+                            ui.image((texture.id(), size * scale));
+                        }
+                        ui.horizontal(|ui| {
+                            let started = self.gif_recorder.seconds_until_start(now).is_none();
+                            pause_button(ui, &mut self.gif_recorder, started);
+                            if started
+                                && ui
+                                    .button("stop")
+                                    .on_hover_text("save what's recorded so far")
+                                    .clicked()
+                            {
+                                self.gif_recorder.stop_early();
+                            }
                             if ui.button("cancel").clicked() {
                                 self.stop_recording_gif(device, &rs.queue);
                                 self.gui.animate = false;
                             }
-                        }
+                        });
+                    }
+                    GifStatus::Paused => {
+                        ui.label(format!(
+                            "paused at frame {}; the animation plays on unrecorded",
+                            self.gif_recorder.frame_count
+                        ));
+                        ui.horizontal(|ui| {
+                            pause_button(ui, &mut self.gif_recorder, true);
+                            if ui.button("cancel").clicked() {
+                                self.stop_recording_gif(device, &rs.queue);
+                                self.gui.animate = false;
+                            }
+                        });
+                    }
 
-                        GifStatus::Error(err) => {
-                            ui.label(format!("Error: {}", err));
-                            ui.horizontal(|ui| {
-                                if ui.button("close").clicked() {
-                                    self.stop_recording_gif(device, &rs.queue);
-                                }
-                            });
+                    GifStatus::Exporting => {
+                        if let Some(job) = self.gif_recorder.job() {
+                            ui.label(format!("saving {}...", job.label));
+                            ui.add(egui::ProgressBar::new(job.fraction()).show_percentage());
                         }
-                        #[cfg(not(target_arch = "wasm32"))]
-                        GifStatus::Complete(path) => {
-                            ui.label("gif saved!");
-                            ui.horizontal(|ui| {
-                                if ui.button("open file").clicked() {
-                                    opener::reveal(path).ok();
-                                }
-                                if ui.button("close").clicked() {
-                                    self.stop_recording_gif(device, &rs.queue);
-                                }
-                            });
+                        if ui.button("cancel").clicked() {
+                            self.stop_recording_gif(device, &rs.queue);
                         }
-                        #[cfg(target_arch = "wasm32")]
-                        GifStatus::Complete => {
-                            // save opens dialog automatically
+                    }
+                    GifStatus::Error(err) => {
+                        ui.label(format!("Error: {}", err));
+                        ui.horizontal(|ui| {
+                            if ui.button("close").clicked() {
+                                self.stop_recording_gif(device, &rs.queue);
+                            }
+                        });
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    GifStatus::Complete(path) => {
+                        ui.label(format!("{} saved!", self.gif_recorder.exported));
+                        ui.horizontal(|ui| {
+                            if ui.button("open file").clicked() {
+                                opener::reveal(path).ok();
+                            }
+                            copy_gif_button(ui, self);
+                            if ui.button("close").clicked() {
+                                self.stop_recording_gif(device, &rs.queue);
+                            }
+                        });
+                    }
+                    #[cfg(target_arch = "wasm32")]
+                    GifStatus::Complete => {
+                        ui.label(format!("{} saved!", self.gif_recorder.exported));
+                        if ui.button("close").clicked() {
                             self.stop_recording_gif(device, &rs.queue);
                         }
-                        GifStatus::None => unreachable!(),
                     }
+                    #[cfg(target_arch = "wasm32")]
+                    GifStatus::Saving => {
+                        ui.label(format!("saving {}...", self.gif_recorder.exported));
+                        ui.spinner();
+                    }
+                    GifStatus::Unsaved => {
+                        ui.label(format!("the {} wasn't saved", self.gif_recorder.exported));
+                        ui.horizontal(|ui| {
+                            if ui.button("save...").clicked() {
+                                self.gif_recorder.save_finished();
+                            }
+                            #[cfg(not(target_arch = "wasm32"))]
+                            copy_gif_button(ui, self);
+                            if ui.button("discard").clicked() {
+                                self.stop_recording_gif(device, &rs.queue);
+                            }
+                        });
+                    }
+                    GifStatus::None => unreachable!(),
                 },
             );
         }
+        if let Some((text, error)) = &mut self.gui.config_editor {
+            let mut close = false;
+            let mut apply = None;
+            Window::new("preferences")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if let Some(path) = Config::path() {
+                        ui.label(format!("saved to {}", path.display()));
+                    }
+                    egui::ScrollArea::vertical()
+                        .max_height(400.0)
+                        .show(ui, |ui| {
+                            ui.add(
+                                egui::TextEdit::multiline(text)
+                                    .code_editor()
+                                    .desired_width(360.0),
+                            );
+                        });
+                    if let Some(err) = error.as_ref() {
+                        ui.colored_label(ui.visuals().error_fg_color, err);
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.button("save").clicked() {
+                            match Config::parse(text) {
+                                Ok(config) => apply = Some(config),
+                                Err(err) => *error = Some(err.to_string()),
+                            }
+                        }
+                        if ui.button("reset to defaults").clicked() {
+                            *text = Config::default().to_toml();
+                            *error = None;
+                        }
+                        if ui.button("cancel").clicked() {
+                            close = true;
+                        }
+                    });
+                });
+            if let Some(config) = apply {
+                self.set_config(ctx, config);
+                close = true;
+            }
+            if close {
+                self.gui.config_editor = None;
+            }
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(watch) = &mut self.gui.folder_watch {
+            for preset in watch.poll() {
+                self.gui.presets.push(preset.clone());
+                self.change_sim(device, &rs.queue, preset, self.gui.presets.len() - 1);
+                self.gui.animate = true;
+                self.gui.has_obamified_once = true;
+            }
+            ctx.request_repaint_after(std::time::Duration::from_secs(1));
+        }
+        if matches!(self.gui.mode, GuiMode::Transform)
+            && self.gui.configuring_generation.is_none()
+            && self.gui.config_editor.is_none()
+            && self.gui.show_progress_modal.is_none()
+            && self.gif_recorder.not_recording()
+            && !ctx.wants_keyboard_input()
+        {
+            let keys = &self.config.keybinds;
+            let pressed = |name: &str| {
+                egui::Key::from_name(name).is_some_and(|key| ctx.input(|i| i.key_pressed(key)))
+            };
+            let (play, reverse, save_gif) = (
+                pressed(&keys.play),
+                pressed(&keys.reverse),
+                pressed(&keys.save_gif),
+            );
+            if play {
+                self.gui.animate = true;
+                self.prepare_play(device);
+            }
+            if reverse {
+                self.reverse = !self.reverse;
+                self.gui.animate = true;
+                self.reset_sim(device, &rs.queue);
+            }
+            if save_gif {
+                self.start_gif(device, &rs.queue, ctx.input(|i| i.time));
+            }
+        }
+        self.queue_window(ctx, device);
+        self.organize_presets_window(ctx);
+        self.export_profiles_window(ctx);
+        self.import_window(ctx);
+        self.broken_presets_window(ctx);
+        self.history_window(ctx);
+        if self.gui.show_stats_chart {
+            let current = self.gui.presets.get(self.gui.current_preset);
+            let stats = current.map(|p| p.stats.as_slice()).unwrap_or_default();
+            let quality = current.and_then(|p| p.quality.as_ref());
+            Window::new("stats")
+                .open(&mut self.gui.show_stats_chart)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    if stats.is_empty() && quality.is_none() {
+                        ui.label("no stats for this preset");
+                    }
+                    if !stats.is_empty() {
+                        convergence_chart(ui, stats, egui::vec2(320.0, 160.0));
+                    }
+                    if let Some(quality) = quality {
+                        quality_report_ui(ui, quality);
+                    }
+                });
+        }
+        if let Some(still) = self.gif_recorder.still_job() {
+            let mut cancel = false;
+            Window::new("saving_frame")
+                .title_bar(false)
+                .resizable(false)
+                .anchor(egui::Align2::LEFT_BOTTOM, (8.0, -8.0))
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("saving frame...");
+                        ui.add(
+                            egui::ProgressBar::new(still.fraction())
+                                .desired_width(120.0)
+                                .show_percentage(),
+                        );
+                        cancel = ui.button("cancel").clicked();
+                    });
+                });
+            if cancel {
+                self.gif_recorder.cancel_still();
+            }
+        }
         if let Some(err) = &self.gui.error_message {
             let mut close = false;
             Window::new("error")
@@ -980,7 +2978,23 @@ impl App for ObamifyApp {
                             let full = ui.available_size();
                             let aspect = self.size.0 as f32 / self.size.1 as f32;
                             let desired = full.x.min(full.y) * egui::vec2(1.0, aspect);
-                            ui.add(egui::Image::new((id, desired)).maintain_aspect_ratio(true));
+                            self.shown_px =
+                                (desired.max_elem() * ctx.pixels_per_point()).ceil() as u32;
+                            if self.gif_recorder.not_recording() {
+                                let px = full * ctx.pixels_per_point();
+                                self.gif_recorder.viewport = (px.x as u32, px.y as u32);
+                            }
+                            let response =
+                                ui.add(egui::Image::new((id, desired)).maintain_aspect_ratio(true));
+
+                            if self.gif_recorder.overlay.enabled {
+                                self.gif_recorder.overlay.paint(ui.painter(), response.rect);
+                            }
+                            if matches!(self.gui.mode, GuiMode::Transform)
+                                && !self.gif_recorder.is_recording()
+                            {
+                                self.handle_capture_region(ui, &response);
+                            }
 
                             #[cfg(not(target_arch = "wasm32"))]
                             if matches!(self.gui.mode, GuiMode::Draw) {
@@ -1158,16 +3172,62 @@ impl App for ObamifyApp {
                 });
         }
 
-        // continuous repaint for animation
-        ctx.request_repaint();
+        // continuous repaint for animation; in low power mode everything but recording
+        // repaints less often, the animation taking bigger steps to keep its pace
+        if self.low_power() && self.gif_recorder.not_recording() {
+            ctx.request_repaint_after(std::time::Duration::from_secs_f32(
+                1.0 / power::LOW_POWER_FPS,
+            ));
+        } else {
+            ctx.request_repaint();
+        }
         self.frame_count += 1;
     }
 }
 
+/// Copies the finished recording to the clipboard, if it's a gif.
+#[cfg(not(target_arch = "wasm32"))]
+fn copy_gif_button(ui: &mut egui::Ui, app: &mut ObamifyApp) {
+    if app
+        .gif_recorder
+        .finished_bytes()
+        .is_none_or(|(_, ext)| ext != "gif")
+    {
+        return;
+    }
+    let label = if app.gif_recorder.copied {
+        "copied!"
+    } else {
+        "copy gif"
+    };
+    if ui
+        .button(label)
+        .on_hover_text("paste it into a chat or document without saving it")
+        .clicked()
+    {
+        if let Err(err) = app.gif_recorder.copy_gif() {
+            app.gui
+                .show_error(format!("failed to copy the gif: {}", err));
+        }
+    }
+}
+
 fn prompt_image(
     title: &'static str,
     app: &mut ObamifyApp,
     callback: impl FnOnce(String, image::RgbImage, &mut ObamifyApp) + 'static,
+) {
+    prompt_dynamic_image(title, app, |name, img, app| {
+        let img = app.config.hdr.convert(&img);
+        callback(name, img, app)
+    });
+}
+
+/// Like `prompt_image`, but keeps the image's alpha channel.
+fn prompt_dynamic_image(
+    title: &'static str,
+    app: &mut ObamifyApp,
+    callback: impl FnOnce(String, image::DynamicImage, &mut ObamifyApp) + 'static,
 ) {
     #[cfg(target_arch = "wasm32")]
     {
@@ -1177,16 +3237,16 @@ fn prompt_image(
         spawn_local(async move {
             if let Some(handle) = rfd::AsyncFileDialog::new()
                 .set_title(title)
-                .add_filter("image files", &["png", "jpg", "jpeg", "webp"])
+                .add_filter("image files", &IMAGE_FILE_EXTENSIONS)
                 .pick_file()
                 .await
             {
                 let name = get_default_preset_name(handle.file_name());
                 let data = handle.read().await;
-                match image::load_from_memory(&data) {
+                match icc::load_from_memory(&data) {
                     Ok(img) => unsafe {
                         if let Some(app) = app_ptr.as_mut() {
-                            callback(name, img.to_rgb8(), app);
+                            callback(name, img, app);
                         }
                     },
                     Err(e) => unsafe {
@@ -1203,118 +3263,1584 @@ fn prompt_image(
     {
         if let Some(file) = rfd::FileDialog::new()
             .set_title(title)
-            .add_filter("image files", &["png", "jpg", "jpeg", "webp"])
+            .add_filter("image files", &IMAGE_FILE_EXTENSIONS)
             .pick_file()
         {
             let name =
                 get_default_preset_name(file.file_name().unwrap().to_string_lossy().to_string());
 
-            match image::open(file) {
-                Ok(img) => callback(name, img.to_rgb8(), app),
+            match icc::open(&file) {
+                Ok(img) => callback(name, img, app),
                 Err(e) => app.gui.show_error(format!("failed to load image: {}", e)),
             }
         }
     }
 }
 
-fn ensure_reasonable_size(img: SourceImg) -> SourceImg {
-    let max_side = 512;
-    let (w, h) = img.dimensions();
-    if w <= max_side && h <= max_side {
-        return img;
+/// Pauses or resumes a recording, once it's `started` capturing.
+fn pause_button(ui: &mut egui::Ui, recorder: &mut GifRecorder, started: bool) {
+    if recorder.is_paused() {
+        if ui.button("▶ resume").clicked() {
+            recorder.resume();
+        }
+    } else if ui
+        .add_enabled(started, egui::Button::new("⏸ pause"))
+        .on_hover_text("stop capturing for a while, the animation keeps playing")
+        .clicked()
+    {
+        recorder.pause();
     }
-    let scale = (max_side as f32 / w as f32).min(max_side as f32 / h as f32);
-    let new_w = (w as f32 * scale).round() as u32;
-    let new_h = (h as f32 * scale).round() as u32;
-
-    image::imageops::resize(&img, new_w, new_h, image::imageops::FilterType::Lanczos3)
 }
 
-fn image_overlap_preview(
-    arg: &str,
-    ui: &mut egui::Ui,
-    settings: &GenerationSettings,
-    cache: &mut GuiImageCache,
-    source_img: &SourceImg,
-    get_raw_target: &SourceImg,
-    blend: f32,
+/// Asks for a file with the given extension and passes its contents to `callback`.
+fn prompt_file(
+    title: &'static str,
+    extension: &'static str,
+    app: &mut ObamifyApp,
+    callback: impl FnOnce(Vec<u8>, &mut ObamifyApp) + 'static,
 ) {
-    let tex = if cache.overlap_preview.is_none()
-        || cache.source_preview.is_none()
-        || cache.target_preview.is_none()
+    #[cfg(target_arch = "wasm32")]
     {
-        let src_img = settings.source_crop_scale.apply(source_img, 64);
-        let tgt_img = settings.target_crop_scale.apply(get_raw_target, 64);
-        let blended = blend_rgb_images(&src_img, &tgt_img, blend);
-        let p = ui.ctx().load_texture(
-            arg,
-            egui::ColorImage::from_rgb([64, 64], blended.as_raw()),
-            egui::TextureOptions::LINEAR,
-        );
-        cache.overlap_preview = Some(p.clone());
-        p
-    } else {
-        cache.overlap_preview.as_ref().unwrap().clone()
-    };
-    ui.add(egui::Image::from_texture(&tex));
-}
+        let picked = app.gui.picked_tx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Some(handle) = rfd::AsyncFileDialog::new()
+                .set_title(title)
+                .add_filter(extension, &[extension])
+                .pick_file()
+                .await
+            {
+                let data = handle.read().await;
+                picked.send(Box::new(move |app| callback(data, app))).ok();
+            }
+        });
+    }
 
-fn image_crop_gui(
-    name: &'static str,
-    ui: &mut egui::Ui,
-    img: &SourceImg,
-    crop_scale: &mut CropScale,
-    cache: &mut Option<TextureHandle>,
-) -> bool {
-    let mut open_file_dialog = false;
-    ui.vertical(|ui| {
-        let tex = match &cache {
-            None => {
-                let p = ui.ctx().load_texture(
-                    name,
-                    egui::ColorImage::from_rgb([128, 128], crop_scale.apply(img, 128).as_raw()),
-                    egui::TextureOptions::LINEAR,
-                );
-                *cache = Some(p.clone());
-                p
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        if let Some(file) = rfd::FileDialog::new()
+            .set_title(title)
+            .add_filter(extension, &[extension])
+            .pick_file()
+        {
+            match std::fs::read(file) {
+                Ok(data) => callback(data, app),
+                Err(e) => app.gui.show_error(format!("failed to read file: {}", e)),
             }
-            Some(t) => t.clone(),
-        };
-        ui.add(egui::Image::from_texture(&tex));
-        if ui.button(format!("change {name} image")).clicked() {
-            open_file_dialog = true;
         }
-        // crop sliders
-        ui.vertical(|ui| {
-            let values = *crop_scale;
-            let slider_w = ui.available_width().min(260.0);
-
-            ui.add_sized(
-                [slider_w, 20.0],
-                egui::Slider::new(&mut crop_scale.scale, 1.0..=5.0)
-                    .show_value(false)
-                    .text("zoom"),
-            );
-            ui.add_sized(
-                [slider_w, 20.0],
-                egui::Slider::new(&mut crop_scale.x, -1.0..=1.0)
-                    .show_value(false)
-                    .text("x-off."),
-            );
-            ui.add_sized(
-                [slider_w, 20.0],
-                egui::Slider::new(&mut crop_scale.y, -1.0..=1.0)
-                    .show_value(false)
-                    .text("y-off."),
-            );
+    }
+}
 
-            if values != *crop_scale {
-                *cache = None; // force reload
+fn prompt_tile_library(
+    app: &mut ObamifyApp,
+    callback: impl FnOnce(TileAtlas, &mut ObamifyApp) + 'static,
+) {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let picked = app.gui.picked_tx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Some(handles) = rfd::AsyncFileDialog::new()
+                .set_title("choose tile images")
+                .add_filter("image files", &IMAGE_FILE_EXTENSIONS)
+                .pick_files()
+                .await
+            {
+                let mut images = Vec::new();
+                for handle in handles.iter().take(mosaic::MAX_LIBRARY_TILES) {
+                    if let Ok(img) = image::load_from_memory(&handle.read().await) {
+                        images.push(img);
+                    }
+                }
+                picked
+                    .send(Box::new(move |app| {
+                        if images.is_empty() {
+                            app.gui.show_error("no images could be loaded".to_owned());
+                        } else {
+                            callback(TileAtlas::from_images(images), app);
+                        }
+                    }))
+                    .ok();
             }
         });
-    });
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        if let Some(dir) = rfd::FileDialog::new()
+            .set_title("choose tile folder")
+            .pick_folder()
+        {
+            match mosaic::load_tile_folder(&dir) {
+                Ok(atlas) => callback(atlas, app),
+                Err(e) => app.gui.show_error(format!("failed to load tiles: {}", e)),
+            }
+        }
+    }
+}
 
-    open_file_dialog
+impl ObamifyApp {
+    fn recording_window(&self) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.gif_recorder.window.enabled
+            && (self.gif_recorder.is_recording() || self.gif_recorder.is_paused());
+        #[cfg(target_arch = "wasm32")]
+        false
+    }
+
+    fn low_power(&self) -> bool {
+        self.power.active(self.config.low_power)
+    }
+
+    /// How many steps the transformation takes each repaint while it plays.
+    fn steps_per_repaint(&self) -> u32 {
+        if self.low_power() && self.gif_recorder.not_recording() {
+            (power::PLAYBACK_FPS / power::LOW_POWER_FPS).round() as u32
+        } else {
+            1
+        }
+    }
+
+    /// Threads for the next solve; just the one in low power mode.
+    fn solver_threads(&self) -> u32 {
+        if self.low_power() {
+            1
+        } else {
+            self.config.solver_threads
+        }
+    }
+
+    /// Starts the next queued job once nothing else is being generated.
+    fn start_next_job(&mut self, device: &egui_wgpu::wgpu::Device, now: f64) {
+        if self.gui.show_progress_modal.is_some() || !matches!(self.gui.mode, GuiMode::Transform) {
+            return;
+        }
+        #[cfg(target_arch = "wasm32")]
+        if self.worker.is_none() {
+            return; // still restarting after a cancel
+        }
+        let Some(job) = self.gui.queue.pop() else {
+            return;
+        };
+        self.gui.show_progress_modal(job.id());
+        self.gui.running_job = Some(job.name().to_owned());
+        let run = RunRecord {
+            name: job.name().to_owned(),
+            finished: 0,
+            seconds: 0.0,
+            cost: None,
+            settings: job.settings.snapshot(),
+        };
+        self.gui.run_started = Some((run, job.settings.parent, now));
+
+        let mut settings = job.settings.finalized();
+        settings.threads = self.solver_threads();
+        self.gui.process_cancelled.store(false, Ordering::Relaxed);
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.gui
+                .population_requested
+                .store(false, Ordering::Relaxed);
+            self.gui.population_saveable =
+                matches!(settings.algorithm, calculate::util::Algorithm::Genetic)
+                    && job.video.is_none()
+                    && job.sequence.is_none();
+        }
+        self.gif_recorder.timelapse = job.record_timelapse.then(Timelapse::default);
+        self.telemetry.start_solve(
+            format!("{:?}", settings.algorithm),
+            settings.sidelen,
+            settings.block_size,
+            now,
+        );
+
+        let unprocessed = UnprocessedPreset {
+            name: settings.name.clone(),
+            width: job.img.width(),
+            height: job.img.height(),
+            source_img: job.img.into_raw(),
+        };
+
+        self.resize_textures(device, (settings.sidelen, settings.sidelen), false);
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.start_job(unprocessed, settings);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let (video, sequence) = (job.video, job.sequence);
+            std::thread::spawn({
+                let tx = self.progress_tx.clone();
+                let cancelled = self.gui.process_cancelled.clone();
+                let snapshot = self.gui.population_requested.clone();
+                move || {
+                    let result = match (video, sequence) {
+                        (Some((video, output)), _) => calculate::video::process_video(
+                            unprocessed,
+                            settings,
+                            video,
+                            output,
+                            &mut tx.clone(),
+                            &cancelled,
+                        ),
+                        (None, Some((sequence, output))) => calculate::video::process_sequence(
+                            settings,
+                            sequence,
+                            output,
+                            &mut tx.clone(),
+                            &cancelled,
+                        ),
+                        (None, None) => calculate::process(
+                            unprocessed,
+                            settings,
+                            &mut tx.clone(),
+                            cancelled,
+                            snapshot,
+                        ),
+                    };
+                    if let Err(err) = result {
+                        tx.send(ProgressMsg::Error(err.to_string())).ok();
+                    }
+                }
+            });
+        }
+    }
+
+    /// Adds the run that just made `preset` to the history of the preset it was tweaked
+    /// from, and gives it to `preset`.
+    fn record_run(&mut self, preset: &mut Preset, now: f64) {
+        let Some((mut run, parent, started)) = self.gui.run_started.take() else {
+            return;
+        };
+        run.finished = preset::now_unix();
+        run.seconds = (now - started) as f32;
+        run.cost = preset.quality.as_ref().map(|q| q.total_cost);
+        let mut history = parent
+            .and_then(|id| self.gui.presets.iter().find(|p| p.meta.id == id))
+            .map(|p| p.meta.history.clone())
+            .unwrap_or_default();
+        preset::push_history(&mut history, run);
+        preset.meta.history = history;
+    }
+
+    /// Lists the current preset's runs, newest first, with what changed in each.
+    fn history_window(&mut self, ctx: &egui::Context) {
+        if !self.gui.show_history {
+            return;
+        }
+        let history = self
+            .gui
+            .presets
+            .get(self.gui.current_preset)
+            .map(|p| p.meta.history.as_slice())
+            .unwrap_or_default();
+        Window::new("history")
+            .open(&mut self.gui.show_history)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                if history.is_empty() {
+                    ui.label("no runs recorded for this preset");
+                    return;
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(320.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("history").striped(true).show(ui, |ui| {
+                            for heading in ["run", "finished", "took", "cost", "changes"] {
+                                ui.strong(heading);
+                            }
+                            ui.end_row();
+                            for (i, run) in history.iter().enumerate().rev() {
+                                ui.label(&run.name);
+                                ui.label(preset::format_date(run.finished));
+                                ui.label(format!("{:.0} s", run.seconds));
+                                ui.label(run.cost.map_or("-".to_owned(), |cost| cost.to_string()));
+                                let changes = match i.checked_sub(1) {
+                                    None => "first run".to_owned(),
+                                    Some(earlier) => {
+                                        let changes = run.changes_from(&history[earlier]);
+                                        if changes.is_empty() {
+                                            "same settings".to_owned()
+                                        } else {
+                                            changes.join("\n")
+                                        }
+                                    }
+                                };
+                                let settings = run
+                                    .settings
+                                    .iter()
+                                    .map(|(name, value)| format!("{}: {}", name, value))
+                                    .collect::<Vec<_>>();
+                                ui.label(changes).on_hover_text(settings.join("\n"));
+                                ui.end_row();
+                            }
+                        });
+                    });
+            });
+    }
+
+    /// Stops the running job. Natively the solver notices the flag and reports back; the
+    /// web worker can't be interrupted, so it's terminated and restarted instead.
+    fn cancel_running_job(&mut self, device: &egui_wgpu::wgpu::Device) {
+        #[cfg(target_arch = "wasm32")]
+        {
+            if let Some(w) = &self.worker {
+                w.terminate();
+            }
+            self.worker = None;
+            self.telemetry.cancel_solve();
+            self.preview_image = None;
+            self.resize_textures(device, (DEFAULT_RESOLUTION, DEFAULT_RESOLUTION), false);
+            self.gui.hide_progress_modal();
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        let _ = device;
+        self.gui.process_cancelled.store(true, Ordering::Relaxed);
+        self.gui.last_progress = 0.0;
+    }
+
+    /// Lists the running and waiting jobs, with controls to reorder or cancel them.
+    fn queue_window(&mut self, ctx: &egui::Context, device: &egui_wgpu::wgpu::Device) {
+        if self.gui.queue.is_empty() {
+            return;
+        }
+        let mut cancel_running = false;
+        Window::new("queue")
+            .collapsible(true)
+            .resizable(false)
+            .anchor(egui::Align2::RIGHT_BOTTOM, (0.0, 0.0))
+            .show(ctx, |ui| {
+                if let Some(name) = &self.gui.running_job {
+                    ui.horizontal(|ui| {
+                        ui.label(format!(
+                            "▶ {} ({:.0}%)",
+                            name,
+                            self.gui.last_progress * 100.0
+                        ));
+                        if ui.small_button("✖").on_hover_text("cancel").clicked() {
+                            cancel_running = true;
+                        }
+                    });
+                }
+                let queue = &mut self.gui.queue;
+                let jobs = queue
+                    .jobs()
+                    .iter()
+                    .map(|job| (job.id(), job.name().to_owned(), job.priority))
+                    .collect::<Vec<_>>();
+                let last = jobs.len() - 1;
+                for (i, (id, name, mut priority)) in jobs.into_iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(name);
+                        if priority_select(ui, id, &mut priority) {
+                            queue.set_priority(id, priority);
+                        }
+                        if ui
+                            .add_enabled(i > 0, egui::Button::new("⏶").small())
+                            .clicked()
+                        {
+                            queue.move_up(id);
+                        }
+                        if ui
+                            .add_enabled(i < last, egui::Button::new("⏷").small())
+                            .clicked()
+                        {
+                            queue.move_down(id);
+                        }
+                        if ui.small_button("✖").on_hover_text("remove").clicked() {
+                            queue.remove(id);
+                        }
+                    });
+                }
+            });
+        if cancel_running {
+            self.cancel_running_job(device);
+        }
+    }
+
+    /// Replaces the config, keeping flags that only come from the command line, and
+    /// saves it.
+    fn set_config(&mut self, ctx: &egui::Context, config: Config) {
+        #[cfg(not(target_arch = "wasm32"))]
+        let config = Config {
+            no_dialog: self.config.no_dialog,
+            ..config
+        };
+        self.config = config;
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(err) = self.config.save() {
+            self.gui
+                .show_error(format!("failed to save config: {}", err));
+        }
+        self.apply_config(ctx);
+    }
+
+    /// Shows what a backup will add and lets the user resolve conflicting presets.
+    fn import_window(&mut self, ctx: &egui::Context) {
+        let Some(import) = &mut self.gui.import else {
+            return;
+        };
+        let presets = &self.gui.presets;
+        let mut apply = false;
+        let mut close = false;
+        Window::new("import backup")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                let new = import.backup.presets.len() - import.conflicts.len();
+                ui.label(format!("{} new presets", new));
+                if import.duplicates > 0 {
+                    ui.weak(format!("{} already here, left out", import.duplicates));
+                }
+                if !import.conflicts.is_empty() {
+                    ui.label("these match a preset you already have:");
+                    egui::ScrollArea::vertical()
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            egui::Grid::new("import_conflicts").show(ui, |ui| {
+                                for conflict in &mut import.conflicts {
+                                    ui.label(&import.backup.presets[conflict.incoming].inner.name);
+                                    if let Some(existing) = conflict.existing.find(presets) {
+                                        ui.weak(format!("vs {}", presets[existing].inner.name));
+                                    }
+                                    for resolution in Resolution::ALL {
+                                        ui.selectable_value(
+                                            &mut conflict.resolution,
+                                            resolution,
+                                            resolution.label(),
+                                        );
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                        });
+                }
+                ui.checkbox(&mut import.import_config, "also replace preferences");
+                ui.horizontal(|ui| {
+                    if ui.button("import").clicked() {
+                        apply = true;
+                    }
+                    if ui.button("cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
+        if !apply {
+            if close {
+                self.gui.import = None;
+            }
+            return;
+        }
+        let Some(import) = self.gui.import.take() else {
+            return;
+        };
+        if let Some(text) = import.apply(&mut self.gui.presets) {
+            match Config::parse(&text) {
+                Ok(config) => self.set_config(ctx, config),
+                Err(err) => self
+                    .gui
+                    .show_error(format!("the backup's preferences are invalid: {}", err)),
+            }
+        }
+    }
+
+    /// Lists presets that failed the startup check, offering to recalculate them from their
+    /// source image, set them aside, or delete them.
+    fn broken_presets_window(&mut self, ctx: &egui::Context) {
+        if !self.gui.show_broken_presets {
+            return;
+        }
+        let mut open = true;
+        let mut repair = None;
+        let mut delete = None;
+        Window::new("broken presets")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("these presets were damaged and can't be opened:");
+                egui::Grid::new("broken_presets").show(ui, |ui| {
+                    for (i, broken) in self.gui.broken_presets.iter_mut().enumerate() {
+                        ui.label(&broken.preset.inner.name);
+                        ui.weak(&broken.problem);
+                        if ui
+                            .add_enabled(broken.preset.source_intact(), egui::Button::new("repair"))
+                            .on_hover_text("recalculate it from its source image")
+                            .on_disabled_hover_text("the source image is damaged too")
+                            .clicked()
+                        {
+                            repair = Some(i);
+                        }
+                        if broken.quarantined {
+                            ui.weak("set aside");
+                        } else if ui
+                            .button("set aside")
+                            .on_hover_text("keep it here without asking again")
+                            .clicked()
+                        {
+                            broken.quarantined = true;
+                        }
+                        if ui.button("delete").clicked() {
+                            delete = Some(i);
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+
+        if let Some(i) = repair {
+            let broken = self.gui.broken_presets.remove(i);
+            let inner = broken.preset.inner;
+            if let Some(img) = SourceImg::from_raw(inner.width, inner.height, inner.source_img) {
+                let mut settings = GenerationSettings::default(Uuid::new_v4(), inner.name);
+                settings.sidelen = inner.width;
+                settings.algorithm = self.config.algorithm;
+                self.gui.queue.push(Job {
+                    img,
+                    settings,
+                    priority: Priority::Normal,
+                    record_timelapse: false,
+                    #[cfg(not(target_arch = "wasm32"))]
+                    video: None,
+                    #[cfg(not(target_arch = "wasm32"))]
+                    sequence: None,
+                });
+            }
+        }
+        if let Some(i) = delete {
+            self.gui.broken_presets.remove(i);
+        }
+        self.gui.show_broken_presets = open && !self.gui.broken_presets.is_empty();
+    }
+
+    /// Lets the user drag presets into folders and edit their tags.
+    fn organize_presets_window(&mut self, ctx: &egui::Context) {
+        if !self.gui.organizing_presets {
+            return;
+        }
+        let (mut folders, _) = preset::folders_and_tags(&self.gui.presets);
+        let new_folder = self.gui.new_folder.trim().to_owned();
+        if !new_folder.is_empty() && !folders.contains(&new_folder) {
+            folders.push(new_folder.clone());
+        }
+
+        let presets = &self.gui.presets;
+        let editing = &mut self.gui.editing_tags;
+        let new_folder_text = &mut self.gui.new_folder;
+        let mut moved = None;
+        let mut tagged = None;
+        let mut open = true;
+        Window::new("organize presets")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("drag presets between folders");
+                ui.horizontal(|ui| {
+                    ui.label("new folder:");
+                    ui.text_edit_singleline(new_folder_text);
+                });
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        for folder in std::iter::once(String::new()).chain(folders) {
+                            let frame = egui::Frame::group(ui.style());
+                            let (_, dropped) = ui.dnd_drop_zone::<usize, ()>(frame, |ui| {
+                                ui.set_min_width(280.0);
+                                if folder.is_empty() {
+                                    ui.strong("unsorted");
+                                } else {
+                                    ui.strong(format!("🗀 {}", folder));
+                                }
+                                for (i, preset) in presets.iter().enumerate() {
+                                    if preset.meta.folder != folder {
+                                        continue;
+                                    }
+                                    ui.horizontal(|ui| {
+                                        let id = egui::Id::new(("organize_preset", i));
+                                        ui.dnd_drag_source(id, i, |ui| {
+                                            ui.label(format!("☰ {}", preset.inner.name));
+                                        });
+                                        match editing {
+                                            Some((index, text)) if *index == i => {
+                                                let response = ui.add(
+                                                    egui::TextEdit::singleline(text)
+                                                        .hint_text("tag, another tag")
+                                                        .desired_width(120.0),
+                                                );
+                                                if response.lost_focus()
+                                                    || ui.small_button("✔").clicked()
+                                                {
+                                                    tagged = Some((i, text.clone()));
+                                                }
+                                            }
+                                            _ => {
+                                                if ui
+                                                    .small_button("🏷")
+                                                    .on_hover_text("edit tags")
+                                                    .clicked()
+                                                {
+                                                    *editing =
+                                                        Some((i, preset.meta.tags.join(", ")));
+                                                }
+                                                for tag in &preset.meta.tags {
+                                                    ui.weak(format!("#{}", tag));
+                                                }
+                                            }
+                                        }
+                                    });
+                                }
+                            });
+                            if let Some(index) = dropped {
+                                moved = Some((*index, folder));
+                            }
+                        }
+                    });
+            });
+
+        if let Some((index, folder)) = moved {
+            if folder == new_folder {
+                self.gui.new_folder.clear();
+            }
+            self.gui.presets[index].meta.folder = folder;
+        }
+        if let Some((index, text)) = tagged {
+            let mut tags = text
+                .split(',')
+                .map(|t| t.trim().trim_start_matches('#').to_owned())
+                .filter(|t| !t.is_empty())
+                .collect::<Vec<_>>();
+            tags.dedup();
+            self.gui.presets[index].meta.tags = tags;
+            self.gui.editing_tags = None;
+        }
+        self.gui.organizing_presets = open;
+    }
+
+    /// Edits the current preset's export profiles.
+    fn export_profiles_window(&mut self, ctx: &egui::Context) {
+        if !self.gui.editing_profiles {
+            return;
+        }
+        let Some(preset) = self.gui.presets.get_mut(self.gui.current_preset) else {
+            self.gui.editing_profiles = false;
+            return;
+        };
+        if preset.meta.export_profiles.is_empty() {
+            preset.meta.export_profiles = ExportProfile::defaults();
+        }
+        let profiles = &mut preset.meta.export_profiles;
+        let mut removed = None;
+        let mut open = true;
+        Window::new(format!("export profiles for {}", preset.inner.name))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                for (i, profile) in profiles.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.add(egui::TextEdit::singleline(&mut profile.name).desired_width(110.0));
+                        egui::ComboBox::from_id_salt(("profile_format", i))
+                            .selected_text(profile.format.label())
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut profile.format, ExportFormat::Gif, "gif");
+                                ui.selectable_value(
+                                    &mut profile.format,
+                                    ExportFormat::Apng,
+                                    "apng",
+                                )
+                                .on_hover_text("full color, without the gif's banding");
+                                ui.selectable_value(
+                                    &mut profile.format,
+                                    ExportFormat::PngZip,
+                                    "png frames (zip)",
+                                )
+                                .on_hover_text("every frame at full quality, for video editors");
+                                ui.selectable_value(
+                                    &mut profile.format,
+                                    ExportFormat::SpriteSheet,
+                                    "sprite sheet",
+                                )
+                                .on_hover_text("laid out like the export menu's sprite sheet");
+                                #[cfg(not(target_arch = "wasm32"))]
+                                ui.selectable_value(
+                                    &mut profile.format,
+                                    ExportFormat::PngFrames,
+                                    "png frames",
+                                );
+                                #[cfg(not(target_arch = "wasm32"))]
+                                ui.selectable_value(
+                                    &mut profile.format,
+                                    ExportFormat::Video,
+                                    "video",
+                                );
+                            });
+                        ui.add(
+                            egui::DragValue::new(&mut profile.resolution)
+                                .range(16..=DEFAULT_RESOLUTION)
+                                .suffix(" px"),
+                        );
+                        ui.add(
+                            egui::DragValue::new(&mut profile.framerate)
+                                .range(1..=MAX_FRAMERATE)
+                                .suffix(" fps"),
+                        );
+                        if profile.format == ExportFormat::Gif {
+                            ui.add(
+                                egui::DragValue::new(&mut profile.max_size_mb)
+                                    .range(1..=500)
+                                    .suffix(" MB"),
+                            );
+                            loop_mode_ui(ui, ("profile_loop", i), &mut profile.loop_mode);
+                        }
+                        if ui.small_button("🗑").on_hover_text("remove").clicked() {
+                            removed = Some(i);
+                        }
+                    });
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("add profile").clicked() {
+                        profiles.push(ExportProfile::default());
+                    }
+                    if ui
+                        .button("reset")
+                        .on_hover_text("go back to the default profiles")
+                        .clicked()
+                    {
+                        *profiles = ExportProfile::defaults();
+                    }
+                });
+            });
+        if let Some(i) = removed {
+            profiles.remove(i);
+        }
+        self.gui.editing_profiles = open;
+    }
+
+    /// Queues a solve saved by a checkpoint to carry on where it was interrupted.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn resume_checkpoint(&mut self) {
+        use calculate::checkpoint::{self, CHECKPOINT_EXTENSION};
+        let dir = checkpoint::dir().filter(|dir| dir.exists());
+        match pick_saved_run("choose run to resume", CHECKPOINT_EXTENSION, dir) {
+            Some(Ok((img, settings))) => self.queue_saved_run(img, settings),
+            Some(Err(err)) => self
+                .gui
+                .show_error(format!("failed to resume the run: {}", err)),
+            None => {}
+        }
+    }
+
+    /// Queues a new run carrying on from a saved population. It gets its own name and a
+    /// fresh seed, so branching from the same population more than once explores
+    /// different paths.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn branch_population(&mut self) {
+        use calculate::checkpoint::POPULATION_EXTENSION;
+        match pick_saved_run(
+            "choose population to branch from",
+            POPULATION_EXTENSION,
+            None,
+        ) {
+            Some(Ok((img, settings))) => {
+                let mut settings = settings.clone_with_new_id();
+                if let Some(state) = &mut settings.resume {
+                    state.seed = Uuid::new_v4().as_u64_pair().0;
+                }
+                self.queue_saved_run(img, settings);
+            }
+            Some(Err(err)) => self
+                .gui
+                .show_error(format!("failed to load the population: {}", err)),
+            None => {}
+        }
+    }
+
+    /// Asks for a folder of numbered frames and starts configuring a generation for them,
+    /// shown with the first frame.
+    #[cfg(not(target_arch = "wasm32"))]
+    /// Opens the region the screenshot portal returned for configuring, once it has.
+    #[cfg(target_os = "linux")]
+    fn poll_screen_capture(&mut self) {
+        let capture = match self.gui.screen_capture.as_ref().map(|rx| rx.try_recv()) {
+            None | Some(Err(std::sync::mpsc::TryRecvError::Empty)) => return,
+            Some(Ok(capture)) => capture,
+            Some(Err(std::sync::mpsc::TryRecvError::Disconnected)) => {
+                Err("the screenshot request stopped unexpectedly".to_owned())
+            }
+        };
+        self.gui.screen_capture = None;
+        match capture {
+            Ok(Some(img)) => {
+                let mut settings =
+                    GenerationSettings::default(Uuid::new_v4(), "screen capture".to_owned());
+                settings.sidelen = self.config.sidelen;
+                settings.algorithm = self.config.algorithm;
+                self.gui.configuring_generation = Some((
+                    ensure_reasonable_size(img),
+                    settings,
+                    GuiImageCache::default(),
+                ));
+            }
+            Ok(None) => {}
+            Err(err) => self
+                .gui
+                .show_error(format!("failed to capture screen: {}", err)),
+        }
+    }
+
+    fn pick_source_sequence(&mut self) {
+        let Some(dir) = rfd::FileDialog::new()
+            .set_title("choose image sequence folder")
+            .pick_folder()
+        else {
+            return;
+        };
+        match calculate::video::SourceSequence::load(&dir) {
+            Ok(mut sequence) => {
+                for frame in &mut sequence.frames {
+                    *frame = ensure_reasonable_size(std::mem::take(frame));
+                }
+                let name = dir
+                    .file_name()
+                    .map_or("sequence".to_owned(), |n| n.to_string_lossy().into_owned());
+                let mut settings = GenerationSettings::default(Uuid::new_v4(), name);
+                settings.sidelen = self.config.sidelen;
+                settings.algorithm = self.config.algorithm;
+                self.gui.configuring_generation = Some((
+                    sequence.frames[0].clone(),
+                    settings,
+                    GuiImageCache::default(),
+                ));
+                self.gui.source_sequence = Some(sequence);
+            }
+            Err(err) => self
+                .gui
+                .show_error(format!("failed to load image sequence: {}", err)),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn queue_saved_run(&mut self, img: SourceImg, settings: GenerationSettings) {
+        self.gui.queue.push(Job {
+            img,
+            settings,
+            priority: Priority::Normal,
+            record_timelapse: false,
+            video: None,
+            sequence: None,
+        });
+    }
+
+    /// Starts or shows the folder watch, which obamifies every image dropped into a
+    /// folder with the last used settings.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn folder_watch_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(watch) = &self.gui.folder_watch else {
+            if ui
+                .button("watch folder...")
+                .on_hover_text("obamify every image dropped into a folder")
+                .clicked()
+            {
+                if let Some(dir) = rfd::FileDialog::new()
+                    .set_title("choose folder to watch")
+                    .pick_folder()
+                {
+                    let mut settings = match &self.gui.saved_config {
+                        Some((_, settings)) => settings.clone(),
+                        None => {
+                            let mut settings =
+                                GenerationSettings::default(Uuid::new_v4(), String::new());
+                            settings.sidelen = self.config.sidelen;
+                            settings.algorithm = self.config.algorithm;
+                            settings
+                        }
+                    };
+                    settings.threads = self.solver_threads();
+                    let results = self
+                        .config
+                        .output_dir
+                        .clone()
+                        .unwrap_or_else(|| dir.join("results"));
+                    self.gui.folder_watch = Some(calculate::watch::FolderWatch::start(
+                        dir,
+                        results,
+                        settings.finalized(),
+                        self.config.hdr,
+                    ));
+                }
+            }
+            return;
+        };
+
+        let status = match &watch.current {
+            Some(name) => format!("processing {}...", name),
+            None => format!("watching, {} done", watch.processed),
+        };
+        ui.label(status).on_hover_text(format!(
+            "watching {}\nresults go to {}",
+            watch.dir.display(),
+            watch.results.display()
+        ));
+        if let Some(err) = &watch.last_error {
+            ui.colored_label(ui.visuals().error_fg_color, "⚠")
+                .on_hover_text(err);
+        }
+        if ui.small_button("stop watching").clicked() {
+            self.gui.folder_watch = None;
+        }
+    }
+}
+
+/// Returns whether the priority changed.
+fn priority_select(ui: &mut egui::Ui, id: impl std::hash::Hash, priority: &mut Priority) -> bool {
+    let mut changed = false;
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(priority.label())
+        .width(70.0)
+        .show_ui(ui, |ui| {
+            for p in Priority::ALL {
+                changed |= ui.selectable_value(priority, p, p.label()).changed();
+            }
+        });
+    changed
+}
+
+/// Plots the mean cell cost per generation, scaled to fit.
+fn quality_report_ui(ui: &mut egui::Ui, report: &QualityReport) {
+    egui::Grid::new("quality_report")
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("total cost");
+            ui.label(report.total_cost.to_string());
+            ui.end_row();
+            ui.label("displacement");
+            ui.label(format!(
+                "{:.1} cells on average, {:.1} at most",
+                report.mean_displacement, report.max_displacement
+            ));
+            ui.end_row();
+            ui.label("color error");
+            ui.label(
+                stats::ERROR_PERCENTILES
+                    .iter()
+                    .zip(report.color_error)
+                    .map(|(p, e)| format!("{p}%: {e:.0}"))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )
+            .on_hover_text("rgb distance to the target color, by percentile");
+            ui.end_row();
+            for (k, fraction) in stats::WITHIN_CELLS.iter().zip(report.within) {
+                ui.label(format!("within {k} cells"));
+                ui.label(format!("{:.1}% of pixels", fraction * 100.0));
+                ui.end_row();
+            }
+        });
+}
+
+fn convergence_chart(ui: &mut egui::Ui, stats: &[GenerationStats], size: egui::Vec2) {
+    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+    let (min, max) = stats.iter().fold((f64::MAX, f64::MIN), |(lo, hi), s| {
+        (lo.min(s.mean_cost), hi.max(s.mean_cost))
+    });
+    let range = (max - min).max(f64::EPSILON);
+    let last = (stats.len() - 1).max(1) as f32;
+    let points = stats
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            egui::pos2(
+                rect.left() + rect.width() * i as f32 / last,
+                rect.bottom() - rect.height() * ((s.mean_cost - min) / range) as f32,
+            )
+        })
+        .collect::<Vec<_>>();
+    painter.add(egui::Shape::line(
+        points,
+        egui::Stroke::new(1.5, ui.visuals().selection.bg_fill),
+    ));
+
+    let latest = stats.last().unwrap();
+    painter.text(
+        rect.right_top() + egui::vec2(-4.0, 2.0),
+        egui::Align2::RIGHT_TOP,
+        format!("gen {} · cost {:.0}", latest.generation, latest.mean_cost),
+        egui::FontId::monospace(10.0),
+        ui.visuals().text_color(),
+    );
+}
+
+/// Picks a loop mode, and the repeat count if it has one. Returns whether it changed.
+fn loop_mode_ui(ui: &mut egui::Ui, id: impl std::hash::Hash, mode: &mut LoopMode) -> bool {
+    let mut changed = false;
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(mode.label())
+        .show_ui(ui, |ui| {
+            for option in LoopMode::ALL {
+                let text = match option {
+                    LoopMode::Repeat(_) => "play n times".to_owned(),
+                    _ => option.label(),
+                };
+                if ui.selectable_label(mode.same_kind(option), text).clicked()
+                    && !mode.same_kind(option)
+                {
+                    *mode = option;
+                    changed = true;
+                }
+            }
+        });
+    if let LoopMode::Repeat(n) = mode {
+        changed |= ui
+            .add(egui::DragValue::new(n).range(2..=100).suffix(" times"))
+            .changed();
+    }
+    changed
+}
+
+/// The end of a recording's progress label, when it's recording a set duration.
+fn countdown(seconds_left: Option<f64>) -> String {
+    seconds_left
+        .map(|s| format!(" {:.0} s left", s.ceil()))
+        .unwrap_or_default()
+}
+
+fn save_file(
+    config: &Config,
+    gui: &mut GuiState,
+    name: String,
+    extension: &'static str,
+    contents: impl AsRef<[u8]> + 'static,
+) {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        if let Some(path) = config.output().save_path("save file", &name, extension) {
+            if let Err(err) = std::fs::write(path, contents) {
+                gui.show_error(format!("failed to save {}: {}", name, err));
+            }
+        }
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = config;
+        let picked = gui.picked_tx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Some(handle) = rfd::AsyncFileDialog::new()
+                .set_file_name(&name)
+                .add_filter(extension, &[extension])
+                .save_file()
+                .await
+            {
+                if let Err(err) = handle.write(contents.as_ref()).await {
+                    let error = format!("failed to save {}: {}", name, err);
+                    picked
+                        .send(Box::new(move |app| app.gui.show_error(error)))
+                        .ok();
+                }
+            }
+        });
+    }
+}
+
+/// Asks for a checkpoint or population file and reads the solve it saved. None if the
+/// dialog was closed.
+#[cfg(not(target_arch = "wasm32"))]
+fn pick_saved_run(
+    title: &str,
+    extension: &'static str,
+    dir: Option<std::path::PathBuf>,
+) -> Option<Result<(SourceImg, GenerationSettings), String>> {
+    use calculate::checkpoint::Checkpoint;
+    let mut dialog = rfd::FileDialog::new()
+        .set_title(title)
+        .add_filter(extension, &[extension]);
+    if let Some(dir) = dir {
+        dialog = dialog.set_directory(dir);
+    }
+    let path = dialog.pick_file()?;
+    Some(
+        std::fs::read(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|data| Checkpoint::decode(&data).map_err(|err| err.to_string()))
+            .and_then(|checkpoint| {
+                checkpoint
+                    .into_job()
+                    .ok_or_else(|| "the saved source image is corrupt".to_owned())
+            }),
+    )
+}
+
+fn ensure_reasonable_size<P: image::Pixel + 'static>(
+    img: image::ImageBuffer<P, Vec<P::Subpixel>>,
+) -> image::ImageBuffer<P, Vec<P::Subpixel>> {
+    let max_side = 512;
+    let (w, h) = img.dimensions();
+    if w <= max_side && h <= max_side {
+        return img;
+    }
+    let scale = (max_side as f32 / w as f32).min(max_side as f32 / h as f32);
+    let new_w = (w as f32 * scale).round() as u32;
+    let new_h = (h as f32 * scale).round() as u32;
+
+    image::imageops::resize(&img, new_w, new_h, image::imageops::FilterType::Lanczos3)
+}
+
+/// Shows how the crops turn out once pixels are moved, after one has been changed.
+fn result_preview_gui(
+    ui: &mut egui::Ui,
+    source_img: &SourceImg,
+    settings: &GenerationSettings,
+    preview: &mut ResultPreview,
+) {
+    let now = ui.input(|i| i.time);
+    if preview.stale && now - preview.started >= RESULT_PREVIEW_REFRESH {
+        preview.solve = CropPreview::new(source_img, settings).ok();
+        preview.texture = None;
+        preview.stale = false;
+        preview.started = now;
+    }
+    let Some(solve) = &mut preview.solve else {
+        return;
+    };
+    if !solve.done() || preview.texture.is_none() {
+        solve.step();
+        let image = egui::ColorImage::from_rgb(
+            [PREVIEW_SIDELEN as usize, PREVIEW_SIDELEN as usize],
+            &solve.image(),
+        );
+        match &mut preview.texture {
+            Some(tex) => tex.set(image, egui::TextureOptions::NEAREST),
+            None => {
+                preview.texture = Some(ui.ctx().load_texture(
+                    "result preview",
+                    image,
+                    egui::TextureOptions::NEAREST,
+                ))
+            }
+        }
+    }
+    if let Some(tex) = &preview.texture {
+        ui.vertical(|ui| {
+            ui.add(
+                egui::Image::from_texture(tex)
+                    .fit_to_exact_size(egui::Vec2::splat(RESULT_PREVIEW_SIZE)),
+            )
+            .on_hover_text("a rough solve of these crops, to judge them by the result");
+            ui.label("result");
+        });
+    }
+}
+
+fn image_overlap_preview(
+    arg: &str,
+    ui: &mut egui::Ui,
+    settings: &GenerationSettings,
+    cache: &mut GuiImageCache,
+    source_img: &SourceImg,
+    get_raw_target: &SourceImg,
+    blend: f32,
+) {
+    let tex = if cache.overlap_preview.is_none()
+        || cache.source_preview.is_none()
+        || cache.target_preview.is_none()
+    {
+        let src_img = settings.source_crop_scale.apply(source_img, 64);
+        let tgt_img = settings.target_crop_scale.apply(get_raw_target, 64);
+        let blended = blend_rgb_images(&src_img, &tgt_img, blend);
+        let p = ui.ctx().load_texture(
+            arg,
+            egui::ColorImage::from_rgb([64, 64], blended.as_raw()),
+            egui::TextureOptions::LINEAR,
+        );
+        cache.overlap_preview = Some(p.clone());
+        p
+    } else {
+        cache.overlap_preview.as_ref().unwrap().clone()
+    };
+    ui.add(egui::Image::from_texture(&tex));
+}
+
+#[derive(PartialEq)]
+enum CropGuiAction {
+    None,
+    ChangeImage,
+    NoFaceFound,
+    PickWeights,
+}
+
+/// A heatmap of the target's weights over its preview, toggled with `shown`. `weights` is
+/// only needed when the preview is rebuilt.
+struct WeightOverlay<'a> {
+    shown: &'a mut bool,
+    weights: Option<&'a SourceImg>,
+}
+
+/// Blue for cells the solver hardly cares about the color of, through green and yellow to
+/// red for the ones it matches most closely.
+fn weight_heat(weight: u8) -> [f32; 3] {
+    let t = weight as f32 / 255.0;
+    let stops = [
+        [0.0, 0.2, 1.0],
+        [0.0, 0.9, 0.3],
+        [1.0, 0.9, 0.0],
+        [1.0, 0.1, 0.0],
+    ];
+    let pos = t * (stops.len() - 1) as f32;
+    let i = (pos as usize).min(stops.len() - 2);
+    let f = pos - i as f32;
+    std::array::from_fn(|c| stops[i][c] + (stops[i + 1][c] - stops[i][c]) * f)
+}
+
+fn image_crop_gui(
+    name: &'static str,
+    ui: &mut egui::Ui,
+    img: &SourceImg,
+    crop_scale: &mut CropScale,
+    cache: &mut Option<TextureHandle>,
+    face_crop: bool, // offer cropping to the face automatically
+    overlay: Option<WeightOverlay<'_>>,
+) -> CropGuiAction {
+    let mut action = CropGuiAction::None;
+    ui.vertical(|ui| {
+        let tex = match &cache {
+            None => {
+                let mut preview = crop_scale.apply(img, 128);
+                if let Some(WeightOverlay {
+                    shown: true,
+                    weights: Some(weights),
+                }) = &overlay
+                {
+                    let weights = crop_scale.apply(weights, 128);
+                    for (pixel, weight) in preview.pixels_mut().zip(weights.pixels()) {
+                        let heat = weight_heat(weight[0]);
+                        for (c, h) in pixel.0.iter_mut().zip(heat) {
+                            *c = (*c as f32 * 0.45 + h * 255.0 * 0.55).round() as u8;
+                        }
+                    }
+                }
+                let p = ui.ctx().load_texture(
+                    name,
+                    egui::ColorImage::from_rgb([128, 128], preview.as_raw()),
+                    egui::TextureOptions::LINEAR,
+                );
+                *cache = Some(p.clone());
+                p
+            }
+            Some(t) => t.clone(),
+        };
+        ui.add(egui::Image::from_texture(&tex));
+        if let Some(overlay) = overlay {
+            if ui
+                .checkbox(overlay.shown, "show weights")
+                .on_hover_text(
+                    "red parts of the target get the closest colors, blue parts whatever is left",
+                )
+                .changed()
+            {
+                *cache = None;
+            }
+            if ui
+                .button("weights from svg...")
+                .on_hover_text("white paths matter most, black least, drawn at the grid's size")
+                .clicked()
+            {
+                action = CropGuiAction::PickWeights;
+            }
+        }
+        if ui.button(format!("change {name} image")).clicked() {
+            action = CropGuiAction::ChangeImage;
+        }
+        if face_crop && ui.button("auto-crop to face").clicked() {
+            match calculate::face::find_face(img) {
+                Some(face) => {
+                    // leave room around the face, for hair and a bit of background
+                    *crop_scale = CropScale::around(
+                        img.width(),
+                        img.height(),
+                        face.x,
+                        face.y,
+                        face.size * 1.6,
+                    );
+                    *cache = None;
+                }
+                None => action = CropGuiAction::NoFaceFound,
+            }
+        }
+        // crop sliders
+        ui.vertical(|ui| {
+            let values = *crop_scale;
+            let slider_w = ui.available_width().min(260.0);
+
+            ui.add_sized(
+                [slider_w, 20.0],
+                egui::Slider::new(&mut crop_scale.scale, 1.0..=MAX_CROP_ZOOM)
+                    .show_value(false)
+                    .text("zoom"),
+            );
+            ui.add_sized(
+                [slider_w, 20.0],
+                egui::Slider::new(&mut crop_scale.x, -1.0..=1.0)
+                    .show_value(false)
+                    .text("x-off."),
+            );
+            ui.add_sized(
+                [slider_w, 20.0],
+                egui::Slider::new(&mut crop_scale.y, -1.0..=1.0)
+                    .show_value(false)
+                    .text("y-off."),
+            );
+
+            if values != *crop_scale {
+                *cache = None; // force reload
+            }
+        });
+    });
+
+    action
+}
+
+/// Paints which pixels of the cropped source take part in the assignment.
+fn source_mask_gui(
+    ui: &mut egui::Ui,
+    source_img: &SourceImg,
+    settings: &mut GenerationSettings,
+    cache: &mut GuiImageCache,
+) {
+    let editor = &mut cache.mask;
+    if cache.source_preview.is_none() {
+        editor.preview = None; // the crop changed
+    }
+
+    ui.horizontal(|ui| {
+        ui.selectable_value(&mut editor.tool, MaskTool::Brush, "brush");
+        ui.selectable_value(&mut editor.tool, MaskTool::Lasso, "lasso");
+        ui.checkbox(&mut editor.erase, "erase");
+    });
+    if editor.tool == MaskTool::Brush {
+        ui.add(egui::Slider::new(&mut editor.brush_radius, 2.0..=48.0).text("brush size"));
+    }
+
+    let tex = editor
+        .preview
+        .get_or_insert_with(|| {
+            ui.ctx().load_texture(
+                "source mask",
+                mask_preview(source_img, settings),
+                egui::TextureOptions::LINEAR,
+            )
+        })
+        .clone();
+    let (rect, response) = ui.allocate_exact_size(
+        egui::vec2(MASK_EDITOR_SIZE, MASK_EDITOR_SIZE),
+        egui::Sense::drag(),
+    );
+    let painter = ui.painter_at(rect);
+    painter.image(
+        tex.id(),
+        rect,
+        egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+        egui::Color32::WHITE,
+    );
+
+    if let Some(pos) = response.interact_pointer_pos() {
+        let uv = ((pos - rect.min) / rect.size()).to_pos2();
+        let far_enough = editor
+            .stroke
+            .last()
+            .is_none_or(|last| last.distance(uv) * MASK_EDITOR_SIZE >= 1.0);
+        if response.dragged() && far_enough {
+            editor.stroke.push(uv);
+        }
+    }
+    let to_screen = |p: &egui::Pos2| rect.min + p.to_vec2() * rect.size();
+    let color = if editor.erase {
+        egui::Color32::from_rgb(255, 80, 80)
+    } else {
+        egui::Color32::WHITE
+    };
+    match editor.tool {
+        MaskTool::Brush => {
+            for p in &editor.stroke {
+                painter.circle_filled(to_screen(p), editor.brush_radius, color.gamma_multiply(0.3));
+            }
+        }
+        MaskTool::Lasso => {
+            painter.add(egui::Shape::line(
+                editor.stroke.iter().map(to_screen).collect(),
+                egui::Stroke::new(1.5, color),
+            ));
+        }
+    }
+
+    if response.drag_stopped() && !editor.stroke.is_empty() {
+        let value = if editor.erase { 0 } else { 255 };
+        let mut mask = settings.source_mask().ok().flatten().unwrap_or_else(|| {
+            // nothing is masked yet (or what was is unreadable), so everything takes part
+            // until something's drawn
+            let start = if editor.erase { 255 } else { 0 };
+            image::GrayImage::from_pixel(
+                source_img.width(),
+                source_img.height(),
+                image::Luma([start]),
+            )
+        });
+        let (x0, y0, side) = settings
+            .source_crop_scale
+            .region(mask.width(), mask.height());
+        let points = editor
+            .stroke
+            .iter()
+            .map(|p| (x0 as f32 + p.x * side as f32, y0 as f32 + p.y * side as f32))
+            .collect::<Vec<_>>();
+        match editor.tool {
+            MaskTool::Brush => {
+                let radius = editor.brush_radius / MASK_EDITOR_SIZE * side as f32;
+                paint_mask_stroke(&mut mask, &points, radius, value);
+            }
+            MaskTool::Lasso => fill_mask_polygon(&mut mask, &points, value),
+        }
+        settings.set_source_mask(Some(mask));
+        editor.stroke.clear();
+        editor.preview = None;
+    }
+
+    ui.horizontal(|ui| {
+        if ui
+            .button("remove background")
+            .on_hover_text("guesses the subject and hides everything around it")
+            .clicked()
+        {
+            settings.set_source_mask(Some(calculate::background::remove_background(source_img)));
+            settings.unmasked = Unmasked::Hidden;
+            editor.preview = None;
+        }
+        if ui.button("invert").clicked() {
+            let mut mask = settings.source_mask().ok().flatten().unwrap_or_else(|| {
+                image::GrayImage::from_pixel(
+                    source_img.width(),
+                    source_img.height(),
+                    image::Luma([255]),
+                )
+            });
+            image::imageops::invert(&mut mask);
+            settings.set_source_mask(Some(mask));
+            editor.preview = None;
+        }
+        if ui
+            .add_enabled(settings.has_source_mask(), egui::Button::new("clear"))
+            .clicked()
+        {
+            settings.set_source_mask(None);
+            editor.preview = None;
+        }
+    });
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_salt("unmasked_select")
+            .selected_text(settings.unmasked.label())
+            .show_ui(ui, |ui| {
+                for unmasked in Unmasked::ALL {
+                    ui.selectable_value(&mut settings.unmasked, unmasked, unmasked.label());
+                }
+            });
+        if settings.unmasked == Unmasked::Hidden {
+            ui.color_edit_button_srgb(&mut settings.pad_color)
+                .on_hover_text("color hidden pixels are painted in");
+        }
+    });
+    if settings.has_source_mask() && !settings.mask_applies() {
+        ui.label(
+            egui::RichText::new(
+                "the mask is ignored with blend sources, mutual swap or enforced symmetry",
+            )
+            .weak(),
+        );
+    }
+}
+
+/// The cropped source with the pixels outside the mask darkened.
+fn mask_preview(source_img: &SourceImg, settings: &GenerationSettings) -> egui::ColorImage {
+    let size = MASK_EDITOR_SIZE as u32;
+    let mut img = settings.source_crop_scale.apply(source_img, size);
+    if let Ok(Some(mask)) = settings.source_mask() {
+        let mask = settings.source_crop_scale.apply(&mask, size);
+        for (pixel, m) in img.pixels_mut().zip(mask.pixels()) {
+            if m[0] < 128 {
+                pixel.0 = pixel.0.map(|c| c / 4);
+            }
+        }
+    }
+    egui::ColorImage::from_rgb([size as usize; 2], img.as_raw())
+}
+
+/// Stamps circles of `radius` along the stroke, close enough together to join up.
+fn paint_mask_stroke(mask: &mut image::GrayImage, points: &[(f32, f32)], radius: f32, value: u8) {
+    let (w, h) = (mask.width() as i32, mask.height() as i32);
+    let mut stamp = |cx: f32, cy: f32| {
+        let y_range =
+            ((cy - radius).floor() as i32).max(0)..=((cy + radius).ceil() as i32).min(h - 1);
+        for y in y_range {
+            for x in
+                ((cx - radius).floor() as i32).max(0)..=((cx + radius).ceil() as i32).min(w - 1)
+            {
+                let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+                if dx * dx + dy * dy <= radius * radius {
+                    mask.put_pixel(x as u32, y as u32, image::Luma([value]));
+                }
+            }
+        }
+    };
+    stamp(points[0].0, points[0].1);
+    for pair in points.windows(2) {
+        let ((ax, ay), (bx, by)) = (pair[0], pair[1]);
+        let steps = ((bx - ax).hypot(by - ay) / (radius * 0.5).max(0.5)).ceil() as u32;
+        for i in 1..=steps.max(1) {
+            let t = i as f32 / steps.max(1) as f32;
+            stamp(ax + (bx - ax) * t, ay + (by - ay) * t);
+        }
+    }
+}
+
+/// Fills the closed polygon through `points`, one row at a time.
+fn fill_mask_polygon(mask: &mut image::GrayImage, points: &[(f32, f32)], value: u8) {
+    if points.len() < 3 {
+        return;
+    }
+    let min_y = points.iter().map(|p| p.1).fold(f32::MAX, f32::min).max(0.0) as u32;
+    let max_y =
+        (points.iter().map(|p| p.1).fold(f32::MIN, f32::max).ceil() as u32).min(mask.height());
+    let mut crossings = Vec::new();
+    for y in min_y..max_y {
+        let cy = y as f32 + 0.5;
+        crossings.clear();
+        for (i, &(ax, ay)) in points.iter().enumerate() {
+            let (bx, by) = points[(i + 1) % points.len()];
+            if (ay <= cy) != (by <= cy) {
+                crossings.push(ax + (cy - ay) / (by - ay) * (bx - ax));
+            }
+        }
+        crossings.sort_by(f32::total_cmp);
+        for span in crossings.chunks_exact(2) {
+            let from = (span[0] - 0.5).ceil().max(0.0) as u32;
+            let to = ((span[1] - 0.5).floor() as i64).min(mask.width() as i64 - 1);
+            for x in from as i64..=to {
+                mask.put_pixel(x as u32, y, image::Luma([value]));
+            }
+        }
+    }
+}
+
+fn block_size_label(block_size: u32) -> String {
+    if block_size <= 1 {
+        "per-pixel matching".to_owned()
+    } else {
+        format!("{block_size}x{block_size} blocks")
+    }
 }
 
 fn get_default_preset_name(mut n: String) -> String {
@@ -0,0 +1,249 @@
+//! Converting images with embedded ICC profiles to sRGB as they're loaded.
+//!
+//! Only matrix/TRC RGB profiles are understood: three colorants and a tone curve per
+//! channel. That covers what phones and cameras embed (Display P3, Adobe RGB, ProPhoto);
+//! anything else, like LUT-based printer profiles, is loaded unconverted.
+
+use std::error::Error;
+use std::io::{BufRead, Seek};
+
+use image::{DynamicImage, ImageDecoder, ImageReader};
+
+use crate::app::calculate::preprocess::srgb_from_linear;
+
+/// XYZ (D50, as ICC profiles use) to linear sRGB, Bradford adapted.
+const XYZ_D50_TO_SRGB: [[f32; 3]; 3] = [
+    [3.133_856, -1.616_867, -0.490_615],
+    [-0.978_768, 1.916_142, 0.033_454],
+    [0.071_945, -0.228_991, 1.405_243],
+];
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open(path: &std::path::Path) -> Result<DynamicImage, Box<dyn Error>> {
+    decode(ImageReader::open(path)?.with_guessed_format()?)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn load_from_memory(data: &[u8]) -> Result<DynamicImage, Box<dyn Error>> {
+    decode(ImageReader::new(std::io::Cursor::new(data)).with_guessed_format()?)
+}
+
+fn decode<R: BufRead + Seek>(reader: ImageReader<R>) -> Result<DynamicImage, Box<dyn Error>> {
+    let mut decoder = reader.into_decoder()?;
+    let icc = decoder.icc_profile().ok().flatten();
+    let img = DynamicImage::from_decoder(decoder)?;
+    Ok(match icc.as_deref().map(Profile::parse) {
+        Some(Ok(profile)) => profile.to_srgb(img),
+        Some(Err(err)) => {
+            tracing::warn!("ignoring embedded color profile: {}", err);
+            img
+        }
+        None => img,
+    })
+}
+
+enum Curve {
+    Gamma(f32),
+    Table(Vec<f32>),
+    // ICC parametric curve: y = (a x + b)^g + e for x >= d, else c x + f
+    Parametric {
+        g: f32,
+        a: f32,
+        b: f32,
+        c: f32,
+        d: f32,
+        e: f32,
+        f: f32,
+    },
+}
+
+impl Curve {
+    fn parse(t: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let param = |i: usize| be_fixed(t, 12 + i * 4);
+        match t.get(..4) {
+            Some(b"curv") => {
+                let count = be_u32(t, 8)? as usize;
+                Ok(match count {
+                    0 => Curve::Gamma(1.0),
+                    1 => Curve::Gamma(be_u16(t, 12)? as f32 / 256.0),
+                    _ => Curve::Table(
+                        (0..count)
+                            .map(|i| Ok(be_u16(t, 12 + i * 2)? as f32 / 65535.0))
+                            .collect::<Result<_, Box<dyn Error>>>()?,
+                    ),
+                })
+            }
+            Some(b"para") => {
+                let g = param(0)?;
+                let (mut a, mut b, mut c, mut d, mut e, mut f) =
+                    (1.0, 0.0, 0.0, f32::MIN, 0.0, 0.0);
+                match be_u16(t, 8)? {
+                    0 => {}
+                    1 => {
+                        (a, b) = (param(1)?, param(2)?);
+                        d = -b / a;
+                    }
+                    2 => {
+                        (a, b, e) = (param(1)?, param(2)?, param(3)?);
+                        d = -b / a;
+                        f = e;
+                    }
+                    3 => (a, b, c, d) = (param(1)?, param(2)?, param(3)?, param(4)?),
+                    4 => {
+                        (a, b, c, d) = (param(1)?, param(2)?, param(3)?, param(4)?);
+                        (e, f) = (param(5)?, param(6)?);
+                    }
+                    other => return Err(format!("unknown curve type {}", other).into()),
+                }
+                Ok(Curve::Parametric {
+                    g,
+                    a,
+                    b,
+                    c,
+                    d,
+                    e,
+                    f,
+                })
+            }
+            _ => Err("unsupported tone curve".into()),
+        }
+    }
+
+    fn eval(&self, x: f32) -> f32 {
+        match self {
+            Curve::Gamma(g) => x.max(0.0).powf(*g),
+            Curve::Table(table) => {
+                let pos = x.clamp(0.0, 1.0) * (table.len() - 1) as f32;
+                let i = (pos as usize).min(table.len() - 2);
+                let t = pos - i as f32;
+                table[i] * (1.0 - t) + table[i + 1] * t
+            }
+            Curve::Parametric {
+                g,
+                a,
+                b,
+                c,
+                d,
+                e,
+                f,
+            } => {
+                if x >= *d {
+                    (a * x + b).max(0.0).powf(*g) + e
+                } else {
+                    c * x + f
+                }
+            }
+        }
+    }
+}
+
+struct Profile {
+    to_srgb: [[f32; 3]; 3], // linear profile RGB to linear sRGB
+    curves: [Curve; 3],
+}
+
+impl Profile {
+    fn parse(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if data.get(16..20) != Some(b"RGB ") {
+            return Err("not an RGB profile".into());
+        }
+        let tag = |sig: &[u8; 4]| -> Result<&[u8], Box<dyn Error>> {
+            for i in 0..be_u32(data, 128)? as usize {
+                let entry = 132 + i * 12;
+                if data.get(entry..entry + 4) == Some(sig) {
+                    let offset = be_u32(data, entry + 4)? as usize;
+                    let size = be_u32(data, entry + 8)? as usize;
+                    return data
+                        .get(offset..offset + size)
+                        .ok_or_else(|| "truncated profile".into());
+                }
+            }
+            Err(format!("no {} tag", String::from_utf8_lossy(sig)).into())
+        };
+
+        let mut to_xyz = [[0.0; 3]; 3];
+        for (column, sig) in [b"rXYZ", b"gXYZ", b"bXYZ"].into_iter().enumerate() {
+            let xyz = tag(sig)?;
+            for (row, value) in to_xyz.iter_mut().enumerate() {
+                value[column] = be_fixed(xyz, 8 + row * 4)?;
+            }
+        }
+        let mut to_srgb = [[0.0; 3]; 3];
+        for (i, row) in to_srgb.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| XYZ_D50_TO_SRGB[i][k] * to_xyz[k][j]).sum();
+            }
+        }
+
+        Ok(Self {
+            to_srgb,
+            curves: [
+                Curve::parse(tag(b"rTRC")?)?,
+                Curve::parse(tag(b"gTRC")?)?,
+                Curve::parse(tag(b"bTRC")?)?,
+            ],
+        })
+    }
+
+    /// Converts 0..1 encoded profile RGB to 0..1 encoded sRGB.
+    fn convert(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let linear = [0, 1, 2].map(|i| self.curves[i].eval(rgb[i]));
+        self.to_srgb.map(|row| {
+            let v = (0..3).map(|k| row[k] * linear[k]).sum::<f32>();
+            srgb_from_linear(v.clamp(0.0, 1.0))
+        })
+    }
+
+    fn to_srgb(&self, img: DynamicImage) -> DynamicImage {
+        let _span = tracing::info_span!("icc_convert").entered();
+        match img {
+            DynamicImage::ImageRgb8(_) | DynamicImage::ImageRgba8(_) => {
+                let mut rgba = img.to_rgba8();
+                for p in rgba.pixels_mut() {
+                    let rgb = self.convert([p[0], p[1], p[2]].map(|c| c as f32 / 255.0));
+                    for (dst, v) in p.0.iter_mut().zip(rgb) {
+                        *dst = (v * 255.0).round() as u8;
+                    }
+                }
+                keep_alpha(DynamicImage::ImageRgba8(rgba), img.color().has_alpha())
+            }
+            DynamicImage::ImageRgb16(_) | DynamicImage::ImageRgba16(_) => {
+                let mut rgba = img.to_rgba16();
+                for p in rgba.pixels_mut() {
+                    let rgb = self.convert([p[0], p[1], p[2]].map(|c| c as f32 / 65535.0));
+                    for (dst, v) in p.0.iter_mut().zip(rgb) {
+                        *dst = (v * 65535.0).round() as u16;
+                    }
+                }
+                keep_alpha(DynamicImage::ImageRgba16(rgba), img.color().has_alpha())
+            }
+            // gray images carry gray profiles, and float images are linear already
+            other => other,
+        }
+    }
+}
+
+fn keep_alpha(img: DynamicImage, had_alpha: bool) -> DynamicImage {
+    match (had_alpha, img) {
+        (true, img) => img,
+        (false, DynamicImage::ImageRgba16(rgba)) => {
+            DynamicImage::ImageRgb16(DynamicImage::ImageRgba16(rgba).to_rgb16())
+        }
+        (false, img) => DynamicImage::ImageRgb8(img.to_rgb8()),
+    }
+}
+
+fn be_u16(data: &[u8], pos: usize) -> Result<u16, Box<dyn Error>> {
+    let bytes = data.get(pos..pos + 2).ok_or("truncated profile")?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn be_u32(data: &[u8], pos: usize) -> Result<u32, Box<dyn Error>> {
+    let bytes = data.get(pos..pos + 4).ok_or("truncated profile")?;
+    Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+/// An s15Fixed16Number.
+fn be_fixed(data: &[u8], pos: usize) -> Result<f32, Box<dyn Error>> {
+    Ok(be_u32(data, pos)? as i32 as f32 / 65536.0)
+}
@@ -0,0 +1,199 @@
+//! Drawing big grids' transformations as one instanced draw.
+//!
+//! Above a few hundred thousand cells, stepping the physics on the CPU, uploading every
+//! position and jump flooding them into cells takes longer than a frame. Instead each
+//! cell's path is uploaded once, as a storage buffer of source and destination, and the
+//! vertex shader places every cell's quad for the current frame, writing ids straight into
+//! the texture the shade pass reads. WebGL has no storage buffers in vertex shaders, so
+//! the web always uses the physics, and so do GL backends natively, whose devices get
+//! WebGL2's limits.
+
+use bytemuck::{Pod, Zeroable};
+use egui_wgpu::wgpu;
+use wgpu::util::DeviceExt;
+
+use crate::app::morph_sim::{CellMotion, Sim};
+
+pub const MIN_CELLS: u32 = 512 * 512;
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct ParamsMotion {
+    width: u32,
+    height: u32,
+    frame: f32,
+    cell: f32, // side of a cell's quad, in pixels
+}
+
+pub struct InstancedMotion {
+    pipeline: wgpu::RenderPipeline,
+    bgl: wgpu::BindGroupLayout,
+    params_buf: wgpu::Buffer,
+    bind_group: Option<wgpu::BindGroup>, // none until there are cells
+    count: u32,
+    end: f32, // the frame the last cell arrives
+    pub frame: u32,
+}
+
+impl InstancedMotion {
+    /// The instanced draw, if `device` allows storage buffers in vertex shaders.
+    pub fn new(adapter: &wgpu::Adapter, device: &wgpu::Device) -> Option<Self> {
+        let flags = adapter.get_downlevel_capabilities().flags;
+        if !flags.contains(wgpu::DownlevelFlags::VERTEX_STORAGE)
+            || device.limits().max_storage_buffers_per_shader_stage == 0
+        {
+            return None;
+        }
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("motion_bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("motion.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/motion.wgsl").into()),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("motion_pipeline"),
+            layout: Some(
+                &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("pl_motion"),
+                    bind_group_layouts: &[&bgl],
+                    push_constant_ranges: &[],
+                }),
+            ),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba8Unorm,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        let params_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("params_motion"),
+            size: std::mem::size_of::<ParamsMotion>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Some(Self {
+            pipeline,
+            bgl,
+            params_buf,
+            bind_group: None,
+            count: 0,
+            end: 0.0,
+            frame: 0,
+        })
+    }
+
+    /// Uploads the cells' paths and starts over from the first frame.
+    pub fn upload(&mut self, device: &wgpu::Device, sim: &Sim) {
+        self.frame = 0;
+        let motion = sim.planned_motion();
+        self.count = motion.len() as u32;
+        self.end = motion.iter().map(CellMotion::end).fold(0.0, f32::max);
+        if motion.is_empty() {
+            self.bind_group = None;
+            return;
+        }
+        let motion_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("motion"),
+            contents: bytemuck::cast_slice(&motion),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bg_motion"),
+            layout: &self.bgl,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: motion_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.params_buf.as_entire_binding(),
+                },
+            ],
+        }));
+    }
+
+    /// Whether every cell is at its destination by now.
+    pub fn arrived(&self) -> bool {
+        self.frame as f32 >= self.end
+    }
+
+    /// Draws the cells as they are this frame into `ids`, which is cleared first.
+    pub fn draw(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        ids: &wgpu::TextureView,
+        size: (u32, u32),
+    ) {
+        let params = ParamsMotion {
+            width: size.0,
+            height: size.1,
+            frame: self.frame as f32,
+            cell: size.0 as f32 / (self.count as f32).sqrt(),
+        };
+        queue.write_buffer(&self.params_buf, 0, bytemuck::bytes_of(&params));
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("motion_draw"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: ids,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        if let Some(bind_group) = &self.bind_group {
+            rpass.set_pipeline(&self.pipeline);
+            rpass.set_bind_group(0, bind_group, &[]);
+            rpass.draw(0..4, 0..self.count);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! The little json the exports and reports write by hand.
+
+use std::fmt::Write as _;
+
+/// `s` as a quoted json string. Toml's basic strings escape the same way.
+pub fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted += "\\\"",
+            '\\' => quoted += "\\\\",
+            '\n' => quoted += "\\n",
+            '\r' => quoted += "\\r",
+            '\t' => quoted += "\\t",
+            c if c.is_control() => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// `v` as a json number, or `null` if it's NaN or infinite, which json can't hold.
+pub fn number(v: f64) -> String {
+    if v.is_finite() {
+        v.to_string()
+    } else {
+        "null".to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_quotes_and_control_characters() {
+        assert_eq!(quote("plain"), "\"plain\"");
+        assert_eq!(
+            quote("a \"b\"\\c\nd\te\u{1}\u{7f}é"),
+            "\"a \\\"b\\\"\\\\c\\nd\\te\\u0001\\u007fé\""
+        );
+        assert_eq!(number(1.5), "1.5");
+        assert_eq!(number(f64::NAN), "null");
+        assert_eq!(number(f64::INFINITY), "null");
+    }
+}
@@ -0,0 +1,174 @@
+//! New pictures played through the current preset's assignment without solving again, so
+//! the permutation works like a fixed filter: a swapped in image, a looping gif, or a file
+//! that keeps being rewritten, such as a webcam's frames saved by another program.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::SystemTime;
+
+use crate::app::{SeedColor, calculate::util::SourceImg};
+
+#[cfg(not(target_arch = "wasm32"))]
+const FILE_POLL_INTERVAL: f64 = 0.05; // seconds between checks of a followed file
+
+enum Feed {
+    Still,
+    #[cfg(not(target_arch = "wasm32"))]
+    Frames {
+        frames: Vec<SourceImg>,
+        delays: Vec<u16>, // hundredths of a second
+        next: usize,
+    },
+    #[cfg(not(target_arch = "wasm32"))]
+    File {
+        path: PathBuf,
+        modified: Option<SystemTime>,
+    },
+}
+
+pub struct LiveSource {
+    feed: Feed,
+    current: Option<SourceImg>,
+    due: f64,    // egui time the feed is looked at next
+    fresh: bool, // `current` hasn't been handed out yet
+    pub label: String,
+}
+
+impl LiveSource {
+    pub fn still(img: SourceImg, label: String) -> Self {
+        Self {
+            feed: Feed::Still,
+            current: Some(img),
+            due: 0.0,
+            fresh: true,
+            label,
+        }
+    }
+
+    /// Plays `frames` on a loop, each for its delay.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn frames(frames: Vec<SourceImg>, delays: Vec<u16>, label: String) -> Self {
+        Self {
+            feed: Feed::Frames {
+                frames,
+                delays,
+                next: 0,
+            },
+            current: None,
+            due: 0.0,
+            fresh: false,
+            label,
+        }
+    }
+
+    /// Reloads the image at `path` whenever it changes.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn file(path: PathBuf) -> Self {
+        let label = path
+            .file_name()
+            .map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+        Self {
+            feed: Feed::File {
+                path,
+                modified: None,
+            },
+            current: None,
+            due: 0.0,
+            fresh: false,
+            label,
+        }
+    }
+
+    /// Hands the current picture out again, for when the colors it was shown with got
+    /// replaced, like by switching presets.
+    pub fn refresh(&mut self) {
+        self.fresh = self.current.is_some();
+    }
+
+    /// The picture to show now, at egui time `now`, if it's not the one already shown.
+    pub fn poll(&mut self, now: f64) -> Result<Option<&SourceImg>, String> {
+        if now >= self.due {
+            self.advance(now)?;
+        }
+        if !std::mem::take(&mut self.fresh) {
+            return Ok(None);
+        }
+        Ok(self.current.as_ref())
+    }
+
+    fn advance(&mut self, now: f64) -> Result<(), String> {
+        match &mut self.feed {
+            Feed::Still => self.due = f64::INFINITY,
+            #[cfg(not(target_arch = "wasm32"))]
+            Feed::Frames {
+                frames,
+                delays,
+                next,
+            } => {
+                self.current = Some(frames[*next].clone());
+                self.due = now + delays[*next].max(2) as f64 / 100.0;
+                *next = (*next + 1) % frames.len();
+                self.fresh = true;
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            Feed::File { path, modified } => {
+                self.due = now + FILE_POLL_INTERVAL;
+                let changed = std::fs::metadata(&*path).and_then(|m| m.modified()).ok();
+                if changed.is_none() || changed == *modified {
+                    return Ok(());
+                }
+                // a writer may be halfway through the file; take it next time if so
+                if let Ok(img) = image::open(&*path) {
+                    *modified = changed;
+                    self.current = Some(img.to_rgb8());
+                    self.fresh = true;
+                } else if modified.is_none() {
+                    return Err(format!("{} isn't an image", path.display()));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `img`'s middle square scaled to a `side` × `side` grid, as the colors of the seeds
+/// that start there.
+pub fn seed_colors(img: &SourceImg, side: u32) -> Vec<SeedColor> {
+    let crop = img.width().min(img.height());
+    let (x, y) = ((img.width() - crop) / 2, (img.height() - crop) / 2);
+    let square = image::imageops::crop_imm(img, x, y, crop, crop).to_image();
+    let scaled =
+        image::imageops::resize(&square, side, side, image::imageops::FilterType::Triangle);
+    scaled
+        .pixels()
+        .map(|p| SeedColor {
+            rgba: [
+                p[0] as f32 / 255.0,
+                p[1] as f32 / 255.0,
+                p[2] as f32 / 255.0,
+                1.0,
+            ],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swapped_image_is_cropped_to_the_grid_once() {
+        // 3 × 1, so only the middle pixel survives the crop
+        let img = SourceImg::from_fn(3, 1, |x, _| image::Rgb([x as u8 * 100, 0, 255]));
+        let mut live = LiveSource::still(img, "test".to_owned());
+        let colors = seed_colors(live.poll(0.0).unwrap().unwrap(), 2);
+        assert_eq!(colors.len(), 4);
+        for color in colors {
+            assert_eq!(color.rgba, [100.0 / 255.0, 0.0, 1.0, 1.0]);
+        }
+        assert!(live.poll(1.0).unwrap().is_none());
+        live.refresh();
+        assert!(live.poll(2.0).unwrap().is_some());
+    }
+}
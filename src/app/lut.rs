@@ -0,0 +1,140 @@
+//! Color grading with 3D LUTs in the .cube format, applied to exported frames (and, if
+//! asked, the preview) so outputs can match an existing look.
+
+use super::SeedColor;
+
+pub const LUT_EXTENSION: &str = "cube";
+const MAX_SIZE: usize = 256;
+
+pub struct Lut {
+    size: usize,
+    table: Vec<[f32; 3]>, // red changes fastest, then green, then blue
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+}
+
+impl Lut {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut size = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut table = Vec::new();
+        let triple = |words: &[&str]| -> Result<[f32; 3], String> {
+            match words {
+                [r, g, b] => {
+                    let value = |w: &str| w.parse::<f32>().map_err(|_| format!("bad number {}", w));
+                    Ok([value(r)?, value(g)?, value(b)?])
+                }
+                _ => Err(format!("expected three numbers, got '{}'", words.join(" "))),
+            }
+        };
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("TITLE") {
+                continue;
+            }
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            match words[0] {
+                "LUT_3D_SIZE" => {
+                    let n = words
+                        .get(1)
+                        .and_then(|n| n.parse::<usize>().ok())
+                        .filter(|n| (2..=MAX_SIZE).contains(n))
+                        .ok_or_else(|| format!("bad LUT size in '{}'", line))?;
+                    size = Some(n);
+                }
+                "LUT_1D_SIZE" => return Err("1D LUTs aren't supported, only 3D ones".to_owned()),
+                "DOMAIN_MIN" => domain_min = triple(&words[1..])?,
+                "DOMAIN_MAX" => domain_max = triple(&words[1..])?,
+                _ if words[0].starts_with(|c: char| c.is_ascii_alphabetic()) => {} // other keywords
+                _ => table.push(triple(&words)?),
+            }
+        }
+        let size = size.ok_or("no LUT_3D_SIZE, so this isn't a 3D LUT")?;
+        if table.len() != size * size * size {
+            return Err(format!(
+                "expected {} entries for a size {} LUT, found {}",
+                size * size * size,
+                size,
+                table.len()
+            ));
+        }
+        Ok(Self {
+            size,
+            table,
+            domain_min,
+            domain_max,
+        })
+    }
+
+    /// Grades a 0..1 color, interpolating between the eight nearest entries.
+    pub fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let n = self.size;
+        let last = (n - 1) as f32;
+        let pos: [f32; 3] = std::array::from_fn(|c| {
+            let span = (self.domain_max[c] - self.domain_min[c]).max(f32::EPSILON);
+            ((rgb[c] - self.domain_min[c]) / span).clamp(0.0, 1.0) * last
+        });
+        let lo = pos.map(|p| (p.floor() as usize).min(n - 2));
+        let t: [f32; 3] = std::array::from_fn(|c| pos[c] - lo[c] as f32);
+        let entry = |r: usize, g: usize, b: usize| self.table[r + g * n + b * n * n];
+        let mut out = [0.0; 3];
+        for corner in 0..8 {
+            let (dr, dg, db) = (corner & 1, (corner >> 1) & 1, (corner >> 2) & 1);
+            let weight = [dr, dg, db]
+                .iter()
+                .zip(t)
+                .map(|(&d, t)| if d == 1 { t } else { 1.0 - t })
+                .product::<f32>();
+            let value = entry(lo[0] + dr, lo[1] + dg, lo[2] + db);
+            for c in 0..3 {
+                out[c] += value[c] * weight;
+            }
+        }
+        out.map(|v| v.clamp(0.0, 1.0))
+    }
+
+    /// Grades RGBA bytes in place; alpha is left alone.
+    pub fn apply_rgba8(&self, rgba: &mut [u8]) {
+        for pixel in rgba.chunks_exact_mut(4) {
+            let graded = self.apply([pixel[0], pixel[1], pixel[2]].map(|v| v as f32 / 255.0));
+            for c in 0..3 {
+                pixel[c] = (graded[c] * 255.0).round() as u8;
+            }
+        }
+    }
+
+    pub fn apply_colors(&self, colors: &[SeedColor]) -> Vec<SeedColor> {
+        colors
+            .iter()
+            .map(|color| {
+                let [r, g, b, a] = color.rgba;
+                let [r, g, b] = self.apply([r, g, b]);
+                SeedColor { rgba: [r, g, b, a] }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inverting_lut_interpolates() {
+        let mut cube = "TITLE \"invert\"\n# comment\nLUT_3D_SIZE 2\n".to_owned();
+        for b in 0..2 {
+            for g in 0..2 {
+                for r in 0..2 {
+                    cube += &format!("{} {} {}\n", 1 - r, 1 - g, 1 - b);
+                }
+            }
+        }
+        let lut = Lut::parse(&cube).unwrap();
+        let graded = lut.apply([0.25, 0.5, 1.0]);
+        for (got, want) in graded.iter().zip([0.75, 0.5, 0.0]) {
+            assert!((got - want).abs() < 1e-5, "{:?}", graded);
+        }
+        assert!(Lut::parse("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+    }
+}
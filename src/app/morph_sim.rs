@@ -1,5 +1,7 @@
 use std::mem;
 
+#[cfg(not(target_arch = "wasm32"))]
+use bytemuck::{Pod, Zeroable};
 use image::ImageBuffer;
 
 use crate::app::{SeedColor, SeedPos, preset::Preset};
@@ -101,6 +103,32 @@ pub struct CellBody {
 const PERSONAL_SPACE: f32 = 0.95;
 const MAX_VELOCITY: f32 = 6.0;
 const ALIGNMENT_FACTOR: f32 = 0.8;
+#[cfg(not(target_arch = "wasm32"))]
+/// Most a cell's pull is multiplied or divided by when focusing on weights.
+const FOCUS_RANGE: f32 = 3.0;
+const MOTION_START: f32 = 3.0; // over dst_force: frames before cells set off, like the physics
+const ARRIVED_DISTANCE: f32 = 0.5; // of a cell's side, from its destination
+
+/// One cell's path when the transformation is drawn without the physics: eased along a
+/// straight line from `src` to `dst`, after `start` frames and taking `frames`.
+#[cfg(not(target_arch = "wasm32"))]
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct CellMotion {
+    src: [f32; 2],
+    dst: [f32; 2],
+    start: f32,
+    frames: f32,
+    _pad: [f32; 2],
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl CellMotion {
+    /// The frame the cell gets to its destination.
+    pub fn end(&self) -> f32 {
+        self.start + self.frames
+    }
+}
 
 fn factor_curve(x: f32) -> f32 {
     (x * x * x).min(1000.0)
@@ -131,6 +159,10 @@ impl CellBody {
         self.dst_force = force;
     }
     #[cfg(not(target_arch = "wasm32"))]
+    pub fn speed(&self) -> f32 {
+        (self.velx * self.velx + self.vely * self.vely).sqrt()
+    }
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn set_stroke_id(&mut self, stroke_id: u32) {
         self.stroke_id = stroke_id;
     }
@@ -255,6 +287,35 @@ impl Sim {
         self.reversed = !self.reversed;
     }
 
+    /// Randomizes how strongly each cell is pulled to its destination, so cells arrive at
+    /// slightly different times. Seed 0 leaves the sim untouched.
+    pub fn stagger(&mut self, seed: u64) {
+        if seed == 0 {
+            return;
+        }
+        let mut rng = frand::Rand::with_seed(seed);
+        for cell in &mut self.cells {
+            cell.dst_force *= 0.75 + 0.5 * rng.r#gen::<f32>();
+        }
+    }
+
+    /// Scales how strongly each cell is pulled to its destination by the weight there in
+    /// `weights`, a `side` × `side` map from 0 to 255, so with `focus` above 0 the
+    /// features that matter most assemble first and the background fills in after; below
+    /// 0 it's the other way around. Call it before playing, while destinations are still
+    /// in the target.
+    pub fn focus_on_weights(&mut self, weights: &[u8], side: u32, sidelen: u32, focus: f32) {
+        if focus == 0.0 || weights.len() != (side * side) as usize {
+            return;
+        }
+        let to_map = |v: f32| ((v / sidelen as f32 * side as f32) as u32).min(side - 1);
+        for cell in &mut self.cells {
+            let weight = weights[(to_map(cell.dsty) * side + to_map(cell.dstx)) as usize];
+            let centered = weight as f32 / 255.0 * 2.0 - 1.0;
+            cell.dst_force *= FOCUS_RANGE.powf(focus * centered);
+        }
+    }
+
     pub fn update(&mut self, positions: &mut [SeedPos], sidelen: u32) {
         let grid_size = (self.cells.len() as f32).sqrt();
         let pixel_size = sidelen as f32 / grid_size;
@@ -335,6 +396,17 @@ impl Sim {
         }
     }
 
+    /// Whether every cell at `positions` is as good as at its destination, so the
+    /// transformation has played out.
+    pub fn arrived(&self, positions: &[SeedPos], sidelen: u32) -> bool {
+        let cell = sidelen as f32 / (self.cells.len() as f32).sqrt();
+        let near = ARRIVED_DISTANCE * cell;
+        self.cells
+            .iter()
+            .zip(positions)
+            .all(|(body, pos)| (body.dstx - pos.xy[0]).hypot(body.dsty - pos.xy[1]) <= near)
+    }
+
     pub fn set_assignments(&mut self, assignments: Vec<usize>, sidelen: u32) {
         let width = (self.cells.len() as f32).sqrt();
         let pixelsize = sidelen as f32 / width;
@@ -359,6 +431,25 @@ impl Sim {
         }
     }
 
+    /// Each cell's path from where it is to where it's going, roughly as fast as the
+    /// physics would move it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn planned_motion(&self) -> Vec<CellMotion> {
+        self.cells
+            .iter()
+            .map(|cell| {
+                let dist = (cell.dstx - cell.srcx).hypot(cell.dsty - cell.srcy);
+                CellMotion {
+                    src: [cell.srcx, cell.srcy],
+                    dst: [cell.dstx, cell.dsty],
+                    start: MOTION_START / cell.dst_force.max(0.01),
+                    frames: (dist / MAX_VELOCITY * 1.5).max(1.0), // eased, so slower than flat out
+                    _pad: [0.0; 2],
+                }
+            })
+            .collect()
+    }
+
     pub(crate) fn prepare_play(&mut self, positions: &mut [SeedPos], reverse: bool) {
         if self.reversed == reverse {
             for (i, cell) in self.cells.iter_mut().enumerate() {
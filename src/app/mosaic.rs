@@ -0,0 +1,275 @@
+use ab_glyph::{Font, FontRef, PxScale, point};
+use image::{Rgba, RgbaImage};
+
+use crate::app::SeedColor;
+
+pub const TILE_SIZE: u32 = 32;
+const ATLAS_COLUMNS: u32 = 64;
+pub const MAX_LIBRARY_TILES: usize = 1024;
+// the glyphs are monochrome, so each emoji gets the color it is known for
+const EMOJI: &[(char, [u8; 3])] = &[
+    ('🍎', [218, 46, 47]),
+    ('🍊', [244, 144, 12]),
+    ('🍋', [255, 214, 51]),
+    ('🍏', [119, 178, 85]),
+    ('🍇', [146, 80, 170]),
+    ('🍆', [116, 64, 140]),
+    ('🥑', [120, 160, 60]),
+    ('🥕', [244, 120, 30]),
+    ('🍑', [255, 160, 120]),
+    ('🍒', [190, 25, 50]),
+    ('🍓', [220, 50, 70]),
+    ('🍌', [255, 220, 90]),
+    ('🥥', [120, 80, 50]),
+    ('🍪', [200, 140, 80]),
+    ('🍞', [220, 170, 110]),
+    ('🥚', [245, 230, 210]),
+    ('🍫', [95, 55, 35]),
+    ('🍄', [210, 60, 50]),
+    ('☁', [225, 232, 237]),
+    ('🌞', [255, 200, 40]),
+    ('🌕', [250, 230, 150]),
+    ('🌑', [50, 50, 55]),
+    ('🔥', [244, 100, 20]),
+    ('💧', [90, 170, 230]),
+    ('🌊', [40, 110, 200]),
+    ('🧊', [190, 230, 245]),
+    ('🌲', [40, 120, 60]),
+    ('🌵', [90, 150, 70]),
+    ('🍀', [60, 170, 60]),
+    ('🌸', [250, 180, 210]),
+    ('🌹', [200, 30, 60]),
+    ('🌻', [250, 200, 40]),
+    ('🐸', [110, 180, 80]),
+    ('🐷', [245, 160, 180]),
+    ('🐻', [140, 90, 60]),
+    ('🐼', [235, 235, 235]),
+    ('🐧', [40, 40, 50]),
+    ('🐘', [150, 150, 160]),
+    ('🐙', [230, 90, 130]),
+    ('🦊', [230, 120, 40]),
+    ('💜', [170, 100, 220]),
+    ('💙', [80, 140, 230]),
+    ('💚', [100, 200, 90]),
+    ('💛', [255, 210, 70]),
+    ('🧡', [250, 150, 50]),
+    ('❤', [220, 40, 50]),
+    ('🖤', [30, 30, 30]),
+    ('🤍', [245, 245, 245]),
+    ('🤎', [120, 80, 55]),
+    ('⬛', [20, 20, 20]),
+    ('⬜', [240, 240, 240]),
+];
+
+pub struct TileAtlas {
+    /// Tiles laid out in rows of `ATLAS_COLUMNS`, followed by one row holding the
+    /// average color of each tile.
+    pub image: RgbaImage,
+    pub avg_colors: Vec<[u8; 3]>,
+}
+
+impl TileAtlas {
+    pub fn emoji() -> Self {
+        let fonts = egui::FontDefinitions::default();
+        let data = &fonts.font_data["NotoEmoji-Regular"];
+        let font = FontRef::try_from_slice(&data.font).expect("bundled emoji font is valid");
+
+        let tiles = EMOJI
+            .iter()
+            .filter_map(|&(c, color)| rasterize(&font, c, color))
+            .collect();
+        Self::from_tiles(tiles)
+    }
+
+    /// Builds a photomosaic library from arbitrary images, center-cropped to squares.
+    pub fn from_images(images: Vec<image::DynamicImage>) -> Self {
+        let tiles = images
+            .into_iter()
+            .take(MAX_LIBRARY_TILES)
+            .map(|img| {
+                let side = img.width().min(img.height());
+                let x = (img.width() - side) / 2;
+                let y = (img.height() - side) / 2;
+                img.crop_imm(x, y, side, side)
+                    .resize_exact(TILE_SIZE, TILE_SIZE, image::imageops::FilterType::Triangle)
+                    .to_rgba8()
+            })
+            .collect();
+        Self::from_tiles(tiles)
+    }
+
+    fn from_tiles(tiles: Vec<RgbaImage>) -> Self {
+        let rows = (tiles.len() as u32).div_ceil(ATLAS_COLUMNS).max(1);
+        let mut image = RgbaImage::new(ATLAS_COLUMNS * TILE_SIZE, rows * TILE_SIZE + 1);
+        let mut avg_colors = Vec::with_capacity(tiles.len());
+        for (i, tile) in tiles.iter().enumerate() {
+            let (x0, y0) = Self::tile_origin(i);
+            let mut sum = [0u64; 3];
+            for (x, y, p) in tile.enumerate_pixels() {
+                // tiles are opaque; flatten any transparency onto black
+                let a = p[3] as u32;
+                let p = Rgba([
+                    (p[0] as u32 * a / 255) as u8,
+                    (p[1] as u32 * a / 255) as u8,
+                    (p[2] as u32 * a / 255) as u8,
+                    255,
+                ]);
+                image.put_pixel(x0 + x, y0 + y, p);
+                for c in 0..3 {
+                    sum[c] += p[c] as u64;
+                }
+            }
+            let n = (TILE_SIZE * TILE_SIZE) as u64;
+            let avg = sum.map(|c| (c / n) as u8);
+            image.put_pixel(
+                i as u32,
+                rows * TILE_SIZE,
+                Rgba([avg[0], avg[1], avg[2], 255]),
+            );
+            avg_colors.push(avg);
+        }
+
+        Self { image, avg_colors }
+    }
+
+    pub fn len(&self) -> usize {
+        self.avg_colors.len()
+    }
+
+    fn tile_origin(index: usize) -> (u32, u32) {
+        let index = index as u32;
+        (
+            (index % ATLAS_COLUMNS) * TILE_SIZE,
+            (index / ATLAS_COLUMNS) * TILE_SIZE,
+        )
+    }
+
+    fn color_dist(color: [f32; 3], avg: [u8; 3]) -> f32 {
+        (0..3)
+            .map(|c| {
+                let d = color[c] * 255.0 - avg[c] as f32;
+                d * d
+            })
+            .sum::<f32>()
+    }
+
+    /// Index of the tile whose average color is closest to `color` (rgb in 0..1).
+    pub fn closest(&self, color: [f32; 3]) -> u16 {
+        let mut best = 0;
+        let mut best_dist = f32::MAX;
+        for (i, avg) in self.avg_colors.iter().enumerate() {
+            let dist = Self::color_dist(color, *avg);
+            if dist < best_dist {
+                best_dist = dist;
+                best = i;
+            }
+        }
+        best as u16
+    }
+
+    /// Picks a tile for every seed. With `unique`, each tile is used as few times as
+    /// the library size allows (exactly once when there are enough tiles).
+    pub fn indices_for(&self, colors: &[SeedColor], unique: bool) -> Vec<u16> {
+        if !unique || self.len() == 0 {
+            return colors
+                .iter()
+                .map(|c| self.closest([c.rgba[0], c.rgba[1], c.rgba[2]]))
+                .collect();
+        }
+
+        let capacity = colors.len().div_ceil(self.len());
+        let mut uses = vec![0usize; self.len()];
+        colors
+            .iter()
+            .map(|c| {
+                let color = [c.rgba[0], c.rgba[1], c.rgba[2]];
+                let mut best = 0;
+                let mut best_dist = f32::MAX;
+                for (i, avg) in self.avg_colors.iter().enumerate() {
+                    if uses[i] >= capacity {
+                        continue;
+                    }
+                    let dist = Self::color_dist(color, *avg);
+                    if dist < best_dist {
+                        best_dist = dist;
+                        best = i;
+                    }
+                }
+                uses[best] += 1;
+                best as u16
+            })
+            .collect()
+    }
+
+    /// Colors that appear in rendered tiles, for building export palettes.
+    pub fn palette_colors(&self) -> Vec<SeedColor> {
+        let tile_rows = self.image.height() - 1;
+        self.image
+            .enumerate_pixels()
+            .filter(|(_, y, _)| *y < tile_rows)
+            .step_by(3)
+            .map(|(_, _, p)| SeedColor {
+                rgba: [
+                    p[0] as f32 / 255.0,
+                    p[1] as f32 / 255.0,
+                    p[2] as f32 / 255.0,
+                    1.0,
+                ],
+            })
+            .collect()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_tile_folder(dir: &std::path::Path) -> Result<TileAtlas, Box<dyn std::error::Error>> {
+    let mut paths = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.is_file())
+        .collect::<Vec<_>>();
+    paths.sort();
+    let images = paths
+        .iter()
+        .filter_map(|p| image::open(p).ok())
+        .collect::<Vec<_>>();
+    if images.is_empty() {
+        return Err("no images found in folder".into());
+    }
+    Ok(TileAtlas::from_images(images))
+}
+
+fn rasterize(font: &FontRef<'_>, c: char, color: [u8; 3]) -> Option<RgbaImage> {
+    let glyph_id = font.glyph_id(c);
+    if glyph_id.0 == 0 {
+        return None; // not in the font
+    }
+    let glyph = glyph_id.with_scale_and_position(PxScale::from(TILE_SIZE as f32), point(0.0, 0.0));
+    let outlined = font.outline_glyph(glyph)?;
+    let bounds = outlined.px_bounds();
+    let ox = ((TILE_SIZE as f32 - bounds.width()) * 0.5).max(0.0) as u32;
+    let oy = ((TILE_SIZE as f32 - bounds.height()) * 0.5).max(0.0) as u32;
+
+    // background is a darker shade so the cell still reads as the emoji's color
+    let shade = color.map(|c| (c as f32 * 0.6) as u8);
+    let mut tile = RgbaImage::from_pixel(
+        TILE_SIZE,
+        TILE_SIZE,
+        Rgba([shade[0], shade[1], shade[2], 255]),
+    );
+    outlined.draw(|x, y, coverage| {
+        let (px, py) = (ox + x, oy + y);
+        if px < TILE_SIZE && py < TILE_SIZE {
+            let mix = |bg: u8, fg: u8| (bg as f32 + (fg as f32 - bg as f32) * coverage) as u8;
+            tile.put_pixel(
+                px,
+                py,
+                Rgba([
+                    mix(shade[0], color[0]),
+                    mix(shade[1], color[1]),
+                    mix(shade[2], color[2]),
+                    255,
+                ]),
+            );
+        }
+    });
+    Some(tile)
+}
@@ -0,0 +1,172 @@
+//! Grid lines and cell labels drawn over the animation, for explaining how pixels find
+//! their places. The preview paints them with egui; recordings get them burned into
+//! every frame.
+
+/// Digits as five rows of three pixels, most significant bit on the left.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+/// Render side that gets one frame pixel per font pixel; bigger renders scale up.
+const FONT_SCALE_SIDE: u32 = 200;
+pub const MAX_DIVISIONS: u32 = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GridOverlay {
+    pub enabled: bool,
+    pub divisions: u32, // cells along each side
+    pub color: egui::Color32,
+    pub labels: bool, // number the columns along the top and the rows down the left
+}
+
+impl Default for GridOverlay {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            divisions: 8,
+            color: egui::Color32::from_rgba_unmultiplied(255, 255, 255, 160),
+            labels: true,
+        }
+    }
+}
+
+impl GridOverlay {
+    fn line_at(&self, i: u32, side: u32) -> u32 {
+        i * side / self.divisions.max(1)
+    }
+
+    /// Paints the grid over the preview image shown at `rect`.
+    pub fn paint(&self, painter: &egui::Painter, rect: egui::Rect) {
+        let n = self.divisions.max(1);
+        let stroke = egui::Stroke::new(1.0, self.color);
+        for i in 1..n {
+            let t = i as f32 / n as f32;
+            let x = rect.min.x + t * rect.width();
+            let y = rect.min.y + t * rect.height();
+            painter.line_segment(
+                [egui::pos2(x, rect.min.y), egui::pos2(x, rect.max.y)],
+                stroke,
+            );
+            painter.line_segment(
+                [egui::pos2(rect.min.x, y), egui::pos2(rect.max.x, y)],
+                stroke,
+            );
+        }
+        if self.labels {
+            let font = egui::FontId::monospace(10.0);
+            for i in 0..n {
+                let t = i as f32 / n as f32;
+                let x = rect.min.x + t * rect.width() + 2.0;
+                let y = rect.min.y + t * rect.height() + 2.0;
+                let label = (i + 1).to_string();
+                let top_left = egui::Align2::LEFT_TOP;
+                painter.text(
+                    egui::pos2(x, rect.min.y + 2.0),
+                    top_left,
+                    &label,
+                    font.clone(),
+                    self.color,
+                );
+                if i > 0 {
+                    painter.text(
+                        egui::pos2(rect.min.x + 2.0, y),
+                        top_left,
+                        label,
+                        font.clone(),
+                        self.color,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Burns the grid into an rgba frame of `size` that shows the part of a `side` ×
+    /// `side` render starting at `origin`, matching what the preview paints.
+    pub fn draw_rgba(&self, rgba: &mut [u8], size: (u32, u32), origin: (u32, u32), side: u32) {
+        let (width, height) = size;
+        let [r, g, b, a] = self.color.to_srgba_unmultiplied();
+        let mut plot = |x: i64, y: i64| {
+            if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+                return;
+            }
+            let i = (y as usize * width as usize + x as usize) * 4;
+            let alpha = a as u32;
+            for (c, v) in [r, g, b].into_iter().enumerate() {
+                let old = rgba[i + c] as u32;
+                rgba[i + c] = ((v as u32 * alpha + old * (255 - alpha)) / 255) as u8;
+            }
+        };
+        let (ox, oy) = (origin.0 as i64, origin.1 as i64);
+        let n = self.divisions.max(1);
+        for i in 1..n {
+            let at = self.line_at(i, side) as i64;
+            for y in 0..height as i64 {
+                plot(at - ox, y);
+            }
+            for x in 0..width as i64 {
+                if x + ox != at {
+                    plot(x, at - oy); // the crossing is already drawn
+                }
+            }
+        }
+        if !self.labels {
+            return;
+        }
+        let scale = (side / FONT_SCALE_SIDE).max(1) as i64;
+        let mut text = |label: u32, x: i64, y: i64| {
+            for (k, digit) in label.to_string().bytes().enumerate() {
+                let glyph = DIGITS[(digit - b'0') as usize];
+                let left = x + k as i64 * 4 * scale;
+                for (row, bits) in glyph.iter().enumerate() {
+                    for col in 0..3 {
+                        if bits & (0b100 >> col) == 0 {
+                            continue;
+                        }
+                        for dy in 0..scale {
+                            for dx in 0..scale {
+                                plot(left + col * scale + dx, y + row as i64 * scale + dy);
+                            }
+                        }
+                    }
+                }
+            }
+        };
+        let pad = 2 * scale;
+        for i in 0..n {
+            let at = self.line_at(i, side) as i64;
+            text(i + 1, at - ox + pad, pad - oy);
+            if i > 0 {
+                text(i + 1, pad - ox, at - oy + pad);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burned_grid_lines_follow_the_crop() {
+        let overlay = GridOverlay {
+            enabled: true,
+            divisions: 4,
+            color: egui::Color32::WHITE,
+            labels: false,
+        };
+        // the right half of a 40 × 40 render: lines at 10, 20 and 30, so 0 and 10 here
+        let mut rgba = vec![0u8; 20 * 40 * 4];
+        overlay.draw_rgba(&mut rgba, (20, 40), (20, 0), 40);
+        let at = |x: usize, y: usize| rgba[(y * 20 + x) * 4];
+        assert_eq!((at(0, 5), at(10, 5), at(5, 5)), (255, 255, 0));
+        assert_eq!((at(5, 10), at(5, 11)), (255, 0));
+    }
+}
@@ -0,0 +1,139 @@
+//! Low power mode, which goes easy on laptops running on battery: solves use a single
+//! thread and the preview repaints less often.
+
+use serde::Deserialize;
+
+/// How often the power supply is looked at again, in seconds.
+const CHECK_INTERVAL: f64 = 30.0;
+/// Preview repaints per second in low power mode, unless recording.
+pub const LOW_POWER_FPS: f32 = 20.0;
+/// The repaint rate the transformation's speed is tuned for, one step per frame.
+pub const PLAYBACK_FPS: f32 = 60.0;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+pub enum LowPower {
+    #[default]
+    Auto, // while on battery
+    Always,
+    Never,
+}
+
+#[derive(Default)]
+pub struct PowerState {
+    on_battery: Option<bool>,  // none if it can't be told
+    last_checked: Option<f64>, // egui time
+}
+
+impl PowerState {
+    /// Looks at the power supply again if it's been a while. `now` is the egui time.
+    pub fn poll(&mut self, now: f64) {
+        if self
+            .last_checked
+            .is_some_and(|checked| now - checked < CHECK_INTERVAL)
+        {
+            return;
+        }
+        self.last_checked = Some(now);
+        self.on_battery = on_battery();
+    }
+
+    /// Whether the machine is running on battery, or `None` if that can't be told, in
+    /// which case `Auto` stays off.
+    pub fn on_battery(&self) -> Option<bool> {
+        self.on_battery
+    }
+
+    pub fn active(&self, setting: LowPower) -> bool {
+        match setting {
+            LowPower::Auto => self.on_battery == Some(true),
+            LowPower::Always => true,
+            LowPower::Never => false,
+        }
+    }
+}
+
+/// Whether the machine is running on battery, or `None` if that can't be told: asks sysfs
+/// on Linux, the system power status on Windows and `pmset` on macOS. Anywhere else low
+/// power mode has to be turned on in the config.
+fn on_battery() -> Option<bool> {
+    #[cfg(target_os = "linux")]
+    {
+        let read = |path: std::path::PathBuf| {
+            std::fs::read_to_string(path)
+                .ok()
+                .map(|s| s.trim().to_owned())
+        };
+        let mut discharging = None;
+        for entry in std::fs::read_dir("/sys/class/power_supply").ok()?.flatten() {
+            let path = entry.path();
+            match read(path.join("type")).as_deref() {
+                Some("Mains") | Some("USB")
+                    if read(path.join("online")).as_deref() == Some("1") =>
+                {
+                    return Some(false);
+                }
+                Some("Battery") => {
+                    let status = read(path.join("status"));
+                    discharging = Some(
+                        discharging.unwrap_or(false) || status.as_deref() == Some("Discharging"),
+                    );
+                }
+                _ => {}
+            }
+        }
+        discharging
+    }
+    #[cfg(target_os = "windows")]
+    {
+        // SYSTEM_POWER_STATUS, from winbase.h
+        #[repr(C)]
+        #[derive(Default)]
+        struct SystemPowerStatus {
+            ac_line_status: u8, // 0 offline, 1 online, 255 unknown
+            battery_flag: u8,   // 128 if there's no battery
+            battery_life_percent: u8,
+            system_status_flag: u8,
+            battery_life_time: u32,
+            battery_full_life_time: u32,
+        }
+        #[link(name = "kernel32")]
+        unsafe extern "system" {
+            fn GetSystemPowerStatus(status: *mut SystemPowerStatus) -> i32;
+        }
+
+        let mut status = SystemPowerStatus::default();
+        // safety: the struct matches SYSTEM_POWER_STATUS, which the call only writes to
+        if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+            return None;
+        }
+        if status.battery_flag == 128 {
+            return Some(false); // no battery to be running on
+        }
+        match status.ac_line_status {
+            0 => Some(true),
+            1 => Some(false),
+            _ => None,
+        }
+    }
+    #[cfg(target_os = "macos")]
+    {
+        // the first line says what it's drawing from: "Now drawing from 'AC Power'"
+        let output = std::process::Command::new("pmset")
+            .args(["-g", "batt"])
+            .output()
+            .ok()?;
+        let text = String::from_utf8_lossy(&output.stdout);
+        let first = text.lines().next()?;
+        if first.contains("'Battery Power'") {
+            Some(true)
+        } else if first.contains("'AC Power'") || first.contains("'UPS Power'") {
+            Some(false)
+        } else {
+            None
+        }
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+    {
+        None
+    }
+}
@@ -1,9 +1,317 @@
 use serde::{Deserialize, Serialize};
 
+use crate::app::calculate::stats::{GenerationStats, QualityReport};
+use crate::app::calculate::util::{self, Algorithm, Aspect};
+use crate::app::gif_recorder::ExportProfile;
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Preset {
     pub inner: UnprocessedPreset,
+    #[serde(with = "crate::app::assignments::compact")]
     pub assignments: Vec<usize>,
+    #[serde(default)]
+    pub stats: Vec<GenerationStats>, // solver convergence, if it was calculated here
+    #[serde(default)]
+    pub quality: Option<QualityReport>, // measured when it was calculated here
+    #[serde(default)]
+    pub meta: PresetMeta,
+}
+
+impl Preset {
+    /// Pixels in the source image going by its size, or `None` if that doesn't fit a usize.
+    fn pixels(&self) -> Option<usize> {
+        (self.inner.width as usize).checked_mul(self.inner.height as usize)
+    }
+
+    /// Whether the source image has the pixels its size says, so it can be recalculated.
+    pub fn source_intact(&self) -> bool {
+        let inner = &self.inner;
+        inner.width > 0
+            && inner.width == inner.height
+            && self.pixels().and_then(|p| p.checked_mul(3)) == Some(inner.source_img.len())
+    }
+
+    /// What's wrong with a preset that would break the simulation, if anything.
+    pub fn problem(&self) -> Option<String> {
+        if !self.source_intact() {
+            return Some("the source image is damaged".to_owned());
+        }
+        let pixels = self.inner.source_img.len() / 3; // the size, now it's known to fit
+        if self.assignments.len() != pixels {
+            return Some(format!(
+                "{} assignments for {} pixels",
+                self.assignments.len(),
+                pixels
+            ));
+        }
+        let mut used = vec![false; pixels];
+        for &source in &self.assignments {
+            if source >= pixels || std::mem::replace(&mut used[source], true) {
+                return Some("the assignments don't use every pixel exactly once".to_owned());
+            }
+        }
+        None
+    }
+}
+
+/// A preset that failed the check at startup, kept aside until it's repaired or deleted.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BrokenPreset {
+    pub preset: Preset,
+    pub problem: String,
+    pub quarantined: bool, // the user chose to leave it for now
+}
+
+/// Splits off presets that would crash when opened.
+pub fn validate(presets: Vec<Preset>) -> (Vec<Preset>, Vec<BrokenPreset>) {
+    let mut valid = Vec::new();
+    let mut broken = Vec::new();
+    for preset in presets {
+        match preset.problem() {
+            None => valid.push(preset),
+            Some(problem) => broken.push(BrokenPreset {
+                preset,
+                problem,
+                quarantined: false,
+            }),
+        }
+    }
+    (valid, broken)
+}
+
+/// How the user organized a preset.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresetMeta {
+    pub id: uuid::Uuid, // nil for bundled presets
+    pub folder: String, // empty if not in a folder
+    pub tags: Vec<String>,
+    pub created: u64, // unix seconds, 0 for bundled presets
+    pub algorithm: Option<Algorithm>,
+    pub favorite: bool,                      // pinned to the top of the list
+    pub aspect: Aspect,                      // recordings are cropped to this frame
+    pub tileable: bool,                      // previewed tiled, since it's made to repeat
+    pub export_profiles: Vec<ExportProfile>, // empty until edited, for the defaults
+    pub weights: Vec<u8>, // WEIGHT_MAP_SIDE² target weights, row by row; empty if not kept
+    pub history: Vec<RunRecord>, // the calculations leading up to this one, oldest first
+}
+
+/// Most runs a preset's history keeps; older ones are dropped.
+pub const MAX_HISTORY: usize = 50;
+
+/// One calculation in a preset's history, so results can be traced to the tweaks that
+/// made them.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub name: String,
+    pub finished: u64, // unix seconds
+    pub seconds: f32,  // how long it took
+    pub cost: Option<i64>,
+    pub settings: Vec<(String, String)>, // from `GenerationSettings::snapshot`
+}
+
+impl RunRecord {
+    /// The settings that differ from `earlier`'s, as "name: before → after".
+    pub fn changes_from(&self, earlier: &RunRecord) -> Vec<String> {
+        let before = |name: &str| {
+            earlier
+                .settings
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.as_str())
+        };
+        self.settings
+            .iter()
+            .filter_map(|(name, value)| match before(name) {
+                Some(old) if old == value => None,
+                Some(old) => Some(format!("{}: {} → {}", name, old, value)),
+                None => Some(format!("{}: {}", name, value)),
+            })
+            .collect()
+    }
+}
+
+/// Adds `run` to the end of `history`, dropping the oldest runs past `MAX_HISTORY`.
+pub fn push_history(history: &mut Vec<RunRecord>, run: RunRecord) {
+    history.push(run);
+    let excess = history.len().saturating_sub(MAX_HISTORY);
+    history.drain(..excess);
+}
+
+/// `unix` seconds as a UTC date and time, like "2024-03-09 14:05".
+pub fn format_date(unix: u64) -> String {
+    // days to a civil date, after Howard Hinnant's `civil_from_days`
+    let days = (unix / 86400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153; // from march
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    let minutes = unix % 86400 / 60;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+/// Side of the weight map kept with presets, plenty for timing the animation by it.
+pub const WEIGHT_MAP_SIDE: u32 = 64;
+
+impl PresetMeta {
+    /// The profiles to offer when exporting this preset.
+    pub fn export_profiles(&self) -> Vec<ExportProfile> {
+        if self.export_profiles.is_empty() {
+            ExportProfile::defaults()
+        } else {
+            self.export_profiles.clone()
+        }
+    }
+
+    /// How much each part of the target matters, as `WEIGHT_MAP_SIDE`² values from 0 to
+    /// 255. Presets that didn't keep theirs, like the bundled ones, get the default
+    /// target's, which they were made for.
+    pub fn weight_map(&self) -> Vec<u8> {
+        if self.weights.len() == (WEIGHT_MAP_SIDE * WEIGHT_MAP_SIDE) as usize {
+            return self.weights.clone();
+        }
+        util::resample_weights(&util::default_weights(), WEIGHT_MAP_SIDE)
+            .into_iter()
+            .map(|w| w as u8)
+            .collect()
+    }
+}
+
+/// Seconds since the unix epoch.
+pub fn now_unix() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        (web_sys::js_sys::Date::now() / 1000.0) as u64
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs())
+    }
+}
+
+/// Which presets the preset list shows.
+#[derive(Clone, Default, PartialEq)]
+pub enum PresetFilter {
+    #[default]
+    All,
+    Folder(String),
+    Tag(String),
+}
+
+impl PresetFilter {
+    pub fn label(&self) -> String {
+        match self {
+            PresetFilter::All => "all presets".to_owned(),
+            PresetFilter::Folder(folder) => format!("🗀 {}", folder),
+            PresetFilter::Tag(tag) => format!("#{}", tag),
+        }
+    }
+
+    pub fn matches(&self, preset: &Preset) -> bool {
+        match self {
+            PresetFilter::All => true,
+            PresetFilter::Folder(folder) => preset.meta.folder == *folder,
+            PresetFilter::Tag(tag) => preset.meta.tags.contains(tag),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PresetSort {
+    Name,
+    Newest,
+    Sidelen,
+    Algorithm,
+}
+
+impl PresetSort {
+    pub const ALL: [PresetSort; 4] = [
+        PresetSort::Name,
+        PresetSort::Newest,
+        PresetSort::Sidelen,
+        PresetSort::Algorithm,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PresetSort::Name => "name",
+            PresetSort::Newest => "newest",
+            PresetSort::Sidelen => "size",
+            PresetSort::Algorithm => "algorithm",
+        }
+    }
+}
+
+/// Indices of the presets whose name or tags contain `query`, favorites first, then in
+/// `sort` order.
+pub fn search(presets: &[Preset], query: &str, sort: PresetSort) -> Vec<usize> {
+    let query = query.trim().to_lowercase();
+    let mut found = presets
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| {
+            query.is_empty()
+                || p.inner.name.to_lowercase().contains(&query)
+                || p.meta
+                    .tags
+                    .iter()
+                    .any(|t| t.to_lowercase().contains(&query))
+        })
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let name = |i: usize| presets[i].inner.name.to_lowercase();
+    match sort {
+        PresetSort::Name => found.sort_by_cached_key(|&i| name(i)),
+        PresetSort::Newest => found.sort_by_key(|&i| std::cmp::Reverse(presets[i].meta.created)),
+        PresetSort::Sidelen => found.sort_by_key(|&i| std::cmp::Reverse(presets[i].inner.width)),
+        PresetSort::Algorithm => found.sort_by_cached_key(|&i| {
+            let algorithm = match presets[i].meta.algorithm {
+                Some(Algorithm::Optimal) => 0,
+                Some(Algorithm::Genetic) => 1,
+                Some(Algorithm::BestOf) => 2,
+                None => 3,
+            };
+            (algorithm, name(i))
+        }),
+    }
+    found.sort_by_key(|&i| !presets[i].meta.favorite); // stable, so favorites keep the order
+    found
+}
+
+/// Every folder and tag in use, sorted, for picking a filter.
+pub fn folders_and_tags(presets: &[Preset]) -> (Vec<String>, Vec<String>) {
+    let mut folders = presets
+        .iter()
+        .map(|p| p.meta.folder.clone())
+        .filter(|f| !f.is_empty())
+        .collect::<Vec<_>>();
+    let mut tags = presets
+        .iter()
+        .flat_map(|p| p.meta.tags.iter().cloned())
+        .collect::<Vec<_>>();
+    folders.sort();
+    folders.dedup();
+    tags.sort();
+    tags.dedup();
+    (folders, tags)
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -13,3 +321,79 @@ pub struct UnprocessedPreset {
     pub height: u32,
     pub source_img: Vec<u8>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preset(side: u32, assignments: Vec<usize>) -> Preset {
+        Preset {
+            inner: UnprocessedPreset {
+                name: "test".to_owned(),
+                width: side,
+                height: side,
+                source_img: vec![0; (side * side * 3) as usize],
+            },
+            assignments,
+            stats: Vec::new(),
+            quality: None,
+            meta: PresetMeta::default(),
+        }
+    }
+
+    #[test]
+    fn validate_sets_aside_presets_the_simulation_cant_run() {
+        let mut huge = preset(2, vec![0, 1, 2, 3]);
+        (huge.inner.width, huge.inner.height) = (u32::MAX, u32::MAX); // overflows a u32
+        let presets = vec![
+            preset(2, vec![3, 2, 1, 0]),
+            preset(2, vec![0, 1, 2]),    // too few
+            preset(2, vec![0, 1, 1, 3]), // 1 twice, 2 never
+            preset(2, vec![0, 1, 2, 4]), // outside the grid
+            huge,
+        ];
+        let (valid, broken) = validate(presets);
+        assert_eq!(valid.len(), 1);
+        assert_eq!(valid[0].assignments, [3, 2, 1, 0]);
+        let problems = broken
+            .iter()
+            .map(|b| b.problem.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            problems,
+            [
+                "3 assignments for 4 pixels",
+                "the assignments don't use every pixel exactly once",
+                "the assignments don't use every pixel exactly once",
+                "the source image is damaged",
+            ]
+        );
+    }
+
+    #[test]
+    fn history_shows_changed_settings_and_dates() {
+        let run = |proximity: &str, finished| RunRecord {
+            name: "test".to_owned(),
+            finished,
+            seconds: 1.0,
+            cost: None,
+            settings: vec![
+                ("algorithm".to_owned(), "Genetic".to_owned()),
+                ("proximity".to_owned(), proximity.to_owned()),
+            ],
+        };
+        let (first, second) = (run("13", 0), run("20", 1_709_993_100));
+        assert_eq!(second.changes_from(&first), ["proximity: 13 → 20"]);
+        assert!(first.changes_from(&first).is_empty());
+        assert_eq!(format_date(first.finished), "1970-01-01 00:00");
+        assert_eq!(format_date(second.finished), "2024-03-09 14:05");
+        assert_eq!(format_date(951_782_400), "2000-02-29 00:00");
+
+        let mut history = Vec::new();
+        for i in 0..MAX_HISTORY as u64 + 2 {
+            push_history(&mut history, run("13", i));
+        }
+        assert_eq!(history.len(), MAX_HISTORY);
+        assert_eq!(history[0].finished, 2);
+    }
+}
@@ -0,0 +1,43 @@
+//! Capturing a region of the screen as a source, through the xdg desktop portal. That
+//! only exists on Linux, so this module does too; other platforms show the button
+//! disabled, saying so.
+
+use std::sync::mpsc;
+
+/// A screenshot of a region the user picked, or `None` if they cancelled.
+pub type Capture = Result<Option<image::RgbImage>, String>;
+
+/// Asks the desktop's screenshot portal for a region the user picks. The portal waits on
+/// the user, so this runs on a thread and the capture comes back through the channel.
+pub fn capture_region() -> mpsc::Receiver<Capture> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        tx.send(request_region().map_err(|err| err.to_string()))
+            .ok();
+    });
+    rx
+}
+
+fn request_region() -> Result<Option<image::RgbImage>, Box<dyn std::error::Error>> {
+    use ashpd::desktop::{ResponseError, screenshot::Screenshot};
+
+    let response = pollster::block_on(async {
+        Screenshot::request()
+            .interactive(true) // lets the user drag out a region
+            .modal(true)
+            .send()
+            .await?
+            .response()
+    });
+    let screenshot = match response {
+        Ok(screenshot) => screenshot,
+        Err(ashpd::Error::Response(ResponseError::Cancelled)) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    // the portal saves it wherever the desktop keeps screenshots; that's the user's now
+    let path = screenshot
+        .uri()
+        .to_file_path()
+        .map_err(|_| "screenshot wasn't saved to a local file")?;
+    Ok(Some(image::open(&path)?.to_rgb8()))
+}
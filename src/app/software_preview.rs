@@ -0,0 +1,143 @@
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::Instant;
+
+use crate::app::config::{Config, Overrides};
+use crate::app::get_presets;
+use crate::app::gif_recorder::GifLimits;
+use crate::app::preset::{self, Preset};
+use crate::app::software_render::{Playback, render_gif};
+
+/// Side length the preview plays at; the CPU rasterizer can't keep up with much more.
+const PREVIEW_SIZE: u32 = 256;
+
+/// A small window for machines where wgpu finds no adapter, drawn with egui's glow
+/// renderer: plays presets with the CPU rasterizer and exports them with `render_gif`.
+pub struct SoftwarePreview {
+    presets: Vec<Preset>,
+    gif: GifLimits, // what exports are recorded with
+    selected: usize,
+    reverse: bool,
+    playback: Playback,
+    started: Instant,
+    texture: Option<egui::TextureHandle>,
+    export: Option<mpsc::Receiver<Result<PathBuf, String>>>,
+    status: Option<String>,
+}
+
+impl SoftwarePreview {
+    pub fn new(cc: &eframe::CreationContext<'_>, overrides: &Overrides) -> Self {
+        let presets = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, "presets"))
+            .unwrap_or_else(get_presets);
+        let (mut presets, _) = preset::validate(presets);
+        if presets.is_empty() {
+            presets = get_presets();
+        }
+        let playback = Playback::new(presets[0].clone(), PREVIEW_SIZE, false);
+        Self {
+            presets,
+            gif: Config::load(overrides).gif,
+            selected: 0,
+            reverse: false,
+            playback,
+            started: Instant::now(),
+            texture: None,
+            export: None,
+            status: None,
+        }
+    }
+
+    fn restart(&mut self) {
+        let preset = self.presets[self.selected].clone();
+        self.playback = Playback::new(preset, PREVIEW_SIZE, self.reverse);
+        self.started = Instant::now();
+    }
+
+    /// Asks where to save the selected preset, then renders it on a thread.
+    fn export(&mut self) {
+        let preset = self.presets[self.selected].clone();
+        let Some(path) = rfd::FileDialog::new()
+            .set_title("export gif")
+            .add_filter("gif", &["gif"])
+            .set_file_name(format!("{}.gif", preset.inner.name))
+            .save_file()
+        else {
+            return;
+        };
+        let (tx, rx) = mpsc::channel();
+        let (gif, reverse) = (self.gif, self.reverse);
+        std::thread::spawn(move || {
+            let result = render_gif(preset, &gif, reverse)
+                .and_then(|data| Ok(std::fs::write(&path, data)?))
+                .map(|()| path)
+                .map_err(|err| err.to_string());
+            tx.send(result).ok();
+        });
+        self.export = Some(rx);
+        self.status = Some("exporting on the CPU, this takes a while...".to_owned());
+    }
+}
+
+impl eframe::App for SoftwarePreview {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if let Some(result) = self.export.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            self.export = None;
+            self.status = Some(match result {
+                Ok(path) => format!("saved {}", path.display()),
+                Err(err) => format!("couldn't export: {}", err),
+            });
+        }
+
+        let frame = self.playback.frame_at(self.started.elapsed().as_secs_f32());
+        let image =
+            egui::ColorImage::from_rgba_unmultiplied([frame.size as usize; 2], &frame.rasterize());
+        match &mut self.texture {
+            Some(texture) => texture.set(image, egui::TextureOptions::NEAREST),
+            None => {
+                self.texture =
+                    Some(ctx.load_texture("software preview", image, egui::TextureOptions::NEAREST))
+            }
+        }
+
+        egui::TopBottomPanel::top("software controls").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let before = (self.selected, self.reverse);
+                egui::ComboBox::from_id_salt("software preset")
+                    .selected_text(&self.presets[self.selected].inner.name)
+                    .show_ui(ui, |ui| {
+                        for (i, preset) in self.presets.iter().enumerate() {
+                            ui.selectable_value(&mut self.selected, i, &preset.inner.name);
+                        }
+                    });
+                ui.checkbox(&mut self.reverse, "reverse");
+                if ui.button("restart").clicked() || before != (self.selected, self.reverse) {
+                    self.restart();
+                }
+                let exporting = self.export.is_some();
+                if ui
+                    .add_enabled(!exporting, egui::Button::new("export gif..."))
+                    .clicked()
+                {
+                    self.export();
+                }
+            });
+            ui.label(
+                self.status
+                    .as_deref()
+                    .unwrap_or("no graphics adapter found, previewing on the CPU"),
+            );
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if let Some(texture) = &self.texture {
+                let side = ui.available_width().min(ui.available_height());
+                ui.centered_and_justified(|ui| {
+                    ui.add(egui::Image::new(texture).fit_to_exact_size(egui::vec2(side, side)))
+                });
+            }
+        });
+        ctx.request_repaint();
+    }
+}
@@ -0,0 +1,412 @@
+use std::borrow::Cow;
+use std::error::Error;
+use std::path::Path;
+
+use crate::app::config::{Config, Overrides};
+use crate::app::export_job::JobProgress;
+use crate::app::gif_recorder::{
+    ColorSpace, GifLimits, WrittenFrame, append_boomerang, build_palette, dither_indices,
+};
+use crate::app::preset::{Preset, PresetMeta, UnprocessedPreset};
+use crate::app::{SeedPos, assignments, get_presets, morph_sim};
+
+/// Draws each pixel in the colour of its nearest seed, like the jump flood pass on the
+/// GPU, into an RGBA image of `size` x `size`. `color_of` gives each seed's colour.
+fn rasterize(seeds: &[SeedPos], size: u32, color_of: impl Fn(usize) -> [u8; 4]) -> Vec<u8> {
+    // bucket the seeds by grid cell so each pixel only looks at the seeds around it
+    let grid = ((seeds.len() as f32).sqrt() as usize).max(1);
+    let cell = size as f32 / grid as f32;
+    let bucket_of = |v: f32| ((v / cell).floor().max(0.0) as usize).min(grid - 1);
+    let mut buckets = vec![Vec::new(); grid * grid];
+    for (i, seed) in seeds.iter().enumerate() {
+        buckets[bucket_of(seed.xy[1]) * grid + bucket_of(seed.xy[0])].push(i);
+    }
+
+    let mut out = vec![0; (size * size * 4) as usize];
+    for y in 0..size {
+        for x in 0..size {
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            let (bx, by) = (bucket_of(px) as i64, bucket_of(py) as i64);
+            let mut best = None::<(f32, usize)>;
+            for r in 0..grid as i64 {
+                for cy in by - r..=by + r {
+                    for cx in bx - r..=bx + r {
+                        let on_ring = (cy - by).abs() == r || (cx - bx).abs() == r;
+                        if !on_ring || cx < 0 || cy < 0 || cx >= grid as i64 || cy >= grid as i64 {
+                            continue;
+                        }
+                        for &i in &buckets[cy as usize * grid + cx as usize] {
+                            let (dx, dy) = (seeds[i].xy[0] - px, seeds[i].xy[1] - py);
+                            let dist = dx * dx + dy * dy;
+                            if best.is_none_or(|(d, _)| dist < d) {
+                                best = Some((dist, i));
+                            }
+                        }
+                    }
+                }
+                // seeds in further rings are at least r cells away
+                let reach = r as f32 * cell;
+                if best.is_some_and(|(d, _)| d <= reach * reach) {
+                    break;
+                }
+            }
+
+            let offset = ((y * size + x) * 4) as usize;
+            out[offset..offset + 4].copy_from_slice(&match best {
+                Some((_, i)) => color_of(i),
+                None => [0, 0, 0, 255],
+            });
+        }
+    }
+    out
+}
+
+/// Records the transformation as a gif entirely on the CPU, with the recorder's frame and
+/// size limits, color space, dithering and loop mode. Slow, but works without any GPU.
+/// Grading, overlays and local palettes are only the recorder's.
+pub fn render_gif(
+    preset: Preset,
+    limits: &GifLimits,
+    reverse: bool,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let size = limits.resolution.max(1);
+    // crop to the preset's frame, centered in the square grid
+    let (fw, fh) = preset.meta.aspect.frame();
+    let (width, height) = (
+        ((fw * size as f32).round() as u32).clamp(1, size),
+        ((fh * size as f32).round() as u32).clamp(1, size),
+    );
+    let (x0, y0) = ((size - width) / 2, (size - height) / 2);
+    let (_, mut seeds, colors, mut sim) = morph_sim::init_image(size, preset);
+    sim.prepare_play(&mut seeds, reverse);
+    for _ in 0..20 {
+        sim.update(&mut seeds, size);
+    }
+
+    // pixels come out as the recorder's captures do once they're converted to sRGB
+    let space = limits.color_space;
+    let encoded = colors
+        .iter()
+        .map(|c| {
+            let [r, g, b, a] = c.rgba;
+            [
+                space.encode(r),
+                space.encode(g),
+                space.encode(b),
+                ColorSpace::Srgb.encode(a),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let palette = build_palette(&colors, space, limits.palette_quality);
+    let mut encoder = gif::Encoder::new(
+        vec![],
+        width as u16,
+        height as u16,
+        &palette.color_map_rgb(),
+    )?;
+    encoder.set_repeat(limits.loop_mode.repeat())?;
+    let boomerang = limits.loop_mode.is_boomerang();
+    // a boomerang writes every frame but its ends twice
+    let budget = limits.max_size_mb as usize * if boomerang { 512 } else { 1024 } * 1024;
+    let mut written = Vec::<WrittenFrame>::new();
+
+    for frame_index in 0..limits.max_frames.max(1) {
+        let _span = tracing::info_span!("software_frame", frame = frame_index).entered();
+        let rgba = rasterize(&seeds, size, |i| encoded[i]);
+        let cropped = (y0..y0 + height)
+            .flat_map(|y| {
+                let row = ((y * size + x0) * 4) as usize;
+                &rgba[row..row + (width * 4) as usize]
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        let pixels = if limits.dither {
+            let mut pixels = vec![0; (width * height) as usize];
+            dither_indices(&palette, &cropped, width as usize, &mut pixels);
+            pixels
+        } else {
+            cropped
+                .chunks_exact(4)
+                .map(|c| palette.index_of(c) as u8)
+                .collect()
+        };
+        if encoder.get_ref().len() + pixels.len() > budget && frame_index >= limits.min_frames {
+            break;
+        }
+        let delay = limits.frame_delay(frame_index);
+        encoder.write_frame(&gif::Frame {
+            width: width as u16,
+            height: height as u16,
+            buffer: Cow::Borrowed(&pixels),
+            delay,
+            ..Default::default()
+        })?;
+        if boomerang {
+            written.push((pixels, None, delay));
+        }
+        for _ in 0..limits.steps_after(frame_index) {
+            sim.update(&mut seeds, size);
+        }
+    }
+    append_boomerang(
+        &mut encoder,
+        &written,
+        (width, height),
+        &JobProgress::default(),
+    )?;
+    Ok(encoder.into_inner()?)
+}
+
+/// Loads a bundled preset by name, or a preset folder with `source.png` and
+/// `assignments.bin` like the ones in `presets/` (or `assignments.json` from older versions).
+/// The assignments file is mapped and checked a chunk at a time; only the decoded indices,
+/// which the simulation needs anyway, end up in memory.
+fn load_preset(name_or_dir: &str) -> Result<Preset, Box<dyn Error>> {
+    if let Some(preset) = get_presets()
+        .into_iter()
+        .find(|p| p.inner.name == name_or_dir)
+    {
+        return Ok(preset);
+    }
+
+    let dir = Path::new(name_or_dir);
+    let img = image::open(dir.join("source.png"))?.to_rgb8();
+    let file = ["assignments.bin", "assignments.json"]
+        .map(|name| dir.join(name))
+        .into_iter()
+        .find(|path| path.exists())
+        .ok_or("no assignments.bin or assignments.json in the preset folder")?;
+    let mapped = assignments::MappedAssignments::open(&file)?;
+
+    // check the size from the header first, so a mismatched file isn't decoded at all
+    let pixels = (img.width() * img.height()) as usize;
+    let wrong_size = "source.png must be square with one assignment per pixel";
+    if img.width() != img.height() || mapped.count().is_some_and(|count| count != pixels) {
+        return Err(wrong_size.into());
+    }
+    let mut assignments = Vec::with_capacity(pixels);
+    for chunk in mapped.chunks() {
+        let chunk = chunk?;
+        if chunk.iter().any(|&a| a >= pixels) {
+            return Err("the assignments refer to pixels outside source.png".into());
+        }
+        assignments.extend(chunk);
+    }
+    if assignments.len() != pixels {
+        return Err(wrong_size.into());
+    }
+    Ok(Preset {
+        inner: UnprocessedPreset {
+            name: dir
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| "preset".to_owned()),
+            width: img.width(),
+            height: img.height(),
+            source_img: img.into_raw(),
+        },
+        assignments,
+        stats: Vec::new(),
+        quality: None,
+        meta: PresetMeta::default(),
+    })
+}
+
+/// Renders a preset's transformation to `output` without opening a window, for machines
+/// with no usable GPU or display. Uses the gif settings from the config file.
+pub fn render_headless(
+    preset: &str,
+    output: &Path,
+    reverse: bool,
+    overrides: &Overrides,
+) -> Result<(), Box<dyn Error>> {
+    let preset = load_preset(preset)?;
+    let config = Config::load(overrides);
+    let data = render_gif(preset, &config.gif, reverse)?;
+    std::fs::write(output, data)?;
+    Ok(())
+}
+
+/// Sim steps per second of playback; the app steps once a frame at 60 frames per second.
+pub const STEPS_PER_SECOND: f32 = 60.0;
+
+/// Where every pixel is at one moment of a transformation: its centre, from 0 to `size`
+/// on both axes, and its sRGB colour.
+pub struct PlaybackFrame {
+    pub size: u32,
+    pub positions: Vec<[f32; 2]>,
+    pub colors: Vec<[u8; 4]>,
+}
+
+impl PlaybackFrame {
+    /// The frame as a `size` x `size` RGBA image, drawn by `rasterize`.
+    pub fn rasterize(&self) -> Vec<u8> {
+        let seeds = self
+            .positions
+            .iter()
+            .map(|&xy| SeedPos { xy })
+            .collect::<Vec<_>>();
+        rasterize(&seeds, self.size, |i| self.colors[i])
+    }
+}
+
+/// Plays a preset's transformation on the CPU, for drawing it with another renderer.
+/// Any moment can be asked for: later ones step the simulation on, earlier ones start it
+/// over, since it can't run backwards.
+pub struct Playback {
+    preset: Preset,
+    size: u32,
+    reverse: bool,
+    sim: morph_sim::Sim,
+    seeds: Vec<SeedPos>,
+    previous: Vec<SeedPos>, // one step before `seeds`
+    colors: Vec<[u8; 4]>,
+    step: u32, // of `seeds`
+}
+
+impl Playback {
+    /// Loads a preset like `render_headless` does, playing it in a `size` square.
+    pub fn open(preset: &str, size: u32, reverse: bool) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::new(load_preset(preset)?, size, reverse))
+    }
+
+    pub(crate) fn new(preset: Preset, size: u32, reverse: bool) -> Self {
+        let size = size.max(1);
+        let (_, seeds, colors, sim) = morph_sim::init_image(size, preset.clone());
+        let colors = colors
+            .iter()
+            .map(|c| c.rgba.map(|v| ColorSpace::Srgb.encode(v)))
+            .collect();
+        let mut playback = Self {
+            preset,
+            size,
+            reverse,
+            sim,
+            previous: seeds.clone(),
+            seeds,
+            colors,
+            step: 0,
+        };
+        playback.rewind();
+        playback
+    }
+
+    /// Back to the start, settled the same way recordings are before their first frame.
+    fn rewind(&mut self) {
+        let (_, seeds, _, sim) = morph_sim::init_image(self.size, self.preset.clone());
+        self.seeds = seeds;
+        self.sim = sim;
+        self.sim.prepare_play(&mut self.seeds, self.reverse);
+        for _ in 0..20 {
+            self.sim.update(&mut self.seeds, self.size);
+        }
+        self.previous = self.seeds.clone();
+        self.step = 0;
+    }
+
+    /// The pixels `seconds` into the transformation, in between two sim steps if it falls
+    /// between them.
+    pub fn frame_at(&mut self, seconds: f32) -> PlaybackFrame {
+        let steps = seconds.max(0.0) * STEPS_PER_SECOND;
+        let (whole, t) = (steps.floor() as u32, steps.fract());
+        if whole + 1 < self.step {
+            self.rewind();
+        }
+        while self.step < whole + 1 {
+            self.previous.copy_from_slice(&self.seeds);
+            self.sim.update(&mut self.seeds, self.size);
+            self.step += 1;
+        }
+        let positions = self
+            .previous
+            .iter()
+            .zip(&self.seeds)
+            .map(|(a, b)| [0, 1].map(|i| a.xy[i] + (b.xy[i] - a.xy[i]) * t))
+            .collect();
+        PlaybackFrame {
+            size: self.size,
+            positions,
+            colors: self.colors.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::gif_recorder::LoopMode;
+
+    /// A 16 x 16 preset whose pixels all travel to the mirrored cell.
+    fn mirrored() -> Preset {
+        let source_img = (0..16 * 16)
+            .flat_map(|i| [(i * 7) as u8, (i * 13) as u8, (i / 16 * 16) as u8])
+            .collect();
+        Preset {
+            inner: UnprocessedPreset {
+                name: "mirrored".to_owned(),
+                width: 16,
+                height: 16,
+                source_img,
+            },
+            assignments: (0..16 * 16).map(|i| i / 16 * 16 + 15 - i % 16).collect(),
+            stats: Vec::new(),
+            quality: None,
+            meta: PresetMeta::default(),
+        }
+    }
+
+    #[test]
+    fn pixels_take_their_nearest_seeds_colour() {
+        let seeds = [[2.0, 2.0], [6.0, 2.0], [2.0, 6.0], [6.0, 6.0]].map(|xy| SeedPos { xy });
+        let colors = [
+            [255, 0, 0, 255],
+            [0, 255, 0, 255],
+            [0, 0, 255, 255],
+            [9, 9, 9, 255],
+        ];
+        let rgba = rasterize(&seeds, 8, |i| colors[i]);
+        for y in 0..8 {
+            for x in 0..8 {
+                let quadrant = (y / 4) * 2 + x / 4;
+                let offset = (y * 8 + x) * 4;
+                assert_eq!(rgba[offset..offset + 4], colors[quadrant], "pixel {x}, {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn seeking_either_way_gives_the_same_frame() {
+        let mut playback = Playback::new(mirrored(), 32, false);
+        let at_half = playback.frame_at(0.5).positions;
+        playback.frame_at(1.5);
+        assert_eq!(playback.frame_at(0.5).positions, at_half); // back, from the start
+        playback.frame_at(0.2);
+        assert_eq!(playback.frame_at(0.5).positions, at_half); // forwards
+    }
+
+    #[test]
+    fn gifs_follow_the_loop_mode() {
+        let frames = |loop_mode| {
+            let limits = GifLimits {
+                resolution: 16,
+                min_frames: 4,
+                max_frames: 4,
+                loop_mode,
+                dither: true,
+                ..Default::default()
+            };
+            let data = render_gif(mirrored(), &limits, false).unwrap();
+            let mut options = gif::DecodeOptions::new();
+            options.set_color_output(gif::ColorOutput::Indexed);
+            let mut decoder = options.read_info(data.as_slice()).unwrap();
+            let mut count = 0;
+            while decoder.read_next_frame().unwrap().is_some() {
+                count += 1;
+            }
+            (count, decoder.repeat())
+        };
+        assert_eq!(frames(LoopMode::Once), (4, gif::Repeat::Finite(0)));
+        // and back again, without either end twice
+        assert_eq!(frames(LoopMode::Boomerang), (6, gif::Repeat::Infinite));
+    }
+}
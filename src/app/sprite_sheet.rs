@@ -0,0 +1,103 @@
+//! Recordings tiled into one png, a few evenly spaced frames of the morph in a grid,
+//! for game engines and CSS animations that step through a sheet.
+
+use crate::app::export_job::JobProgress;
+
+pub const MAX_CELLS: u32 = 32; // along either side
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpriteSheet {
+    pub columns: u32,
+    pub rows: u32,
+    pub frames: u32, // taken from the recording, at most columns × rows
+}
+
+impl Default for SpriteSheet {
+    fn default() -> Self {
+        Self {
+            columns: 8,
+            rows: 4,
+            frames: 32,
+        }
+    }
+}
+
+impl SpriteSheet {
+    pub fn frame_count(&self) -> usize {
+        self.frames.clamp(1, self.columns.max(1) * self.rows.max(1)) as usize
+    }
+
+    /// Which of `recorded` frames go in the sheet, spread evenly from the first to the last.
+    fn picks(&self, recorded: usize) -> Vec<usize> {
+        let n = self.frame_count().min(recorded);
+        if n <= 1 {
+            return vec![0; n];
+        }
+        (0..n).map(|i| i * (recorded - 1) / (n - 1)).collect()
+    }
+
+    /// Tiles evenly spaced rgba `frames` of `size` into a png, left to right and then top
+    /// to bottom. Rows left empty at the bottom are cut off; empty cells are transparent.
+    pub fn encode(
+        &self,
+        frames: &[Vec<u8>],
+        (width, height): (u32, u32),
+        job: &JobProgress,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let picks = self.picks(frames.len());
+        if picks.is_empty() {
+            return Err("no frames were recorded".into());
+        }
+        let _span = tracing::info_span!("sprite_sheet", frames = picks.len()).entered();
+        let columns = self.columns.max(1).min(picks.len() as u32);
+        let rows = (picks.len() as u32).div_ceil(columns);
+        let (sheet_width, sheet_height) = (columns * width, rows * height);
+        let row_bytes = (width * 4) as usize;
+        let sheet_row_bytes = (sheet_width * 4) as usize;
+        let mut sheet = vec![0u8; sheet_row_bytes * sheet_height as usize];
+        for (cell, &frame) in picks.iter().enumerate() {
+            job.step(cell, picks.len())?;
+            let (column, row) = (cell as u32 % columns, cell as u32 / columns);
+            let left = (column * width) as usize * 4;
+            for (y, src) in frames[frame].chunks_exact(row_bytes).enumerate() {
+                let start = (row * height) as usize * sheet_row_bytes + y * sheet_row_bytes + left;
+                sheet[start..start + row_bytes].copy_from_slice(src);
+            }
+        }
+        let mut data = Vec::new();
+        let mut encoder = png::Encoder::new(&mut data, sheet_width, sheet_height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&sheet)?;
+        writer.finish()?;
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_spread_from_first_to_last() {
+        let sheet = SpriteSheet {
+            columns: 2,
+            rows: 2,
+            frames: 4,
+        };
+        assert_eq!(sheet.picks(10), [0, 3, 6, 9]);
+        assert_eq!(sheet.picks(3), [0, 1, 2]);
+        let frames = (0..10u8).map(|i| vec![i; 4]).collect::<Vec<_>>();
+        let png = sheet
+            .encode(&frames, (1, 1), &JobProgress::default())
+            .unwrap();
+        let mut reader = png::Decoder::new(std::io::Cursor::new(png))
+            .read_info()
+            .unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((reader.info().width, reader.info().height), (2, 2));
+        assert_eq!([pixels[0], pixels[4], pixels[8], pixels[12]], [0, 3, 6, 9]);
+    }
+}
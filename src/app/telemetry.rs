@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+
+use crate::app::json;
+
+const MAX_RECORDS: usize = 500;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub kind: String, // "solve", "gif", "gif takes" or "png frames"
+    pub algorithm: Option<String>,
+    pub sidelen: Option<u32>,
+    pub block_size: Option<u32>,
+    pub frames: Option<u32>,
+    pub seconds: f64,
+}
+
+/// Performance stats kept on this machine only when the user opts in. They're never sent
+/// anywhere; the user can export them as json to attach to a bug report.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Telemetry {
+    pub enabled: bool,
+    pub adapter: String,
+    records: Vec<Record>,
+    #[serde(skip)]
+    solve_started: Option<(Record, f64)>,
+    #[serde(skip)]
+    export_started: Option<(Record, f64)>,
+}
+
+impl Telemetry {
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    fn push(&mut self, record: Record) {
+        if !self.enabled {
+            return;
+        }
+        if self.records.len() >= MAX_RECORDS {
+            self.records.remove(0);
+        }
+        self.records.push(record);
+    }
+
+    /// `now` is in seconds, like `egui::InputState::time`.
+    pub fn start_solve(&mut self, algorithm: String, sidelen: u32, block_size: u32, now: f64) {
+        let record = Record {
+            kind: "solve".to_owned(),
+            algorithm: Some(algorithm),
+            sidelen: Some(sidelen),
+            block_size: Some(block_size),
+            frames: None,
+            seconds: 0.0,
+        };
+        self.solve_started = Some((record, now));
+    }
+
+    pub fn finish_solve(&mut self, now: f64) {
+        if let Some((mut record, started)) = self.solve_started.take() {
+            record.seconds = now - started;
+            self.push(record);
+        }
+    }
+
+    pub fn cancel_solve(&mut self) {
+        self.solve_started = None;
+    }
+
+    pub fn start_export(&mut self, kind: &str, sidelen: u32, now: f64) {
+        let record = Record {
+            kind: kind.to_owned(),
+            algorithm: None,
+            sidelen: Some(sidelen),
+            block_size: None,
+            frames: None,
+            seconds: 0.0,
+        };
+        self.export_started = Some((record, now));
+    }
+
+    pub fn finish_export(&mut self, frames: u32, now: f64) {
+        if let Some((mut record, started)) = self.export_started.take() {
+            record.frames = Some(frames);
+            record.seconds = now - started;
+            self.push(record);
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let field = |name: &str, value: Option<String>| {
+            value.map_or_else(String::new, |v| format!(", \"{}\": {}", name, v))
+        };
+        let quoted = json::quote;
+        let records = self
+            .records
+            .iter()
+            .map(|r| {
+                format!(
+                    "    {{\"kind\": {}{}{}{}{}, \"seconds\": {:.3}}}",
+                    quoted(&r.kind),
+                    field("algorithm", r.algorithm.as_deref().map(quoted)),
+                    field("sidelen", r.sidelen.map(|v| v.to_string())),
+                    field("block_size", r.block_size.map(|v| v.to_string())),
+                    field("frames", r.frames.map(|v| v.to_string())),
+                    r.seconds
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{{\n  \"version\": {},\n  \"os\": {},\n  \"adapter\": {},\n  \"records\": [\n{}\n  ]\n}}\n",
+            quoted(env!("CARGO_PKG_VERSION")),
+            quoted(std::env::consts::OS),
+            quoted(&self.adapter),
+            records.join(",\n")
+        )
+    }
+}
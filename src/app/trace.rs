@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+use crate::app::json;
+
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static THREAD_ID: u64 = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+}
+
+struct TraceEvent {
+    name: String,
+    phase: char, // 'B'egin, 'E'nd or 'i'nstant, as in the chrome trace format
+    micros: f64,
+    thread: u64,
+}
+
+struct SpanData {
+    name: String,
+    refs: usize,
+}
+
+#[derive(Default)]
+struct Recorded {
+    spans: HashMap<u64, SpanData>,
+    events: Vec<TraceEvent>,
+}
+
+/// Records every span as begin/end events, to be written as a chrome trace
+/// (viewable in chrome://tracing or https://ui.perfetto.dev).
+struct ChromeTrace {
+    start: Instant,
+    next_id: AtomicU64,
+    recorded: Arc<Mutex<Recorded>>,
+}
+
+impl ChromeTrace {
+    fn push(&self, name: String, phase: char) {
+        let event = TraceEvent {
+            name,
+            phase,
+            micros: self.start.elapsed().as_secs_f64() * 1e6,
+            thread: THREAD_ID.with(|id| *id),
+        };
+        self.recorded.lock().unwrap().events.push(event);
+    }
+}
+
+/// Collects `key=value` pairs of a span or event into one label.
+struct FieldLabel(String);
+
+impl tracing::field::Visit for FieldLabel {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            write!(self.0, " {:?}", value).ok();
+        } else {
+            write!(self.0, " {}={:?}", field.name(), value).ok();
+        }
+    }
+}
+
+impl Subscriber for ChromeTrace {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut label = FieldLabel(span.metadata().name().to_owned());
+        span.record(&mut label);
+        self.recorded.lock().unwrap().spans.insert(
+            id,
+            SpanData {
+                name: label.0,
+                refs: 1,
+            },
+        );
+        Id::from_u64(id)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut label = FieldLabel(event.metadata().target().to_owned());
+        event.record(&mut label);
+        self.push(label.0, 'i');
+    }
+
+    fn enter(&self, span: &Id) {
+        let name = self.recorded.lock().unwrap().spans[&span.into_u64()]
+            .name
+            .clone();
+        self.push(name, 'B');
+    }
+
+    fn exit(&self, span: &Id) {
+        let name = self.recorded.lock().unwrap().spans[&span.into_u64()]
+            .name
+            .clone();
+        self.push(name, 'E');
+    }
+
+    fn clone_span(&self, span: &Id) -> Id {
+        if let Some(data) = self
+            .recorded
+            .lock()
+            .unwrap()
+            .spans
+            .get_mut(&span.into_u64())
+        {
+            data.refs += 1;
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: Id) -> bool {
+        let mut recorded = self.recorded.lock().unwrap();
+        let id = span.into_u64();
+        let Some(data) = recorded.spans.get_mut(&id) else {
+            return false;
+        };
+        data.refs -= 1;
+        if data.refs == 0 {
+            recorded.spans.remove(&id);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Writes the collected trace when dropped, so keep it alive until the app exits.
+pub struct TraceGuard {
+    path: PathBuf,
+    recorded: Arc<Mutex<Recorded>>,
+}
+
+impl Drop for TraceGuard {
+    fn drop(&mut self) {
+        let recorded = self.recorded.lock().unwrap();
+        let mut trace = "{\"traceEvents\":[\n".to_owned();
+        for (i, event) in recorded.events.iter().enumerate() {
+            if i > 0 {
+                trace += ",\n";
+            }
+            write!(
+                trace,
+                "{{\"name\":{},\"ph\":\"{}\",\"ts\":{:.1},\"pid\":1,\"tid\":{}{}}}",
+                json::quote(&event.name),
+                event.phase,
+                event.micros,
+                event.thread,
+                if event.phase == 'i' {
+                    ",\"s\":\"t\""
+                } else {
+                    ""
+                }
+            )
+            .ok();
+        }
+        trace += "\n]}\n";
+        if let Err(err) = std::fs::write(&self.path, trace) {
+            eprintln!("failed to write trace to {}: {}", self.path.display(), err);
+        }
+    }
+}
+
+/// Starts recording spans from the solver and recorder into a chrome trace file at `path`.
+pub fn init_chrome_trace(path: PathBuf) -> Result<TraceGuard, Box<dyn Error>> {
+    let recorded = Arc::new(Mutex::new(Recorded::default()));
+    tracing::subscriber::set_global_default(ChromeTrace {
+        start: Instant::now(),
+        next_id: AtomicU64::new(1),
+        recorded: recorded.clone(),
+    })?;
+    Ok(TraceGuard { path, recorded })
+}
@@ -0,0 +1,70 @@
+//! Where each cell's pixel comes from, for reproducing the morph in other tools with
+//! other footage: as a UV map image a shader can sample through, or as plain coordinates.
+
+/// A 16-bit png where each cell's red and green are the x and y of the center of the
+/// source pixel it's assigned, from 0 at the left and top to 65535 at the right and
+/// bottom edges. Blue is always 0 and alpha opaque. Precise for sides up to 65536.
+pub fn to_png(assignments: &[usize], side: u32) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if assignments.len() != (side * side) as usize {
+        return Err("the assignments don't cover the grid".into());
+    }
+    let uv = |i: usize| ((i as f64 + 0.5) / side as f64 * u16::MAX as f64).round() as u16;
+    let mut pixels = Vec::with_capacity(assignments.len() * 8);
+    for &source in assignments {
+        let (x, y) = (source % side as usize, source / side as usize);
+        for channel in [uv(x), uv(y), 0, u16::MAX] {
+            pixels.extend(channel.to_be_bytes()); // pngs are big endian
+        }
+    }
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, side, side);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Sixteen);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)?;
+    writer.finish()?;
+    Ok(data)
+}
+
+/// The source pixel of every cell as exact `[x, y]` coordinates, row by row.
+pub fn to_json(assignments: &[usize], side: u32) -> String {
+    let rows = assignments
+        .chunks(side.max(1) as usize)
+        .map(|row| {
+            let cells = row
+                .iter()
+                .map(|&source| format!("[{}, {}]", source % side as usize, source / side as usize))
+                .collect::<Vec<_>>();
+            format!("    [{}]", cells.join(", "))
+        })
+        .collect::<Vec<_>>();
+    format!(
+        "{{\n  \"width\": {side},\n  \"height\": {side},\n  \"origins\": [\n{}\n  ]\n}}\n",
+        rows.join(",\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uv_map_holds_each_cells_source() {
+        // a 2 × 2 grid with its pixels turned around
+        let assignments = [3, 2, 1, 0];
+        let data = to_png(&assignments, 2).unwrap();
+        let mut reader = png::Decoder::new(std::io::Cursor::new(data))
+            .read_info()
+            .unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        let channel = |cell: usize, c: usize| {
+            u16::from_be_bytes([pixels[cell * 8 + c * 2], pixels[cell * 8 + c * 2 + 1]])
+        };
+        let (near, far) = (16384, 49151); // the centers of the first and second pixels
+        assert_eq!((channel(0, 0), channel(0, 1)), (far, far));
+        assert_eq!((channel(1, 0), channel(1, 1)), (near, far));
+        assert_eq!((channel(3, 0), channel(3, 1)), (near, near));
+        assert!(to_json(&assignments, 2).contains("[[1, 1], [0, 1]]"));
+    }
+}
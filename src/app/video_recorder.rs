@@ -0,0 +1,498 @@
+//! Video export: recorded frames are encoded as AV1 with rav1e and muxed into a WebM,
+//! which keeps every color and plays in browsers, unlike a gif's 256-color palette.
+//! Music can come along from an Opus file.
+
+use rav1e::prelude::*;
+
+use crate::app::export_job::JobProgress;
+
+const SPEED_PRESET: u8 = 10; // rav1e's fastest; recordings are short and flat-shaded
+const QUANTIZER: usize = 60; // 0..255, lower looks better
+const TIMESTAMP_SCALE: u64 = 1_000_000; // nanoseconds per tick, so ticks are milliseconds
+const MAX_CLUSTER_SPAN: u64 = 30_000; // block timestamps are i16 ticks off their cluster's
+
+const OBU_SEQUENCE_HEADER: u8 = 1;
+const OBU_TEMPORAL_DELIMITER: u8 = 2;
+
+const OPUS_RATE: f64 = 48_000.0; // what opus timestamps count in, whatever it was recorded at
+const OPUS_SEEK_PRE_ROLL: u64 = 80_000_000; // nanoseconds, as the webm spec asks for
+
+const VIDEO_TRACK: u8 = 1;
+const AUDIO_TRACK: u8 = 2;
+
+/// An encoded frame or audio packet, timestamped in milliseconds.
+struct Block {
+    track: u8,
+    timestamp: u64,
+    keyframe: bool,
+    data: Vec<u8>,
+}
+
+/// The stream of an Ogg Opus file. Webm audio is Opus too, so its packets are copied
+/// into videos as they are, with no decoding or encoding.
+#[derive(Clone)]
+pub struct OpusAudio {
+    head: Vec<u8>, // the OpusHead packet, which is also the track's codec private data
+    packets: Vec<Vec<u8>>,
+}
+
+impl OpusAudio {
+    /// Reads the first Opus stream out of an Ogg file.
+    pub fn parse(mut ogg: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut serial = None;
+        let mut packets = Vec::new();
+        let mut packet = Vec::new();
+        while !ogg.is_empty() {
+            if ogg.len() < 27 || &ogg[..4] != b"OggS" {
+                return Err("not an ogg file".into());
+            }
+            let page_serial = u32::from_le_bytes(ogg[14..18].try_into().unwrap());
+            let segments = ogg[26] as usize;
+            let table = ogg.get(27..27 + segments).ok_or("truncated ogg page")?;
+            let body_len = table.iter().map(|&s| s as usize).sum::<usize>();
+            let mut body = ogg
+                .get(27 + segments..27 + segments + body_len)
+                .ok_or("truncated ogg page")?;
+            ogg = &ogg[27 + segments + body_len..];
+            if *serial.get_or_insert(page_serial) != page_serial {
+                continue; // another stream muxed alongside
+            }
+            for &len in table {
+                let (segment, rest) = body.split_at(len as usize);
+                body = rest;
+                packet.extend_from_slice(segment);
+                if len < 255 {
+                    packets.push(std::mem::take(&mut packet));
+                }
+            }
+        }
+        let mut packets = packets.into_iter();
+        let head = packets
+            .next()
+            .filter(|p| p.len() >= 19 && p.starts_with(b"OpusHead"))
+            .ok_or("not an opus file; only ogg opus (.opus) audio can go into videos")?;
+        let packets = packets
+            .filter(|p| !p.starts_with(b"OpusTags") && opus_samples(p) > 0)
+            .collect::<Vec<_>>();
+        if packets.is_empty() {
+            return Err("the opus file has no audio".into());
+        }
+        Ok(Self { head, packets })
+    }
+
+    fn channels(&self) -> u64 {
+        self.head[9] as u64
+    }
+
+    /// Samples the decoder throws away at the start, at 48 kHz.
+    fn pre_skip(&self) -> u64 {
+        u16::from_le_bytes([self.head[10], self.head[11]]) as u64
+    }
+
+    /// Packets covering `duration` milliseconds, looping the audio if it's shorter.
+    fn blocks(&self, duration: f64) -> Vec<Block> {
+        let mut blocks = Vec::new();
+        let mut samples = 0u64;
+        for packet in self.packets.iter().cycle() {
+            let timestamp = samples as f64 * 1000.0 / OPUS_RATE;
+            if timestamp >= duration {
+                break;
+            }
+            blocks.push(Block {
+                track: AUDIO_TRACK,
+                timestamp: timestamp.round() as u64,
+                keyframe: true,
+                data: packet.clone(),
+            });
+            samples += opus_samples(packet);
+        }
+        blocks
+    }
+}
+
+/// How many 48 kHz samples an Opus packet decodes to, from its table of contents byte.
+fn opus_samples(packet: &[u8]) -> u64 {
+    let Some(&toc) = packet.first() else {
+        return 0;
+    };
+    let config = (toc >> 3) as usize;
+    let frame = match config {
+        0..=11 => [480, 960, 1920, 2880][config % 4], // silk
+        12..=15 => [480, 960][config % 2],            // hybrid
+        _ => [120, 240, 480, 960][config % 4],        // celt
+    };
+    let frames = match toc & 3 {
+        0 => 1,
+        1 | 2 => 2,
+        _ => packet.get(1).map_or(0, |b| (b & 0x3f) as u64),
+    };
+    frame * frames
+}
+
+/// Encodes frames into a WebM in memory as they're recorded.
+pub struct VideoEncoder {
+    ctx: Context<u8>,
+    width: usize,
+    height: usize,
+    frame_ms: f64,
+    sent: u64,
+    blocks: Vec<Block>,
+    sequence_header: Option<Vec<u8>>, // the stream's, for the track's codec private data
+}
+
+impl VideoEncoder {
+    /// A video of `width` by `height` frames, each shown for `frame_ms` milliseconds. Both
+    /// sides need to be at least 16.
+    pub fn new(width: u32, height: u32, frame_ms: f64) -> Result<Self, Box<dyn std::error::Error>> {
+        let enc = EncoderConfig {
+            width: width as usize,
+            height: height as usize,
+            time_base: Rational::new(1, 1000),
+            bit_depth: 8,
+            chroma_sampling: ChromaSampling::Cs420,
+            pixel_range: PixelRange::Limited,
+            color_description: Some(ColorDescription {
+                color_primaries: ColorPrimaries::BT709,
+                transfer_characteristics: TransferCharacteristics::SRGB,
+                matrix_coefficients: MatrixCoefficients::BT709,
+            }),
+            quantizer: QUANTIZER,
+            speed_settings: SpeedSettings::from_preset(SPEED_PRESET),
+            ..Default::default()
+        };
+        let ctx = Config::new()
+            .with_encoder_config(enc)
+            .new_context()
+            .map_err(|err| format!("can't encode a {}x{} video: {}", width, height, err))?;
+        Ok(Self {
+            ctx,
+            width: width as usize,
+            height: height as usize,
+            frame_ms,
+            sent: 0,
+            blocks: Vec::new(),
+            sequence_header: None,
+        })
+    }
+
+    /// Encodes the next frame from sRGB RGBA bytes. Alpha is dropped.
+    pub fn write_frame(&mut self, rgba: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let _span = tracing::info_span!("video_encode_frame", frame = self.sent).entered();
+        let mut frame = self.ctx.new_frame();
+        let (y, u, v) = rgba_to_yuv420(rgba, self.width, self.height);
+        let chroma_width = self.width.div_ceil(2);
+        frame.planes[0].copy_from_raw_u8(&y, self.width, 1);
+        frame.planes[1].copy_from_raw_u8(&u, chroma_width, 1);
+        frame.planes[2].copy_from_raw_u8(&v, chroma_width, 1);
+        self.ctx.send_frame(frame)?;
+        self.sent += 1;
+        self.drain(None)
+    }
+
+    /// Takes whatever packets the encoder has ready, counting them against the frames
+    /// sent as `job`'s progress if there is one.
+    fn drain(&mut self, job: Option<&JobProgress>) -> Result<(), Box<dyn std::error::Error>> {
+        loop {
+            match self.ctx.receive_packet() {
+                Ok(packet) => {
+                    self.push_packet(packet);
+                    if let Some(job) = job {
+                        job.step(self.blocks.len(), self.sent as usize)?;
+                    }
+                }
+                Err(EncoderStatus::Encoded) => {}
+                Err(EncoderStatus::NeedMoreData | EncoderStatus::LimitReached) => return Ok(()),
+                Err(err) => return Err(format!("video encoding failed: {}", err).into()),
+            }
+        }
+    }
+
+    fn push_packet(&mut self, packet: Packet<u8>) {
+        let mut data = Vec::with_capacity(packet.data.len());
+        for (obu_type, obu) in obus(&packet.data) {
+            match obu_type {
+                // the container marks where frames start
+                OBU_TEMPORAL_DELIMITER => continue,
+                OBU_SEQUENCE_HEADER if self.sequence_header.is_none() => {
+                    self.sequence_header = Some(obu.to_vec());
+                }
+                _ => {}
+            }
+            data.extend_from_slice(obu);
+        }
+        self.blocks.push(Block {
+            track: VIDEO_TRACK,
+            timestamp: (packet.input_frameno as f64 * self.frame_ms).round() as u64,
+            keyframe: packet.frame_type == FrameType::KEY,
+            data,
+        });
+    }
+
+    /// Encodes the frames still in the encoder and returns the finished WebM, with
+    /// `audio` looped or cut to the video's length.
+    pub fn finish(
+        mut self,
+        audio: Option<&OpusAudio>,
+        job: &JobProgress,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let _span = tracing::info_span!("video_finish", frames = self.sent).entered();
+        if self.sent == 0 {
+            return Err("no frames were recorded".into());
+        }
+        self.ctx.flush();
+        self.drain(Some(job))?;
+        let mut codec_private = self.ctx.container_sequence_header();
+        codec_private.extend(self.sequence_header.take().unwrap_or_default());
+        let duration = self.sent as f64 * self.frame_ms;
+        if let Some(audio) = audio {
+            self.blocks.extend(audio.blocks(duration));
+            // tracks interleave in time; the sort is stable, so frames stay in order
+            self.blocks.sort_by_key(|b| b.timestamp);
+        }
+        Ok(webm(
+            self.width as u64,
+            self.height as u64,
+            &codec_private,
+            audio,
+            duration,
+            &self.blocks,
+        ))
+    }
+}
+
+/// BT.709 limited range YUV planes of an sRGB image, chroma averaged over 2x2 blocks.
+fn rgba_to_yuv420(rgba: &[u8], width: usize, height: usize) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+    const KR: f32 = 0.2126;
+    const KB: f32 = 0.0722;
+    let rgb = |x: usize, y: usize| {
+        let i = (y * width + x) * 4;
+        [rgba[i], rgba[i + 1], rgba[i + 2]].map(|c| c as f32 / 255.0)
+    };
+    let luma = |[r, g, b]: [f32; 3]| KR * r + (1.0 - KR - KB) * g + KB * b;
+    let byte = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+
+    let mut y_plane = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            y_plane.push(byte(16.0 + 219.0 * luma(rgb(x, y))));
+        }
+    }
+
+    let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+    let mut u_plane = Vec::with_capacity(chroma_width * chroma_height);
+    let mut v_plane = Vec::with_capacity(chroma_width * chroma_height);
+    for cy in 0..chroma_height {
+        for cx in 0..chroma_width {
+            let mut sum = [0.0; 3];
+            let mut count = 0.0;
+            for y in cy * 2..(cy * 2 + 2).min(height) {
+                for x in cx * 2..(cx * 2 + 2).min(width) {
+                    let c = rgb(x, y);
+                    sum = std::array::from_fn(|i| sum[i] + c[i]);
+                    count += 1.0;
+                }
+            }
+            let [r, g, b] = sum.map(|v| v / count);
+            let l = luma([r, g, b]);
+            u_plane.push(byte(128.0 + 224.0 * (b - l) / (2.0 * (1.0 - KB))));
+            v_plane.push(byte(128.0 + 224.0 * (r - l) / (2.0 * (1.0 - KR))));
+        }
+    }
+    (y_plane, u_plane, v_plane)
+}
+
+/// The OBUs in an AV1 packet, with their types. rav1e always writes their sizes.
+fn obus(mut data: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    std::iter::from_fn(move || {
+        let header = *data.first()?;
+        let obu_type = (header >> 3) & 0xf;
+        let mut pos = 1 + ((header >> 2) & 1) as usize; // extension byte
+        let size = if header & 2 != 0 {
+            let mut size = 0usize;
+            for shift in (0..56).step_by(7) {
+                let byte = *data.get(pos)?;
+                pos += 1;
+                size |= ((byte & 0x7f) as usize) << shift;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            size
+        } else {
+            data.len() - pos
+        };
+        let end = (pos + size).min(data.len());
+        let (obu, rest) = data.split_at(end);
+        data = rest;
+        Some((obu_type, obu))
+    })
+}
+
+/// Appends an EBML element. Sizes are always written in 8 bytes, which every reader
+/// takes and saves working out how long they'll be in advance.
+fn element(out: &mut Vec<u8>, id: u32, body: &[u8]) {
+    let id = id.to_be_bytes();
+    let skip = id.iter().take_while(|&&b| b == 0).count();
+    out.extend_from_slice(&id[skip..]);
+    out.push(0x01);
+    out.extend_from_slice(&(body.len() as u64).to_be_bytes()[1..]);
+    out.extend_from_slice(body);
+}
+
+fn uint(out: &mut Vec<u8>, id: u32, value: u64) {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take_while(|&&b| b == 0).count().min(7);
+    element(out, id, &bytes[skip..]);
+}
+
+/// A WebM holding an AV1 track and maybe an Opus one, starting a cluster at every
+/// keyframe.
+fn webm(
+    width: u64,
+    height: u64,
+    codec_private: &[u8],
+    audio: Option<&OpusAudio>,
+    duration: f64,
+    blocks: &[Block],
+) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut header = Vec::new();
+    uint(&mut header, 0x4286, 1); // EBMLVersion
+    uint(&mut header, 0x42f7, 1); // EBMLReadVersion
+    uint(&mut header, 0x42f2, 4); // EBMLMaxIDLength
+    uint(&mut header, 0x42f3, 8); // EBMLMaxSizeLength
+    element(&mut header, 0x4282, b"webm"); // DocType
+    uint(&mut header, 0x4287, 4); // DocTypeVersion
+    uint(&mut header, 0x4285, 2); // DocTypeReadVersion
+    element(&mut out, 0x1a45dfa3, &header);
+
+    let mut segment = Vec::new();
+    let mut info = Vec::new();
+    uint(&mut info, 0x2ad7b1, TIMESTAMP_SCALE);
+    element(&mut info, 0x4489, &duration.to_be_bytes());
+    element(&mut info, 0x4d80, b"obamify"); // MuxingApp
+    element(&mut info, 0x5741, b"obamify"); // WritingApp
+    element(&mut segment, 0x1549a966, &info);
+
+    let mut video = Vec::new();
+    uint(&mut video, 0xb0, width);
+    uint(&mut video, 0xba, height);
+    let mut track = Vec::new();
+    uint(&mut track, 0xd7, VIDEO_TRACK as u64); // TrackNumber
+    uint(&mut track, 0x73c5, VIDEO_TRACK as u64); // TrackUID
+    uint(&mut track, 0x83, 1); // TrackType: video
+    uint(&mut track, 0x9c, 0); // FlagLacing
+    element(&mut track, 0x86, b"V_AV1");
+    element(&mut track, 0x63a2, codec_private);
+    element(&mut track, 0xe0, &video);
+    let mut tracks = Vec::new();
+    element(&mut tracks, 0xae, &track);
+    if let Some(audio) = audio {
+        let mut settings = Vec::new();
+        element(&mut settings, 0xb5, &OPUS_RATE.to_be_bytes()); // SamplingFrequency
+        uint(&mut settings, 0x9f, audio.channels());
+        let mut track = Vec::new();
+        uint(&mut track, 0xd7, AUDIO_TRACK as u64); // TrackNumber
+        uint(&mut track, 0x73c5, AUDIO_TRACK as u64); // TrackUID
+        uint(&mut track, 0x83, 2); // TrackType: audio
+        uint(&mut track, 0x9c, 0); // FlagLacing
+        element(&mut track, 0x86, b"A_OPUS");
+        element(&mut track, 0x63a2, &audio.head);
+        let codec_delay = audio.pre_skip() as f64 * 1e9 / OPUS_RATE;
+        uint(&mut track, 0x56aa, codec_delay.round() as u64); // CodecDelay
+        uint(&mut track, 0x56bb, OPUS_SEEK_PRE_ROLL);
+        element(&mut track, 0xe1, &settings);
+        element(&mut tracks, 0xae, &track);
+    }
+    element(&mut segment, 0x1654ae6b, &tracks);
+
+    let mut cluster: Option<(u64, Vec<u8>)> = None;
+    for block in blocks {
+        let start_new = match &cluster {
+            None => true,
+            Some((start, _)) => {
+                (block.keyframe && block.track == VIDEO_TRACK)
+                    || block.timestamp - start > MAX_CLUSTER_SPAN
+            }
+        };
+        if start_new {
+            if let Some((_, body)) = cluster.take() {
+                element(&mut segment, 0x1f43b675, &body);
+            }
+            let mut body = Vec::new();
+            uint(&mut body, 0xe7, block.timestamp); // Timestamp
+            cluster = Some((block.timestamp, body));
+        }
+        let (start, body) = cluster.as_mut().unwrap();
+        let mut simple_block = vec![0x80 | block.track]; // one byte track number
+        simple_block.extend_from_slice(&((block.timestamp - *start) as i16).to_be_bytes());
+        simple_block.push(if block.keyframe { 0x80 } else { 0 });
+        simple_block.extend_from_slice(&block.data);
+        element(body, 0xa3, &simple_block);
+    }
+    if let Some((_, body)) = cluster {
+        element(&mut segment, 0x1f43b675, &body);
+    }
+    element(&mut out, 0x18538067, &segment);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_a_playable_webm() {
+        let (width, height) = (33, 17); // odd, so chroma planes round up
+        let mut encoder = VideoEncoder::new(width, height, 40.0).unwrap();
+        for i in 0..4u8 {
+            let frame = [i * 60, 128, 255 - i * 60, 255].repeat((width * height) as usize);
+            encoder.write_frame(&frame).unwrap();
+        }
+        let data = encoder.finish(None, &JobProgress::default()).unwrap();
+        assert_eq!(&data[..4], &[0x1a, 0x45, 0xdf, 0xa3]);
+        assert!(data.windows(5).any(|w| w == b"V_AV1"));
+        let blocks = data.windows(2).filter(|w| w == &[0xa3, 0x01]).count();
+        assert!(blocks >= 4, "{} blocks", blocks);
+    }
+
+    /// An ogg page holding `packets` whole, which is all the parser needs.
+    fn ogg_page(packets: &[&[u8]]) -> Vec<u8> {
+        let mut table = Vec::new();
+        for packet in packets {
+            table.extend(std::iter::repeat_n(255, packet.len() / 255));
+            table.push((packet.len() % 255) as u8);
+        }
+        let mut page = b"OggS".to_vec();
+        page.extend_from_slice(&[0; 10]); // version, flags and granule position
+        page.extend_from_slice(&7u32.to_le_bytes()); // serial
+        page.extend_from_slice(&[0; 8]); // sequence number and checksum
+        page.push(table.len() as u8);
+        page.extend(table);
+        for packet in packets {
+            page.extend_from_slice(packet);
+        }
+        page
+    }
+
+    #[test]
+    fn opus_audio_loops_to_the_video_length() {
+        let mut head = b"OpusHead".to_vec();
+        head.extend_from_slice(&[1, 2, 0x38, 0x01, 0x80, 0xbb, 0, 0, 0, 0, 0]);
+        let celt_20ms = [19 << 3, 1, 2, 3]; // one 20 ms frame
+        let long = vec![19 << 3; 300]; // spans segments, but still 20 ms
+        let mut ogg = ogg_page(&[&head]);
+        ogg.extend(ogg_page(&[b"OpusTags\0\0\0\0\0\0\0\0"]));
+        ogg.extend(ogg_page(&[&celt_20ms, &long, &celt_20ms]));
+
+        let audio = OpusAudio::parse(&ogg).unwrap();
+        assert_eq!(audio.channels(), 2);
+        assert_eq!(audio.pre_skip(), 312);
+        let timestamps = audio
+            .blocks(200.0)
+            .iter()
+            .map(|b| b.timestamp)
+            .collect::<Vec<_>>();
+        assert_eq!(timestamps, (0..10).map(|i| i * 20).collect::<Vec<_>>());
+    }
+}
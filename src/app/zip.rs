@@ -0,0 +1,105 @@
+//! Just enough of the zip format to bundle exported files into one download. Entries are
+//! stored as they are, since what goes in (pngs) is already compressed.
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_DIRECTORY: u32 = 0x0605_4b50;
+const VERSION: u16 = 20; // 2.0, the first with folders and nothing newer needed
+const DOS_DATE: u16 = (1 << 5) | 1; // 1980-01-01, the earliest there is; no clock on the web
+
+/// An archive being written into memory.
+#[derive(Default)]
+pub struct ZipWriter {
+    data: Vec<u8>,
+    directory: Vec<u8>,
+    entries: u16,
+}
+
+impl ZipWriter {
+    /// Adds a file called `name` holding `contents`.
+    pub fn add(&mut self, name: &str, contents: &[u8]) -> Result<(), String> {
+        let too_big = || format!("too much to fit in a zip at {}", name);
+        let offset = u32::try_from(self.data.len()).map_err(|_| too_big())?;
+        let size = u32::try_from(contents.len()).map_err(|_| too_big())?;
+        self.entries = self.entries.checked_add(1).ok_or_else(too_big)?;
+        let mut crc = flate2::Crc::new();
+        crc.update(contents);
+        let name_len = name.len() as u16;
+
+        // the local header and the central directory's share most of their fields
+        let shared = |out: &mut Vec<u8>| {
+            for half in [VERSION, 0, 0, 0, DOS_DATE] {
+                out.extend(half.to_le_bytes()); // version needed, flags, stored, time, date
+            }
+            for word in [crc.sum(), size, size] {
+                out.extend(word.to_le_bytes());
+            }
+            out.extend(name_len.to_le_bytes());
+            out.extend(0u16.to_le_bytes()); // no extra field
+        };
+        self.data.extend(LOCAL_HEADER.to_le_bytes());
+        shared(&mut self.data);
+        self.data.extend(name.as_bytes());
+        self.data.extend(contents);
+
+        self.directory.extend(CENTRAL_HEADER.to_le_bytes());
+        self.directory.extend(VERSION.to_le_bytes()); // made by
+        shared(&mut self.directory);
+        for half in [0u16; 3] {
+            self.directory.extend(half.to_le_bytes()); // comment, disk, internal attributes
+        }
+        self.directory.extend(0u32.to_le_bytes()); // external attributes
+        self.directory.extend(offset.to_le_bytes());
+        self.directory.extend(name.as_bytes());
+        Ok(())
+    }
+
+    /// The finished archive.
+    pub fn finish(mut self) -> Result<Vec<u8>, String> {
+        let too_big = || "too much to fit in a zip".to_owned();
+        let directory_offset = u32::try_from(self.data.len()).map_err(|_| too_big())?;
+        let directory_size = u32::try_from(self.directory.len()).map_err(|_| too_big())?;
+        self.data.append(&mut self.directory);
+        self.data.extend(END_OF_DIRECTORY.to_le_bytes());
+        for half in [0, 0, self.entries, self.entries] {
+            self.data.extend(half.to_le_bytes()); // this disk, the directory's, entries twice
+        }
+        self.data.extend(directory_size.to_le_bytes());
+        self.data.extend(directory_offset.to_le_bytes());
+        self.data.extend(0u16.to_le_bytes()); // no comment
+        Ok(self.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directory_points_at_each_entry() {
+        let mut zip = ZipWriter::default();
+        zip.add("a.txt", b"hello").unwrap();
+        zip.add("b.txt", b"zip").unwrap();
+        let data = zip.finish().unwrap();
+        let u16_at = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]) as usize;
+        let u32_at = |i: usize| u32::from_le_bytes(data[i..i + 4].try_into().unwrap()) as usize;
+
+        let end = data.len() - 22;
+        assert_eq!(u32_at(end), END_OF_DIRECTORY as usize);
+        assert_eq!(u16_at(end + 10), 2);
+        let mut entry = u32_at(end + 16);
+        for (name, contents) in [("a.txt", &b"hello"[..]), ("b.txt", b"zip")] {
+            assert_eq!(u32_at(entry), CENTRAL_HEADER as usize);
+            let local = u32_at(entry + 42);
+            assert_eq!(u32_at(local), LOCAL_HEADER as usize);
+            let name_len = u16_at(local + 26);
+            assert_eq!(&data[local + 30..local + 30 + name_len], name.as_bytes());
+            let start = local + 30 + name_len;
+            assert_eq!(&data[start..start + u32_at(local + 22)], contents);
+            let mut crc = flate2::Crc::new();
+            crc.update(contents);
+            assert_eq!(u32_at(local + 14), crc.sum() as usize);
+            entry += 46 + u16_at(entry + 28);
+        }
+    }
+}
@@ -2,5 +2,13 @@
 
 mod app;
 pub use app::ObamifyApp;
+#[cfg(not(target_arch = "wasm32"))]
+pub use app::config::Overrides;
+#[cfg(not(target_arch = "wasm32"))]
+pub use app::software_preview::SoftwarePreview;
+#[cfg(not(target_arch = "wasm32"))]
+pub use app::software_render::{Playback, PlaybackFrame, STEPS_PER_SECOND, render_headless};
+#[cfg(not(target_arch = "wasm32"))]
+pub use app::trace::init_chrome_trace;
 #[cfg(target_arch = "wasm32")]
 pub use app::worker_entry;
@@ -6,22 +6,197 @@
 fn main() -> eframe::Result {
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
 
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let trace_path = args
+        .trace
+        .or_else(|| std::env::var_os("OBAMIFY_TRACE").map(Into::into));
+    let _trace = trace_path.and_then(|path| match obamify::init_chrome_trace(path) {
+        Ok(guard) => Some(guard),
+        Err(err) => {
+            eprintln!("failed to start tracing: {}", err);
+            None
+        }
+    });
+
+    if let Some((preset, output)) = &args.render {
+        let result = obamify::render_headless(preset, output, args.reverse, &args.overrides);
+        if let Err(err) = result {
+            eprintln!("failed to render {}: {}", preset, err);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let force_software =
+        args.software || std::env::var("OBAMIFY_SOFTWARE").is_ok_and(|v| !v.is_empty());
+
+    let viewport = egui::ViewportBuilder::default()
+        .with_inner_size([1024.0, 1024.0])
+        .with_min_inner_size([400.0, 400.0])
+        .with_icon(
+            // NOTE: Adding an icon is optional
+            eframe::icon_data::from_png_bytes(&include_bytes!("../assets/icon128.png")[..])
+                .expect("Failed to load icon"),
+        );
+
+    let setup = egui_wgpu::WgpuSetupCreateNew::default();
+    // winit only lets us open one event loop, so check for an adapter before picking a
+    // renderer: without one, preview and export on the CPU through glow instead
+    let instance = egui_wgpu::wgpu::Instance::new(&setup.instance_descriptor);
+    if instance
+        .enumerate_adapters(setup.instance_descriptor.backends)
+        .is_empty()
+    {
+        eprintln!("no graphics adapter found, falling back to the CPU preview");
+        let native_options = eframe::NativeOptions {
+            renderer: eframe::Renderer::Glow,
+            viewport,
+            ..Default::default()
+        };
+        return eframe::run_native(
+            "obamify",
+            native_options,
+            Box::new(move |cc| Ok(Box::new(obamify::SoftwarePreview::new(cc, &args.overrides)))),
+        );
+    }
+
     let native_options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size([1024.0, 1024.0])
-            .with_min_inner_size([400.0, 400.0])
-            .with_icon(
-                // NOTE: Adding an icon is optional
-                eframe::icon_data::from_png_bytes(&include_bytes!("../assets/icon128.png")[..])
-                    .expect("Failed to load icon"),
-            ),
+        wgpu_options: egui_wgpu::WgpuConfiguration {
+            wgpu_setup: egui_wgpu::WgpuSetup::CreateNew(egui_wgpu::WgpuSetupCreateNew {
+                native_adapter_selector: Some(std::sync::Arc::new(move |adapters, surface| {
+                    pick_adapter(adapters, surface, force_software)
+                })),
+                ..setup
+            }),
+            ..Default::default()
+        },
+        viewport,
         ..Default::default()
     };
-    eframe::run_native(
+    let result = eframe::run_native(
         "obamify",
         native_options,
-        Box::new(|cc| Ok(Box::new(obamify::ObamifyApp::new(cc)))),
-    )
+        Box::new(move |cc| Ok(Box::new(obamify::ObamifyApp::new(cc, &args.overrides)))),
+    );
+    if result.is_err() {
+        eprintln!(
+            "couldn't open a window; without a GPU or display you can still record with \
+             `obamify --render <preset> <output.gif>`"
+        );
+    }
+    result
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+const USAGE: &str = "\
+usage: obamify [options]
+       obamify --render <preset name or folder> <output.gif> [--reverse] [options]
+
+  --trace <trace.json>   record a chrome trace for profiling (or OBAMIFY_TRACE)
+  --render <preset> <output.gif>
+                         record on the CPU without opening a window, for machines
+                         with no GPU or display
+  --reverse              with --render, play the transformation backwards
+  --software             use a CPU adapter like llvmpipe or WARP even when a GPU is
+                         available (or OBAMIFY_SOFTWARE=1)
+  --output-dir <dir>     where save dialogs start (or OBAMIFY_OUTPUT_DIR)
+  --sidelen <n>          grid side length for new presets (or OBAMIFY_SIDELEN)
+  --algorithm <name>     optimal, genetic or bestof (or OBAMIFY_ALGORITHM)
+  --threads <n>          solver threads, 0 for one per core but one (or OBAMIFY_THREADS)
+  --low-power            always run in low power mode (or OBAMIFY_LOW_POWER)
+  --no-dialog            save straight to the output dir (or OBAMIFY_NO_DIALOG)";
+
+/// The command line, parsed once up front.
+#[cfg(not(target_arch = "wasm32"))]
+struct Args {
+    trace: Option<std::path::PathBuf>,
+    render: Option<(String, std::path::PathBuf)>, // preset and output
+    reverse: bool,
+    software: bool,
+    overrides: obamify::Overrides,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Args {
+    fn parse() -> Result<Self, Box<dyn std::error::Error>> {
+        let mut args = pico_args::Arguments::from_env();
+        if args.contains(["-h", "--help"]) {
+            println!("{}", USAGE);
+            std::process::exit(0);
+        }
+        let trace = args.opt_value_from_str("--trace")?;
+        let preset = args.opt_value_from_str::<_, String>("--render")?;
+        let reverse = args.contains("--reverse");
+        let software = args.contains("--software");
+        let overrides = obamify::Overrides::from_args(&mut args)?;
+        // free arguments go last, once every flag is taken out
+        let render = match preset {
+            Some(preset) => {
+                let output = args
+                    .free_from_str()
+                    .map_err(|_| "--render needs an output file after the preset")?;
+                Some((preset, output))
+            }
+            None => None,
+        };
+        let unknown = args.finish();
+        if !unknown.is_empty() {
+            let unknown = unknown
+                .iter()
+                .map(|arg| arg.to_string_lossy())
+                .collect::<Vec<_>>();
+            return Err(format!("unknown arguments: {}", unknown.join(" ")).into());
+        }
+        if reverse && render.is_none() {
+            return Err("--reverse only applies to --render".into());
+        }
+        Ok(Self {
+            trace,
+            render,
+            reverse,
+            software,
+            overrides,
+        })
+    }
+}
+
+/// Prefers real GPUs, falling back to a software (CPU) adapter when that's all there is.
+#[cfg(not(target_arch = "wasm32"))]
+fn pick_adapter(
+    adapters: &[egui_wgpu::wgpu::Adapter],
+    surface: Option<&egui_wgpu::wgpu::Surface<'_>>,
+    force_software: bool,
+) -> Result<egui_wgpu::wgpu::Adapter, String> {
+    use egui_wgpu::wgpu::DeviceType;
+    let rank = |device_type: DeviceType| match device_type {
+        DeviceType::Cpu if force_software => 0,
+        DeviceType::DiscreteGpu => 1,
+        DeviceType::IntegratedGpu => 2,
+        DeviceType::VirtualGpu => 3,
+        DeviceType::Other => 4,
+        DeviceType::Cpu => 5,
+    };
+    let adapter = adapters
+        .iter()
+        .filter(|a| surface.is_none_or(|s| a.is_surface_supported(s)))
+        .min_by_key(|a| rank(a.get_info().device_type))
+        .ok_or("no graphics adapter found")?;
+    let info = adapter.get_info();
+    if info.device_type == DeviceType::Cpu {
+        eprintln!(
+            "using software rendering on {}, expect it to be slow",
+            info.name
+        );
+    }
+    Ok(adapter.clone())
 }
 
 // When compiling to web using trunk:
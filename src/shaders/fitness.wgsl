@@ -0,0 +1,94 @@
+// Sums the solver's cost function over whole assignments, one per workgroup row.
+// WGSL has no 64 bit integers, so costs are (low, high) pairs of u32.
+
+struct Params {
+    count: u32,      // cells per assignment
+    wrap: u32,       // grid width distances wrap around at, 0 for none
+    _pad0: u32,
+    _pad1: u32,
+};
+
+struct Cell {
+    pos: u32,    // x | y << 16
+    rgb: u32,    // r | g << 8 | b << 16
+    weight: u32,    // targets only
+    proximity: u32, // targets only
+};
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> source: array<Cell>;
+@group(0) @binding(2) var<storage, read> target_cells: array<Cell>;
+@group(0) @binding(3) var<storage, read> assignments: array<u32>; // one after another
+@group(0) @binding(4) var<storage, read_write> partials: array<vec2<u32>>; // per workgroup
+
+const GROUP_SIZE: u32 = 256u;
+var<workgroup> sums: array<vec2<u32>, GROUP_SIZE>;
+
+fn add64(a: vec2<u32>, b: vec2<u32>) -> vec2<u32> {
+    let lo = a.x + b.x;
+    return vec2<u32>(lo, a.y + b.y + select(0u, 1u, lo < a.x));
+}
+
+fn mul64(a: u32, b: u32) -> vec2<u32> {
+    let a0 = a & 0xffffu;
+    let a1 = a >> 16u;
+    let b0 = b & 0xffffu;
+    let b1 = b >> 16u;
+    let mid1 = a1 * b0;
+    let mid = mid1 + a0 * b1;
+    let mid_carry = select(0u, 1u << 16u, mid < mid1);
+    let low = a0 * b0;
+    let lo = low + (mid << 16u);
+    let carry = select(0u, 1u, lo < low);
+    return vec2<u32>(lo, a1 * b1 + (mid >> 16u) + mid_carry + carry);
+}
+
+fn axis_dist(a: u32, b: u32) -> u32 {
+    let d = max(a, b) - min(a, b);
+    if params.wrap > 0u {
+        return min(d, params.wrap - d);
+    }
+    return d;
+}
+
+fn channel_diff(a: u32, b: u32, shift: u32) -> u32 {
+    let ca = (a >> shift) & 0xffu;
+    let cb = (b >> shift) & 0xffu;
+    let d = max(ca, cb) - min(ca, cb);
+    return d * d;
+}
+
+fn cell_cost(s: Cell, t: Cell) -> vec2<u32> {
+    let dx = axis_dist(s.pos & 0xffffu, t.pos & 0xffffu);
+    let dy = axis_dist(s.pos >> 16u, t.pos >> 16u);
+    let weighted_spatial = (dx * dx + dy * dy) * t.proximity;
+    let color = channel_diff(s.rgb, t.rgb, 0u) + channel_diff(s.rgb, t.rgb, 8u)
+        + channel_diff(s.rgb, t.rgb, 16u);
+    return add64(mul64(color, t.weight), mul64(weighted_spatial, weighted_spatial));
+}
+
+@compute @workgroup_size(256)
+fn main(
+    @builtin(local_invocation_id) lid: vec3<u32>,
+    @builtin(workgroup_id) wid: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let cell = wid.x * GROUP_SIZE + lid.x;
+    var cost = vec2<u32>(0u, 0u);
+    if cell < params.count {
+        let s = source[assignments[wid.y * params.count + cell]];
+        cost = cell_cost(s, target_cells[cell]);
+    }
+    sums[lid.x] = cost;
+    workgroupBarrier();
+
+    for (var stride = GROUP_SIZE / 2u; stride > 0u; stride = stride / 2u) {
+        if lid.x < stride {
+            sums[lid.x] = add64(sums[lid.x], sums[lid.x + stride]);
+        }
+        workgroupBarrier();
+    }
+    if lid.x == 0u {
+        partials[wid.y * groups.x + wid.x] = sums[0];
+    }
+}
@@ -0,0 +1,54 @@
+// Draws every cell as one instanced quad at its place along the way from source to
+// destination, writing cell ids like the seed splat. For big grids this replaces the
+// physics, the splat and the jump flood.
+
+struct Motion {
+  src: vec2<f32>,
+  dst: vec2<f32>,
+  start: f32,  // frames before the cell sets off
+  frames: f32, // frames it takes to arrive
+  _pad0: f32,
+  _pad1: f32,
+};
+
+struct Params { width: u32, height: u32, frame: f32, cell: f32 };
+
+@group(0) @binding(0) var<storage, read> motion: array<Motion>;
+@group(0) @binding(1) var<uniform> params: Params;
+
+// quads grow by up to this much of a cell while flying, so moving cells leave few gaps
+const FLIGHT_GROWTH: f32 = 1.0;
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) @interpolate(flat) seed_id: u32,
+};
+
+@vertex
+fn vs_main(
+  @builtin(vertex_index) vertex_index: u32,
+  @builtin(instance_index) instance: u32,
+) -> VertexOutput {
+  let m = motion[instance];
+  let t = clamp((params.frame - m.start) / m.frames, 0.0, 1.0);
+  let center = mix(m.src, m.dst, smoothstep(0.0, 1.0, t));
+  let size = params.cell * (1.0 + FLIGHT_GROWTH * min(1.0, 8.0 * t * (1.0 - t)));
+  let corner = vec2<f32>(f32(vertex_index & 1u), f32((vertex_index >> 1u) & 1u)) - 0.5;
+  let p = center + corner * size;
+
+  var output: VertexOutput;
+  let x = p.x / f32(params.width) * 2.0 - 1.0;
+  let y = p.y / f32(params.height) * 2.0 - 1.0;
+  output.position = vec4<f32>(x, -y, 0.0, 1.0);
+  output.seed_id = instance;
+  return output;
+}
+
+@fragment
+fn fs_main(@location(0) @interpolate(flat) seed_id: u32) -> @location(0) vec4<f32> {
+  let r = f32((seed_id >> 0u) & 0xFFu) / 255.0;
+  let g = f32((seed_id >> 8u) & 0xFFu) / 255.0;
+  let b = f32((seed_id >> 16u) & 0xFFu) / 255.0;
+  let a = f32((seed_id >> 24u) & 0xFFu) / 255.0;
+  return vec4<f32>(r, g, b, a);
+}
@@ -0,0 +1,94 @@
+// Maps part of the rendered frame to entries of a gif palette, picked the way NeuQuant
+// picks them so these are the bytes it would write, four pixels to a word, so a recording
+// reads back a byte per pixel instead of four.
+
+struct Params {
+    origin: vec2<u32>, // of the recorded part, in the frame
+    size: vec2<u32>,   // of the recorded part
+    colors: u32,       // palette entries in use
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+};
+
+struct Tables {
+    palette: array<vec4<u32>, 256>,  // rgba, sorted by green as NeuQuant leaves it
+    encoding: array<vec4<u32>, 64>,  // the gif byte of each stored byte, four to an entry
+    netindex: array<vec4<u32>, 64>,  // where each green starts being searched from, likewise
+};
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<uniform> tables: Tables;
+@group(0) @binding(2) var frame: texture_2d<f32>;
+@group(0) @binding(3) var<storage, read_write> indices: array<u32>;
+
+const GROUP_SIZE: u32 = 256u;
+
+fn encode(stored: u32) -> u32 {
+    return tables.encoding[stored / 4u][stored % 4u];
+}
+
+fn distance(entry: u32, rgba: vec4<i32>) -> i32 {
+    let d = vec4<i32>(tables.palette[entry]) - rgba;
+    return dot(d, d);
+}
+
+// NeuQuant's own search, `search_netindex`: outwards from where the pixel's green starts
+// in the palette, until an entry's green alone is as far as the best so far. That isn't
+// always the closest entry, and ties go to whichever it meets first, so it's followed
+// step for step.
+fn nearest(color: vec4<u32>) -> u32 {
+    let rgba = vec4<i32>(color);
+    var best = 0u;
+    var best_distance = 1 << 30;
+    var i = tables.netindex[color.g / 4u][color.g % 4u];
+    var j = select(0u, i - 1u, i > 0u);
+    while (i < params.colors || j > 0u) {
+        if (i < params.colors) {
+            let g = i32(tables.palette[i].g) - rgba.g;
+            if (g * g >= best_distance) {
+                break;
+            }
+            let d = distance(i, rgba);
+            if (d < best_distance) {
+                best = i;
+                best_distance = d;
+            }
+            i++;
+        }
+        if (j > 0u) {
+            let g = i32(tables.palette[j].g) - rgba.g;
+            if (g * g >= best_distance) {
+                break;
+            }
+            let d = distance(j, rgba);
+            if (d < best_distance) {
+                best = j;
+                best_distance = d;
+            }
+            j--;
+        }
+    }
+    return best;
+}
+
+@compute @workgroup_size(GROUP_SIZE)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let pixels = params.size.x * params.size.y;
+    let first = id.x * 4u;
+    if (first >= pixels) {
+        return;
+    }
+    var word = 0u;
+    for (var k = 0u; k < 4u; k++) {
+        let i = first + k;
+        if (i < pixels) {
+            let at = params.origin + vec2<u32>(i % params.size.x, i / params.size.x);
+            // unorm, so this gives back the stored bytes exactly
+            let stored = vec4<u32>(round(clamp(textureLoad(frame, at, 0), vec4(0.0), vec4(1.0)) * 255.0));
+            let rgba = vec4<u32>(encode(stored.r), encode(stored.g), encode(stored.b), stored.a);
+            word |= nearest(rgba) << (8u * k);
+        }
+    }
+    indices[id.x] = word;
+}
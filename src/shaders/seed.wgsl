@@ -1,6 +1,6 @@
 @group(0) @binding(0) var seed_tex: texture_2d<f32>;
 
-struct ParamsCommon { width: u32, height: u32, n_seeds: u32, _pad: u32 };
+struct ParamsCommon { width: u32, height: u32, n_seeds: u32, render_mode: u32 };
 @group(0) @binding(1) var<uniform> params: ParamsCommon;
 
 struct VertexOutput {
@@ -4,9 +4,19 @@
 @group(0) @binding(2) var seed_tex: texture_2d<f32>;
 @group(0) @binding(3) var color_tex: texture_2d<f32>;
 
-struct ParamsCommon { width: u32, height: u32, n_seeds: u32, _pad: u32 };
+struct ParamsCommon { width: u32, height: u32, n_seeds: u32, render_mode: u32, cvd_filter: u32, tiled: u32, provenance: u32, _pad: u32 };
 @group(0) @binding(4) var<uniform> params: ParamsCommon;
 
+@group(0) @binding(5) var tile_atlas: texture_2d<f32>;
+@group(0) @binding(6) var tile_index_tex: texture_2d<f32>;
+
+const RENDER_MODE_PIXELS: u32 = 0u;
+const CVD_NONE: u32 = 0u;
+const CVD_PROTANOPIA: u32 = 1u;
+const CVD_DEUTERANOPIA: u32 = 2u;
+const TILE_SIZE: f32 = 32.0;
+const TILE_COLUMNS: u32 = 64u;
+
 fn load_seed_pos(seed_id: u32) -> vec2<f32> {
     let tex_width = 1024u;
     let seed_x = seed_id % tex_width;
@@ -31,6 +41,62 @@ fn decode_id(rgba: vec4<f32>) -> u32 {
 
 fn dist2(a: vec2<f32>, b: vec2<f32>) -> f32 { let d = a - b; return dot(d,d); }
 
+// Draws the seed's tile (emoji or photo), centered on the seed and sized to one grid cell.
+fn tile_color(seed_id: u32, seed: vec2<f32>, p: vec2<f32>) -> vec4<f32> {
+    let tex_width = 1024u;
+    let idx_rgba = textureLoad(tile_index_tex, vec2<i32>(i32(seed_id % tex_width), i32(seed_id / tex_width)), 0);
+    let tile = u32(idx_rgba.r * 255.0 + 0.5) | (u32(idx_rgba.g * 255.0 + 0.5) << 8u);
+
+    let cell = f32(params.width) / sqrt(f32(params.n_seeds));
+    let local = clamp((p - seed) / cell + vec2<f32>(0.5, 0.5), vec2<f32>(0.0, 0.0), vec2<f32>(0.999, 0.999));
+    let origin = vec2<f32>(f32(tile % TILE_COLUMNS), f32(tile / TILE_COLUMNS)) * TILE_SIZE;
+    let texel = vec2<i32>(origin + floor(local * TILE_SIZE));
+    return textureLoad(tile_atlas, texel, 0);
+}
+
+// Where the seed's pixel started in the source, as a color wheel around the middle: hue
+// for the direction and saturation for the distance from it.
+fn provenance_color(seed_id: u32) -> vec3<f32> {
+    let side = u32(round(sqrt(f32(params.n_seeds))));
+    let p = (vec2<f32>(f32(seed_id % side), f32(seed_id / side)) + 0.5) / f32(side) * 2.0 - 1.0;
+    let hue = atan2(p.y, p.x) / 6.2831853 + 0.5;
+    let sat = clamp(length(p) / 1.41421356, 0.0, 1.0);
+    let k = fract(vec3<f32>(1.0, 2.0 / 3.0, 1.0 / 3.0) + hue) * 6.0 - 3.0;
+    let rgb = clamp(abs(k) - 1.0, vec3<f32>(0.0), vec3<f32>(1.0));
+    return mix(vec3<f32>(1.0), rgb, sat);
+}
+
+fn to_linear(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045));
+}
+
+fn to_srgb(c: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3<f32>(0.0031308));
+}
+
+// Simulates color blindness with the full-severity matrices from Machado et al. (2009).
+fn simulate_cvd(rgb: vec3<f32>, mode: u32) -> vec3<f32> {
+    var r: vec3<f32>;
+    var g: vec3<f32>;
+    var b: vec3<f32>;
+    if (mode == CVD_PROTANOPIA) {
+        r = vec3<f32>(0.152286, 1.052583, -0.204868);
+        g = vec3<f32>(0.114503, 0.786281, 0.099216);
+        b = vec3<f32>(-0.003882, -0.048116, 1.051998);
+    } else if (mode == CVD_DEUTERANOPIA) {
+        r = vec3<f32>(0.367322, 0.860646, -0.227968);
+        g = vec3<f32>(0.280085, 0.672501, 0.047413);
+        b = vec3<f32>(-0.011820, 0.042940, 0.968881);
+    } else { // tritanopia
+        r = vec3<f32>(1.255528, -0.076749, -0.178779);
+        g = vec3<f32>(-0.078411, 0.930809, 0.147602);
+        b = vec3<f32>(0.004733, 0.691367, 0.303900);
+    }
+    let lin = to_linear(clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0)));
+    let sim = clamp(vec3<f32>(dot(r, lin), dot(g, lin), dot(b, lin)), vec3<f32>(0.0), vec3<f32>(1.0));
+    return to_srgb(sim);
+}
+
 struct VertexOutput {
   @builtin(position) position: vec4<f32>,
   @location(0) uv: vec2<f32>,
@@ -47,7 +113,9 @@ fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
 }
 
 @fragment
-fn fs_main(@location(0) uv: vec2<f32>) -> @location(0) vec4<f32> {
+fn fs_main(@location(0) screen_uv: vec2<f32>) -> @location(0) vec4<f32> {
+  // tiled previews repeat the grid three times each way
+  let uv = select(screen_uv, fract(screen_uv * 3.0), params.tiled != 0u);
   let gid = vec2<i32>(i32(uv.x * f32(params.width)), i32(uv.y * f32(params.height)));
   
   if (gid.x >= i32(params.width) || gid.y >= i32(params.height)) {
@@ -64,8 +132,18 @@ fn fs_main(@location(0) uv: vec2<f32>) -> @location(0) vec4<f32> {
   var rgba: vec4<f32>;
   if (id == 0xfffffffFu) {
     rgba = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+  } else if (params.render_mode != RENDER_MODE_PIXELS) {
+    rgba = tile_color(id, seed, vec2<f32>(f32(gid.x), f32(gid.y)));
+    rgba.a = rgba.a * load_color(id).a;
   } else {
     rgba = load_color(id);
   }
+  if (params.provenance != 0u && id != 0xfffffffFu) {
+    let luma = dot(rgba.rgb, vec3<f32>(0.299, 0.587, 0.114));
+    rgba = vec4<f32>(provenance_color(id) * (0.35 + 0.65 * luma), rgba.a);
+  }
+  if (params.cvd_filter != CVD_NONE) {
+    rgba = vec4<f32>(simulate_cvd(rgba.rgb, params.cvd_filter), rgba.a);
+  }
   return rgba;
 }
\ No newline at end of file